@@ -1,7 +1,20 @@
 #![forbid(unsafe_code)]
 
+use std::{
+    collections::HashMap,
+    fmt,
+    path::{Path, PathBuf},
+    process,
+    time::Instant,
+};
+
 use bumpalo::Bump;
-use curse_interner::StringInterner;
+use clap::{Parser, Subcommand, ValueEnum};
+use curse_ast::ast;
+use curse_hir::hir;
+use curse_interner::{InternedString, StringInterner};
+use curse_interpreter::error::EvalError;
+use curse_mir::Ty as _;
 use miette::{Diagnostic, GraphicalReportHandler, NamedSource};
 use thiserror::Error;
 
@@ -11,13 +24,7 @@ mod programs;
 // Choice variant usefulness
 // Choice variant parsing in expressions and patterns
 // type-inferred function syntax
-// Attributes (like inline)
-// Tail recursion checking- I feel like with tail recursion, people either _need_ it or do not care
-// about it. It shouldn't be a hassle to determine if it's applied or not, so we should have a
-// `#[tail_recursive]` attribute where if present, it _will_ be tail recursive (or compiler error),
-// and if absent, no tail recursion will be applied. This would require good diagnostics for why it
-// cannot be applied too if that error occurs.
-// Since this language is pretty functional right now, tail recursion is a must.
+// More attributes (like inline)
 
 #[derive(Debug, Diagnostic, Error)]
 #[error("{reason}")]
@@ -41,173 +48,722 @@ impl<E: Diagnostic> Errors<E> {
     }
 }
 
-fn main() {
-    let mut interner = StringInterner::new();
+#[derive(Parser)]
+#[command(name = "curse", about = "The curse compiler")]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+}
 
-    let input: &str = programs::REGIONS;
+#[derive(Subcommand)]
+enum Command {
+    /// Print a program after one of the stages of compilation.
+    Dump {
+        /// The stage to stop after.
+        #[arg(long, value_enum, default_value_t = Stage::Hir)]
+        stage: Stage,
 
-    let mut parser = curse_parse::Parser::new(&mut interner);
-    let ast_program = parser.parse_program(input);
+        /// Run the CPS optimization passes before printing.
+        #[arg(long)]
+        optimize: bool,
 
-    if !parser.errors.is_empty() {
+        /// With `--stage mir`, also write the type inference graph to this file as Graphviz dot,
+        /// which shows why each pair of types had to be the same.
+        #[arg(long)]
+        equations: Option<PathBuf>,
+
+        /// Instead of printing the program, draw it as a Graphviz graph and write it to
+        /// `<FILE>.<STAGE>.dot` in the current directory. Doesn't work with `--stage ast`.
+        #[arg(long)]
+        dot: bool,
+
+        /// The program to compile. Defaults to a built-in example.
+        file: Option<PathBuf>,
+    },
+
+    /// Compile a program to a native executable by way of C.
+    Build {
+        /// The program to compile.
+        file: PathBuf,
+
+        /// Where to put the executable.
+        #[arg(short, long, default_value = "a.out")]
+        output: PathBuf,
+
+        /// Also write the generated C to this file.
+        #[arg(long)]
+        emit_c: Option<PathBuf>,
+
+        /// The C compiler to use.
+        #[arg(long, default_value = "cc")]
+        cc: String,
+
+        /// The static library of `curse_runtime` to link against. Defaults to the one built along
+        /// with this executable.
+        #[arg(long)]
+        runtime: Option<PathBuf>,
+    },
+
+    /// Run a program and print the value `main` returns.
+    Run {
+        /// The program to run.
+        file: PathBuf,
+
+        /// Compile the program in-process with Cranelift instead of interpreting it.
+        #[arg(long, conflicts_with = "vm")]
+        jit: bool,
+
+        /// Compile the program to bytecode and run that, instead of walking the HIR.
+        #[arg(long)]
+        vm: bool,
+
+        /// Print how long running the program took.
+        #[arg(long)]
+        time: bool,
+    },
+
+    /// Check a program and everything it imports for errors without running it, reporting the
+    /// syntax, lowering and type errors all at once.
+    Check {
+        /// The program to check.
+        file: PathBuf,
+    },
+
+    /// Format files in place, laying them out the canonical way but keeping their comments.
+    Fmt {
+        /// Don't change anything, and fail if any of the files aren't formatted already.
+        #[arg(long)]
+        check: bool,
+
+        /// The files to format. Unlike the other commands, this doesn't follow imports.
+        #[arg(required = true)]
+        files: Vec<PathBuf>,
+    },
+
+    /// Run a language server over stdin and stdout, so that editors can show errors and types as
+    /// files are being written.
+    Lsp,
+
+    /// Run the `#[test]` functions in a program and everything it imports.
+    Test {
+        /// The program to test.
+        file: PathBuf,
+
+        /// Only run the tests whose names contain this.
+        filter: Option<String>,
+    },
+}
+
+#[derive(Copy, Clone, PartialEq, Eq, ValueEnum)]
+enum Stage {
+    Ast,
+    Hir,
+    Mir,
+    Cps,
+}
+
+impl Stage {
+    fn name(self) -> &'static str {
+        match self {
+            Stage::Ast => "ast",
+            Stage::Hir => "hir",
+            Stage::Mir => "mir",
+            Stage::Cps => "cps",
+        }
+    }
+}
+
+/// The contents of a file along with a name to show in diagnostics.
+#[derive(Clone)]
+struct Source {
+    name: String,
+    input: String,
+}
+
+impl Source {
+    fn read(path: &Path) -> Source {
+        match std::fs::read_to_string(path) {
+            Ok(input) => Source {
+                name: path.display().to_string(),
+                input,
+            },
+            Err(e) => {
+                eprintln!("error: couldn't read {}: {e}", path.display());
+                process::exit(1);
+            }
+        }
+    }
+
+    fn report<E: Diagnostic>(&self, reason: &'static str, errors: Vec<E>) {
         Errors {
-            code: NamedSource::new("input", input.to_string()),
-            reason: "A parsing error occurred",
-            errors: parser.errors,
+            code: NamedSource::new(&self.name, self.input.clone()),
+            reason,
+            errors,
         }
         .print_report();
+    }
+}
 
-        return;
+/// Every file that a program was read from, and which of them each top level function is in, so
+/// that errors in imported files can be shown with the right source. Errors in an impl are keyed by
+/// the name of the type it's for, so impls are recorded under that name.
+#[derive(Default)]
+struct Files {
+    sources: Vec<Source>,
+    functions: HashMap<InternedString, usize>,
+}
+
+fn main() {
+    let command = Cli::parse().command.unwrap_or(Command::Dump {
+        stage: Stage::Hir,
+        optimize: false,
+        equations: None,
+        dot: false,
+        file: None,
+    });
+
+    match command {
+        Command::Dump {
+            stage,
+            optimize,
+            equations,
+            dot,
+            file,
+        } => {
+            let source = match &file {
+                Some(path) => Source::read(path),
+                None => Source {
+                    name: "input".to_string(),
+                    input: programs::REGIONS.to_string(),
+                },
+            };
+
+            let dot = dot.then(|| {
+                let stem = file
+                    .as_deref()
+                    .and_then(Path::file_stem)
+                    .map_or("input".into(), |stem| stem.to_string_lossy());
+                PathBuf::from(format!("{stem}.{}.dot", stage.name()))
+            });
+            dump(
+                &source,
+                stage,
+                optimize,
+                equations.as_deref(),
+                dot.as_deref(),
+            );
+        }
+        Command::Build {
+            file,
+            output,
+            emit_c,
+            cc,
+            runtime,
+        } => {
+            let Some(runtime) = runtime.or_else(curse_codegen_c::runtime_library) else {
+                eprintln!(
+                    "error: couldn't find {}, pass `--runtime` or build the whole workspace",
+                    curse_codegen_c::RUNTIME_LIBRARY
+                );
+                process::exit(1);
+            };
+            if !build(
+                &Source::read(&file),
+                &output,
+                emit_c.as_deref(),
+                &cc,
+                &runtime,
+            ) {
+                process::exit(1);
+            }
+        }
+        Command::Run {
+            file,
+            jit,
+            vm,
+            time,
+        } => {
+            let source = Source::read(&file);
+            // the interpreter recurses a lot, so give it plenty of room
+            let succeeded = std::thread::Builder::new()
+                .stack_size(1 << 30)
+                .spawn(move || run(&source, jit, vm, time))
+                .unwrap()
+                .join()
+                .unwrap();
+            if !succeeded {
+                process::exit(1);
+            }
+        }
+        Command::Check { file } => {
+            if !check(&Source::read(&file)) {
+                process::exit(1);
+            }
+        }
+        Command::Fmt { check, files } => {
+            let mut succeeded = true;
+            for file in &files {
+                succeeded &= fmt(file, check);
+            }
+            if !succeeded {
+                process::exit(1);
+            }
+        }
+        Command::Lsp => {
+            if let Err(e) = curse_lsp::run() {
+                eprintln!("error: {e}");
+                process::exit(1);
+            }
+        }
+        Command::Test { file, filter } => {
+            let source = Source::read(&file);
+            let succeeded = std::thread::Builder::new()
+                .stack_size(1 << 30)
+                .spawn(move || test(&source, filter.as_deref().unwrap_or("")))
+                .unwrap()
+                .join()
+                .unwrap();
+            if !succeeded {
+                process::exit(1);
+            }
+        }
     }
+}
+
+/// Parses `source` along with everything it `dynamic_import`s, reporting any errors and adding
+/// each file to `files`. Imports are relative to the current directory, the same as in the
+/// interpreter. The parser recovers from syntax errors, so this gives back whatever could be
+/// parsed, along with whether all of it could be.
+fn parse(
+    interner: &mut StringInterner,
+    source: &Source,
+    files: &mut Files,
+) -> (ast::Program, bool) {
+    let mut parser = curse_parse::Parser::new(interner);
+    let mut ast_program = parser.parse_program(&source.input);
 
+    let mut parsed = parser.errors.is_empty();
+    if !parsed {
+        source.report("A parsing error occurred", parser.errors);
+    }
+
+    let index = files.sources.len();
+    files.sources.push(source.clone());
+    for def in &ast_program.function_defs {
+        files.functions.insert(def.ident.symbol, index);
+    }
+    for def in &ast_program.impl_defs {
+        files.functions.insert(def.ty.symbol, index);
+    }
+    for def in &ast_program.trait_defs {
+        files.functions.insert(def.ident.symbol, index);
+    }
+
+    for import in std::mem::take(&mut ast_program.dynamic_imports) {
+        let file_string = import
+            .file_string
+            .symbol
+            .string_in(interner)
+            .expect("file name in interner")
+            .to_string();
+        // trim off quotes
+        let path = &file_string[1..file_string.len() - 1];
+
+        let (other_program, other_parsed) = parse(interner, &Source::read(Path::new(path)), files);
+        parsed &= other_parsed;
+        ast_program
+            .function_defs
+            .extend(other_program.function_defs);
+        ast_program.choice_defs.extend(other_program.choice_defs);
+        ast_program.struct_defs.extend(other_program.struct_defs);
+        ast_program.impl_defs.extend(other_program.impl_defs);
+        ast_program.trait_defs.extend(other_program.trait_defs);
+    }
+
+    (ast_program, parsed)
+}
+
+/// Parses and lowers `source`, reporting any errors.
+fn lower<'hir>(
+    hir_arena: &'hir Bump,
+    source: &Source,
+    files: &mut Files,
+) -> Option<hir::Program<'hir>> {
+    let mut interner = StringInterner::new();
+    let (ast_program, parsed) = parse(&mut interner, source, files);
+    if !parsed {
+        return None;
+    }
+    curse_interner::replace(Some(interner));
+
+    let mut lowerer = curse_ast_lowering::Lowerer::new(hir_arena);
+    let hir_program = curse_ast_lowering::Lower::lower(&ast_program, &mut lowerer);
+
+    if !lowerer.errors.is_empty() {
+        source.report("A lowering error occurred", lowerer.errors);
+        return None;
+    }
+
+    Some(hir_program)
+}
+
+/// Parses, lowers and type checks `source`, reporting the errors from every stage. Syntax errors
+/// don't stop the later stages, which treat the parts that couldn't be parsed as unknown so that
+/// they don't cause any more errors.
+fn check(source: &Source) -> bool {
+    let mut interner = StringInterner::new();
+    let mut files = Files::default();
+    let (ast_program, parsed) = parse(&mut interner, source, &mut files);
     curse_interner::replace(Some(interner));
 
     let hir_arena = Bump::new();
     let mut lowerer = curse_ast_lowering::Lowerer::new(&hir_arena);
     let hir_program = curse_ast_lowering::Lower::lower(&ast_program, &mut lowerer);
+    let lowered = lowerer.errors.is_empty();
+    if !lowered {
+        source.report("A lowering error occurred", lowerer.errors);
+    }
 
-    if !lowerer.errors.is_empty() {
-        Errors {
-            code: NamedSource::new("input", input.to_string()),
-            reason: "A lowering error occurred",
-            errors: lowerer.errors,
+    let typed = typecheck(&hir_program, &files).is_some();
+
+    parsed && lowered && typed
+}
+
+/// Type checks a lowered program, reporting each type error in the file that it's in. Gives back
+/// which `impl` each use of a trait goes to if it type checks.
+fn typecheck(hir_program: &hir::Program<'_>, files: &Files) -> Option<curse_mir::Dispatches> {
+    let global = curse_mir::ctx::Global::default();
+    let mut typeck = curse_mir::ctx::Typeck::with_global(&global);
+    match curse_mir::check_program(&mut typeck, hir_program) {
+        Ok(typed) => Some(typed.dispatches),
+        Err(failures) => {
+            for (name, errors) in failures {
+                files.sources[files.functions[&name]].report("A type error occurred", errors);
+            }
+            None
         }
-        .print_report();
+    }
+}
+
+/// Formats the file at `path`, or with `check`, just says whether it's formatted already. Files with
+/// syntax errors are left alone, since it isn't clear where their code goes.
+fn fmt(path: &Path, check: bool) -> bool {
+    let source = Source::read(path);
+    let mut interner = StringInterner::new();
+    let mut parser = curse_parse::Parser::new(&mut interner);
+    let cst = parser.parse_cst(&source.input);
+    if !parser.errors.is_empty() {
+        source.report("A parsing error occurred", parser.errors);
+        return false;
+    }
+
+    let formatted = curse_fmt::format(&cst);
+    if formatted == source.input {
+        return true;
+    }
+    if check {
+        println!("{} isn't formatted", path.display());
+        return false;
+    }
+    if let Err(e) = std::fs::write(path, formatted) {
+        eprintln!("error: couldn't write {}: {e}", path.display());
+        return false;
+    }
+    true
+}
+
+/// Converts a program to CPS for the compiled backends, reporting everything in it that they
+/// don't support yet in the file that it's in.
+fn convert(
+    hir_program: &hir::Program<'_>,
+    dispatches: &curse_mir::Dispatches,
+    files: &Files,
+) -> Option<curse_cps::cpsexpr::CPSExpr> {
+    let unsupported = match curse_cps::convert_program(hir_program, dispatches) {
+        Ok(cps) => return Some(cps),
+        Err(unsupported) => unsupported,
+    };
+
+    let mut by_file: Vec<Vec<curse_cps::Unsupported>> =
+        files.sources.iter().map(|_| vec![]).collect();
+    for error in unsupported {
+        by_file[files.functions.get(&error.function).copied().unwrap_or(0)].push(error);
+    }
+    for (source, errors) in files.sources.iter().zip(by_file) {
+        if !errors.is_empty() {
+            source.report("Something couldn't be compiled", errors);
+        }
+    }
+    None
+}
+
+fn build(source: &Source, output: &Path, emit_c: Option<&Path>, cc: &str, runtime: &Path) -> bool {
+    let hir_arena = Bump::new();
+    let mut files = Files::default();
+    let Some(hir_program) = lower(&hir_arena, source, &mut files) else {
+        return false;
+    };
+
+    // the same as with the JIT, code generated for an ill-typed program isn't memory safe
+    let Some(dispatches) = typecheck(&hir_program, &files) else {
+        return false;
+    };
+    let Some(cps) = convert(&hir_program, &dispatches, &files) else {
+        return false;
+    };
+    let cps = curse_cps::optimize::optimize(cps, Default::default());
+    let c_program = match curse_codegen_c::generate(&cps) {
+        Ok(c_program) => c_program,
+        Err(error) => {
+            source.report("A code generation error occurred", vec![error]);
+            return false;
+        }
+    };
+
+    let c_path = match emit_c {
+        Some(path) => path.to_path_buf(),
+        None => std::env::temp_dir().join(format!("curse-{}.c", process::id())),
+    };
+    if let Err(e) = std::fs::write(&c_path, c_program) {
+        eprintln!("error: couldn't write {}: {e}", c_path.display());
+        return false;
+    }
+
+    let status = process::Command::new(cc)
+        .arg("-O2")
+        .arg("-o")
+        .arg(output)
+        .arg(&c_path)
+        .arg(runtime)
+        .args(curse_codegen_c::SYSTEM_LIBRARIES)
+        .status();
+
+    if emit_c.is_none() {
+        let _ = std::fs::remove_file(&c_path);
+    }
+
+    match status {
+        Ok(status) if status.success() => true,
+        Ok(status) => {
+            eprintln!("error: `{cc}` failed with {status}");
+            false
+        }
+        Err(e) => {
+            eprintln!("error: couldn't run `{cc}`: {e}");
+            false
+        }
+    }
+}
 
+fn run(source: &Source, jit: bool, vm: bool, time: bool) -> bool {
+    let hir_arena = Bump::new();
+    let mut files = Files::default();
+    let Some(hir_program) = lower(&hir_arena, source, &mut files) else {
+        return false;
+    };
+
+    let start = Instant::now();
+    let succeeded = if jit {
+        // compiled code trusts the types, so an ill-typed program could do anything
+        let Some(dispatches) = typecheck(&hir_program, &files) else {
+            return false;
+        };
+        let Some(cps) = convert(&hir_program, &dispatches, &files) else {
+            return false;
+        };
+        let cps = curse_cps::optimize::optimize(cps, Default::default());
+        match curse_jit::run(&cps) {
+            Ok(value) => {
+                println!("{value}");
+                true
+            }
+            Err(error) => {
+                source.report("A code generation error occurred", vec![error]);
+                false
+            }
+        }
+    } else if vm {
+        print_result(curse_interpreter::vm::execute_program(&hir_program))
+    } else {
+        print_result(curse_interpreter::evaluation::execute_program(&hir_program))
+    };
+
+    if time {
+        eprintln!("took {:?}", start.elapsed());
+    }
+    succeeded
+}
+
+fn print_result<T: fmt::Debug>(result: Result<T, EvalError>) -> bool {
+    match result {
+        Ok(value) => {
+            println!("{value:#?}");
+            true
+        }
+        Err(error) => {
+            eprintln!("{error:?}");
+            false
+        }
+    }
+}
+
+fn test(source: &Source, filter: &str) -> bool {
+    let hir_arena = Bump::new();
+    let mut files = Files::default();
+    let Some(hir_program) = lower(&hir_arena, source, &mut files) else {
+        return false;
+    };
+
+    let tests = curse_interpreter::testing::tests(&hir_program, filter);
+    let total = tests.len();
+    println!("running {total} tests");
+
+    let mut failures = vec![];
+    for test in tests {
+        match curse_interpreter::testing::run(&hir_program, test) {
+            Ok(()) => println!("test {} ... ok", test.ident),
+            Err(failure) => {
+                println!("test {} ... FAILED", test.ident);
+                failures.push((test, failure));
+            }
+        }
+    }
+
+    let failed = failures.len();
+    if failed > 0 {
+        println!("\nfailures:\n");
+        for (test, failure) in failures {
+            println!("---- {} ----", test.ident);
+            match failure {
+                // Spans don't say which file they're in, so this assumes the error happened in
+                // the same file as the test, which is the case for assertions in the test itself.
+                curse_interpreter::testing::Failure::Error(error) => {
+                    let file = files.functions[&test.ident.symbol];
+                    files.sources[file].report("A test failed", vec![error]);
+                }
+                curse_interpreter::testing::Failure::Crashed(message) => {
+                    println!("the interpreter panicked: {message}\n");
+                }
+            }
+        }
+    }
+
+    println!(
+        "test result: {}. {} passed; {failed} failed",
+        if failed == 0 { "ok" } else { "FAILED" },
+        total - failed,
+    );
+    failed == 0
+}
+
+/// Writes `contents` to `path`, reporting whether it worked.
+fn write_file(path: &Path, contents: String) {
+    match std::fs::write(path, contents) {
+        Ok(()) => eprintln!("wrote {}", path.display()),
+        Err(e) => eprintln!("error: couldn't write {}: {e}", path.display()),
+    }
+}
+
+/// Prints the program after `stage`, or draws it to the file at `dot` if there is one.
+fn dump(
+    source: &Source,
+    stage: Stage,
+    optimize: bool,
+    equations: Option<&Path>,
+    dot: Option<&Path>,
+) {
+    if stage == Stage::Ast {
+        if dot.is_some() {
+            eprintln!("error: `--dot` doesn't work with `--stage ast`");
+            process::exit(1);
+        }
+        let (ast_program, parsed) =
+            parse(&mut StringInterner::new(), source, &mut Files::default());
+        if parsed {
+            println!("{ast_program:#?}");
+        }
         return;
     }
 
-    println!("{hir_program:#?}");
-
-    // // println!("{:#?}", program.choice_defs);
-    //
-    // let global_ctx = ctx::Global::default();
-    //
-    // let mut typeck_ctx = ctx::Typeck::with_global(&global_ctx);
-    //
-    // // Once we have custom types, we'll need to add them here
-    // let type_scope: HashMap<mir::TypeSymbol, mir::Type<'_>> = HashMap::new();
-    //
-    // // program.struct_defs
-    // // program.choice_defs
-    //
-    // let mut function_to_typescope: HashMap<
-    //     mir::ValueSymbol,
-    //     HashMap<mir::TypeSymbol, mir::Type<'_>>,
-    // > = HashMap::new();
-    //
-    // let globals: HashMap<mir::ValueSymbol, mir::TypeTemplate> = typeck_ctx
-    //     .default_globals()
-    //     .chain(program.fn_defs.iter().map(|fn_def| {
-    //         // Since items (i.e. functions for now) can be generic over types,
-    //         // we need to extend the set of currently in-scope types with the
-    //         // generics that this item introduces. To avoid bringing the types
-    //         // into the global program scope, we'll create a temporary inner scope
-    //         let mut inner_type_scope = type_scope.clone();
-    //
-    //         let sig = fn_def
-    //             .type_sig
-    //             .as_ref()
-    //             .expect("functions with inferred type signatures aren't yet supported");
-    //
-    //         let mut typevars = SmallVec::with_capacity(sig.generics.len());
-    //         inner_type_scope.reserve(sig.generics.len());
-    //
-    //         for generic in sig.generics.iter() {
-    //             let var = typeck_ctx.new_typevar();
-    //             typevars.push(var);
-    //             inner_type_scope.insert(
-    //                 typeck_ctx.global.get_or_intern_type_ident(generic).symbol,
-    //                 mir::Type {
-    //                     kind: mir::TypeKind::Var(var),
-    //                     span: generic.span(),
-    //                 },
-    //             );
-    //         }
-    //
-    //         // TODO(quinn): make types brought into scope for the particular function
-    //         // (aka the generics here) be passed in in some other intelligent
-    //         // way so we don't have to clone the hashmap.
-    //         let ty = typeck_ctx.type_from_ast(sig.ty, &inner_type_scope);
-    //
-    //         let fn_name = typeck_ctx
-    //             .global
-    //             .get_or_intern_value_ident(&fn_def.name)
-    //             .symbol;
-    //
-    //         function_to_typescope.insert(fn_name, inner_type_scope);
-    //         (fn_name, mir::TypeTemplate { typevars, ty })
-    //     }))
-    //     .collect();
-    //
-    // let mut locals: Vec<(mir::ValueIdent, mir::Type<'_>)> = Vec::with_capacity(16);
-    // let mut errors: Vec<mir::LowerError<'_>> = Vec::with_capacity(0);
-    //
-    // let lowered_items: Result<
-    //     HashMap<mir::ValueSymbol, (mir::TypeTemplate, mir::Expr<'_>)>,
-    //     mir::PushedErrors,
-    // > = program
-    //     .fn_defs
-    //     .iter()
-    //     .map(|item| {
-    //         let item_name: mir::ValueSymbol = typeck_ctx
-    //             .global
-    //             .get_or_intern_value_ident(&item.name)
-    //             .symbol;
-    //
-    //         let mut scope = mir::Scope::new(
-    //             &mut typeck_ctx,
-    //             &function_to_typescope[&item_name],
-    //             &mut errors,
-    //             &globals,
-    //             &mut locals,
-    //         );
-    //
-    //         let polytype = globals[&item_name].clone();
-    //         let expr = scope.lower_closure(&item.function)?;
-    //
-    //         // Make sure the function actually lines up with its type signature.
-    //         let ty = scope.ctx.monomorphize(&polytype);
-    //         scope.unify(expr.ty(), ty);
-    //         if scope.had_errors() {
-    //             return Err(mir::PushedErrors);
-    //         }
-    //         Ok((item_name, (polytype, expr)))
-    //     })
-    //     .collect();
-    //
-    // // Lowering and type errors
-    // let Ok(lowered_items) = lowered_items else {
-    //     assert!(!errors.is_empty());
-    //     let errors = mir::LowerErrors {
-    //         code: NamedSource::new("input", input.to_string()),
-    //         errors,
-    //     };
-    //     let mut buf = String::with_capacity(1024);
-    //     GraphicalReportHandler::new()
-    //         .render_report(&mut buf, &errors)
-    //         .unwrap();
-    //     println!("{buf}");
-    //     return;
-    // };
-    //
-    // assert!(errors.is_empty());
-    //
-    // let usefulness_errors =
-    //     mir::usefulness::check(lowered_items.values().map(|(_, expr)| expr), &typeck_ctx);
-    //
-    // if usefulness_errors.is_empty() {
-    //     println!("all matches are exhaustive and useful");
-    // } else {
-    //     let errors = mir::usefulness::UsefulnessErrors {
-    //         code: NamedSource::new("input", input.to_string()),
-    //         errors: usefulness_errors,
-    //     };
-    //     let mut buf = String::with_capacity(1024);
-    //     GraphicalReportHandler::new()
-    //         .render_report(&mut buf, &errors)
-    //         .unwrap();
-    //     println!("{buf}");
-    //     return;
-    // }
+    let hir_arena = Bump::new();
+    let mut files = Files::default();
+    let Some(hir_program) = lower(&hir_arena, source, &mut files) else {
+        return;
+    };
+
+    if stage == Stage::Hir {
+        if let Some(path) = dot {
+            let mut builder = curse_hir::dot::Builder::new();
+            builder.visit_program(&hir_program);
+            write_file(path, builder.finish());
+        } else {
+            println!("{hir_program:#?}");
+        }
+        return;
+    }
+
+    if stage == Stage::Mir {
+        dump_types(&hir_program, &files, equations, dot);
+        return;
+    }
+
+    let Some(dispatches) = typecheck(&hir_program, &files) else {
+        return;
+    };
+    let Some(mut cps) = convert(&hir_program, &dispatches, &files) else {
+        return;
+    };
+    if optimize {
+        cps = curse_cps::optimize::optimize(cps, Default::default());
+    }
+    if let Some(path) = dot {
+        let mut builder = curse_cps::dot::Builder::new();
+        builder.visit_expr(&cps);
+        write_file(path, builder.finish());
+    } else {
+        println!("{cps}");
+    }
+}
+
+/// Infers the type of every top level function, printing them in the order that they were checked
+/// in, or reporting the type errors in each of the files they're in. With `dot`, the typed
+/// functions are drawn to that file instead of being printed.
+fn dump_types(
+    hir_program: &hir::Program<'_>,
+    files: &Files,
+    equations: Option<&Path>,
+    dot: Option<&Path>,
+) {
+    let global = curse_mir::ctx::Global::default();
+    let mut typeck = curse_mir::ctx::Typeck::with_global(&global);
+    let checked = curse_mir::check_program(&mut typeck, hir_program);
+
+    if let Some(path) = equations {
+        write_file(path, typeck.equations.dot(&typeck));
+    }
+
+    match (checked, dot) {
+        (Ok(typed), Some(path)) => {
+            let mut builder = curse_mir::dot::Builder::new(&typeck);
+            builder.visit_program(&typed);
+            write_file(path, builder.finish());
+        }
+        (Ok(typed), None) => {
+            for name in typed.components.iter().flatten() {
+                let template = &typed.functions[name].template;
+                println!("{name}: {}", template.display(&typeck));
+            }
+            let mut impls: Vec<_> = typed.impls.iter().collect();
+            impls.sort_by_key(|((trait_, ty), _)| (trait_.name(), ty.to_string()));
+            for ((trait_, ty), expr) in impls {
+                let ty_printer = expr.ty().kind.display(&typeck);
+                println!("impl {} {ty}: {ty_printer}", trait_.name());
+            }
+        }
+        (Err(failures), _) => {
+            for (name, errors) in failures {
+                files.sources[files.functions[&name]].report("A type error occurred", errors);
+            }
+        }
+    }
 }
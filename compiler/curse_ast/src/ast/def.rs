@@ -7,10 +7,10 @@ ast_struct! {
     /// Example: `|K * V|`
     #[derive(Clone, Debug)]
     pub struct GenericParams {
-        open: tok::Pipe,
-        params: Vec<(Ident, tok::Star)>,
-        last: Ident,
-        close: tok::Pipe,
+        pub open: tok::Pipe,
+        pub params: Vec<(Ident, tok::Star)>,
+        pub last: Ident,
+        pub close: tok::Pipe,
     }
 }
 
@@ -20,10 +20,22 @@ impl GenericParams {
     }
 }
 
+ast_struct! {
+    /// Example: `#[tail_recursive]`
+    #[derive(Clone, Debug)]
+    pub struct Attribute {
+        pub pound: tok::Pound,
+        pub lbracket: tok::LBracket,
+        pub ident: Ident,
+        pub rbracket: tok::RBracket,
+    }
+}
+
 ast_struct! {
     /// Example: `fn add = |x, y| x + y`
     #[derive(Clone, Debug)]
     pub struct FunctionDef {
+        pub attributes: Vec<Attribute>,
         pub fn_: tok::Fn,
         pub ident: Ident,
         pub function: Closure,
@@ -51,6 +63,41 @@ ast_struct! {
     }
 }
 
+ast_struct! {
+    /// Example: `impl Add Point |a, b| ...`
+    #[derive(Clone, Debug)]
+    pub struct ImplDef {
+        pub impl_: tok::Impl,
+        pub trait_: Ident,
+        pub ty: Ident,
+        pub function: Closure,
+    }
+}
+
+ast_struct! {
+    /// Example: `trait Combine combine |Self, Self| Self`
+    #[derive(Clone, Debug)]
+    pub struct TraitDef {
+        pub trait_: tok::Trait,
+        pub ident: Ident,
+        pub method: Ident,
+        pub signature: MethodSignature,
+    }
+}
+
+ast_struct! {
+    /// Example: `|Self, I32| Self`
+    #[derive(Clone, Debug)]
+    pub struct MethodSignature {
+        pub open: tok::Pipe,
+        pub lhs: Type,
+        pub comma: tok::Comma,
+        pub rhs: Type,
+        pub close: tok::Pipe,
+        pub output: Type,
+    }
+}
+
 ast_struct! {
     /// Example: `choice Option |T| { Some T, None {} }`
     #[derive(Clone, Debug)]
@@ -66,10 +113,10 @@ ast_struct! {
     /// Example: `{ Some T, None {} }`
     #[derive(Debug, Clone)]
     pub struct Variants {
-        lbrace: tok::LBrace,
-        variants: Vec<(VariantDef, tok::Comma)>,
-        last: Option<VariantDef>,
-        rbrace: tok::RBrace,
+        pub lbrace: tok::LBrace,
+        pub variants: Vec<(VariantDef, tok::Comma)>,
+        pub last: Option<VariantDef>,
+        pub rbrace: tok::RBrace,
     }
 }
 
@@ -98,9 +145,22 @@ impl HasSpan for GenericParams {
     }
 }
 
+impl HasSpan for Attribute {
+    fn start(&self) -> u32 {
+        self.pound.start()
+    }
+
+    fn end(&self) -> u32 {
+        self.rbracket.end()
+    }
+}
+
 impl HasSpan for FunctionDef {
     fn start(&self) -> u32 {
-        self.fn_.start()
+        match self.attributes.first() {
+            Some(attribute) => attribute.start(),
+            None => self.fn_.start(),
+        }
     }
 
     fn end(&self) -> u32 {
@@ -118,6 +178,36 @@ impl HasSpan for StructDef {
     }
 }
 
+impl HasSpan for ImplDef {
+    fn start(&self) -> u32 {
+        self.impl_.start()
+    }
+
+    fn end(&self) -> u32 {
+        self.function.end()
+    }
+}
+
+impl HasSpan for TraitDef {
+    fn start(&self) -> u32 {
+        self.trait_.start()
+    }
+
+    fn end(&self) -> u32 {
+        self.signature.end()
+    }
+}
+
+impl HasSpan for MethodSignature {
+    fn start(&self) -> u32 {
+        self.open.start()
+    }
+
+    fn end(&self) -> u32 {
+        self.output.end()
+    }
+}
+
 impl HasSpan for ChoiceDef {
     fn start(&self) -> u32 {
         self.choice.start()
@@ -7,20 +7,39 @@ pub enum Closure {
     NonPiecewise(Arm),
     Piecewise(
         tok::LParen,
-        // Parser will only produce vecs with len >= 1
+        // Parser will only produce vecs with len >= 1, unless some arms had syntax errors
         Vec<(Arm, tok::Comma)>,
         Option<Arm>,
         tok::RParen,
     ),
     Empty(tok::LParen, tok::RParen),
+    /// A function's closure with a syntax error, which has already been reported.
+    Error(Span),
 }
 
 impl Closure {
+    /// Makes a piecewise closure out of the arms that parsed, leaving out the ones that didn't.
+    pub fn piecewise(
+        lparen: tok::LParen,
+        arms: Vec<(Option<Arm>, tok::Comma)>,
+        last: Option<Option<Arm>>,
+        rparen: tok::RParen,
+    ) -> Self {
+        let arms: Vec<_> = arms
+            .into_iter()
+            .filter_map(|(arm, comma)| Some((arm?, comma)))
+            .collect();
+        match (arms.is_empty(), last.flatten()) {
+            (true, None) => Closure::Empty(lparen, rparen),
+            (_, last) => Closure::Piecewise(lparen, arms, last, rparen),
+        }
+    }
+
     pub fn iter_arms(&self) -> Iter<'_, Arm, tok::Comma> {
         let (arms, last) = match self {
             Closure::NonPiecewise(arm) => (&[] as _, Some(arm)),
             Closure::Piecewise(_, arms, last, _) => (arms.as_slice(), last.as_ref()),
-            Closure::Empty(_, _) => (&[] as _, None),
+            Closure::Empty(_, _) | Closure::Error(_) => (&[] as _, None),
         };
 
         Iter::new(arms.iter(), last)
@@ -32,6 +51,7 @@ impl HasSpan for Closure {
         match self {
             Closure::NonPiecewise(arm) => arm.start(),
             Closure::Piecewise(lparen, ..) | Closure::Empty(lparen, ..) => lparen.start(),
+            Closure::Error(span) => span.start(),
         }
     }
 
@@ -39,6 +59,7 @@ impl HasSpan for Closure {
         match self {
             Closure::NonPiecewise(arm) => arm.end(),
             Closure::Piecewise(.., rparen) | Closure::Empty(_, rparen) => rparen.end(),
+            Closure::Error(span) => span.end(),
         }
     }
 
@@ -49,6 +70,7 @@ impl HasSpan for Closure {
                 start: lparen.start(),
                 end: rparen.end(),
             },
+            Closure::Error(span) => *span,
         }
     }
 }
@@ -15,7 +15,8 @@ pub enum Expr {
     Closure(Box<Closure>),
     Appl(Box<Appl>),
     Region(Box<Region>),
-    Error,
+    /// An expression with a syntax error, which has already been reported.
+    Error(Span),
 }
 
 ast_struct! {
@@ -87,7 +88,7 @@ impl HasSpan for Expr {
             Expr::Closure(closure) => closure.start(),
             Expr::Appl(appl) => appl.start(),
             Expr::Region(region) => region.start(),
-            Expr::Error => todo!(),
+            Expr::Error(span) => span.start(),
         }
     }
 
@@ -101,7 +102,7 @@ impl HasSpan for Expr {
             Expr::Closure(closure) => closure.end(),
             Expr::Appl(appl) => appl.end(),
             Expr::Region(region) => region.end(),
-            Expr::Error => todo!(),
+            Expr::Error(span) => span.end(),
         }
     }
 
@@ -115,7 +116,7 @@ impl HasSpan for Expr {
             Expr::Closure(closure) => closure.span(),
             Expr::Appl(appl) => appl.span(),
             Expr::Region(region) => region.span(),
-            Expr::Error => todo!(),
+            Expr::Error(span) => *span,
         }
     }
 }
@@ -202,7 +203,7 @@ impl HasSpan for Region {
     }
 
     fn end(&self) -> u32 {
-        self.body.end()
+        self.rbrace.end()
     }
 }
 
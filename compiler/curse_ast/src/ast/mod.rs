@@ -23,7 +23,8 @@ pub mod bikeshed {
 }
 
 pub use def::{
-    ChoiceDef, ExplicitTypes, FunctionDef, GenericParams, StructDef, VariantDef, Variants,
+    Attribute, ChoiceDef, ExplicitTypes, FunctionDef, GenericParams, ImplDef, MethodSignature,
+    StructDef, TraitDef, VariantDef, Variants,
 };
 pub use expr::{Appl, Arm, Closure, Expr, Param, Paren, Region, RegionKind, Symbol};
 pub use pat::Pat;
@@ -1,10 +1,12 @@
-use crate::ast::{bikeshed, ChoiceDef, FunctionDef, StructDef};
+use crate::ast::{bikeshed, ChoiceDef, FunctionDef, ImplDef, StructDef, TraitDef};
 
 #[derive(Clone, Debug, Default)]
 pub struct Program {
     pub function_defs: Vec<FunctionDef>,
     pub struct_defs: Vec<StructDef>,
     pub choice_defs: Vec<ChoiceDef>,
+    pub impl_defs: Vec<ImplDef>,
+    pub trait_defs: Vec<TraitDef>,
     pub dynamic_imports: Vec<bikeshed::DynamicImport>,
 }
 
@@ -24,6 +26,16 @@ impl Program {
         self
     }
 
+    pub fn with_impl_def(mut self, impl_def: ImplDef) -> Self {
+        self.impl_defs.push(impl_def);
+        self
+    }
+
+    pub fn with_trait_def(mut self, trait_def: TraitDef) -> Self {
+        self.trait_defs.push(trait_def);
+        self
+    }
+
     pub fn with_dynamic_import(mut self, dynamic_import: bikeshed::DynamicImport) -> Self {
         self.dynamic_imports.push(dynamic_import);
         self
@@ -1,7 +1,7 @@
 use crate::ast::{tok, Iter};
 use crate::ast_struct;
-use curse_span::HasSpan;
 use curse_interner::Ident;
+use curse_span::HasSpan;
 
 ast_struct! {
     #[derive(Clone, Debug)]
@@ -9,6 +9,8 @@ ast_struct! {
         pub lbrace: tok::LBrace,
         pub fields: Vec<(Field<T>, tok::Comma)>,
         pub trailing: Option<Field<T>>,
+        // the `..` at the end of a record pattern that matches records with other fields too
+        pub rest: Option<tok::DotDot>,
         pub rbrace: tok::RBrace,
     }
 }
@@ -91,9 +91,14 @@ declare_tokens! {
     "fn" => Fn,
     "choice" => Choice,
     "struct" => Struct,
+    "impl" => Impl,
+    "trait" => Trait,
     "{" => LBrace,
     "}" => RBrace,
     "->" => Arrow,
+    "#" => Pound,
+    "[" => LBracket,
+    "]" => RBracket,
 
     "=" => Eq,
     "<" => Lt,
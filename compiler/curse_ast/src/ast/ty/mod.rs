@@ -10,7 +10,8 @@ pub enum Type {
     // Failing to specify the type of a field in a record should be reported during ast lowering,
     // not during parsing, so we allow for a type to be omitted in this representation.
     Record(Box<Record<Self>>),
-    Error,
+    /// A type with a syntax error, which has already been reported.
+    Error(Span),
 }
 
 impl HasSpan for Type {
@@ -18,7 +19,7 @@ impl HasSpan for Type {
         match self {
             Type::Named(named) => named.start(),
             Type::Record(record) => record.start(),
-            Type::Error => todo!(),
+            Type::Error(span) => span.start(),
         }
     }
 
@@ -26,7 +27,7 @@ impl HasSpan for Type {
         match self {
             Type::Named(named) => named.end(),
             Type::Record(record) => record.end(),
-            Type::Error => todo!(),
+            Type::Error(span) => span.end(),
         }
     }
 
@@ -34,7 +35,7 @@ impl HasSpan for Type {
         match self {
             Type::Named(named) => named.span(),
             Type::Record(record) => record.span(),
-            Type::Error => todo!(),
+            Type::Error(span) => *span,
         }
     }
 }
@@ -0,0 +1,181 @@
+//! The lossless concrete syntax tree (CST), which is the AST along with the comments and
+//! whitespace that the parser skips.
+//!
+//! Rather than putting trivia in the AST itself, it's kept in a side table next to the span of
+//! every token, and attached to nodes through their spans. A comment on the same line as the end
+//! of a node trails it, and anything else leads the node after it:
+//!
+//! ```text
+//! // leads `fn add`
+//! fn add |x, y| x + y // trails `fn add`, and the `y` at the end of it
+//! ```
+
+use crate::ast::{tok, Program};
+use curse_span::{HasSpan, Span};
+use std::fmt;
+
+/// A parsed file that can be turned back into its input byte for byte.
+#[derive(Clone, Debug)]
+pub struct Cst<'input> {
+    pub program: Program,
+    pub tokens: Tokens<'input>,
+}
+
+impl fmt::Display for Cst<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.tokens, f)
+    }
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum TriviaKind {
+    Whitespace,
+    /// Example: `// hello`, without the line break after it.
+    Comment,
+    /// Example: `$`, or anything else the lexer couldn't make a token out of. It's reported as a
+    /// syntax error, and the parser carries on as if it weren't there.
+    Unknown,
+}
+
+/// A piece of the input that the parser skips.
+#[derive(Copy, Clone, Debug)]
+pub struct Trivia<'input> {
+    pub kind: TriviaKind,
+    pub literal: tok::Literal<'input>,
+}
+
+impl Trivia<'_> {
+    fn has_line_break(&self) -> bool {
+        self.kind == TriviaKind::Whitespace && self.literal.literal.contains('\n')
+    }
+}
+
+impl HasSpan for Trivia<'_> {
+    fn start(&self) -> u32 {
+        self.literal.start()
+    }
+
+    fn end(&self) -> u32 {
+        self.literal.end()
+    }
+}
+
+impl fmt::Display for Trivia<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.literal, f)
+    }
+}
+
+/// Every token in a file, in order, with the trivia in the gaps between them.
+#[derive(Clone, Debug)]
+pub struct Tokens<'input> {
+    input: &'input str,
+    spans: Vec<Span>,
+    trivia: Vec<Trivia<'input>>,
+    /// Where each gap starts in `trivia`. Gap `i` comes just before token `i`, and the last one is
+    /// at the end of the file, so there's one more gap than there are tokens.
+    gaps: Vec<usize>,
+}
+
+impl<'input> Tokens<'input> {
+    pub fn new(input: &'input str) -> Self {
+        Tokens {
+            input,
+            spans: vec![],
+            trivia: vec![],
+            gaps: vec![0],
+        }
+    }
+
+    /// Adds the next token, which has to come after everything added so far.
+    pub fn push_token(&mut self, span: Span) {
+        self.spans.push(span);
+        self.gaps.push(self.trivia.len());
+    }
+
+    /// Adds the next piece of trivia, which has to come after everything added so far.
+    pub fn push_trivia(&mut self, kind: TriviaKind, span: Span) {
+        self.trivia.push(Trivia {
+            kind,
+            literal: tok::Literal {
+                location: span.start,
+                literal: &self.input[span.start as usize..span.end as usize],
+            },
+        });
+    }
+
+    /// The span of every token, in order.
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+
+    /// The input that `span` covers.
+    pub fn text(&self, span: Span) -> &'input str {
+        &self.input[span.start as usize..span.end as usize]
+    }
+
+    /// The trivia just before `node`, on the lines above it or at the start of its line.
+    pub fn leading(&self, node: impl HasSpan) -> &[Trivia<'input>] {
+        match self
+            .spans
+            .binary_search_by_key(&node.start(), |span| span.start)
+        {
+            Ok(index) => self.split(index).1,
+            Err(_) => &[],
+        }
+    }
+
+    /// The trivia just after `node` on the same line as its end, like a comment after it.
+    pub fn trailing(&self, node: impl HasSpan) -> &[Trivia<'input>] {
+        match self
+            .spans
+            .binary_search_by_key(&node.end(), |span| span.end)
+        {
+            Ok(index) => self.split(index + 1).0,
+            Err(_) => &[],
+        }
+    }
+
+    /// The trivia after the last token that doesn't trail it.
+    pub fn dangling(&self) -> &[Trivia<'input>] {
+        self.split(self.spans.len()).1
+    }
+
+    fn gap(&self, index: usize) -> &[Trivia<'input>] {
+        let end = self
+            .gaps
+            .get(index + 1)
+            .copied()
+            .unwrap_or(self.trivia.len());
+        &self.trivia[self.gaps[index]..end]
+    }
+
+    /// Splits a gap into what trails the token before it and what leads the token after it, at
+    /// the first line break. Nothing trails the start of the file.
+    fn split(&self, index: usize) -> (&[Trivia<'input>], &[Trivia<'input>]) {
+        let gap = self.gap(index);
+        if index == 0 {
+            return (&[], gap);
+        }
+        let line_break = gap
+            .iter()
+            .position(Trivia::has_line_break)
+            .unwrap_or(gap.len());
+        gap.split_at(line_break)
+    }
+}
+
+impl fmt::Display for Tokens<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, span) in self.spans.iter().enumerate() {
+            for trivia in self.gap(index) {
+                fmt::Display::fmt(trivia, f)?;
+            }
+            f.write_str(&self.input[span.start as usize..span.end as usize])?;
+        }
+        for trivia in self.gap(self.spans.len()) {
+            fmt::Display::fmt(trivia, f)?;
+        }
+        Ok(())
+    }
+}
@@ -1,11 +1,12 @@
-use curse_hir::hir::PrimitiveType;
+use crate::{Escape, NonTailCall};
+use curse_hir::hir::{PrimitiveType, Trait};
 use curse_interner::{Ident, InternedString};
 use curse_span::{HasSpan, Span};
 use miette::{Diagnostic, LabeledSpan};
 use std::{fmt, iter};
 use thiserror::Error;
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum LoweringError {
     TypeRecordMissingFieldType {
         field_ident: Ident,
@@ -29,9 +30,61 @@ pub enum LoweringError {
         arg_spans: Vec<Span>,
     },
     Region(RegionError, Span),
+    UnknownAttribute {
+        attribute: Ident,
+        span: Span,
+    },
+    NotTailRecursive {
+        ident: Ident,
+        /// The `#[tail_recursive]` attribute.
+        attribute: Span,
+        /// at least 1
+        calls: Vec<NonTailCall>,
+    },
+    UnknownTrait {
+        trait_: Ident,
+    },
+    BuiltinTrait {
+        trait_: Ident,
+    },
+    MethodNotOnSelf {
+        method: Ident,
+        /// The type of the left argument.
+        lhs: Span,
+    },
+    MultipleImpls {
+        trait_: Trait,
+        ty: Ident,
+        previous: Span,
+        redefined: Span,
+    },
+    UnknownType {
+        /// The whole path, e.g. `std::Option`.
+        path: String,
+        span: Span,
+    },
+    WrongTypeArgCount {
+        /// The struct or choice that got the wrong number of arguments.
+        def_ident: Ident,
+        expected: usize,
+        found: usize,
+        span: Span,
+    },
+    RecursiveStruct {
+        ident: Ident,
+        /// The part of the struct's type that contains the struct again.
+        field: Span,
+    },
+    EscapingReference {
+        /// The name that the region shadows with the reference.
+        ident: Ident,
+        /// The whole region.
+        region: Span,
+        escape: Escape,
+    },
 }
 
-#[derive(Debug, Error)]
+#[derive(Clone, Debug, Error)]
 pub enum RegionError {
     #[error("cannot shadow boolean literal `true`")]
     LiteralTrue,
@@ -41,9 +94,11 @@ pub enum RegionError {
     LiteralNumber,
     #[error("cannot shadow a record with fixed values")]
     RecordWithValue,
+    #[error("cannot shadow a constructor")]
+    Constructor,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Debug)]
 pub enum UnexpectedTypeArgs {
     /// A generic param that unexpectedly had type arguments, e.g. `T I32`.
     GenericParam { def_ident: Ident },
@@ -82,6 +137,44 @@ impl fmt::Display for LoweringError {
                 ),
             },
             LoweringError::Region(err, _) => fmt::Display::fmt(err, f),
+            LoweringError::UnknownAttribute { attribute, .. } => {
+                write!(f, "unknown attribute `{attribute}`")
+            }
+            LoweringError::NotTailRecursive { ident, .. } => {
+                write!(f, "function `{ident}` is not tail recursive")
+            }
+            LoweringError::UnknownTrait { trait_ } => write!(f, "unknown trait `{trait_}`"),
+            LoweringError::BuiltinTrait { trait_ } => {
+                write!(f, "`{trait_}` is already a builtin trait")
+            }
+            LoweringError::MethodNotOnSelf { method, .. } => {
+                write!(f, "the left argument of method `{method}` is not `Self`")
+            }
+            LoweringError::MultipleImpls { trait_, ty, .. } => {
+                write!(
+                    f,
+                    "`{}` is implemented for `{ty}` multiple times",
+                    trait_.name()
+                )
+            }
+            LoweringError::UnknownType { path, .. } => write!(f, "unknown type `{path}`"),
+            LoweringError::WrongTypeArgCount {
+                def_ident,
+                expected,
+                found,
+                ..
+            } => write!(
+                f,
+                "`{def_ident}` takes {expected} type argument{}, but {found} {} given",
+                if *expected == 1 { "" } else { "s" },
+                if *found == 1 { "was" } else { "were" }
+            ),
+            LoweringError::RecursiveStruct { ident, .. } => {
+                write!(f, "struct `{ident}` contains itself")
+            }
+            LoweringError::EscapingReference { ident, .. } => {
+                write!(f, "the reference to `{ident}` escapes its region")
+            }
         }
     }
 }
@@ -103,6 +196,41 @@ impl Diagnostic for LoweringError {
             LoweringError::Region(_, _) => {
                 Some(Box::new("use an identifer, or a record of identifiers"))
             }
+            LoweringError::UnknownAttribute { .. } => {
+                Some(Box::new("the attributes are `#[tail_recursive]` and `#[test]`"))
+            }
+            LoweringError::NotTailRecursive { .. } => Some(Box::new(
+                "make each recursive call the last thing its arm does, e.g. by passing an accumulator along",
+            )),
+            LoweringError::UnknownTrait { .. } => Some(Box::new(format!(
+                "the builtin traits are {}, and others can be declared with `trait`",
+                Trait::ALL
+                    .map(|(name, _)| format!("`{name}`"))
+                    .join(", ")
+            ))),
+            LoweringError::BuiltinTrait { trait_ } => {
+                Some(Box::new(format!("use a name other than `{trait_}`")))
+            }
+            LoweringError::MethodNotOnSelf { .. } => Some(Box::new(
+                "change it to `Self`, since the method calls the impl for the type of its left argument",
+            )),
+            LoweringError::MultipleImpls { .. } => Some(Box::new("remove one of the impls")),
+            LoweringError::UnknownType { .. } => {
+                Some(Box::new("use a struct or choice that is defined"))
+            }
+            LoweringError::WrongTypeArgCount { expected: 0, .. } => {
+                Some(Box::new("remove the type arguments"))
+            }
+            LoweringError::WrongTypeArgCount { expected, .. } => Some(Box::new(format!(
+                "give it {expected} type argument{}, separated by `*`",
+                if *expected == 1 { "" } else { "s" }
+            ))),
+            LoweringError::RecursiveStruct { .. } => Some(Box::new(
+                "put it inside a choice, so that there's a way for it to end",
+            )),
+            LoweringError::EscapingReference { ident, .. } => Some(Box::new(format!(
+                "give back what you need from `{ident}` instead of the reference itself"
+            ))),
         }
     }
 
@@ -183,10 +311,125 @@ impl Diagnostic for LoweringError {
                         RegionError::RecordWithValue => {
                             "record patterns with fixed value now allowed here"
                         }
+                        RegionError::Constructor => "constructor not allowed here",
                     },
                 ))))
                 //
             }
+            LoweringError::UnknownAttribute { attribute, span } => {
+                Some(Box::new(iter::once(LabeledSpan::at(
+                    span.start_len(),
+                    format!("`{attribute}` is not an attribute"),
+                ))))
+            }
+            LoweringError::NotTailRecursive {
+                ident,
+                attribute,
+                calls,
+            } => Some(Box::new(
+                iter::once(LabeledSpan::at(
+                    attribute.start_len(),
+                    "required to be tail recursive here",
+                ))
+                .chain(calls.iter().map(move |call| match call {
+                    NonTailCall::Argument(span) => LabeledSpan::at(
+                        span.start_len(),
+                        "the result of this call is used afterwards",
+                    ),
+                    NonTailCall::InClosure(span) => {
+                        LabeledSpan::at(span.start_len(), "this call is inside of a closure")
+                    }
+                    NonTailCall::Escapes(span) => LabeledSpan::at(
+                        span.start_len(),
+                        format!(
+                            "`{ident}` is used as a value here, so it can be called from anywhere"
+                        ),
+                    ),
+                })),
+            )),
+            LoweringError::UnknownTrait { trait_ } => Some(Box::new(iter::once(LabeledSpan::at(
+                trait_.span().start_len(),
+                format!("`{trait_}` is not a trait"),
+            )))),
+            LoweringError::BuiltinTrait { trait_ } => Some(Box::new(iter::once(LabeledSpan::at(
+                trait_.span().start_len(),
+                format!("`{trait_}` is declared here"),
+            )))),
+            LoweringError::MethodNotOnSelf { lhs, .. } => Some(Box::new(iter::once(
+                LabeledSpan::at(lhs.start_len(), "expected `Self` here"),
+            ))),
+            LoweringError::MultipleImpls {
+                previous,
+                redefined,
+                ..
+            } => Some(Box::new(
+                [
+                    LabeledSpan::at(previous.start_len(), "previous impl here"),
+                    LabeledSpan::at(redefined.start_len(), "implemented again here"),
+                ]
+                .into_iter(),
+            )),
+            LoweringError::UnknownType { path, span } => Some(Box::new(iter::once(
+                LabeledSpan::at(span.start_len(), format!("`{path}` is not defined")),
+            ))),
+            LoweringError::WrongTypeArgCount {
+                def_ident,
+                expected,
+                span,
+                ..
+            } => Some(Box::new(
+                [
+                    LabeledSpan::at(
+                        def_ident.span().start_len(),
+                        format!("`{def_ident}` defined here"),
+                    ),
+                    LabeledSpan::at(
+                        span.start_len(),
+                        format!(
+                            "expected {expected} type argument{}",
+                            if *expected == 1 { "" } else { "s" }
+                        ),
+                    ),
+                ]
+                .into_iter(),
+            )),
+            LoweringError::RecursiveStruct { ident, field } => Some(Box::new(
+                [
+                    LabeledSpan::at(ident.span().start_len(), format!("`{ident}` defined here")),
+                    LabeledSpan::at(field.start_len(), format!("contains `{ident}` here")),
+                ]
+                .into_iter(),
+            )),
+            LoweringError::EscapingReference {
+                ident,
+                region,
+                escape,
+            } => {
+                let region = LabeledSpan::at(
+                    region.start_len(),
+                    format!("`{ident}` is a reference inside of this region"),
+                );
+                let escape = match *escape {
+                    Escape::Returned(span) => vec![LabeledSpan::at(
+                        span.start_len(),
+                        format!("`{ident}` can be part of the region's result from here"),
+                    )],
+                    Escape::Captured { closure, capture } => vec![
+                        LabeledSpan::at(
+                            closure.start_len(),
+                            "this closure can be part of the region's result",
+                        ),
+                        LabeledSpan::at(capture.start_len(), format!("it captures `{ident}` here")),
+                    ],
+                    Escape::Assigned(span) => vec![LabeledSpan::at(
+                        span.start_len(),
+                        format!(
+                            "`{ident}` can be assigned to something that outlives the region here"
+                        ),
+                    )],
+                };
+                Some(Box::new(iter::once(region).chain(escape)))
+            }
         }
     }
 }
@@ -2,6 +2,11 @@
 
 mod error;
 mod lowerer;
+mod recursive_structs;
+mod regions;
+mod tail_calls;
 
 pub use error::{LoweringError, UnexpectedTypeArgs};
-pub use lowerer::{Lower, Lowerer};
+pub use lowerer::{Items, Lower, Lowerer};
+pub use regions::Escape;
+pub use tail_calls::NonTailCall;
@@ -1,13 +1,17 @@
 use crate::error::RegionError;
+use crate::recursive_structs::recursive_structs;
+use crate::regions::escaping_references;
+use crate::tail_calls::non_tail_calls;
 use crate::{LoweringError, UnexpectedTypeArgs};
 use bumpalo::Bump;
 use curse_ast::ast;
 use curse_hir::hir::{
-    Appl, Arm, ChoiceDef, Constructor, Expr, ExprKind, ExprRef, FunctionDef, Lit, Map, Param, Pat,
-    PatKind, PatRef, Program, Region, RegionKind, StructDef, Symbol, Type, TypeKind, TypeRef,
+    Appl, Arm, Attribute, AttributeKind, ChoiceDef, Constructor, Expr, ExprKind, ExprRef,
+    FunctionDef, ImplDef, Lit, Map, Param, Pat, PatKind, PatRef, Program, Region, RegionKind,
+    StructDef, Symbol, Trait, TraitDef, Type, TypeKind, TypeRef,
 };
 use curse_interner::{Ident, InternedString};
-use curse_span::HasSpan;
+use curse_span::{HasSpan, Span};
 use std::collections::HashSet;
 use std::{collections::HashMap, slice};
 
@@ -37,6 +41,13 @@ pub trait Lower<'hir> {
 pub struct Lowerer<'hir> {
     pub bump: &'hir Bump,
     in_scope_generic_params: Option<&'hir [Ident]>,
+    /// The name of each struct and choice in the program, and how many
+    /// generic parameters it has, so that named types can be resolved before
+    /// all of the defs are lowered.
+    type_defs: HashMap<InternedString, (Ident, usize)>,
+    /// The name of each trait that the program declares, so that impls can
+    /// refer to them.
+    trait_names: HashSet<InternedString>,
     pub errors: Vec<LoweringError>,
 }
 
@@ -45,6 +56,8 @@ impl<'hir> Lowerer<'hir> {
         Lowerer {
             bump,
             in_scope_generic_params: None,
+            type_defs: HashMap::new(),
+            trait_names: HashSet::new(),
             errors: Vec::with_capacity(0),
         }
     }
@@ -83,10 +96,92 @@ impl<'hir> Lowerer<'hir> {
     }
 }
 
+impl<'hir> Lowerer<'hir> {
+    /// Makes the structs, choices and traits of `program` known to the lowerer, so that its
+    /// items can be lowered one at a time. A program that's made of several files declares each
+    /// of them in turn, and the first definition of a name is the one that's kept.
+    pub fn declare(&mut self, program: &ast::Program) {
+        let struct_names = program
+            .struct_defs
+            .iter()
+            .map(|def| (def.ident, &def.generic_params));
+        let choice_names = program
+            .choice_defs
+            .iter()
+            .map(|def| (def.ident, &def.generic_params));
+        for (ident, generic_params) in struct_names.chain(choice_names) {
+            // redefinitions are reported when linking
+            let arity = generic_params
+                .as_ref()
+                .map_or(0, |params| params.iter_params().count());
+            self.type_defs.entry(ident.symbol).or_insert((ident, arity));
+        }
+
+        self.trait_names
+            .extend(program.trait_defs.iter().map(|def| def.ident.symbol));
+    }
+}
+
 impl<'hir> Lower<'hir> for ast::Program {
     type Lowered = Program<'hir>;
 
     fn lower(&self, lowerer: &mut Lowerer<'hir>) -> Self::Lowered {
+        lowerer.declare(self);
+
+        let items = Items {
+            function_defs: self
+                .function_defs
+                .iter()
+                .map(|def| def.lower(lowerer))
+                .collect(),
+            struct_defs: self
+                .struct_defs
+                .iter()
+                .map(|def| def.lower(lowerer))
+                .collect(),
+            choice_defs: self
+                .choice_defs
+                .iter()
+                .map(|def| def.lower(lowerer))
+                .collect(),
+            trait_defs: self
+                .trait_defs
+                .iter()
+                .filter_map(|def| def.lower(lowerer))
+                .collect(),
+            impl_defs: self
+                .impl_defs
+                .iter()
+                .filter_map(|def| def.lower(lowerer))
+                .collect(),
+            dynamic_imports: self
+                .dynamic_imports
+                .iter()
+                .map(|di| di.file_string)
+                .collect(),
+        };
+
+        items.link(&mut lowerer.errors)
+    }
+}
+
+/// The items of a program after they've each been lowered on their own, in the order they're
+/// written in. Traits and impls that couldn't be lowered are left out.
+#[derive(Debug, Default)]
+pub struct Items<'hir> {
+    pub function_defs: Vec<FunctionDef<'hir>>,
+    pub struct_defs: Vec<StructDef<'hir>>,
+    pub choice_defs: Vec<ChoiceDef<'hir>>,
+    pub trait_defs: Vec<TraitDef<'hir>>,
+    pub impl_defs: Vec<ImplDef<'hir>>,
+    pub dynamic_imports: HashSet<Ident>,
+}
+
+impl<'hir> Items<'hir> {
+    /// Puts the items together into a program, which is where the checks that look across all
+    /// of them happen: names that are defined more than once, structs that contain themselves,
+    /// and references that escape their regions.
+    pub fn link(self, errors: &mut Vec<LoweringError>) -> Program<'hir> {
         use std::collections::hash_map::Entry;
 
         /// Inserts the lowered def into the map if the name is unique,
@@ -115,44 +210,148 @@ impl<'hir> Lower<'hir> for ast::Program {
             function_defs: HashMap::with_capacity(self.function_defs.len()),
             struct_defs: HashMap::with_capacity(self.struct_defs.len()),
             choice_defs: HashMap::with_capacity(self.choice_defs.len()),
-            dynamic_imports: self
-                .dynamic_imports
-                .iter()
-                .map(|di| di.file_string)
-                .collect(),
+            impl_defs: HashMap::with_capacity(self.impl_defs.len()),
+            trait_defs: HashMap::with_capacity(self.trait_defs.len()),
+            dynamic_imports: self.dynamic_imports,
         };
 
-        for ast_def in self.function_defs.iter() {
-            let def = ast_def.lower(lowerer);
-            insert_or_push_err(
-                program.function_defs.entry(def.ident.symbol),
-                def,
-                &mut lowerer.errors,
-            );
+        for def in self.function_defs {
+            insert_or_push_err(program.function_defs.entry(def.ident.symbol), def, errors);
         }
 
-        for def in self.struct_defs.iter() {
-            let def = def.lower(lowerer);
-            insert_or_push_err(
-                program.struct_defs.entry(def.ident.symbol),
-                def,
-                &mut lowerer.errors,
-            )
+        for def in self.struct_defs {
+            insert_or_push_err(program.struct_defs.entry(def.ident.symbol), def, errors)
         }
 
-        for def in self.choice_defs.iter() {
-            let def = def.lower(lowerer);
-            insert_or_push_err(
-                program.choice_defs.entry(def.ident.symbol),
-                def,
-                &mut lowerer.errors,
-            )
+        for def in self.choice_defs {
+            insert_or_push_err(program.choice_defs.entry(def.ident.symbol), def, errors)
+        }
+
+        for (ident, field) in recursive_structs(&program) {
+            errors.push(LoweringError::RecursiveStruct { ident, field });
+        }
+
+        let mut methods: HashMap<InternedString, Span> = HashMap::new();
+        for def in self.trait_defs {
+            // Methods are called like functions, so they share their names.
+            let previous = program
+                .function_defs
+                .get(&def.method.symbol)
+                .map(|function| function.span)
+                .or_else(|| methods.get(&def.method.symbol).copied());
+            if let Some(previous) = previous {
+                errors.push(LoweringError::MultipleDefsWithSameName {
+                    ident: def.method.symbol,
+                    previous,
+                    redefined: def.method.span(),
+                });
+                continue;
+            }
+            methods.insert(def.method.symbol, def.method.span());
+            insert_or_push_err(program.trait_defs.entry(def.ident.symbol), def, errors);
+        }
+
+        for def in self.impl_defs {
+            match program.impl_defs.entry((def.trait_, def.ty.symbol)) {
+                Entry::Occupied(occupied) => {
+                    errors.push(LoweringError::MultipleImpls {
+                        trait_: def.trait_,
+                        ty: def.ty,
+                        previous: occupied.get().span,
+                        redefined: def.span,
+                    });
+                }
+                Entry::Vacant(vacant) => {
+                    vacant.insert(def);
+                }
+            }
+        }
+
+        for (ident, region, escape) in escaping_references(&program) {
+            errors.push(LoweringError::EscapingReference {
+                ident,
+                region,
+                escape,
+            });
         }
 
         program
     }
 }
 
+impl<'hir> Lower<'hir> for ast::ImplDef {
+    type Lowered = Option<ImplDef<'hir>>;
+
+    fn lower(&self, lowerer: &mut Lowerer<'hir>) -> Self::Lowered {
+        // Still lowered when the trait is unknown, so that the errors in
+        // the body get reported too.
+        let arms = self.function.lower(lowerer);
+
+        let trait_ = match Trait::named(&self.trait_.symbol.string()) {
+            Some(trait_) => trait_,
+            None if lowerer.trait_names.contains(&self.trait_.symbol) => {
+                Trait::Declared(self.trait_.symbol)
+            }
+            None => {
+                lowerer.errors.push(LoweringError::UnknownTrait {
+                    trait_: self.trait_,
+                });
+                return None;
+            }
+        };
+
+        Some(ImplDef {
+            trait_,
+            trait_span: self.trait_.span(),
+            ty: self.ty,
+            arms,
+            span: self.span(),
+        })
+    }
+}
+
+impl<'hir> Lower<'hir> for ast::TraitDef {
+    type Lowered = Option<TraitDef<'hir>>;
+
+    fn lower(&self, lowerer: &mut Lowerer<'hir>) -> Self::Lowered {
+        if Trait::named(&self.ident.symbol.string()).is_some() {
+            lowerer
+                .errors
+                .push(LoweringError::BuiltinTrait { trait_: self.ident });
+            return None;
+        }
+
+        // `Self` is the only generic parameter, standing for the type of each impl.
+        let generic_params = &*lowerer
+            .bump
+            .alloc_slice_copy(&[Ident::new("Self", self.ident.span())]);
+        let (lhs, rhs, output) = lowerer.with_generic_params(generic_params, |lowerer| {
+            (
+                self.signature.lhs.lower(lowerer),
+                self.signature.rhs.lower(lowerer),
+                self.signature.output.lower(lowerer),
+            )
+        });
+
+        if !matches!(lhs.kind, TypeKind::Generic { .. }) {
+            lowerer.errors.push(LoweringError::MethodNotOnSelf {
+                method: self.method,
+                lhs: lhs.span,
+            });
+            return None;
+        }
+
+        Some(TraitDef {
+            ident: self.ident,
+            method: self.method,
+            lhs: lowerer.bump.alloc(lhs),
+            rhs: lowerer.bump.alloc(rhs),
+            output: lowerer.bump.alloc(output),
+            span: self.span(),
+        })
+    }
+}
+
 impl<'hir> Lower<'hir> for ast::StructDef {
     type Lowered = StructDef<'hir>;
 
@@ -240,10 +439,34 @@ impl<'hir> Lower<'hir> for ast::FunctionDef {
 
         let (generic_params, ty) = Default::default();
 
+        let attributes: Vec<_> = self
+            .attributes
+            .iter()
+            .filter_map(|attribute| attribute.lower(lowerer))
+            .collect();
+        let attributes = &*lowerer.bump.alloc_slice_copy(&attributes);
+
         let arms =
             lowerer.with_generic_params(generic_params, |lowerer| self.function.lower(lowerer));
 
+        for attribute in attributes.iter() {
+            match attribute.kind {
+                AttributeKind::TailRecursive => {
+                    let calls = non_tail_calls(self.ident, arms);
+                    if !calls.is_empty() {
+                        lowerer.errors.push(LoweringError::NotTailRecursive {
+                            ident: self.ident,
+                            attribute: attribute.span,
+                            calls,
+                        });
+                    }
+                }
+                AttributeKind::Test => {}
+            }
+        }
+
         FunctionDef {
+            attributes,
             ident: Ident::from(self.ident),
             generic_params,
             ty,
@@ -253,6 +476,29 @@ impl<'hir> Lower<'hir> for ast::FunctionDef {
     }
 }
 
+impl<'hir> Lower<'hir> for ast::Attribute {
+    type Lowered = Option<Attribute>;
+
+    fn lower(&self, lowerer: &mut Lowerer<'hir>) -> Self::Lowered {
+        let kind = match &*self.ident.symbol.string() {
+            "tail_recursive" => AttributeKind::TailRecursive,
+            "test" => AttributeKind::Test,
+            _ => {
+                lowerer.errors.push(LoweringError::UnknownAttribute {
+                    attribute: self.ident,
+                    span: self.span(),
+                });
+                return None;
+            }
+        };
+
+        Some(Attribute {
+            kind,
+            span: self.span(),
+        })
+    }
+}
+
 impl<'hir> Lower<'hir> for ast::Expr {
     type Lowered = Expr<'hir>;
 
@@ -293,7 +539,7 @@ impl<'hir> Lower<'hir> for ast::Expr {
             ast::Expr::Appl(appl) => ExprKind::Appl(appl.lower(lowerer)),
             ast::Expr::Region(region) => ExprKind::Region(region.lower(lowerer)),
             // ast::Expr::Field(_expr, _field) => todo!("lowering fields"),
-            ast::Expr::Error => todo!(),
+            ast::Expr::Error(_) => ExprKind::Error,
         };
 
         Expr {
@@ -381,6 +627,29 @@ impl<'hir> Lower<'hir> for ast::Closure {
     type Lowered = &'hir [Arm<'hir>];
 
     fn lower(&self, lowerer: &mut Lowerer<'hir>) -> Self::Lowered {
+        // a function that couldn't be parsed still takes anything, so that calling it doesn't
+        // cause any more errors
+        if let ast::Closure::Error(span) = self {
+            let wildcard = Ident {
+                symbol: InternedString::get_or_intern("_"),
+                span: *span,
+            };
+            let pat = lowerer.bump.alloc(Pat {
+                kind: PatKind::Lit(Lit::Ident(wildcard)),
+                span: *span,
+            });
+            let param = Param {
+                pat,
+                ascription: None,
+            };
+            let body = lowerer.bump.alloc(Expr {
+                kind: ExprKind::Error,
+                span: *span,
+            });
+            let params = lowerer.bump.alloc_slice_copy(&[param, param]);
+            return slice::from_ref(lowerer.bump.alloc(Arm { params, body }));
+        }
+
         lowerer
             .bump
             .alloc_slice_fill_iter(self.iter_arms().map(|arm| arm.lower(lowerer)))
@@ -477,7 +746,13 @@ impl<'hir> Lower<'hir> for ast::Region {
                         Ident::from(field.ident)
                     }))
             }
-            ast::Pat::Constructor(_) => todo!("constructors in regions are currently unsupported"),
+            ast::Pat::Constructor(constructor) => {
+                lowerer.errors.push(LoweringError::Region(
+                    RegionError::Constructor,
+                    constructor.path.span(),
+                ));
+                &[]
+            }
         };
 
         Region {
@@ -497,14 +772,17 @@ impl<'hir> Lower<'hir> for ast::Pat {
                 Ok(lit) => PatKind::Lit(lit),
                 Err(()) => PatKind::Error,
             },
-            ast::Pat::Record(record) => PatKind::Record(Map {
-                entries: lowerer.lower_record(record, |field, lowerer| {
-                    field.value.as_ref().map(|(_colon, pat)| {
-                        let pat = pat.lower(lowerer);
-                        &*lowerer.bump.alloc(pat)
-                    })
-                }),
-            }),
+            ast::Pat::Record(record) => PatKind::Record(
+                Map {
+                    entries: lowerer.lower_record(record, |field, lowerer| {
+                        field.value.as_ref().map(|(_colon, pat)| {
+                            let pat = pat.lower(lowerer);
+                            &*lowerer.bump.alloc(pat)
+                        })
+                    }),
+                },
+                record.rest.is_some(),
+            ),
             ast::Pat::Constructor(constructor) => {
                 let path = constructor.path.lower(lowerer);
                 let inner = constructor.inner.lower(lowerer);
@@ -546,7 +824,7 @@ impl<'hir> Lower<'hir> for ast::Type {
                     }
                 }),
             }),
-            ast::Type::Error => todo!(),
+            ast::Type::Error(_) => TypeKind::Error,
         };
 
         Type {
@@ -581,7 +859,7 @@ impl<'hir> Lower<'hir> for ast::NamedType {
         let [ident] = path else {
             // More than 1 item in the path, can't be a generic or a primitive
             // Note: path cannot have 0 elements since `ast::Path` has 1 inlined.
-            return TypeKind::Named { path, generic_args };
+            return lowerer.resolve_named_type(path, generic_args, self.span());
         };
 
         // Now that the path and args are lowered, do some light name resolution.
@@ -629,7 +907,49 @@ impl<'hir> Lower<'hir> for ast::NamedType {
 
             TypeKind::Primitive(prim)
         } else {
-            TypeKind::Named { path, generic_args }
+            lowerer.resolve_named_type(path, generic_args, self.span())
         }
     }
 }
+
+impl<'hir> Lowerer<'hir> {
+    /// Checks that `path` is a struct or choice in the program, and that it
+    /// gets as many type arguments as it has generic parameters.
+    fn resolve_named_type(
+        &mut self,
+        path: &'hir [Ident],
+        generic_args: &'hir [Type<'hir>],
+        span: curse_span::Span,
+    ) -> TypeKind<'hir> {
+        // everything is defined at the top level, so a path with more than
+        // one segment can't name anything yet
+        let def = match path {
+            [ident] => self.type_defs.get(&ident.symbol).copied(),
+            _ => None,
+        };
+
+        let Some((def_ident, arity)) = def else {
+            self.errors.push(LoweringError::UnknownType {
+                path: path
+                    .iter()
+                    .map(|ident| ident.to_string())
+                    .collect::<Vec<_>>()
+                    .join("::"),
+                span,
+            });
+            return TypeKind::Error;
+        };
+
+        if generic_args.len() != arity {
+            self.errors.push(LoweringError::WrongTypeArgCount {
+                def_ident,
+                expected: arity,
+                found: generic_args.len(),
+                span,
+            });
+            return TypeKind::Error;
+        }
+
+        TypeKind::Named { path, generic_args }
+    }
+}
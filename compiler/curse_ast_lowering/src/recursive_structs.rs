@@ -0,0 +1,129 @@
+//! Finds structs that contain themselves without a choice in between.
+//!
+//! A struct is just its type, so `struct List { head: I32, tail: List }` would
+//! have to be infinitely big, and there'd be no way to make one. Going through
+//! a choice is fine, since a choice can always have a variant that ends it,
+//! like `Option::None`. Generic structs contain their type arguments when they
+//! use the parameter directly, so `struct Wrap |T| { inner: T }` makes
+//! `struct Bad Wrap Bad` contain itself too.
+
+use curse_hir::hir::{Program, StructDef, TypeKind, TypeRef};
+use curse_interner::{Ident, InternedString};
+use curse_span::Span;
+
+/// Every struct in `program` that contains itself, along with the part of its
+/// type that does, sorted by name.
+pub fn recursive_structs(program: &Program<'_>) -> Vec<(Ident, Span)> {
+    let mut found: Vec<_> = program
+        .struct_defs
+        .values()
+        .filter_map(|def| {
+            let checker = Checker {
+                program,
+                target: def.ident.symbol,
+            };
+            checker.find(def.ty).map(|span| (def.ident, span))
+        })
+        .collect();
+    found.sort_by_key(|(ident, _)| ident.symbol.string().to_string());
+    found
+}
+
+struct Checker<'a, 'hir> {
+    program: &'a Program<'hir>,
+    target: InternedString,
+}
+
+impl<'hir> Checker<'_, 'hir> {
+    /// The type of the field of `ty`, however deeply nested in records, or
+    /// `ty` itself, that contains the target struct.
+    fn find(&self, ty: TypeRef<'hir>) -> Option<Span> {
+        match &ty.kind {
+            TypeKind::Record(map) => map.entries.iter().find_map(|&(_, ty)| self.find(ty)),
+            _ => self.contains(ty, &mut vec![]).then_some(ty.span),
+        }
+    }
+
+    /// Whether `ty` contains the target struct without a choice in between.
+    /// Structs in `visiting` are already being looked through.
+    fn contains(&self, ty: TypeRef<'hir>, visiting: &mut Vec<InternedString>) -> bool {
+        match &ty.kind {
+            TypeKind::Record(map) => map
+                .entries
+                .iter()
+                .any(|&(_, ty)| self.contains(ty, visiting)),
+            TypeKind::Named {
+                path: [name],
+                generic_args,
+            } => {
+                if name.symbol == self.target {
+                    return true;
+                }
+                let Some(def) = self.program.struct_defs.get(&name.symbol) else {
+                    return false;
+                };
+
+                let through_def = !visiting.contains(&name.symbol) && {
+                    visiting.push(name.symbol);
+                    let found = self.contains(def.ty, visiting);
+                    visiting.pop();
+                    found
+                };
+                through_def
+                    || generic_args.iter().enumerate().any(|(index, arg)| {
+                        self.holds_param(def, index, &mut vec![]) && self.contains(arg, visiting)
+                    })
+            }
+            TypeKind::Named { .. }
+            | TypeKind::Generic { .. }
+            | TypeKind::Primitive(_)
+            | TypeKind::Error => false,
+        }
+    }
+
+    /// Whether `def` contains its generic parameter at `index` without a
+    /// choice in between. The parameters in `visiting` are already being
+    /// looked for.
+    fn holds_param(
+        &self,
+        def: &StructDef<'hir>,
+        index: usize,
+        visiting: &mut Vec<(InternedString, usize)>,
+    ) -> bool {
+        if visiting.contains(&(def.ident.symbol, index)) {
+            return false;
+        }
+        visiting.push((def.ident.symbol, index));
+        let found = self.mentions_param(def.ty, index, visiting);
+        visiting.pop();
+        found
+    }
+
+    fn mentions_param(
+        &self,
+        ty: TypeRef<'hir>,
+        index: usize,
+        visiting: &mut Vec<(InternedString, usize)>,
+    ) -> bool {
+        match &ty.kind {
+            TypeKind::Record(map) => map
+                .entries
+                .iter()
+                .any(|&(_, ty)| self.mentions_param(ty, index, visiting)),
+            TypeKind::Generic { index: param, .. } => *param as usize == index,
+            TypeKind::Named {
+                path: [name],
+                generic_args,
+            } => {
+                let Some(def) = self.program.struct_defs.get(&name.symbol) else {
+                    return false;
+                };
+                generic_args.iter().enumerate().any(|(inner, arg)| {
+                    self.mentions_param(arg, index, visiting)
+                        && self.holds_param(def, inner, visiting)
+                })
+            }
+            TypeKind::Named { .. } | TypeKind::Primitive(_) | TypeKind::Error => false,
+        }
+    }
+}
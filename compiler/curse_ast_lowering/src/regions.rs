@@ -0,0 +1,484 @@
+//! Checks that the references that regions make can't outlive them.
+//!
+//! Inside of `ref x { ... }`, `x` is a reference to the `x` outside, so the region's result
+//! can't hold it, and neither can closures that are made in the region and given back by it.
+//! Nothing here knows the types of things, so anything that a call is given might be part of what
+//! it gives back. Top level functions and impls are looked into to see which of their arguments
+//! can end up in their results, so that passing a reference to `len` doesn't count as leaking it,
+//! but a variable could be any function, which might give back all of its arguments.
+//!
+//! A reference also escapes when it's assigned to a cell with `assign`, since the cell could be
+//! from outside of the region, or be written back to a name outside of it when the region ends.
+
+use std::collections::HashMap;
+
+use curse_hir::hir::{Arm, ExprKind, ExprRef, Lit, PatKind, PatRef, Program, Symbol, Trait};
+use curse_interner::{Ident, InternedString};
+use curse_span::Span;
+
+/// How a reference gets out of its region.
+#[derive(Copy, Clone, Debug)]
+pub enum Escape {
+    /// The reference is part of the region's result, e.g. `ref x { { inner: x } }`.
+    Returned(Span),
+    /// A closure that captures the reference is part of the region's result, e.g.
+    /// `ref x { || x }`.
+    Captured { closure: Span, capture: Span },
+    /// The reference is assigned to a cell, e.g. `mut y { ref x { y assign x } }`.
+    Assigned(Span),
+}
+
+/// The builtins of the interpreter that give back `{}` when nothing else has their name.
+const UNIT_BUILTINS: [&str; 4] = ["assign", "assert", "assert_eq", "panic"];
+
+/// Every reference in `program` that escapes its region, along with the name that the region
+/// shadows with it and the whole region, in the order that the regions are written in.
+pub fn escaping_references(program: &Program<'_>) -> Vec<(Ident, Span, Escape)> {
+    let summaries = Summaries::new(program);
+    let mut checker = RegionChecker {
+        flow: Flow {
+            summaries: &summaries,
+            locals: vec![],
+            tracked: vec![],
+            called: vec![],
+        },
+        found: vec![],
+    };
+
+    let functions = program.function_defs.values().map(|def| def.arms);
+    let impls = program.impl_defs.values().map(|def| def.arms);
+    for arms in functions.chain(impls) {
+        checker.arms(arms);
+    }
+
+    checker
+        .found
+        .sort_by_key(|(ident, region, _)| (region.start, ident.span.start));
+    checker.found
+}
+
+/// Which arguments of each top level function and impl can be part of what it gives back.
+struct Summaries {
+    functions: HashMap<InternedString, Sides>,
+    /// Since there's no way to tell which impl an operator calls, each trait holds what any of its
+    /// impls can give back.
+    traits: HashMap<Trait, Sides>,
+}
+
+/// How the left and right arguments of a function can be part of its result.
+#[derive(Copy, Clone, Default, PartialEq, Eq)]
+struct Sides {
+    lhs: Flows,
+    rhs: Flows,
+}
+
+impl Sides {
+    /// What a function that we know nothing about could do with its arguments.
+    const ANY: Sides = Sides {
+        lhs: Flows::ANY,
+        rhs: Flows::ANY,
+    };
+
+    fn join(self, other: Sides) -> Sides {
+        Sides {
+            lhs: self.lhs.join(other.lhs),
+            rhs: self.rhs.join(other.rhs),
+        }
+    }
+}
+
+/// How one argument of a function can be part of its result.
+///
+/// These are kept apart so that passing a closure to a function that only calls it, like
+/// `x in |y| ...`, only counts what the closure gives back, and not everything it captures.
+#[derive(Copy, Clone, Default, PartialEq, Eq)]
+struct Flows {
+    /// The argument itself, or a part of it.
+    value: bool,
+    /// What the argument gives back when it's called.
+    called: bool,
+}
+
+impl Flows {
+    const ANY: Flows = Flows {
+        value: true,
+        called: true,
+    };
+
+    fn join(self, other: Flows) -> Flows {
+        Flows {
+            value: self.value || other.value,
+            called: self.called || other.called,
+        }
+    }
+}
+
+impl Summaries {
+    /// Starts out assuming that nothing gives back its arguments, and then looks through every
+    /// function again until nothing changes, since a function can give back an argument by
+    /// passing it to another function that does, including itself.
+    fn new(program: &Program<'_>) -> Self {
+        let mut summaries = Summaries {
+            functions: program
+                .function_defs
+                .keys()
+                .map(|name| (*name, Sides::default()))
+                .collect(),
+            traits: HashMap::new(),
+        };
+        loop {
+            let mut changed = false;
+            for (name, def) in &program.function_defs {
+                let sides = summaries.arms(def.arms);
+                changed |= summaries.functions.insert(*name, sides) != Some(sides);
+            }
+            for def in program.impl_defs.values() {
+                let sides = summaries.arms(def.arms);
+                let summary = summaries.traits.entry(def.trait_).or_default();
+                let joined = summary.join(sides);
+                changed |= *summary != joined;
+                *summary = joined;
+            }
+
+            if !changed {
+                return summaries;
+            }
+        }
+    }
+
+    /// Which arguments `arms` can give back, assuming the summaries found so far.
+    fn arms(&self, arms: &[Arm<'_>]) -> Sides {
+        let mut sides = Sides::default();
+        for arm in arms {
+            let mut locals = vec![];
+            for param in arm.params {
+                bindings(param.pat, &mut locals);
+            }
+
+            // a function called with one argument gets it on the left
+            for (index, param) in arm.params.iter().enumerate() {
+                let mut bound = vec![];
+                bindings(param.pat, &mut bound);
+                let value = Flow {
+                    summaries: self,
+                    locals: locals.clone(),
+                    tracked: bound.clone(),
+                    called: vec![],
+                };
+                let called = Flow {
+                    tracked: vec![],
+                    called: bound,
+                    ..value.shadowed([])
+                };
+                let flows = Flows {
+                    value: value.result(arm.body).is_some(),
+                    called: called.result(arm.body).is_some(),
+                };
+                match index {
+                    0 => sides.lhs = sides.lhs.join(flows),
+                    _ => sides.rhs = sides.rhs.join(flows),
+                }
+            }
+        }
+        sides
+    }
+}
+
+/// Where the values of some variables can end up.
+struct Flow<'a> {
+    summaries: &'a Summaries,
+    /// Every variable in scope, which hide top level functions with the same name.
+    locals: Vec<InternedString>,
+    /// The variables whose values are being followed.
+    tracked: Vec<InternedString>,
+    /// The variables whose results are being followed wherever they're called.
+    called: Vec<InternedString>,
+}
+
+impl Flow<'_> {
+    /// Where a tracked variable becomes part of the value of `expr`, if it can.
+    fn result(&self, expr: ExprRef<'_>) -> Option<Escape> {
+        match expr.kind {
+            ExprKind::Lit(Lit::Ident(ident)) => self.returned(ident),
+            ExprKind::Symbol(_) | ExprKind::Lit(_) | ExprKind::Error => None,
+            ExprKind::Record(map) => map.entries.iter().find_map(|(ident, value)| match value {
+                Some(value) => self.result(value),
+                // `{ x }` is short for `{ x: x }`
+                None => self.returned(*ident),
+            }),
+            ExprKind::Constructor(constructor) => self.result(constructor.inner),
+            ExprKind::Closure(arms) => self.captured(arms).map(|capture| Escape::Captured {
+                closure: expr.span,
+                capture,
+            }),
+            // an inner region that shadows a tracked variable can't give back its reference,
+            // which is checked on its own, and can't refer to the variable otherwise
+            ExprKind::Region(region) => self
+                .shadowed(region.shadows.iter().map(|ident| ident.symbol))
+                .result(region.body),
+            ExprKind::Appl(appl) => {
+                let sides = match appl.fun().kind {
+                    // a field of a reference is part of what it refers to
+                    ExprKind::Symbol(Symbol::Dot) => return self.result(appl.lhs()),
+                    ExprKind::Symbol(Symbol::Semi) => return self.result(appl.rhs()),
+                    // the builtin operators give back new integers and bools, but impls could
+                    // give back anything they're given
+                    ExprKind::Symbol(symbol) => symbol
+                        .trait_()
+                        .and_then(|trait_| self.summaries.traits.get(&trait_))
+                        .copied()
+                        .unwrap_or_default(),
+                    ExprKind::Lit(Lit::Ident(ident)) if !self.locals.contains(&ident.symbol) => {
+                        match self.summaries.functions.get(&ident.symbol) {
+                            Some(sides) => *sides,
+                            None if self.is_unit_builtin(ident) => Sides::default(),
+                            // not a function that we know of, so it could be anything
+                            None => Sides::ANY,
+                        }
+                    }
+                    _ => {
+                        return self
+                            .calls(appl.fun())
+                            .or_else(|| self.through(Flows::ANY, appl.lhs()))
+                            .or_else(|| self.through(Flows::ANY, appl.rhs()))
+                    }
+                };
+
+                self.through(sides.lhs, appl.lhs())
+                    .or_else(|| self.through(sides.rhs, appl.rhs()))
+            }
+        }
+    }
+
+    /// Where a tracked variable becomes part of what a function gives back, when it's given
+    /// `arg` in a way that `flows`.
+    fn through(&self, flows: Flows, arg: ExprRef<'_>) -> Option<Escape> {
+        let value = flows.value.then(|| self.result(arg)).flatten();
+        value.or_else(|| flows.called.then(|| self.calls(arg)).flatten())
+    }
+
+    /// Where a tracked variable becomes part of what calling the value of `expr` gives back.
+    ///
+    /// Calling a reference gives back whatever the function it refers to does, which isn't the
+    /// reference, so only closures made here and the results of calls are looked into.
+    fn calls(&self, expr: ExprRef<'_>) -> Option<Escape> {
+        match expr.kind {
+            ExprKind::Lit(Lit::Ident(ident)) => self
+                .called
+                .contains(&ident.symbol)
+                .then_some(Escape::Returned(ident.span)),
+            ExprKind::Symbol(_)
+            | ExprKind::Lit(_)
+            | ExprKind::Record(_)
+            | ExprKind::Constructor(_)
+            | ExprKind::Error => None,
+            ExprKind::Closure(arms) => arms.iter().find_map(|arm| {
+                let mut bound = vec![];
+                for param in arm.params {
+                    bindings(param.pat, &mut bound);
+                }
+                self.shadowed(bound).result(arm.body)
+            }),
+            ExprKind::Region(region) => self
+                .shadowed(region.shadows.iter().map(|ident| ident.symbol))
+                .calls(region.body),
+            // the result could be a closure that captured something that's tracked, or that
+            // calls something whose calls are followed
+            ExprKind::Appl(_) => {
+                let mut tracked = self.tracked.clone();
+                tracked.extend(&self.called);
+                Flow {
+                    tracked,
+                    ..self.shadowed([])
+                }
+                .result(expr)
+            }
+        }
+    }
+
+    /// Where `expr` assigns something that holds a tracked variable to a cell.
+    fn assigned(&self, expr: ExprRef<'_>) -> Option<Escape> {
+        match expr.kind {
+            ExprKind::Symbol(_) | ExprKind::Lit(_) | ExprKind::Error => None,
+            ExprKind::Record(map) => map
+                .entries
+                .iter()
+                .find_map(|(_, value)| value.and_then(|value| self.assigned(value))),
+            ExprKind::Constructor(constructor) => self.assigned(constructor.inner),
+            ExprKind::Closure(arms) => arms.iter().find_map(|arm| {
+                let mut bound = vec![];
+                for param in arm.params {
+                    bindings(param.pat, &mut bound);
+                }
+                self.shadowed(bound).assigned(arm.body)
+            }),
+            ExprKind::Region(region) => self
+                .shadowed(region.shadows.iter().map(|ident| ident.symbol))
+                .assigned(region.body),
+            ExprKind::Appl(appl) => {
+                if let ExprKind::Lit(Lit::Ident(ident)) = appl.fun().kind {
+                    if self.is_builtin(ident, "assign") && self.result(appl.rhs()).is_some() {
+                        return Some(Escape::Assigned(expr.span));
+                    }
+                }
+                self.assigned(appl.lhs())
+                    .or_else(|| self.assigned(appl.fun()))
+                    .or_else(|| self.assigned(appl.rhs()))
+            }
+        }
+    }
+
+    /// Whether `ident` refers to the builtin called `name`, because nothing else has its name.
+    fn is_builtin(&self, ident: Ident, name: &str) -> bool {
+        !self.locals.contains(&ident.symbol)
+            && !self.summaries.functions.contains_key(&ident.symbol)
+            && *ident.symbol.string() == *name
+    }
+
+    fn is_unit_builtin(&self, ident: Ident) -> bool {
+        UNIT_BUILTINS
+            .iter()
+            .any(|name| self.is_builtin(ident, name))
+    }
+
+    fn returned(&self, ident: Ident) -> Option<Escape> {
+        self.tracked
+            .contains(&ident.symbol)
+            .then_some(Escape::Returned(ident.span))
+    }
+
+    /// Where one of `arms` refers to a tracked variable, which a closure made of them would
+    /// capture.
+    fn captured(&self, arms: &[Arm<'_>]) -> Option<Span> {
+        arms.iter().find_map(|arm| {
+            let mut bound = vec![];
+            for param in arm.params {
+                bindings(param.pat, &mut bound);
+            }
+            self.shadowed(bound).mention(arm.body)
+        })
+    }
+
+    /// Where `expr` refers to a tracked variable at all.
+    fn mention(&self, expr: ExprRef<'_>) -> Option<Span> {
+        match expr.kind {
+            ExprKind::Lit(Lit::Ident(ident)) => self.mentions(ident),
+            ExprKind::Symbol(_) | ExprKind::Lit(_) | ExprKind::Error => None,
+            ExprKind::Record(map) => map.entries.iter().find_map(|(ident, value)| match value {
+                Some(value) => self.mention(value),
+                None => self.mentions(*ident),
+            }),
+            ExprKind::Constructor(constructor) => self.mention(constructor.inner),
+            ExprKind::Closure(arms) => self.captured(arms),
+            // borrowing a variable refers to it too
+            ExprKind::Region(region) => region
+                .shadows
+                .iter()
+                .find_map(|ident| self.mentions(*ident))
+                .or_else(|| {
+                    self.shadowed(region.shadows.iter().map(|ident| ident.symbol))
+                        .mention(region.body)
+                }),
+            ExprKind::Appl(appl) => self
+                .mention(appl.lhs())
+                .or_else(|| self.mention(appl.fun()))
+                .or_else(|| self.mention(appl.rhs())),
+        }
+    }
+
+    fn mentions(&self, ident: Ident) -> Option<Span> {
+        self.tracked.contains(&ident.symbol).then_some(ident.span)
+    }
+
+    /// The same flow, inside of a scope that binds `names`.
+    fn shadowed(&self, names: impl IntoIterator<Item = InternedString>) -> Self {
+        let mut locals = self.locals.clone();
+        let mut tracked = self.tracked.clone();
+        let mut called = self.called.clone();
+        for name in names {
+            locals.push(name);
+            tracked.retain(|tracked| *tracked != name);
+            called.retain(|called| *called != name);
+        }
+        Flow {
+            summaries: self.summaries,
+            locals,
+            tracked,
+            called,
+        }
+    }
+}
+
+/// Looks for regions everywhere, keeping track of the variables that are in scope.
+struct RegionChecker<'a> {
+    flow: Flow<'a>,
+    found: Vec<(Ident, Span, Escape)>,
+}
+
+impl RegionChecker<'_> {
+    fn arms(&mut self, arms: &[Arm<'_>]) {
+        for arm in arms {
+            let scope = self.flow.locals.len();
+            for param in arm.params {
+                bindings(param.pat, &mut self.flow.locals);
+            }
+            self.expr(arm.body);
+            self.flow.locals.truncate(scope);
+        }
+    }
+
+    fn expr(&mut self, expr: ExprRef<'_>) {
+        match expr.kind {
+            ExprKind::Symbol(_) | ExprKind::Lit(_) | ExprKind::Error => {}
+            ExprKind::Record(map) => {
+                for value in map.entries.iter().filter_map(|(_, value)| *value) {
+                    self.expr(value);
+                }
+            }
+            ExprKind::Constructor(constructor) => self.expr(constructor.inner),
+            ExprKind::Closure(arms) => self.arms(arms),
+            ExprKind::Appl(appl) => {
+                self.expr(appl.lhs());
+                self.expr(appl.fun());
+                self.expr(appl.rhs());
+            }
+            ExprKind::Region(region) => {
+                let scope = self.flow.locals.len();
+                self.flow
+                    .locals
+                    .extend(region.shadows.iter().map(|ident| ident.symbol));
+                for &ident in region.shadows {
+                    self.flow.tracked = vec![ident.symbol];
+                    let escape = self
+                        .flow
+                        .result(region.body)
+                        .or_else(|| self.flow.assigned(region.body));
+                    if let Some(escape) = escape {
+                        self.found.push((ident, expr.span, escape));
+                    }
+                }
+                self.flow.tracked.clear();
+
+                self.expr(region.body);
+                self.flow.locals.truncate(scope);
+            }
+        }
+    }
+}
+
+/// Adds the names of the variables that `pat` binds to `names`.
+fn bindings(pat: PatRef<'_>, names: &mut Vec<InternedString>) {
+    match &pat.kind {
+        PatKind::Lit(Lit::Ident(ident)) => names.push(ident.symbol),
+        PatKind::Lit(_) | PatKind::Error => {}
+        PatKind::Record(map, _) => {
+            for (ident, value) in map.entries {
+                match value {
+                    Some(value) => bindings(value, names),
+                    None => names.push(ident.symbol),
+                }
+            }
+        }
+        PatKind::Constructor(_, inner) => bindings(inner, names),
+    }
+}
@@ -0,0 +1,132 @@
+//! Checks that every recursive call in a `#[tail_recursive]` function is a tail call.
+//!
+//! A call is in tail position when it's the whole body of one of the function's arms, since then
+//! nothing is left to do with its result and the caller's frame can be reused. Anything else that
+//! refers to the function means its frames could pile up: calls whose results are passed along to
+//! something else, calls from inside closures, which could be called from anywhere, and using the
+//! function as a value, which lets anything call it.
+
+use curse_hir::hir::{Arm, ExprKind, ExprRef, Lit, PatKind, PatRef};
+use curse_interner::{Ident, InternedString};
+use curse_span::Span;
+
+/// A place where a function refers to itself without making a tail call.
+#[derive(Copy, Clone, Debug)]
+pub enum NonTailCall {
+    /// A call whose result is used by the rest of the function, e.g. `n * (n - 1 fact {})`.
+    Argument(Span),
+    /// A call from inside of a closure, e.g. `cond then_do (|| n loop {})`.
+    InClosure(Span),
+    /// The function used as a value instead of being called, e.g. `n in loop`.
+    Escapes(Span),
+}
+
+/// Finds everywhere in `arms` that the function named `ident` refers to itself without making a
+/// tail call.
+pub fn non_tail_calls(ident: Ident, arms: &[Arm<'_>]) -> Vec<NonTailCall> {
+    let mut checker = Checker {
+        symbol: ident.symbol,
+        found: vec![],
+    };
+
+    for arm in arms {
+        checker.arm(arm, Position::Tail);
+    }
+
+    checker.found
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Position {
+    Tail,
+    Argument,
+    Closure,
+}
+
+impl Position {
+    /// The position of the parts of an expression in this position.
+    fn inner(self) -> Position {
+        match self {
+            Position::Tail | Position::Argument => Position::Argument,
+            Position::Closure => Position::Closure,
+        }
+    }
+}
+
+struct Checker {
+    symbol: InternedString,
+    found: Vec<NonTailCall>,
+}
+
+impl Checker {
+    fn arm(&mut self, arm: &Arm<'_>, position: Position) {
+        // the function isn't visible in an arm that shadows its name
+        if arm.params.iter().any(|param| self.binds(param.pat)) {
+            return;
+        }
+
+        self.expr(arm.body, position);
+    }
+
+    fn expr(&mut self, expr: ExprRef<'_>, position: Position) {
+        match expr.kind {
+            ExprKind::Lit(Lit::Ident(ident)) => self.reference(ident),
+            ExprKind::Symbol(_) | ExprKind::Lit(_) | ExprKind::Error => {}
+            ExprKind::Record(map) => {
+                for (ident, value) in map.entries {
+                    match value {
+                        Some(value) => self.expr(value, position.inner()),
+                        // `{ f }` is short for `{ f: f }`
+                        None => self.reference(*ident),
+                    }
+                }
+            }
+            ExprKind::Constructor(constructor) => self.expr(constructor.inner, position.inner()),
+            ExprKind::Closure(arms) => {
+                for arm in arms {
+                    self.arm(arm, Position::Closure);
+                }
+            }
+            ExprKind::Appl(appl) => {
+                self.expr(appl.lhs(), position.inner());
+                match appl.fun().kind {
+                    ExprKind::Lit(Lit::Ident(ident)) if ident.symbol == self.symbol => {
+                        match position {
+                            Position::Tail => {}
+                            Position::Argument => {
+                                self.found.push(NonTailCall::Argument(expr.span));
+                            }
+                            Position::Closure => {
+                                self.found.push(NonTailCall::InClosure(expr.span));
+                            }
+                        }
+                    }
+                    _ => self.expr(appl.fun(), position.inner()),
+                }
+                self.expr(appl.rhs(), position.inner());
+            }
+            // Regions don't do anything at runtime yet, but they're likely to have to clean up
+            // after their bodies once they do.
+            ExprKind::Region(region) => self.expr(region.body, position.inner()),
+        }
+    }
+
+    fn reference(&mut self, ident: Ident) {
+        if ident.symbol == self.symbol {
+            self.found.push(NonTailCall::Escapes(ident.span));
+        }
+    }
+
+    /// Returns whether `pat` introduces a variable with the function's name.
+    fn binds(&self, pat: PatRef<'_>) -> bool {
+        match &pat.kind {
+            PatKind::Lit(Lit::Ident(ident)) => ident.symbol == self.symbol,
+            PatKind::Lit(_) | PatKind::Error => false,
+            PatKind::Record(map, _) => map.entries.iter().any(|(ident, value)| match value {
+                Some(value) => self.binds(value),
+                None => ident.symbol == self.symbol,
+            }),
+            PatKind::Constructor(_, inner) => self.binds(inner),
+        }
+    }
+}
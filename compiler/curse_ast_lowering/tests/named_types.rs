@@ -0,0 +1,144 @@
+//! Checks that named types have to be structs or choices in the program, with the right number of
+//! type arguments, and that structs can't contain themselves.
+
+use bumpalo::Bump;
+use curse_ast_lowering::LoweringError;
+use curse_test_support::{lock, text};
+
+const DEFS: &str = "
+choice Option |T| {
+    Some T,
+    None {},
+}
+
+struct Pair |A * B| { first: A, second: B }
+";
+
+/// Lowers `input` after some type definitions, returning what each error is about and the text
+/// that it points to.
+fn check(input: &str) -> Vec<(String, String)> {
+    let input = format!("{DEFS}\n{input}");
+    let arena = Bump::new();
+    let (_, errors) =
+        curse_test_support::lower_with_errors(&arena, &curse_test_support::parse(&input));
+    errors
+        .iter()
+        .map(|error| {
+            let span = match error {
+                LoweringError::UnknownType { span, .. }
+                | LoweringError::WrongTypeArgCount { span, .. } => *span,
+                LoweringError::RecursiveStruct { field, .. } => *field,
+                error => panic!("unexpected error: {error:?}"),
+            };
+            (error.to_string(), text(&input, span).to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn types_can_be_used_before_theyre_defined() {
+    let _interner = lock();
+    assert_eq!(
+        check(
+            "
+struct Wrapped Option (Pair (I32 * Later))
+struct Later { x: I32 }
+fn f |x: Option Later| x
+"
+        ),
+        []
+    );
+}
+
+#[test]
+fn unknown_types() {
+    let _interner = lock();
+    assert_eq!(
+        check("fn f |x: Missing| x"),
+        [("unknown type `Missing`".into(), "Missing".into())]
+    );
+    assert_eq!(
+        check("struct S { inner: std::Option I32 }"),
+        [(
+            "unknown type `std::Option`".into(),
+            "std::Option I32".into()
+        )]
+    );
+}
+
+#[test]
+fn wrong_number_of_type_arguments() {
+    let _interner = lock();
+    assert_eq!(
+        check("struct S Option (I32 * Bool)"),
+        [(
+            "`Option` takes 1 type argument, but 2 were given".into(),
+            "Option (I32 * Bool)".into()
+        )]
+    );
+    assert_eq!(
+        check("fn f |x: Pair I32| x"),
+        [(
+            "`Pair` takes 2 type arguments, but 1 was given".into(),
+            "Pair I32".into()
+        )]
+    );
+    assert_eq!(
+        check("struct S Pair"),
+        [(
+            "`Pair` takes 2 type arguments, but 0 were given".into(),
+            "Pair".into()
+        )]
+    );
+}
+
+#[test]
+fn structs_can_contain_themselves_through_a_choice() {
+    let _interner = lock();
+    assert_eq!(
+        check(
+            "
+struct List { head: I32, tail: Option List }
+choice Tree { Leaf {}, Node { left: Tree, right: Tree } }
+"
+        ),
+        []
+    );
+}
+
+#[test]
+fn structs_cant_contain_themselves_otherwise() {
+    let _interner = lock();
+    // directly, through records, or through other structs
+    assert_eq!(
+        check(
+            "
+struct Direct Direct
+struct Field { head: I32, tail: { more: Field } }
+struct Even { odd: Odd }
+struct Odd { even: Even }
+"
+        ),
+        [
+            ("struct `Direct` contains itself".into(), "Direct".into()),
+            ("struct `Even` contains itself".into(), "Odd".into()),
+            ("struct `Field` contains itself".into(), "Field".into()),
+            ("struct `Odd` contains itself".into(), "Even".into()),
+        ]
+    );
+
+    // or through the arguments of generic structs that hold them directly
+    assert_eq!(
+        check(
+            "
+struct Boxed |T| { inner: T }
+struct Nested Pair (I32 * Boxed Nested)
+struct Fine Pair (I32 * Option Fine)
+"
+        ),
+        [(
+            "struct `Nested` contains itself".into(),
+            "Pair (I32 * Boxed Nested)".into()
+        )]
+    );
+}
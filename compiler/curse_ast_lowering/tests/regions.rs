@@ -0,0 +1,139 @@
+//! Checks that references can't escape the regions that make them, and where the errors point
+//! when they do.
+
+use bumpalo::Bump;
+use curse_ast_lowering::{Escape, LoweringError};
+use curse_test_support::lock;
+
+const DEFS: &str = "
+fn in |x, f| x f {}
+
+fn len |_| 3
+
+fn keep |x, _| x
+
+fn wrap |x| { inner: x }
+";
+
+/// Lowers `input` after some functions, returning the reference that escapes each region, and
+/// the text that the region's result holds it through.
+fn check(input: &str) -> Vec<(String, String)> {
+    let input = format!("{DEFS}\n{input}");
+    let arena = Bump::new();
+    let (_, errors) =
+        curse_test_support::lower_with_errors(&arena, &curse_test_support::parse(&input));
+
+    let text = |span| curse_test_support::text(&input, span).to_string();
+    errors
+        .iter()
+        .map(|error| match error {
+            LoweringError::EscapingReference { ident, escape, .. } => {
+                let through = match *escape {
+                    Escape::Returned(span) => text(span),
+                    Escape::Captured { closure, capture } => {
+                        assert_eq!(text(capture), ident.to_string());
+                        text(closure)
+                    }
+                    Escape::Assigned(span) => text(span),
+                };
+                (ident.to_string(), through)
+            }
+            error => panic!("unexpected error: {error:?}"),
+        })
+        .collect()
+}
+
+#[test]
+fn references_can_be_used_for_anything_that_doesnt_give_them_back() {
+    let _interner = lock();
+    assert_eq!(
+        check(
+            "
+fn f |x, y| {
+    len: ref x { x len {} },
+    sum: ref x { (x . a) + 1 },
+    kept: ref x { y keep x },
+    dropped: ref x { (|| x) ; 1 },
+    ignored: ref x { x ; 1 },
+    shadowed: ref x { |x| x },
+    reborrowed: ref x { ref x { x len {} } },
+    mutated: mut { x, y } { x + y },
+    assigned: mut x { x assign (x + 1) },
+    looped: mut y { 1 in |_| y assign (y + 1) },
+}
+"
+        ),
+        []
+    );
+}
+
+#[test]
+fn references_cant_be_part_of_the_result() {
+    let _interner = lock();
+    assert_eq!(check("fn f |x| ref x { x }"), [("x".into(), "x".into())]);
+    assert_eq!(
+        check("fn f |x, y| ref mut { x, y } { { a: x, b: Option::Some y } }"),
+        [("x".into(), "x".into()), ("y".into(), "y".into())]
+    );
+    assert_eq!(
+        check("fn f |x| ref x { x . a }"),
+        [("x".into(), "x".into())]
+    );
+    assert_eq!(
+        check("fn f |x| ref x { 1 ; x }"),
+        [("x".into(), "x".into())]
+    );
+}
+
+#[test]
+fn references_cant_escape_through_functions_that_give_back_their_arguments() {
+    let _interner = lock();
+    // even indirectly
+    assert_eq!(
+        check("fn f |x| ref x { x wrap {} }"),
+        [("x".into(), "x".into())]
+    );
+    assert_eq!(
+        check("fn f |x| ref x { x in |y| y }"),
+        [("x".into(), "x".into())]
+    );
+    assert_eq!(
+        check("fn f |x, g| ref x { 1 g x }"),
+        [("x".into(), "x".into())]
+    );
+    assert_eq!(
+        check("fn f |x| ref x { 1 in |_| x }"),
+        [("x".into(), "x".into())]
+    );
+    assert_eq!(
+        check(
+            "
+fn forward |x, y| x keep y
+fn f |x| ref x { x forward 1 }
+"
+        ),
+        [("x".into(), "x".into())]
+    );
+}
+
+#[test]
+fn references_cant_escape_in_closures_that_capture_them() {
+    let _interner = lock();
+    assert_eq!(
+        check("fn f |x| ref x { |y| y + x }"),
+        [("x".into(), "|y| y + x".into())]
+    );
+    assert_eq!(
+        check("fn f |x| ref x { { f: || ref x { 1 } } }"),
+        [("x".into(), "|| ref x { 1 }".into())]
+    );
+}
+
+#[test]
+fn references_cant_be_assigned_to_cells() {
+    let _interner = lock();
+    assert_eq!(
+        check("fn f |x, y| mut y { ref x { y assign x } }"),
+        [("x".into(), "y assign x".into())]
+    );
+}
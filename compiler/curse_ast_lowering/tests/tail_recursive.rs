@@ -0,0 +1,99 @@
+//! Checks which functions `#[tail_recursive]` accepts, and where it points when it rejects one.
+
+use bumpalo::Bump;
+use curse_ast_lowering::{LoweringError, NonTailCall};
+use curse_span::HasSpan;
+use curse_test_support::lock;
+
+/// Lowers `input`, returning the text that each problem points to, or `None` if it lowered fine.
+fn check(input: &str) -> Option<Vec<(&'static str, &str)>> {
+    let arena = Bump::new();
+    let (_, errors) =
+        curse_test_support::lower_with_errors(&arena, &curse_test_support::parse(input));
+
+    let text = |span| curse_test_support::text(input, span);
+    let errors = match errors.as_slice() {
+        [] => return None,
+        [LoweringError::NotTailRecursive {
+            attribute, calls, ..
+        }] => {
+            assert_eq!(text(*attribute), "#[tail_recursive]");
+            calls
+                .iter()
+                .map(|call| match *call {
+                    NonTailCall::Argument(span) => ("argument", text(span)),
+                    NonTailCall::InClosure(span) => ("closure", text(span)),
+                    NonTailCall::Escapes(span) => ("escapes", text(span)),
+                })
+                .collect()
+        }
+        [LoweringError::UnknownAttribute { span, .. }] => vec![("unknown", text(span.span()))],
+        errors => panic!("unexpected errors: {errors:?}"),
+    };
+    Some(errors)
+}
+
+#[test]
+fn calls_in_tail_position() {
+    let _interner = lock();
+    // in every arm
+    assert_eq!(
+        check(
+            "#[tail_recursive]
+            fn count (
+                |0, acc| acc,
+                |n, acc| n - 1 count (acc + 1),
+            )"
+        ),
+        None
+    );
+}
+
+#[test]
+fn shadowed_functions_arent_calls() {
+    let _interner = lock();
+    assert_eq!(
+        check("#[tail_recursive] fn apply |x, apply| 1 + (x apply {})"),
+        None
+    );
+}
+
+#[test]
+fn calls_in_arguments() {
+    let _interner = lock();
+    assert_eq!(
+        check(
+            "#[tail_recursive]
+            fn fact (
+                |0| 1,
+                |n| n * (n - 1 fact {}),
+            )"
+        ),
+        Some(vec![("argument", "n - 1 fact {}")])
+    );
+    assert_eq!(
+        check("#[tail_recursive] fn f |x| Some (x f {}) in f"),
+        Some(vec![("argument", "x f {}"), ("escapes", "f")])
+    );
+}
+
+#[test]
+fn calls_in_closures_and_escaping_functions() {
+    let _interner = lock();
+    assert_eq!(
+        check(
+            "#[tail_recursive]
+            fn loop |n| (n = 0) then_do (|| n - 1 loop {}) else_do (|| { loop })"
+        ),
+        Some(vec![("closure", "n - 1 loop {}"), ("escapes", "loop")])
+    );
+}
+
+#[test]
+fn unknown_attributes() {
+    let _interner = lock();
+    assert_eq!(
+        check("#[inline] fn f |x| x"),
+        Some(vec![("unknown", "#[inline]")])
+    );
+}
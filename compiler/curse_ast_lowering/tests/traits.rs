@@ -0,0 +1,93 @@
+//! Checks that declared traits can't reuse the name of a builtin trait or of a function, that
+//! their methods are on `Self`, and that impls can name them.
+
+use bumpalo::Bump;
+use curse_ast_lowering::LoweringError;
+use curse_hir::hir::{self, Trait};
+use curse_interner::InternedString;
+use curse_test_support::{lock, text};
+
+const INPUT: &str = "
+struct Score I32
+
+trait Combine combine |Self, I32| Self
+
+impl Combine Score |Score a, n| Score (a + n)
+
+trait Add plus |Self, Self| Self
+
+trait Flipped flipped |I32, Self| Self
+
+fn scale |a, b| a * b
+
+trait Scale scale |Self, Self| Self
+
+impl Missing Score |a, b| a
+";
+
+/// Lowers [`INPUT`] into `arena`.
+fn lower(arena: &Bump) -> (hir::Program<'_>, Vec<LoweringError>) {
+    curse_test_support::lower_with_errors(arena, &curse_test_support::parse(INPUT))
+}
+
+#[test]
+fn errors() {
+    let _interner = lock();
+    let arena = Bump::new();
+    let (_, errors) = lower(&arena);
+    let errors: Vec<_> = errors
+        .iter()
+        .map(|error| {
+            let span = match error {
+                LoweringError::BuiltinTrait { trait_ } | LoweringError::UnknownTrait { trait_ } => {
+                    trait_.span
+                }
+                LoweringError::MethodNotOnSelf { lhs, .. } => *lhs,
+                LoweringError::MultipleDefsWithSameName { redefined, .. } => *redefined,
+                error => panic!("unexpected error: {error:?}"),
+            };
+            (error.to_string(), text(INPUT, span))
+        })
+        .collect();
+    assert_eq!(
+        errors,
+        [
+            ("`Add` is already a builtin trait".to_string(), "Add"),
+            (
+                "the left argument of method `flipped` is not `Self`".to_string(),
+                "I32"
+            ),
+            ("unknown trait `Missing`".to_string(), "Missing"),
+            // the items are all lowered before they're put together and checked for redefinitions
+            (
+                "the name `scale` is defined multiple times".to_string(),
+                "scale"
+            ),
+        ]
+    );
+}
+
+#[test]
+fn declared_traits_and_their_impls() {
+    let _interner = lock();
+    let arena = Bump::new();
+    let (program, _) = lower(&arena);
+
+    let combine = InternedString::get_or_intern("Combine");
+    let score = InternedString::get_or_intern("Score");
+    let names: Vec<_> = program
+        .trait_defs
+        .keys()
+        .map(|name| name.to_string())
+        .collect();
+    assert_eq!(names, ["Combine"]);
+    assert_eq!(
+        program
+            .method(InternedString::get_or_intern("combine"))
+            .map(|def| def.ident.symbol),
+        Some(combine)
+    );
+    assert!(program
+        .impl_defs
+        .contains_key(&(Trait::Declared(combine), score)));
+}
@@ -0,0 +1,441 @@
+//! Generates portable C from the CPS IR.
+//!
+//! Every function in a `Fix` becomes a C function taking its own closure, which is a heap object
+//! holding a pointer to the C function and the function's free variables. Since nothing in CPS
+//! ever returns, a call is just storing the arguments and the closure being called in globals and
+//! returning to a trampoline in `main`, so the C stack never grows. Records are heap allocated,
+//! and integers are tagged so that they can be told apart from pointers. The heap is garbage
+//! collected by `curse_runtime`, the same as the JIT's: every function makes room for everything
+//! it allocates when it's entered, collecting with its closure and arguments as the roots if it
+//! has to. So generated programs have to be linked against the runtime's static library, which
+//! [`runtime_library`] finds.
+
+use std::{
+    collections::HashMap,
+    fmt::Write,
+    path::{Path, PathBuf},
+};
+
+use curse_cps::cpsexpr::{CPSExpr, Function, Primop, Value};
+use curse_interner::InternedString;
+use miette::Diagnostic;
+use thiserror::Error;
+
+const RUNTIME: &str = include_str!("runtime.c");
+
+/// What the static library of `curse_runtime` is called.
+pub const RUNTIME_LIBRARY: &str = "libcurse_runtime.a";
+
+/// The libraries that the Rust standard library inside of the runtime needs, which have to come
+/// after it when linking.
+pub const SYSTEM_LIBRARIES: &[&str] = if cfg!(target_os = "macos") {
+    &[]
+} else {
+    &["-lpthread", "-ldl", "-lm"]
+};
+
+/// Finds the static library of `curse_runtime` that was built along with the running executable.
+///
+/// Building the whole workspace puts it right next to the executables, but when it's only built
+/// as a dependency it stays in `deps/` with a hash in its name, which is also where test
+/// executables are. If there are several of those, the newest one is the one that was just built.
+pub fn runtime_library() -> Option<PathBuf> {
+    let exe = std::env::current_exe().ok()?;
+    let dir = exe.parent()?;
+    let next_to_exe = dir.join(RUNTIME_LIBRARY);
+    if next_to_exe.is_file() {
+        return Some(next_to_exe);
+    }
+
+    let (stem, extension) = RUNTIME_LIBRARY.split_once('.')?;
+    [dir.join("deps"), dir.to_path_buf()]
+        .iter()
+        .filter_map(|dir| std::fs::read_dir(dir).ok())
+        .flatten()
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .filter(|path| is_hashed(path, stem, extension))
+        .max_by_key(|path| {
+            path.metadata()
+                .and_then(|metadata| metadata.modified())
+                .ok()
+        })
+}
+
+/// Whether `path` is named `{stem}-{hash}.{extension}`.
+fn is_hashed(path: &Path, stem: &str, extension: &str) -> bool {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .and_then(|name| name.strip_prefix(stem)?.strip_prefix('-'))
+        .and_then(|name| name.strip_suffix(extension)?.strip_suffix('.'))
+        .is_some_and(|hash| hash.chars().all(|c| c.is_ascii_hexdigit()))
+}
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum Error {
+    #[error("Unbound variable `{0}`")]
+    #[diagnostic(help("Every variable has to be defined somewhere in the program, including the standard library."))]
+    UnboundVariable(InternedString),
+}
+
+/// Generates a C program that evaluates `expr` and prints the value it halts with.
+pub fn generate(expr: &CPSExpr) -> Result<String, Error> {
+    let mut generator = Generator::default();
+
+    let entry = generator.fresh_function("entry");
+    let mut body = String::new();
+    generator.expr(expr, HashMap::new(), &mut body, 1)?;
+    generator.define(&entry, &[], allocation(expr), body);
+
+    let mut program = String::from(RUNTIME);
+
+    program.push('\n');
+    for (index, string) in generator.strings.iter().enumerate() {
+        writeln!(
+            program,
+            "static struct string string_{index} = {{ HEADER(KIND_STRING, 1), {} }};",
+            c_string(&string.string())
+        )
+        .unwrap();
+    }
+
+    program.push('\n');
+    for prototype in generator.prototypes.iter() {
+        writeln!(program, "{prototype};").unwrap();
+    }
+    for definition in generator.definitions.iter() {
+        program.push('\n');
+        program.push_str(definition);
+    }
+
+    write!(
+        program,
+        "
+int main(void) {{
+    heap = curse_heap_new(HEAP_WORDS);
+    next = alloc(KIND_CLOSURE, 1);
+    FIELD(next, 0) = (value){entry};
+    while (!halted) {{
+        ((code)FIELD(next, 0))(next);
+    }}
+    print_value(result);
+    printf(\"\\n\");
+    return 0;
+}}
+"
+    )
+    .unwrap();
+
+    Ok(program)
+}
+
+/// What each variable in scope is called in C.
+type Env = HashMap<InternedString, String>;
+
+#[derive(Default)]
+struct Generator {
+    prototypes: Vec<String>,
+    definitions: Vec<String>,
+    strings: Vec<InternedString>,
+    string_indices: HashMap<InternedString, usize>,
+    next_id: usize,
+}
+
+impl Generator {
+    fn fresh(&mut self, prefix: &str, name: &str) -> String {
+        self.next_id += 1;
+        format!("{prefix}_{}_{}", mangle(name), self.next_id)
+    }
+
+    fn fresh_var(&mut self, name: InternedString) -> String {
+        self.fresh("v", &name.string())
+    }
+
+    fn fresh_function(&mut self, name: &str) -> String {
+        self.fresh("fn", name)
+    }
+
+    /// Defines a C function named `name`, which reserves `words` words on the heap and then
+    /// copies each argument it uses out of `args` into the variable named alongside it.
+    fn define(&mut self, name: &str, params: &[(usize, String)], words: usize, body: String) {
+        self.prototypes
+            .push(format!("static void {name}(value self)"));
+
+        let mut definition = format!("static void {name}(value self) {{\n");
+        if words > 0 {
+            writeln!(definition, "    reserve(&self, {words});").unwrap();
+        }
+        for (index, param) in params.iter() {
+            writeln!(definition, "    value {param} = args[{index}];").unwrap();
+        }
+        definition.push_str(&body);
+        definition.push_str("}\n");
+        self.definitions.push(definition);
+    }
+
+    fn value(&mut self, value: Value, env: &Env) -> Result<String, Error> {
+        match value {
+            Value::Var(name) => env.get(&name).cloned().ok_or(Error::UnboundVariable(name)),
+            Value::Int(n) => Ok(format!("MK_INT({n})")),
+            Value::String(string) => {
+                let next_index = self.strings.len();
+                let index = *self.string_indices.entry(string).or_insert(next_index);
+                if index == next_index {
+                    self.strings.push(string);
+                }
+                Ok(format!("(value)&string_{index}"))
+            }
+        }
+    }
+
+    fn expr(
+        &mut self,
+        expr: &CPSExpr,
+        mut env: Env,
+        out: &mut String,
+        indent: usize,
+    ) -> Result<(), Error> {
+        let pad = "    ".repeat(indent);
+
+        match expr {
+            CPSExpr::Primop(primop) => {
+                let left = self.value(primop.left, &env)?;
+                let right = self.value(primop.right, &env)?;
+
+                let condition = match primop.primop {
+                    Primop::Eq => Some(format!("curse_compare({left}, {right}) == 0")),
+                    Primop::Lt => Some(format!("curse_compare({left}, {right}) < 0")),
+                    Primop::Gt => Some(format!("curse_compare({left}, {right}) > 0")),
+                    Primop::Le => Some(format!("curse_compare({left}, {right}) <= 0")),
+                    Primop::Ge => Some(format!("curse_compare({left}, {right}) >= 0")),
+                    Primop::Record => {
+                        Some(format!("!IS_INT({left}) && KIND({left}) == KIND_RECORD"))
+                    }
+                    _ => None,
+                };
+
+                if let Some(condition) = condition {
+                    writeln!(out, "{pad}if ({condition}) {{").unwrap();
+                    self.expr(&primop.continuations[0], env.clone(), out, indent + 1)?;
+                    writeln!(out, "{pad}}} else {{").unwrap();
+                    self.expr(&primop.continuations[1], env, out, indent + 1)?;
+                    writeln!(out, "{pad}}}").unwrap();
+                    return Ok(());
+                }
+
+                let result = match primop.primop {
+                    // go through 64 bits so that the arithmetic wraps the same way `u32`s do
+                    Primop::Plus => format!("MK_INT((uint64_t)INT({left}) + INT({right}))"),
+                    Primop::Minus => format!("MK_INT((uint64_t)INT({left}) - INT({right}))"),
+                    Primop::Times => format!("MK_INT((uint64_t)INT({left}) * INT({right}))"),
+                    Primop::Div | Primop::Mod => {
+                        writeln!(
+                            out,
+                            "{pad}if (INT({right}) == 0) curse_panic(\"division by zero\");"
+                        )
+                        .unwrap();
+                        let op = if primop.primop == Primop::Div {
+                            '/'
+                        } else {
+                            '%'
+                        };
+                        format!("MK_INT(INT({left}) {op} INT({right}))")
+                    }
+                    Primop::Semi => right,
+                    _ => unreachable!("branching primops are handled above"),
+                };
+
+                let name = self.fresh_var(primop.name);
+                writeln!(out, "{pad}value {name} = {result};").unwrap();
+                env.insert(primop.name, name);
+                self.expr(&primop.continuations[0], env, out, indent)
+            }
+            CPSExpr::Record(record) => {
+                let name = self.fresh_var(record.name);
+                writeln!(
+                    out,
+                    "{pad}value {name} = alloc(KIND_RECORD, {});",
+                    record.values.len()
+                )
+                .unwrap();
+                for (index, value) in record.values.iter().enumerate() {
+                    let value = self.value(*value, &env)?;
+                    writeln!(out, "{pad}FIELD({name}, {index}) = {value};").unwrap();
+                }
+                env.insert(record.name, name);
+                self.expr(&record.continuation, env, out, indent)
+            }
+            CPSExpr::Select(select) => {
+                let record = self.value(select.record, &env)?;
+                let name = self.fresh_var(select.result);
+                writeln!(
+                    out,
+                    "{pad}value {name} = FIELD({record}, {});",
+                    select.index
+                )
+                .unwrap();
+                env.insert(select.result, name);
+                self.expr(&select.continuation, env, out, indent)
+            }
+            CPSExpr::Appl(appl) => {
+                for (index, arg) in appl.args.iter().enumerate() {
+                    let arg = self.value(*arg, &env)?;
+                    writeln!(out, "{pad}args[{index}] = {arg};").unwrap();
+                }
+                let function = self.value(appl.function, &env)?;
+                writeln!(out, "{pad}next = {function};").unwrap();
+                writeln!(out, "{pad}return;").unwrap();
+                Ok(())
+            }
+            CPSExpr::Fix(fix) => {
+                // Allocate all of the closures first so that they can refer to each other.
+                let mut closures = vec![];
+                for function in fix.functions.iter() {
+                    let name = function
+                        .name
+                        .var()
+                        .expect("functions are named by variables");
+                    let closure = self.fresh_var(name);
+                    let free_vars = sorted_free_vars(function, name);
+                    writeln!(
+                        out,
+                        "{pad}value {closure} = alloc(KIND_CLOSURE, {});",
+                        free_vars.len() + 1
+                    )
+                    .unwrap();
+                    env.insert(name, closure.clone());
+                    closures.push((closure, free_vars));
+                }
+
+                for (function, (closure, free_vars)) in fix.functions.iter().zip(closures) {
+                    let code = self.function(function, &free_vars)?;
+                    writeln!(out, "{pad}FIELD({closure}, 0) = (value){code};").unwrap();
+                    for (index, free_var) in free_vars.iter().enumerate() {
+                        let value = self.value(Value::Var(*free_var), &env)?;
+                        writeln!(out, "{pad}FIELD({closure}, {}) = {value};", index + 1).unwrap();
+                    }
+                }
+
+                self.expr(&fix.continuation, env, out, indent)
+            }
+            CPSExpr::Halt(value) => {
+                let value = self.value(*value, &env)?;
+                writeln!(out, "{pad}result = {value};").unwrap();
+                writeln!(out, "{pad}halted = 1;").unwrap();
+                writeln!(out, "{pad}return;").unwrap();
+                Ok(())
+            }
+        }
+    }
+
+    /// Generates the C function for `function`, whose closure stores `free_vars` in order after
+    /// the code pointer. Returns the name of the C function.
+    fn function(
+        &mut self,
+        function: &Function,
+        free_vars: &[InternedString],
+    ) -> Result<String, Error> {
+        let name = function
+            .name
+            .var()
+            .expect("functions are named by variables");
+        let code = self.fresh_function(&name.string());
+
+        let mut env = Env::new();
+        let mut body = String::new();
+
+        // dummy parameters aren't bound, but they still take up a slot in `args`
+        let mut params = vec![];
+        for (index, param) in [Some(function.left), Some(function.right), function.ret]
+            .into_iter()
+            .enumerate()
+        {
+            if let Some(Value::Var(param)) = param {
+                let c_name = self.fresh_var(param);
+                env.insert(param, c_name.clone());
+                params.push((index, c_name));
+            }
+        }
+
+        for (index, free_var) in free_vars.iter().enumerate() {
+            let c_name = self.fresh_var(*free_var);
+            writeln!(body, "    value {c_name} = FIELD(self, {});", index + 1).unwrap();
+            env.insert(*free_var, c_name);
+        }
+        // parameters shadow the function's own name
+        env.entry(name).or_insert_with(|| "self".to_string());
+
+        self.expr(&function.continuation, env, &mut body, 1)?;
+        self.define(&code, &params, allocation(&function.continuation), body);
+        Ok(code)
+    }
+}
+
+/// How many words running `expr` allocates at most, not counting what the functions it defines
+/// allocate when they're called.
+fn allocation(expr: &CPSExpr) -> usize {
+    match expr {
+        CPSExpr::Primop(primop) => primop
+            .continuations
+            .iter()
+            .map(allocation)
+            .max()
+            .unwrap_or(0),
+        CPSExpr::Record(record) => record.values.len() + 1 + allocation(&record.continuation),
+        CPSExpr::Select(select) => allocation(&select.continuation),
+        CPSExpr::Fix(fix) => {
+            let closures: usize = fix
+                .functions
+                .iter()
+                .map(|function| {
+                    let name = function
+                        .name
+                        .var()
+                        .expect("functions are named by variables");
+                    sorted_free_vars(function, name).len() + 2
+                })
+                .sum();
+            closures + allocation(&fix.continuation)
+        }
+        CPSExpr::Appl(_) | CPSExpr::Halt(_) => 0,
+    }
+}
+
+/// The free variables of `function` other than itself, in a consistent order.
+fn sorted_free_vars(function: &Function, name: InternedString) -> Vec<InternedString> {
+    let mut free_vars: Vec<_> = function
+        .free_vars()
+        .into_iter()
+        .filter(|free_var| *free_var != name)
+        .collect();
+    free_vars.sort_by_cached_key(|free_var| free_var.string().to_string());
+    free_vars
+}
+
+/// Turns a name into something that's a valid C identifier.
+fn mangle(name: &str) -> String {
+    let mut mangled = String::with_capacity(name.len());
+    for byte in name.bytes() {
+        if byte.is_ascii_alphanumeric() || byte == b'_' {
+            mangled.push(byte as char);
+        } else {
+            write!(mangled, "_x{byte:02x}").unwrap();
+        }
+    }
+    mangled
+}
+
+/// Writes `string` as a C string literal.
+fn c_string(string: &str) -> String {
+    let mut literal = String::from("\"");
+    for byte in string.bytes() {
+        match byte {
+            b'"' => literal.push_str("\\\""),
+            b'\\' => literal.push_str("\\\\"),
+            b' '..=b'~' => literal.push(byte as char),
+            _ => write!(literal, "\\{byte:03o}").unwrap(),
+        }
+    }
+    literal.push('"');
+    literal
+}
@@ -0,0 +1,113 @@
+/* The runtime that every program generated by `curse_codegen_c` is prepended with. */
+
+#include <stdint.h>
+#include <stdio.h>
+#include <stdlib.h>
+
+/* Every value is a single word. Integers have their lowest bit set, everything else is a pointer
+ * to an object whose first word is a header. */
+typedef uintptr_t value;
+
+#define MK_INT(n) ((((value)(uint32_t)(n)) << 1) | 1)
+#define INT(v) ((uint32_t)((v) >> 1))
+#define IS_INT(v) ((v) & 1)
+
+/* Headers store the kind of object in the low bits and the number of fields in the rest. */
+enum kind { KIND_RECORD, KIND_CLOSURE, KIND_STRING };
+
+#define HEADER(kind, len) ((((value)(len)) << 2) | (kind))
+#define KIND(v) (((value *)(v))[0] & 3)
+#define LEN(v) (((value *)(v))[0] >> 2)
+#define FIELD(v, i) (((value *)(v))[(i) + 1])
+
+/* Closures are objects whose first field is the code pointer and whose remaining fields are the
+ * free variables of the function. Strings are statically allocated and only ever compared by
+ * address, since the compiler only emits one copy of each. */
+typedef void (*code)(value self);
+
+struct string {
+    value header;
+    const char *chars;
+};
+
+/* Functions never return a value. Instead, they store the arguments to the next call here along
+ * with the closure being called, and return to the trampoline in `main`. */
+static value args[3];
+static value next;
+static int halted;
+static value result;
+
+/* The heap and its collector live in `curse_runtime`, which every generated program is linked
+ * against. Nothing ever returns, so when a function is entered the only live values are its
+ * closure and `args`. Every function reserves room for everything it allocates before doing
+ * anything else, and collects with those as the roots if there isn't enough. Strings live outside
+ * of the heap and are left alone. */
+#ifndef HEAP_WORDS
+#define HEAP_WORDS (1 << 20)
+#endif
+
+/* The start of `curse_runtime`'s heap, which is all that generated code touches directly. */
+struct heap {
+    value *ptr;
+    value *limit;
+};
+
+struct heap *curse_heap_new(size_t words);
+void curse_collect(struct heap *heap, value *roots, size_t count, size_t needed);
+int64_t curse_compare(value left, value right);
+
+static struct heap *heap;
+
+static void curse_panic(const char *message) {
+    fprintf(stderr, "error: %s\n", message);
+    exit(1);
+}
+
+/* Makes sure that the heap has room for `words` more words, collecting it with the closure of the
+ * function being run and `args` as the roots if it doesn't. */
+static void reserve(value *self, size_t words) {
+    if ((size_t)(heap->limit - heap->ptr) >= words) {
+        return;
+    }
+
+    value roots[4] = {*self, args[0], args[1], args[2]};
+    curse_collect(heap, roots, 4, words);
+    *self = roots[0];
+    for (size_t i = 0; i < 3; i++) {
+        args[i] = roots[i + 1];
+    }
+}
+
+/* Allocates an object of `kind` with `len` fields, which `reserve` must have made room for. */
+static value alloc(enum kind kind, size_t len) {
+    value *object = heap->ptr;
+    heap->ptr += len + 1;
+    object[0] = HEADER(kind, len);
+    return (value)object;
+}
+
+static void print_value(value v) {
+    if (IS_INT(v)) {
+        printf("%u", INT(v));
+        return;
+    }
+
+    switch (KIND(v)) {
+    case KIND_RECORD:
+        printf("{");
+        for (size_t i = 0; i < LEN(v); i++) {
+            if (i > 0) {
+                printf(", ");
+            }
+            print_value(FIELD(v, i));
+        }
+        printf("}");
+        break;
+    case KIND_CLOSURE:
+        printf("<function>");
+        break;
+    case KIND_STRING:
+        printf("\"%s\"", ((struct string *)v)->chars);
+        break;
+    }
+}
@@ -0,0 +1,126 @@
+//! Compiles the Project Euler programs and the CPS expected-output programs with the system C
+//! compiler, and checks that running them prints the same thing that the CPS reference evaluator
+//! computes. Skipped if there's no `cc` to run.
+
+use std::{fmt::Write, fs, path::Path, process::Command};
+
+use bumpalo::Bump;
+use curse_cps::{eval, optimize};
+use curse_test_support::lock;
+
+/// Formats a value the same way the generated `print_value` does.
+fn format(value: &eval::Value, out: &mut String) {
+    match value {
+        eval::Value::Int(n) => write!(out, "{n}").unwrap(),
+        eval::Value::String(s) => write!(out, "\"{s}\"").unwrap(),
+        eval::Value::Record(values) => {
+            out.push('{');
+            for (i, value) in values.iter().enumerate() {
+                if i > 0 {
+                    out.push_str(", ");
+                }
+                format(value, out);
+            }
+            out.push('}');
+        }
+        eval::Value::Function(_) => out.push_str("<function>"),
+    }
+}
+
+/// Compiles and runs `path`, linked against `runtime`, with each of `heaps`, which are extra flags
+/// for the C compiler, returning what it printed each time and what it should have printed.
+fn run(path: &Path, dir: &Path, runtime: &Path, heaps: &[&[&str]]) -> (Vec<String>, String) {
+    let ast_program = curse_test_support::parse_with(|interner| {
+        curse_interpreter::flatten_asts(interner, path.to_str().unwrap()).expect("program parses")
+    });
+
+    let arena = Bump::new();
+    let program = curse_test_support::lower_program(&arena, &ast_program);
+
+    let global = curse_mir::ctx::Global::default();
+    let mut typeck = curse_mir::ctx::Typeck::with_global(&global);
+    let typed = curse_mir::check_program(&mut typeck, &program).expect("type checks");
+    let unoptimized =
+        curse_cps::convert_program(&program, &typed.dispatches).expect("converts to CPS");
+    let mut expected = String::new();
+    format(&eval::eval(&unoptimized).expect("evaluates"), &mut expected);
+    expected.push('\n');
+
+    let cps = optimize::optimize(unoptimized, Default::default());
+    let c_program = curse_codegen_c::generate(&cps).expect("generates C");
+
+    let name = path.file_stem().unwrap().to_str().unwrap();
+    let c_path = dir.join(format!("{name}.c"));
+    let exe_path = dir.join(name);
+    fs::write(&c_path, c_program).unwrap();
+
+    let outputs = heaps
+        .iter()
+        .map(|flags| {
+            let status = Command::new("cc")
+                .args(*flags)
+                .arg("-o")
+                .arg(&exe_path)
+                .arg(&c_path)
+                .arg(runtime)
+                .args(curse_codegen_c::SYSTEM_LIBRARIES)
+                .status()
+                .unwrap();
+            assert!(status.success(), "{} doesn't compile", c_path.display());
+
+            let output = Command::new(&exe_path).output().unwrap();
+            assert!(output.status.success(), "{} failed", exe_path.display());
+            String::from_utf8(output.stdout).unwrap()
+        })
+        .collect();
+
+    (outputs, expected)
+}
+
+/// Checks every program in `dir`.
+fn agree(dir: &str) {
+    let _interner = lock();
+    if Command::new("cc").arg("--version").output().is_err() {
+        eprintln!("skipping: no `cc` found");
+        return;
+    }
+
+    let runtime = curse_codegen_c::runtime_library().expect("the runtime is built for the tests");
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let interpreter_dir = manifest_dir.join("../curse_interpreter");
+
+    let mut programs: Vec<_> = fs::read_dir(manifest_dir.join(dir))
+        .expect("directory exists")
+        .map(|entry| entry.expect("readable entry").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "curse"))
+        .collect();
+    programs.sort();
+
+    let dir = std::env::temp_dir().join(format!("curse-codegen-c-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+
+    // `dynamic_import`s are relative to the working directory, and the standard library lives
+    // next to the interpreter.
+    std::env::set_current_dir(&interpreter_dir).unwrap();
+
+    // a tiny heap makes sure that the garbage collector runs, and often
+    let heaps: &[&[&str]] = &[&[], &["-DHEAP_WORDS=16"]];
+    for program in programs {
+        let (outputs, expected) = run(&program, &dir, &runtime, heaps);
+        for (actual, flags) in outputs.iter().zip(heaps) {
+            assert_eq!(actual, &expected, "{} {flags:?}", program.display());
+        }
+    }
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn project_euler() {
+    agree("../curse_interpreter/project_euler");
+}
+
+#[test]
+fn expected_programs() {
+    agree("../curse_cps/tests/expected");
+}
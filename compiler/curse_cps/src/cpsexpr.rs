@@ -1,3 +1,5 @@
+use std::collections::HashSet;
+
 use curse_interner::InternedString;
 
 /// Represents literal values, records and functions are created via CPSFix and CPSRecord
@@ -10,6 +12,16 @@ pub enum Value {
     String(InternedString),
 }
 
+impl Value {
+    /// The name of this value if it's a variable.
+    pub fn var(self) -> Option<InternedString> {
+        match self {
+            Value::Var(name) => Some(name),
+            _ => None,
+        }
+    }
+}
+
 pub fn var(s: &str) -> Value {
     Value::Var(InternedString::get_or_intern(s))
 }
@@ -19,7 +31,7 @@ pub fn var_from_id(id: curse_interner::Ident) -> Value {
 }
 
 /// Primitive operations (special things handled by the compiler).
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum Primop {
     Plus,
     Times,
@@ -59,6 +71,63 @@ pub enum CPSExpr {
     Halt(Value),
 }
 
+impl CPSExpr {
+    /// Every value this expression uses directly, not counting nested expressions.
+    pub fn values(&self) -> Vec<Value> {
+        match self {
+            CPSExpr::Primop(primop) => vec![primop.left, primop.right],
+            CPSExpr::Record(record) => record.values.clone(),
+            CPSExpr::Select(select) => vec![select.record],
+            CPSExpr::Appl(appl) => [appl.function]
+                .into_iter()
+                .chain(appl.args.iter().copied())
+                .collect(),
+            CPSExpr::Fix(_) => vec![],
+            CPSExpr::Halt(value) => vec![*value],
+        }
+    }
+
+    /// The variables this expression uses without binding them itself.
+    pub fn free_vars(&self) -> HashSet<InternedString> {
+        let mut free: HashSet<InternedString> = self
+            .values()
+            .into_iter()
+            .filter_map(|value| match value {
+                Value::Var(name) => Some(name),
+                _ => None,
+            })
+            .collect();
+
+        let mut extend_except = |free_vars: HashSet<InternedString>, bound: &[InternedString]| {
+            free.extend(free_vars.into_iter().filter(|name| !bound.contains(name)));
+        };
+
+        match self {
+            CPSExpr::Primop(primop) => {
+                for continuation in primop.continuations.iter() {
+                    extend_except(continuation.free_vars(), &[primop.name]);
+                }
+            }
+            CPSExpr::Record(record) => {
+                extend_except(record.continuation.free_vars(), &[record.name])
+            }
+            CPSExpr::Select(select) => {
+                extend_except(select.continuation.free_vars(), &[select.result])
+            }
+            CPSExpr::Fix(fix) => {
+                let names: Vec<_> = fix.functions.iter().filter_map(|f| f.name.var()).collect();
+                for function in fix.functions.iter() {
+                    extend_except(function.free_vars(), &names);
+                }
+                extend_except(fix.continuation.free_vars(), &names);
+            }
+            CPSExpr::Appl(_) | CPSExpr::Halt(_) => {}
+        }
+
+        free
+    }
+}
+
 #[derive(Debug, Eq, PartialEq)]
 pub struct CPSPrimop {
     pub primop: Primop,
@@ -137,24 +206,53 @@ impl CPSAppl {
     }
 }
 
-/// Every function takes three args now: `left`, `right`, and a continuation.
+/// Every function takes three args now: `left`, `right`, and a continuation. Continuations
+/// themselves only take `left`, so they have no `ret` and a dummy `right`.
 #[derive(Debug, PartialEq, Eq)]
 pub struct Function {
     pub left: Value,
     pub name: Value,
     pub right: Value,
+    pub ret: Option<Value>,
     pub continuation: Box<CPSExpr>,
 }
 
 impl Function {
-    pub fn new(left: Value, name: Value, right: Value, continuation: Box<CPSExpr>) -> Self {
+    pub fn new(
+        left: Value,
+        name: Value,
+        right: Value,
+        ret: Option<Value>,
+        continuation: Box<CPSExpr>,
+    ) -> Self {
         Self {
             left,
             name,
             right,
+            ret,
             continuation,
         }
     }
+
+    /// The parameters this function actually binds, in the order arguments are passed.
+    pub fn params(&self) -> Vec<Value> {
+        [Some(self.left), Some(self.right), self.ret]
+            .into_iter()
+            .flatten()
+            .filter(|param| matches!(param, Value::Var(_)))
+            .collect()
+    }
+
+    /// The variables the body of this function uses that aren't its parameters. This includes the
+    /// function's own name if it's recursive.
+    pub fn free_vars(&self) -> HashSet<InternedString> {
+        let params: Vec<_> = self.params().into_iter().filter_map(Value::var).collect();
+        self.continuation
+            .free_vars()
+            .into_iter()
+            .filter(|name| !params.contains(name))
+            .collect()
+    }
 }
 
 /// A `Vec` of functions to define (basically always either the list of top level functions or a
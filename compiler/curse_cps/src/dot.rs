@@ -0,0 +1,134 @@
+//! Writing CPS expressions in dot format for Graphviz.
+//!
+//! Each expression is a node, with an edge to each of its continuations. The functions that a
+//! `Fix` defines are drawn as clusters holding their bodies, so that it's easy to see which code
+//! belongs to which function.
+
+use std::fmt::Write as _;
+
+use crate::cpsexpr::{CPSExpr, Value};
+
+pub struct Builder {
+    count: u32,
+    out: String,
+    /// How deeply nested in clusters the next line is.
+    depth: usize,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        let out = String::with_capacity(2048) + "digraph cps {\n    node [shape = box]";
+
+        Builder {
+            count: 0,
+            out,
+            depth: 1,
+        }
+    }
+
+    /// Draws `expr` and everything after it, returning the id of its node.
+    pub fn visit_expr(&mut self, expr: &CPSExpr) -> u32 {
+        match expr {
+            CPSExpr::Halt(value) => self.node(&format!("halt {value}")),
+            CPSExpr::Appl(appl) => {
+                let label = format!("app {}{}", appl.function, values(&appl.args, " "));
+                self.node(&label)
+            }
+            CPSExpr::Primop(primop) => {
+                let label = format!(
+                    "{} = {} {} {}",
+                    primop.name,
+                    primop.primop.name(),
+                    primop.left,
+                    primop.right
+                );
+                let id = self.node(&label);
+                let branches: &[&str] = match primop.continuations.len() {
+                    2 => &["true", "false"],
+                    _ => &[""],
+                };
+                for (continuation, branch) in primop.continuations.iter().zip(branches) {
+                    let next = self.visit_expr(continuation);
+                    self.edge(id, next, branch);
+                }
+                id
+            }
+            CPSExpr::Record(record) => {
+                let label = format!("{} = ({})", record.name, values(&record.values, ""));
+                let id = self.node(&label);
+                let next = self.visit_expr(&record.continuation);
+                self.edge(id, next, "");
+                id
+            }
+            CPSExpr::Select(select) => {
+                let label = format!("{} = {}.{}", select.result, select.record, select.index);
+                let id = self.node(&label);
+                let next = self.visit_expr(&select.continuation);
+                self.edge(id, next, "");
+                id
+            }
+            CPSExpr::Fix(fix) => {
+                let id = self.node("fix");
+                for function in fix.functions.iter() {
+                    let cluster = self.fresh();
+                    let mut label =
+                        format!("{} {} {}", function.name, function.left, function.right);
+                    if let Some(ret) = function.ret {
+                        write!(label, " {ret}").unwrap();
+                    }
+
+                    self.line(&format!("subgraph cluster_{cluster} {{"));
+                    self.depth += 1;
+                    self.line(&format!("label = {label:?}"));
+                    let body = self.visit_expr(&function.continuation);
+                    self.depth -= 1;
+                    self.line("}");
+
+                    self.edge(id, body, &function.name.to_string());
+                }
+                let next = self.visit_expr(&fix.continuation);
+                self.edge(id, next, "");
+                id
+            }
+        }
+    }
+
+    fn fresh(&mut self) -> u32 {
+        self.count += 1;
+        self.count
+    }
+
+    fn line(&mut self, line: &str) {
+        write!(self.out, "\n{:indent$}{line}", "", indent = self.depth * 4).unwrap();
+    }
+
+    fn node(&mut self, label: &str) -> u32 {
+        let id = self.fresh();
+        self.line(&format!("p{id}[label = {label:?}]"));
+        id
+    }
+
+    fn edge(&mut self, from: u32, to: u32, label: &str) {
+        self.line(&format!("p{from} -> p{to}[label = {label:?}]"));
+    }
+
+    pub fn finish(self) -> String {
+        self.out + "\n}\n"
+    }
+}
+
+/// Writes `values` separated by spaces, with `prefix` before the first one.
+fn values(values: &[Value], prefix: &str) -> String {
+    let mut out = String::new();
+    for (i, value) in values.iter().enumerate() {
+        out += if i == 0 { prefix } else { " " };
+        write!(out, "{value}").unwrap();
+    }
+    out
+}
@@ -0,0 +1,263 @@
+//! A reference evaluator for `CPSExpr`s.
+//!
+//! This is intentionally as simple as possible so that it can be trusted when checking the output
+//! of the CPS conversion against the tree-walking interpreter. Since nothing in CPS ever returns,
+//! evaluation is just a loop that jumps from one expression to the next.
+
+use std::{cmp::Ordering, rc::Rc};
+
+use curse_interner::InternedString;
+
+use crate::cpsexpr::{self, CPSExpr, CPSFix, Primop};
+
+/// The result of evaluating a `cpsexpr::Value`.
+#[derive(Clone, Debug)]
+pub enum Value<'cps> {
+    Int(u32),
+    String(InternedString),
+    Record(Rc<[Value<'cps>]>),
+    Function(Closure<'cps>),
+}
+
+/// A function defined in a `Fix`, along with the environment the `Fix` was evaluated in.
+///
+/// We don't store the functions of the `Fix` in the environment directly since they can be
+/// mutually recursive. Instead, they are all bound again whenever one of them is applied.
+#[derive(Clone)]
+pub struct Closure<'cps> {
+    fix: &'cps CPSFix,
+    index: usize,
+    env: Env<'cps>,
+}
+
+impl std::fmt::Debug for Closure<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "<function {:?}>", self.fix.functions[self.index].name)
+    }
+}
+
+#[derive(Debug)]
+pub enum EvalError {
+    UnboundVariable(InternedString),
+    NotAFunction,
+    NotARecord,
+    NotAnInteger,
+    SelectOutOfBounds { index: usize, len: usize },
+    DivideByZero,
+}
+
+/// A persistent linked list of bindings, so that closures can capture their environment without
+/// copying it.
+#[derive(Clone, Default)]
+struct Env<'cps>(Option<Rc<Frame<'cps>>>);
+
+struct Frame<'cps> {
+    name: InternedString,
+    value: Value<'cps>,
+    parent: Env<'cps>,
+}
+
+impl<'cps> Env<'cps> {
+    fn bind(&self, name: InternedString, value: Value<'cps>) -> Self {
+        Env(Some(Rc::new(Frame {
+            name,
+            value,
+            parent: self.clone(),
+        })))
+    }
+
+    /// Binds `param` to `value` if `param` is actually a variable. Continuations use `Int(0)` as a
+    /// dummy parameter, which doesn't bind anything.
+    fn bind_param(&self, param: cpsexpr::Value, value: Value<'cps>) -> Self {
+        match param {
+            cpsexpr::Value::Var(name) => self.bind(name, value),
+            _ => self.clone(),
+        }
+    }
+
+    /// Binds every function in `fix` as a closure over `self`.
+    fn bind_fix(&self, fix: &'cps CPSFix) -> Self {
+        fix.functions
+            .iter()
+            .enumerate()
+            .fold(self.clone(), |env, (index, function)| {
+                env.bind_param(
+                    function.name,
+                    Value::Function(Closure {
+                        fix,
+                        index,
+                        env: self.clone(),
+                    }),
+                )
+            })
+    }
+
+    fn lookup(&self, name: InternedString) -> Result<Value<'cps>, EvalError> {
+        let mut env = self;
+        while let Some(frame) = &env.0 {
+            if frame.name == name {
+                return Ok(frame.value.clone());
+            }
+            env = &frame.parent;
+        }
+        Err(EvalError::UnboundVariable(name))
+    }
+
+    fn value(&self, value: cpsexpr::Value) -> Result<Value<'cps>, EvalError> {
+        match value {
+            cpsexpr::Value::Var(name) => self.lookup(name),
+            cpsexpr::Value::Int(n) => Ok(Value::Int(n)),
+            cpsexpr::Value::String(s) => Ok(Value::String(s)),
+        }
+    }
+}
+
+fn int(value: &Value) -> Result<u32, EvalError> {
+    match value {
+        Value::Int(n) => Ok(*n),
+        _ => Err(EvalError::NotAnInteger),
+    }
+}
+
+/// Whether two values are the same, for `Primop::Eq`. Records, which choice and struct values
+/// are too, are the same when all of their fields are.
+fn equal(lhs: &Value, rhs: &Value) -> Result<bool, EvalError> {
+    match (lhs, rhs) {
+        (Value::String(a), Value::String(b)) => Ok(a == b),
+        (Value::Record(a), Value::Record(b)) => {
+            if a.len() != b.len() {
+                return Ok(false);
+            }
+            for (a, b) in a.iter().zip(b.iter()) {
+                if !equal(a, b)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+        _ => Ok(int(lhs)? == int(rhs)?),
+    }
+}
+
+/// How two values are ordered, for the comparison primops. Records are ordered by their fields
+/// in turn, so choice values are ordered by their tag, which is the position of the variant,
+/// and then by their payload.
+fn compare(lhs: &Value, rhs: &Value) -> Result<Ordering, EvalError> {
+    match (lhs, rhs) {
+        (Value::Record(a), Value::Record(b)) => {
+            for (a, b) in a.iter().zip(b.iter()) {
+                match compare(a, b)? {
+                    Ordering::Equal => {}
+                    ordering => return Ok(ordering),
+                }
+            }
+            Ok(a.len().cmp(&b.len()))
+        }
+        _ => Ok(int(lhs)?.cmp(&int(rhs)?)),
+    }
+}
+
+/// Evaluates `expr` until it halts, returning the value it halted with.
+pub fn eval(expr: &CPSExpr) -> Result<Value<'_>, EvalError> {
+    let mut env = Env::default();
+    let mut expr = expr;
+
+    loop {
+        match expr {
+            CPSExpr::Primop(primop) => {
+                let left = env.value(primop.left)?;
+                let right = env.value(primop.right)?;
+
+                let branch = |cond: bool| &primop.continuations[if cond { 0 } else { 1 }];
+                let arith = |op: fn(u32, u32) -> Option<u32>| -> Result<Value, EvalError> {
+                    op(int(&left)?, int(&right)?)
+                        .map(Value::Int)
+                        .ok_or(EvalError::DivideByZero)
+                };
+
+                let result = match primop.primop {
+                    Primop::Plus => arith(|a, b| Some(a.wrapping_add(b)))?,
+                    Primop::Minus => arith(|a, b| Some(a.wrapping_sub(b)))?,
+                    Primop::Times => arith(|a, b| Some(a.wrapping_mul(b)))?,
+                    Primop::Div => arith(u32::checked_div)?,
+                    Primop::Mod => arith(u32::checked_rem)?,
+                    Primop::Semi => right.clone(),
+                    Primop::Eq => {
+                        expr = branch(equal(&left, &right)?);
+                        continue;
+                    }
+                    Primop::Lt => {
+                        expr = branch(compare(&left, &right)?.is_lt());
+                        continue;
+                    }
+                    Primop::Gt => {
+                        expr = branch(compare(&left, &right)?.is_gt());
+                        continue;
+                    }
+                    Primop::Le => {
+                        expr = branch(compare(&left, &right)?.is_le());
+                        continue;
+                    }
+                    Primop::Ge => {
+                        expr = branch(compare(&left, &right)?.is_ge());
+                        continue;
+                    }
+                    Primop::Record => {
+                        expr = branch(matches!(left, Value::Record(_)));
+                        continue;
+                    }
+                };
+
+                env = env.bind(primop.name, result);
+                expr = &primop.continuations[0];
+            }
+            CPSExpr::Record(record) => {
+                let values = record
+                    .values
+                    .iter()
+                    .map(|value| env.value(*value))
+                    .collect::<Result<_, _>>()?;
+
+                env = env.bind(record.name, Value::Record(values));
+                expr = &record.continuation;
+            }
+            CPSExpr::Select(select) => {
+                let Value::Record(values) = env.value(select.record)? else {
+                    return Err(EvalError::NotARecord);
+                };
+                let value = values
+                    .get(select.index)
+                    .ok_or(EvalError::SelectOutOfBounds {
+                        index: select.index,
+                        len: values.len(),
+                    })?
+                    .clone();
+
+                env = env.bind(select.result, value);
+                expr = &select.continuation;
+            }
+            CPSExpr::Appl(appl) => {
+                let Value::Function(closure) = env.value(appl.function)? else {
+                    return Err(EvalError::NotAFunction);
+                };
+                let function = &closure.fix.functions[closure.index];
+
+                let mut new_env = closure.env.bind_fix(closure.fix);
+                let params = [Some(function.left), Some(function.right), function.ret];
+                for (param, arg) in params.into_iter().zip(appl.args.iter()) {
+                    if let Some(param) = param {
+                        new_env = new_env.bind_param(param, env.value(*arg)?);
+                    }
+                }
+
+                env = new_env;
+                expr = &function.continuation;
+            }
+            CPSExpr::Fix(fix) => {
+                env = env.bind_fix(fix);
+                expr = &fix.continuation;
+            }
+            CPSExpr::Halt(value) => return env.value(*value),
+        }
+    }
+}
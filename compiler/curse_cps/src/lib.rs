@@ -1,16 +1,31 @@
 // just for now
 #![allow(dead_code)]
 
-use std::{cell::RefCell, rc::Rc};
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    rc::Rc,
+};
 
 use cpsexpr::{
-    var_from_id, CPSAppl, CPSExpr, CPSFix, CPSPrimop, CPSRecord, Function, Primop, Value,
+    var_from_id, CPSAppl, CPSExpr, CPSFix, CPSPrimop, CPSRecord, CPSSelect, Function, Primop, Value,
+};
+use curse_hir::hir::{self, ExprKind, Trait};
+use curse_interner::{Ident, InternedString};
+use curse_mir::{Dispatch, Dispatches, Owner};
+use match_compiler::{
+    compile_match_expr, Binding, BindingValue, Body, Constructor, Decision, Test,
 };
-use curse_hir::hir::{self, ExprKind};
-use curse_interner::InternedString;
 
 pub mod cpsexpr;
+pub mod dot;
+pub mod eval;
 mod match_compiler;
+pub mod optimize;
+pub mod sexpr;
+pub mod unsupported;
+
+pub use unsupported::Unsupported;
 
 #[cfg(test)]
 mod tests;
@@ -37,8 +52,7 @@ fn symbol_to_primop(symbol: hir::Symbol) -> Primop {
         hir::Symbol::Plus => Primop::Plus,
         hir::Symbol::Minus => Primop::Minus,
         hir::Symbol::Star => Primop::Times,
-        hir::Symbol::Dot => todo!(),
-        hir::Symbol::DotDot => todo!(),
+        hir::Symbol::Dot | hir::Symbol::DotDot => unreachable!("`.` and `..` are unsupported"),
         hir::Symbol::Semi => Primop::Semi,
         hir::Symbol::Percent => Primop::Mod,
         hir::Symbol::Slash => Primop::Div,
@@ -59,24 +73,397 @@ fn branching_symbol(symbol: hir::Symbol) -> bool {
     }
 }
 
+thread_local! {
+    /// The position of each variant in its choice, keyed by the name of the choice and the
+    /// variant, for the program being converted.
+    static VARIANTS: RefCell<HashMap<(InternedString, InternedString), u32>> =
+        RefCell::new(HashMap::new());
+}
+
+/// Remembers the position of every variant in `program`, which the tags of its choice values are.
+fn set_variants(program: &hir::Program) {
+    let variants = program
+        .choice_defs
+        .iter()
+        .flat_map(|(name, def)| {
+            def.variants
+                .entries
+                .iter()
+                .enumerate()
+                .map(|(index, (variant, _))| ((*name, variant.symbol), index as u32))
+        })
+        .collect();
+    VARIANTS.with(|cell| *cell.borrow_mut() = variants);
+}
+
+/// Choice and struct values are records whose first entry is a tag naming the constructor, which
+/// is the position of the variant in its choice, or 0 for a struct. Comparing two values of the
+/// same choice then orders them by variant first and payload second, the same as the interpreter.
+fn constructor_tag(path: hir::Path) -> Value {
+    let [choice, variant] = path else {
+        return Value::Int(0);
+    };
+    let index = VARIANTS.with(|cell| cell.borrow().get(&(choice.symbol, variant.symbol)).copied());
+    Value::Int(index.expect("type checking makes sure that every variant exists"))
+}
+
+thread_local! {
+    /// How the traits used in the program being converted are carried out.
+    static DISPATCH: RefCell<Dispatching> = RefCell::new(Dispatching::default());
+}
+
+/// Traits are carried out by calling the function made for an `impl`, and generic functions are
+/// copied for each set of [`Dispatch`]es that they're used with, so that every use of a trait in
+/// a copy goes to a particular `impl` or builtin.
+#[derive(Default)]
+struct Dispatching {
+    dispatches: Dispatches,
+    /// The name of the function made for each `impl`.
+    impls: HashMap<(Trait, InternedString), InternedString>,
+    /// The function or `impl` being converted, and what it was given for each of its constraints.
+    owner: Option<Owner>,
+    dictionary: Vec<Dispatch>,
+    /// The name of the copy of each generic function for what it's given.
+    copies: HashMap<(InternedString, Vec<Dispatch>), InternedString>,
+    /// Every copy in the order that they were named in, which they're converted in too.
+    queue: Vec<(InternedString, Vec<Dispatch>, InternedString)>,
+    unsupported: Vec<Unsupported>,
+}
+
+impl Dispatching {
+    /// What the traits used at `span` in the function being converted go to, or `None` if none
+    /// are.
+    fn at(&self, span: curse_span::Span) -> Option<Vec<Dispatch>> {
+        let sites = self.dispatches.sites.get(&(self.owner?, span))?;
+        Some(
+            sites
+                .iter()
+                .map(|dispatch| dispatch.substitute(&self.dictionary))
+                .collect(),
+        )
+    }
+
+    /// Reports something at `span` that can't be carried out, once even if the function that
+    /// it's in is copied.
+    fn unsupported(&mut self, what: &'static str, span: curse_span::Span) {
+        let function = match self.owner {
+            Some(Owner::Function(name)) | Some(Owner::Impl(_, name)) => name,
+            None => return,
+        };
+        let span = span.start_len().into();
+        if !self.unsupported.iter().any(|found| found.span == span) {
+            self.unsupported.push(Unsupported {
+                what,
+                function,
+                span,
+            });
+        }
+    }
+}
+
+/// How a symbol is carried out.
+enum Operation {
+    /// The primop on `I32`s and `Bool`s.
+    Primop,
+    /// Calls the function made for an `impl`, with the arguments swapped if `swap`, and the
+    /// result negated if `negate`. That way every comparison only needs `<` from `Ord`.
+    Call {
+        function: Value,
+        swap: bool,
+        negate: bool,
+    },
+}
+
+/// How the symbol at `span` is carried out, or `None` if it can't be, which is reported.
+fn operation(symbol: hir::Symbol, span: curse_span::Span) -> Option<Operation> {
+    DISPATCH.with(|cell| {
+        let mut dispatching = cell.borrow_mut();
+        let dispatch = dispatching
+            .at(span)
+            .and_then(|sites| sites.into_iter().next())
+            .unwrap_or(Dispatch::Builtin);
+        match dispatch {
+            Dispatch::Builtin => Some(Operation::Primop),
+            Dispatch::Impl(trait_, ty) => {
+                let (swap, negate) = match symbol {
+                    hir::Symbol::Gt => (true, false),
+                    hir::Symbol::Le => (true, true),
+                    hir::Symbol::Ge => (false, true),
+                    _ => (false, false),
+                };
+                Some(Operation::Call {
+                    function: Value::Var(dispatching.impls[&(trait_, ty)]),
+                    swap,
+                    negate,
+                })
+            }
+            Dispatch::Structural(_) => {
+                dispatching.unsupported("Comparing values with `impl`s inside", span);
+                None
+            }
+            Dispatch::Generic(_) | Dispatch::Unknown => {
+                dispatching.unsupported("Traits used on types that nothing decides", span);
+                None
+            }
+        }
+    })
+}
+
+/// What `ident` refers to: the copy of a generic function for what it's given there, the
+/// function made for the `impl` that a method goes to, or otherwise just the variable. `None` if
+/// it can't be worked out, which is reported.
+fn reference(ident: Ident) -> Option<Value> {
+    DISPATCH.with(|cell| {
+        let mut dispatching = cell.borrow_mut();
+        let Some(dictionary) = dispatching.at(ident.span) else {
+            return Some(var_from_id(ident));
+        };
+
+        if dispatching.dispatches.generics.contains_key(&ident.symbol) {
+            let key = (ident.symbol, dictionary);
+            if let Some(&copy) = dispatching.copies.get(&key) {
+                return Some(Value::Var(copy));
+            }
+            let copy = gensym(&ident.symbol.to_string());
+            dispatching.queue.push((key.0, key.1.clone(), copy));
+            dispatching.copies.insert(key, copy);
+            return Some(Value::Var(copy));
+        }
+
+        match dictionary.as_slice() {
+            [Dispatch::Impl(trait_, ty)] => Some(Value::Var(dispatching.impls[&(*trait_, *ty)])),
+            _ => {
+                dispatching.unsupported("Methods used on types that nothing decides", ident.span);
+                None
+            }
+        }
+    })
+}
+
+/// Converts the function or `impl` that `owner` names into a function named `name`, where it's
+/// given `dictionary` for its constraints.
+fn convert_owned(
+    owner: Owner,
+    dictionary: Vec<Dispatch>,
+    name: Value,
+    arms: &[hir::Arm],
+) -> Function {
+    DISPATCH.with(|cell| {
+        let mut dispatching = cell.borrow_mut();
+        dispatching.owner = Some(owner);
+        dispatching.dictionary = dictionary;
+    });
+    convert_function(name, arms)
+}
+
+/// Converts a whole program into a single `Fix` of all of its top level functions and `impl`s,
+/// which then calls `main` with a continuation that halts on the result. Generic functions are
+/// copied for each set of `impl`s that they're used with, as type checking resolved them in
+/// `dispatches`. Fails with everything in the program that can't be converted yet.
+pub fn convert_program(
+    program: &hir::Program,
+    dispatches: &Dispatches,
+) -> Result<CPSExpr, Vec<Unsupported>> {
+    let unsupported = unsupported::find(program);
+    if !unsupported.is_empty() {
+        return Err(unsupported);
+    }
+
+    // start from scratch so that the generated names only depend on `program`
+    reset_sym_counter();
+    set_variants(program);
+
+    let mut defs: Vec<_> = program.function_defs.values().collect();
+    // sort so that the generated names don't depend on `HashMap` iteration order
+    defs.sort_by_key(|def| def.ident.symbol.string().to_string());
+    let mut impl_defs: Vec<_> = program.impl_defs.values().collect();
+    impl_defs.sort_by_key(|def| (def.ty.symbol.string().to_string(), def.trait_.name()));
+
+    let impls = impl_defs
+        .iter()
+        .map(|def| {
+            let name = gensym(&format!("{}_{}", def.trait_.name(), def.ty.symbol));
+            ((def.trait_, def.ty.symbol), name)
+        })
+        .collect();
+    DISPATCH.with(|cell| {
+        *cell.borrow_mut() = Dispatching {
+            dispatches: dispatches.clone(),
+            impls,
+            ..Dispatching::default()
+        }
+    });
+
+    // generic functions are only converted once it's known what they're given
+    let mut functions: Vec<_> = defs
+        .into_iter()
+        .filter(|def| !dispatches.generics.contains_key(&def.ident.symbol))
+        .map(|def| {
+            let owner = Owner::Function(def.ident.symbol);
+            convert_owned(owner, vec![], Value::Var(def.ident.symbol), def.arms)
+        })
+        .collect();
+    for def in impl_defs {
+        let key = (def.trait_, def.ty.symbol);
+        let name = DISPATCH.with(|cell| cell.borrow().impls[&key]);
+        functions.push(convert_owned(
+            Owner::Impl(key.0, key.1),
+            vec![],
+            Value::Var(name),
+            def.arms,
+        ));
+    }
+    // copying a generic function can need copies of others, so keep going until there are none
+    let mut copied = 0;
+    while let Some((name, dictionary, copy)) =
+        DISPATCH.with(|cell| cell.borrow().queue.get(copied).cloned())
+    {
+        copied += 1;
+        let def = &program.function_defs[&name];
+        functions.push(convert_owned(
+            Owner::Function(name),
+            dictionary,
+            Value::Var(copy),
+            def.arms,
+        ));
+    }
+
+    let unsupported = DISPATCH.with(|cell| std::mem::take(&mut cell.borrow_mut().unsupported));
+    if !unsupported.is_empty() {
+        return Err(unsupported);
+    }
+
+    let x = Value::Var(gensym("x"));
+    let k = Value::Var(gensym("k"));
+    Ok(CPSFix::new(
+        functions,
+        Box::new(CPSFix::new(
+            vec![Function::new(
+                x,
+                k,
+                Value::Int(0),
+                None,
+                Box::new(CPSExpr::Halt(x)),
+            )],
+            Box::new(CPSAppl::new(
+                Value::Var(InternedString::get_or_intern("main")),
+                vec![Value::Int(0), Value::Int(0), k],
+            )),
+        )),
+    ))
+}
+
+/// Converts a (possibly piecewise) closure into a function named `name`, compiling its arms into
+/// a decision tree over the two arguments.
+fn convert_function(name: Value, arms: &[hir::Arm]) -> Function {
+    let x = gensym("x");
+    let y = gensym("y");
+    let k = gensym("k");
+    let decision = compile_match_expr(arms, x, y);
+    let body = convert_decision_tree(&decision, &[x, y], &mut |v| {
+        CPSAppl::new(Value::Var(k), vec![v])
+    });
+
+    Function::new(
+        Value::Var(x),
+        name,
+        Value::Var(y),
+        Some(Value::Var(k)),
+        Box::new(body),
+    )
+}
+
+/// Calls the function made for an `impl` on `lhs` and `rhs`, passing the result on to `k`.
+fn call_impl(
+    function: Value,
+    swap: bool,
+    negate: bool,
+    lhs: Value,
+    rhs: Value,
+    k: Value,
+) -> CPSExpr {
+    let args = if swap { [rhs, lhs] } else { [lhs, rhs] };
+    if !negate {
+        return CPSAppl::new(function, vec![args[0], args[1], k]);
+    }
+
+    let b = Value::Var(gensym("b"));
+    let r = Value::Var(gensym("r"));
+    CPSFix::new(
+        vec![Function::new(
+            b,
+            r,
+            Value::Int(0),
+            None,
+            Box::new(CPSPrimop::new(
+                Primop::Eq,
+                b,
+                Value::Int(1),
+                gensym("eq"),
+                vec![
+                    CPSAppl::new(k, vec![Value::Int(0)]),
+                    CPSAppl::new(k, vec![Value::Int(1)]),
+                ],
+            )),
+        )],
+        Box::new(CPSAppl::new(function, vec![args[0], args[1], r])),
+    )
+}
+
 fn convert_expr(expr: hir::Expr, cont: &mut dyn FnMut(Value) -> CPSExpr) -> CPSExpr {
     match expr.kind {
         ExprKind::Symbol(symb) => {
+            let Some(operation) = operation(symb, expr.span) else {
+                return CPSExpr::Halt(Value::Int(0));
+            };
+            if let Operation::Call {
+                function,
+                swap,
+                negate,
+            } = operation
+            {
+                let x = gensym("x");
+                let y = gensym("y");
+                let f = gensym("f");
+                let k = Value::Var(gensym("k"));
+                let call = call_impl(function, swap, negate, Value::Var(x), Value::Var(y), k);
+                return CPSFix::new(
+                    vec![Function::new(
+                        Value::Var(x),
+                        Value::Var(f),
+                        Value::Var(y),
+                        Some(k),
+                        Box::new(call),
+                    )],
+                    Box::new(cont(Value::Var(f))),
+                );
+            }
+
             let x = gensym("x");
             let y = gensym("y");
             let f = gensym("f");
             let t = gensym("t");
+            let k = Value::Var(gensym("k"));
+            let continuations = if branching_symbol(symb) {
+                vec![
+                    CPSAppl::new(k, vec![Value::Int(1)]),
+                    CPSAppl::new(k, vec![Value::Int(0)]),
+                ]
+            } else {
+                vec![CPSAppl::new(k, vec![Value::Var(t)])]
+            };
             CPSFix::new(
                 vec![Function::new(
                     Value::Var(x),
                     Value::Var(f),
                     Value::Var(y),
+                    Some(k),
                     Box::new(CPSPrimop::new(
                         symbol_to_primop(symb),
                         Value::Var(x),
                         Value::Var(y),
                         t,
-                        vec![cont(Value::Var(t))],
+                        continuations,
                     )),
                 )],
                 Box::new(cont(Value::Var(f))),
@@ -85,7 +472,13 @@ fn convert_expr(expr: hir::Expr, cont: &mut dyn FnMut(Value) -> CPSExpr) -> CPSE
         ExprKind::Lit(hir::Lit::Integer(n)) => cont(Value::Int(n)),
         ExprKind::Lit(hir::Lit::Bool(true)) => cont(Value::Int(1)),
         ExprKind::Lit(hir::Lit::Bool(false)) => cont(Value::Int(0)),
-        ExprKind::Lit(hir::Lit::Ident(var)) => cont(var_from_id(var)),
+        ExprKind::Lit(hir::Lit::Ident(var)) => match reference(var) {
+            Some(value) => cont(value),
+            None => CPSExpr::Halt(Value::Int(0)),
+        },
+        // the unit record is represented the same way the match compiler expects missing
+        // parameters to be
+        ExprKind::Record(map) if map.entries.is_empty() => cont(Value::Int(0)),
         ExprKind::Record(map) => {
             let results = vec![];
             let name = gensym("record");
@@ -103,22 +496,45 @@ fn convert_expr(expr: hir::Expr, cont: &mut dyn FnMut(Value) -> CPSExpr) -> CPSE
                 0,
             )
         }
-        // represent a constructor as a record with a string tag and value
-        // TODO(william) represent tag with an integer (requires knowing all the variants in each
-        // enum)
+        // represent a constructor as a record with a tag and value, even for structs so that
+        // their payload is at the same index
         ExprKind::Constructor(hir::Constructor { path, inner }) => {
             let name = gensym("ctor");
             convert_expr(*inner, &mut |inner_val| {
                 CPSRecord::new(
-                    vec![Value::String(InternedString::get_or_intern(&format!("{path:?}"))), inner_val],
+                    vec![constructor_tag(path), inner_val],
                     name,
                     Box::new(cont(Value::Var(name))),
                 )
             })
         }
-        ExprKind::Closure(_) => todo!(),
+        ExprKind::Closure(arms) => {
+            let f = Value::Var(gensym("f"));
+            CPSFix::new(vec![convert_function(f, arms)], Box::new(cont(f)))
+        }
         ExprKind::Appl(appl) => match appl.fun().kind {
             ExprKind::Symbol(symb) => {
+                let Some(operation) = operation(symb, appl.fun().span) else {
+                    return CPSExpr::Halt(Value::Int(0));
+                };
+                if let Operation::Call {
+                    function,
+                    swap,
+                    negate,
+                } = operation
+                {
+                    let x = Value::Var(gensym("x"));
+                    let r = Value::Var(gensym("r"));
+                    return CPSFix::new(
+                        vec![Function::new(x, r, Value::Int(0), None, Box::new(cont(x)))],
+                        Box::new(convert_expr(*appl.lhs(), &mut |lhs| {
+                            convert_expr(*appl.rhs(), &mut |rhs| {
+                                call_impl(function, swap, negate, lhs, rhs, r)
+                            })
+                        })),
+                    );
+                }
+
                 if branching_symbol(symb) {
                     convert_expr(*appl.lhs(), &mut |lhs| {
                         convert_expr(*appl.rhs(), &mut |rhs| {
@@ -127,7 +543,7 @@ fn convert_expr(expr: hir::Expr, cont: &mut dyn FnMut(Value) -> CPSExpr) -> CPSE
                             let k = Value::Var(gensym("k"));
                             let b = gensym("b");
                             CPSFix::new(
-                                vec![Function::new(x, k, y, Box::new(cont(x)))],
+                                vec![Function::new(x, k, y, None, Box::new(cont(x)))],
                                 Box::new(CPSPrimop::new(
                                     symbol_to_primop(symb),
                                     lhs,
@@ -160,7 +576,7 @@ fn convert_expr(expr: hir::Expr, cont: &mut dyn FnMut(Value) -> CPSExpr) -> CPSE
                 let x = Value::Var(gensym("x"));
                 let r = Value::Var(gensym("r"));
                 CPSFix::new(
-                    vec![Function::new(x, r, Value::Int(0), Box::new(cont(x)))],
+                    vec![Function::new(x, r, Value::Int(0), None, Box::new(cont(x)))],
                     Box::new(convert_expr(*appl.fun(), &mut |f| {
                         convert_expr(*appl.lhs(), &mut |lhs| {
                             convert_expr(*appl.rhs(), &mut |rhs| CPSAppl::new(f, vec![lhs, rhs, r]))
@@ -169,8 +585,10 @@ fn convert_expr(expr: hir::Expr, cont: &mut dyn FnMut(Value) -> CPSExpr) -> CPSE
                 )
             }
         },
-        ExprKind::Region(_) => todo!(),
-        ExprKind::Error => todo!(),
+        ExprKind::Region(_) => unreachable!("regions are unsupported"),
+        // an expression that couldn't be parsed stops the program, the same way a refuted match
+        // does
+        ExprKind::Error => CPSExpr::Halt(Value::Int(0)),
     }
 }
 
@@ -193,92 +611,171 @@ fn convert_record(
                 map_index + 1,
             )
         }),
-        Some((ident, None)) => {
-            current_vec.borrow_mut().push(var_from_id(*ident));
-            convert_record(map_vec, current_vec, cont, map_index + 1)
-        }
+        Some((ident, None)) => match reference(*ident) {
+            Some(value) => {
+                current_vec.borrow_mut().push(value);
+                convert_record(map_vec, current_vec, cont, map_index + 1)
+            }
+            None => CPSExpr::Halt(Value::Int(0)),
+        },
         None => (cont.borrow_mut())(current_vec.clone()),
     }
 }
 
-// bindings should be reversed for quick popping
-// fn convert_bindings(bindings: &mut bumpalo::collections::Vec<Binding>, cont: &mut dyn FnMut() -> CPSExpr) -> CPSExpr {
-//     match bindings.pop() {
-//         Some(Binding {
-//             variable: lhs,
-//             value: BindingValue::Variable(rhs),
-//         }) => CPSPrimop::new(
-//             Primop::Semi,
-//             Value::Var(lhs),
-//             Value::Int(0),
-//             *rhs,
-//             vec![cont()],
-//         ),
-//         Some(Binding {
-//             variable,
-//             value: BindingValue::Record { name, index },
-//         }) => CPSSelect::new(*index, Value::Var(*name), variable, Box::new(cont())),
-//         None => cont(),
-//     }
-// }
-
-// fn convert_decision_tree<'hir, 'a>(tree: &'a Decision<'hir, 'a>, cont: &mut dyn FnMut(Value) -> CPSExpr) -> CPSExpr {
-//     match tree {
-//         Decision::Success(&Body {
-//             value,
-//             mut bindings,
-//         }) => {
-//             bindings.reverse();
-//             convert_bindings(&mut bindings, &mut || convert_expr(value, cont))
-//         }
-//         Decision::Failure => CPSExpr::Halt(Value::Int(0)),
-//         Decision::Branch {
-//             test: Test {
-//                 variable,
-//                 constructor,
-//             },
-//             match_path,
-//             fail_path,
-//         } => {
-//             let next = vec![
-//                 convert_decision_tree(*match_path, cont),
-//                 convert_decision_tree(*fail_path, cont),
-//             ];
-//             match constructor {
-//                 Constructor::Integer(n) => CPSPrimop::new(
-//                     Primop::Eq,
-//                     Value::Var(*variable),
-//                     Value::Int(*n),
-//                     gensym("eq"),
-//                     next,
-//                 ),
-//                 Constructor::Boolean(b) => CPSPrimop::new(
-//                     Primop::Eq,
-//                     Value::Var(*variable),
-//                     Value::Int(if *b { 1 } else { 0 }),
-//                     gensym("eq"),
-//                     next,
-//                 ),
-//                 Constructor::Record(_) => CPSPrimop::new(
-//                     Primop::Record,
-//                     Value::Var(*variable),
-//                     Value::Int(0),
-//                     gensym("rec"),
-//                     next,
-//                 ),
-//                 Constructor::NamedConstructor(path, _) => CPSPrimop::new(
-//                     Primop::Eq,
-//                     Value::Var(*variable),
-//                     Value::Int(resolve_path(path)),
-//                     gensym("ctor"),
-//                     next,
-//                 ),
-//                 Constructor::Variable(_) => unreachable!("already converted to binding in body"),
-//             }
-//         }
-//     }
-// }
-
-fn resolve_path<'hir>(_path: hir::Path<'hir>) -> u32 {
-    todo!()
+/// Converts a decision tree testing the already bound `params` into a chain of comparisons.
+fn convert_decision_tree(
+    decision: &Decision,
+    params: &[InternedString],
+    cont: &mut dyn FnMut(Value) -> CPSExpr,
+) -> CPSExpr {
+    // The match compiler only records where a variable comes from in the bindings of the bodies
+    // that use it, but tests can happen on those variables long before we reach a body, so
+    // collect them all up front and bind variables lazily the first time they're needed.
+    fn collect_sources(decision: &Decision, sources: &mut HashMap<InternedString, BindingValue>) {
+        match decision {
+            Decision::Success(body) => {
+                for binding in body.bindings.iter() {
+                    if let BindingValue::Record { .. } = binding.value {
+                        sources.insert(binding.variable, binding.value.clone());
+                    }
+                }
+            }
+            Decision::Failure => {}
+            Decision::Branch {
+                match_path,
+                fail_path,
+                ..
+            } => {
+                collect_sources(match_path, sources);
+                collect_sources(fail_path, sources);
+            }
+        }
+    }
+
+    let mut sources = HashMap::new();
+    collect_sources(decision, &mut sources);
+
+    convert_decision(decision, &sources, params.iter().copied().collect(), cont)
+}
+
+/// Makes sure `variable` is bound before continuing, selecting it out of its record (and that
+/// record out of its own, and so on) if it hasn't been already.
+fn ensure_bound(
+    variable: InternedString,
+    sources: &HashMap<InternedString, BindingValue>,
+    bound: &mut HashSet<InternedString>,
+    cont: &mut dyn FnMut(&mut HashSet<InternedString>) -> CPSExpr,
+) -> CPSExpr {
+    match sources.get(&variable) {
+        Some(BindingValue::Record { name, index }) if !bound.contains(&variable) => {
+            let index = *index;
+            ensure_bound(*name, sources, bound, &mut |bound| {
+                bound.insert(variable);
+                CPSSelect::new(index, Value::Var(*name), variable, Box::new(cont(bound)))
+            })
+        }
+        _ => cont(bound),
+    }
+}
+
+fn convert_bindings(
+    bindings: &[Binding],
+    sources: &HashMap<InternedString, BindingValue>,
+    bound: &mut HashSet<InternedString>,
+    cont: &mut dyn FnMut() -> CPSExpr,
+) -> CPSExpr {
+    match bindings.split_first() {
+        Some((binding, rest)) if bound.contains(&binding.variable) => {
+            convert_bindings(rest, sources, bound, cont)
+        }
+        Some((
+            Binding {
+                variable,
+                value: BindingValue::Variable(source),
+            },
+            rest,
+        )) => ensure_bound(*source, sources, bound, &mut |bound| {
+            bound.insert(*variable);
+            CPSPrimop::new(
+                Primop::Semi,
+                Value::Int(0),
+                Value::Var(*source),
+                *variable,
+                vec![convert_bindings(rest, sources, bound, cont)],
+            )
+        }),
+        // fields of records are only selected once something actually needs them
+        Some((_, rest)) => convert_bindings(rest, sources, bound, cont),
+        None => cont(),
+    }
+}
+
+fn convert_decision(
+    decision: &Decision,
+    sources: &HashMap<InternedString, BindingValue>,
+    mut bound: HashSet<InternedString>,
+    cont: &mut dyn FnMut(Value) -> CPSExpr,
+) -> CPSExpr {
+    match decision {
+        Decision::Success(Body { value, bindings }) => {
+            convert_bindings(bindings, sources, &mut bound, &mut || {
+                convert_expr(*value, cont)
+            })
+        }
+        // refuted patterns can't happen once we have exhaustiveness checking
+        Decision::Failure => CPSExpr::Halt(Value::Int(0)),
+        Decision::Branch {
+            test: Test {
+                variable,
+                constructor,
+            },
+            match_path,
+            fail_path,
+        } => ensure_bound(*variable, sources, &mut bound, &mut |bound| {
+            let mut branch = |compare: Value| {
+                CPSPrimop::new(
+                    Primop::Eq,
+                    Value::Var(*variable),
+                    compare,
+                    gensym("eq"),
+                    vec![
+                        convert_decision(match_path, sources, bound.clone(), cont),
+                        convert_decision(fail_path, sources, bound.clone(), cont),
+                    ],
+                )
+            };
+            match constructor {
+                Constructor::Integer(n) => branch(Value::Int(*n)),
+                Constructor::Boolean(b) => branch(Value::Int(*b as u32)),
+                // type checking guarantees that a value matched against a record pattern is a
+                // record, so the test always succeeds
+                Constructor::Record(_) => {
+                    convert_decision(match_path, sources, bound.clone(), cont)
+                }
+                // and the only constructor of a struct's type is the struct itself
+                Constructor::NamedConstructor([_], _) => {
+                    convert_decision(match_path, sources, bound.clone(), cont)
+                }
+                Constructor::NamedConstructor(path, _) => {
+                    let tag = gensym("tag");
+                    CPSSelect::new(
+                        0,
+                        Value::Var(*variable),
+                        tag,
+                        Box::new(CPSPrimop::new(
+                            Primop::Eq,
+                            Value::Var(tag),
+                            constructor_tag(path),
+                            gensym("eq"),
+                            vec![
+                                convert_decision(match_path, sources, bound.clone(), cont),
+                                convert_decision(fail_path, sources, bound.clone(), cont),
+                            ],
+                        )),
+                    )
+                }
+                Constructor::Variable(_) => unreachable!("already converted to binding in body"),
+            }
+        }),
+    }
 }
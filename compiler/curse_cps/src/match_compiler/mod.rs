@@ -30,18 +30,28 @@ pub enum BindingValue {
 
 /// The expression to evaluate once reaching the end of the tree with the bindings accrued
 /// along the way.
-#[derive(Debug, PartialEq, Eq, Clone)]
+#[derive(Debug, Clone)]
 pub struct Body<'hir> {
-    pub value: hir::ExprKind<'hir>,
+    pub value: hir::Expr<'hir>,
     pub bindings: Vec<Binding>,
 }
 
 impl<'hir> Body<'hir> {
-    fn new(value: hir::ExprKind<'hir>, bindings: Vec<Binding>) -> Self {
+    fn new(value: hir::Expr<'hir>, bindings: Vec<Binding>) -> Self {
         Self { value, bindings }
     }
 }
 
+/// Where the body is doesn't change the tree, so two bodies are the same if they evaluate the
+/// same thing with the same bindings.
+impl PartialEq for Body<'_> {
+    fn eq(&self, other: &Self) -> bool {
+        self.value.kind == other.value.kind && self.bindings == other.bindings
+    }
+}
+
+impl Eq for Body<'_> {}
+
 /// The constructors we can compare values against. Although choice types will be represented with
 /// records, we're still working with the hir at this point which distinguishes them, which comes
 /// in handy for making better decision trees.
@@ -60,9 +70,12 @@ impl<'hir> Constructor<'hir> {
             hir::PatKind::Lit(hir::Lit::Integer(n)) => Constructor::Integer(*n),
             hir::PatKind::Lit(hir::Lit::Bool(b)) => Constructor::Boolean(*b),
             hir::PatKind::Lit(hir::Lit::Ident(id)) => Constructor::Variable(id.symbol),
-            hir::PatKind::Record(map) => Constructor::Record(
+            hir::PatKind::Record(_, true) => unreachable!("open record patterns are unsupported"),
+            // lowering sorts the fields of both records and their patterns by name, so the fields
+            // of a closed pattern line up with the record's
+            hir::PatKind::Record(map, false) => Constructor::Record(
                 map.entries
-                    .into_iter()
+                    .iter()
                     .map(|x| match *x {
                         (_, Some(pat)) => Constructor::from_pattern(&pat.kind),
                         (id, None) => Constructor::Variable(id.symbol),
@@ -72,7 +85,21 @@ impl<'hir> Constructor<'hir> {
             hir::PatKind::Constructor(path, pat) => {
                 Constructor::NamedConstructor(path, Box::new(Constructor::from_pattern(&pat.kind)))
             }
-            hir::PatKind::Error => todo!(),
+            hir::PatKind::Error => unreachable!("arms with error patterns are left out"),
+        }
+    }
+
+    /// Whether `pat` has an error in it anywhere, which means that it can't match anything.
+    fn is_poisoned(pat: &hir::PatKind<'_>) -> bool {
+        match pat {
+            hir::PatKind::Error => true,
+            hir::PatKind::Lit(_) => false,
+            hir::PatKind::Record(map, _) => map
+                .entries
+                .iter()
+                .filter_map(|(_, pat)| *pat)
+                .any(|pat| Constructor::is_poisoned(&pat.kind)),
+            hir::PatKind::Constructor(_, pat) => Constructor::is_poisoned(&pat.kind),
         }
     }
 
@@ -157,7 +184,7 @@ impl<'hir> Clause<'hir> {
                 Test::new(left_variable, left_cons),
                 Test::new(right_variable, right_cons),
             ],
-            body: Body::new(arm.body.kind, vec![]),
+            body: Body::new(*arm.body, vec![]),
         }
     }
 
@@ -184,8 +211,15 @@ pub fn compile_match_expr<'hir>(
     left: Variable,
     right: Variable,
 ) -> Decision<'hir> {
+    // an arm with an error in one of its patterns is poisoned, so it never matches, and a value
+    // that only it would have matched ends up at `Decision::Failure`
     let match_expr = hir_closure
-        .into_iter()
+        .iter()
+        .filter(|arm| {
+            !arm.params
+                .iter()
+                .any(|param| Constructor::is_poisoned(&param.pat.kind))
+        })
         .map(|arm| Clause::from_arm(arm, left, right))
         .collect();
 
@@ -1,7 +1,7 @@
 use super::*;
 use crate::reset_sym_counter;
 use bumpalo::Bump;
-use curse_hir::hir::{ExprKind, Lit};
+use curse_hir::hir::{Expr, ExprKind, Lit};
 use curse_interner::Ident;
 use curse_span::Span;
 use Decision::*;
@@ -12,16 +12,17 @@ fn var(s: &str) -> InternedString {
 fn idnt(s: &str) -> Ident {
     Ident::new(s, Span { start: 0, end: 0 })
 }
+fn expr(kind: ExprKind) -> Expr {
+    Expr {
+        kind,
+        span: Span { start: 0, end: 0 },
+    }
+}
 
 fn get_decision<'a>(input: &str, arena: &'a Bump) -> Decision<'a> {
-    let mut interner = curse_interner::init().unwrap();
-    let mut parser = curse_parse::Parser::new(&mut interner);
-    let ast_program = parser.parse_program(input);
-
-    curse_interner::replace(Some(interner));
-
-    let mut lowerer = curse_ast_lowering::Lowerer::new(&arena);
-    let hir_program = curse_ast_lowering::Lower::lower(&ast_program, &mut lowerer);
+    // some of the patterns are expected to lower to errors
+    let (hir_program, _) =
+        curse_test_support::lower_with_errors(arena, &curse_test_support::parse(input));
 
     let (_, fun_def) = hir_program.function_defs.iter().next().unwrap();
 
@@ -31,6 +32,7 @@ fn get_decision<'a>(input: &str, arena: &'a Bump) -> Decision<'a> {
 
 #[test]
 fn basic_numbers2() {
+    let _interner = curse_test_support::lock();
     let input = r#"
         fn foo (
             |1| 0,
@@ -52,11 +54,11 @@ fn basic_numbers2() {
                 constructor: Integer(1),
             },
             match_path: Box::new(Success(Body {
-                value: ExprKind::Lit(Lit::Integer(0)),
+                value: expr(ExprKind::Lit(Lit::Integer(0))),
                 bindings: vec![],
             })),
             fail_path: Box::new(Success(Body {
-                value: ExprKind::Lit(Lit::Ident(idnt("n"))),
+                value: expr(ExprKind::Lit(Lit::Ident(idnt("n")))),
                 bindings: vec![Binding {
                     variable: var("n"),
                     value: BindingValue::Variable(var("x__1_")),
@@ -69,8 +71,41 @@ fn basic_numbers2() {
     assert_eq!(get_decision(input, &arena), expected);
 }
 
+#[test]
+fn error_patterns() {
+    let _interner = curse_test_support::lock();
+    // the literal is too big, so it's lowered to an error
+    let input = r#"
+        fn foo (
+            |99999999999| 0,
+            |n| n,
+        )
+    "#;
+
+    let arena = Bump::new();
+
+    use Constructor::*;
+    let expected = Branch {
+        test: Test {
+            variable: var("y__2_"),
+            constructor: Integer(0),
+        },
+        match_path: Box::new(Success(Body {
+            value: expr(ExprKind::Lit(Lit::Ident(idnt("n")))),
+            bindings: vec![Binding {
+                variable: var("n"),
+                value: BindingValue::Variable(var("x__1_")),
+            }],
+        })),
+        fail_path: Box::new(Failure),
+    };
+
+    assert_eq!(get_decision(input, &arena), expected);
+}
+
 #[test]
 fn basic_ctors() {
+    let _interner = curse_test_support::lock();
     let input = r#"
         fn foo (
             |Option::Some x| x,
@@ -79,7 +114,6 @@ fn basic_ctors() {
     "#;
 
     let arena = Bump::new();
-    let _interner = curse_interner::init();
     let path1 = &[idnt("Option"), idnt("Some")];
     let path2 = &[idnt("Option"), idnt("None")];
 
@@ -95,7 +129,7 @@ fn basic_ctors() {
                 constructor: NamedConstructor(path1, Box::new(Variable(var("x")))),
             },
             match_path: Box::new(Success(Body {
-                value: ExprKind::Lit(Lit::Ident(idnt("x"))),
+                value: expr(ExprKind::Lit(Lit::Ident(idnt("x")))),
                 bindings: vec![
                     Binding {
                         variable: var("c__3_"),
@@ -121,7 +155,7 @@ fn basic_ctors() {
                         constructor: Record(vec![]),
                     },
                     match_path: Box::new(Success(Body {
-                        value: ExprKind::Lit(Lit::Integer(0)),
+                        value: expr(ExprKind::Lit(Lit::Integer(0))),
                         bindings: vec![Binding {
                             variable: var("c__4_"),
                             value: BindingValue::Record {
@@ -143,6 +177,7 @@ fn basic_ctors() {
 
 #[test]
 fn basic_record() {
+    let _interner = curse_test_support::lock();
     let input = r#"
         fn foo (
             |{ a, b }, c| 3,
@@ -157,7 +192,7 @@ fn basic_record() {
             constructor: Record(vec![Variable(var("a")), Variable(var("b"))]),
         },
         match_path: Box::new(Success(Body {
-            value: ExprKind::Lit(Lit::Integer(3)),
+            value: expr(ExprKind::Lit(Lit::Integer(3))),
             bindings: vec![
                 Binding {
                     variable: var("c"),
@@ -195,6 +230,7 @@ fn basic_record() {
 
 #[test]
 fn complex() {
+    let _interner = curse_test_support::lock();
     let input = r#"
         fn foo (
             |Add { x: 0, y: 0 }| 1,
@@ -0,0 +1,515 @@
+//! Optimization passes over the CPS IR.
+//!
+//! The conversion to CPS is deliberately naive and leaves a lot of administrative redexes behind,
+//! like continuations that only forward their argument to another continuation. Each pass here
+//! cleans up one kind of mess, and they are run together until none of them make progress.
+
+use std::collections::{HashMap, HashSet};
+
+use curse_interner::InternedString;
+
+use crate::cpsexpr::{CPSExpr, CPSFix, Function, Primop, Value};
+
+#[cfg(test)]
+mod tests;
+
+/// Which passes to run. Everything is enabled by default.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Passes {
+    /// Inline functions that are only ever called from a single place.
+    pub beta_contraction: bool,
+    /// Replace functions that only forward their arguments to another function, e.g.
+    /// `|x, k, y| k x`, with that function.
+    pub eta_reduction: bool,
+    /// Remove `Record`s and `Fix`ed functions that are never used.
+    pub dead_code: bool,
+    /// Evaluate `Primop`s on integer literals at compile time.
+    pub constant_folding: bool,
+}
+
+impl Passes {
+    /// No passes at all, so that individual passes can be turned on.
+    pub fn none() -> Self {
+        Passes {
+            beta_contraction: false,
+            eta_reduction: false,
+            dead_code: false,
+            constant_folding: false,
+        }
+    }
+}
+
+impl Default for Passes {
+    fn default() -> Self {
+        Passes {
+            beta_contraction: true,
+            eta_reduction: true,
+            dead_code: true,
+            constant_folding: true,
+        }
+    }
+}
+
+/// Upper bound on how many times we run the passes, in case they never settle.
+const MAX_ROUNDS: usize = 16;
+
+/// Runs the enabled `passes` over `expr` until they stop changing anything.
+pub fn optimize(mut expr: CPSExpr, passes: Passes) -> CPSExpr {
+    for _ in 0..MAX_ROUNDS {
+        let mut optimizer = Optimizer {
+            passes,
+            changed: false,
+        };
+        optimizer.visit(&mut expr);
+        if !optimizer.changed {
+            break;
+        }
+    }
+    expr
+}
+
+struct Optimizer {
+    passes: Passes,
+    changed: bool,
+}
+
+impl Optimizer {
+    fn visit(&mut self, expr: &mut CPSExpr) {
+        if self.passes.constant_folding {
+            self.fold_constants(expr);
+        }
+        if self.passes.dead_code {
+            self.eliminate_dead_code(expr);
+        }
+        if let CPSExpr::Fix(fix) = expr {
+            if self.passes.eta_reduction {
+                self.eta_reduce(fix);
+            }
+            if self.passes.beta_contraction {
+                self.beta_contract(fix);
+            }
+            if fix.functions.is_empty() {
+                take_continuation(expr);
+                self.changed = true;
+                return self.visit(expr);
+            }
+        }
+
+        for child in children(expr) {
+            self.visit(child);
+        }
+    }
+
+    fn fold_constants(&mut self, expr: &mut CPSExpr) {
+        while let CPSExpr::Primop(primop) = expr {
+            let folded = match (primop.left, primop.right) {
+                (Value::Int(a), Value::Int(b)) => match primop.primop {
+                    Primop::Plus => Folded::Value(Value::Int(a.wrapping_add(b))),
+                    Primop::Minus => Folded::Value(Value::Int(a.wrapping_sub(b))),
+                    Primop::Times => Folded::Value(Value::Int(a.wrapping_mul(b))),
+                    // leave division by zero for the runtime to complain about
+                    Primop::Div if b != 0 => Folded::Value(Value::Int(a / b)),
+                    Primop::Mod if b != 0 => Folded::Value(Value::Int(a % b)),
+                    Primop::Semi => Folded::Value(Value::Int(b)),
+                    Primop::Eq => Folded::Branch(a == b),
+                    Primop::Lt => Folded::Branch(a < b),
+                    Primop::Gt => Folded::Branch(a > b),
+                    Primop::Le => Folded::Branch(a <= b),
+                    Primop::Ge => Folded::Branch(a >= b),
+                    Primop::Record => Folded::Branch(false),
+                    Primop::Div | Primop::Mod => return,
+                },
+                (_, Value::Int(b)) if primop.primop == Primop::Semi => Folded::Value(Value::Int(b)),
+                (Value::String(a), Value::String(b)) if primop.primop == Primop::Eq => {
+                    Folded::Branch(a == b)
+                }
+                _ => return,
+            };
+
+            let mut continuations = std::mem::take(&mut primop.continuations);
+            *expr = match folded {
+                Folded::Value(value) => {
+                    let mut next = continuations.swap_remove(0);
+                    substitute(&mut next, &HashMap::from([(primop.name, value)]));
+                    next
+                }
+                Folded::Branch(cond) => continuations.swap_remove(if cond { 0 } else { 1 }),
+            };
+            self.changed = true;
+        }
+    }
+
+    fn eliminate_dead_code(&mut self, expr: &mut CPSExpr) {
+        loop {
+            match expr {
+                CPSExpr::Record(record) if !uses(&record.continuation).contains(&record.name) => {
+                    take_continuation(expr);
+                }
+                CPSExpr::Fix(fix) => {
+                    let live = live_functions(fix);
+                    if live.len() == fix.functions.len() {
+                        return;
+                    }
+                    fix.functions
+                        .retain(|function| live.contains(&var_name(function.name)));
+                    if !fix.functions.is_empty() {
+                        self.changed = true;
+                        return;
+                    }
+                    take_continuation(expr);
+                }
+                _ => return,
+            }
+            self.changed = true;
+        }
+    }
+
+    fn eta_reduce(&mut self, fix: &mut CPSFix) {
+        let mut index = 0;
+        while index < fix.functions.len() {
+            let function = &fix.functions[index];
+            let Some(target) = forwards_to(function) else {
+                index += 1;
+                continue;
+            };
+
+            let scope_binds = scope_bound_names(fix, index);
+            if target == function.name || scope_binds.contains(&var_name(target)) {
+                index += 1;
+                continue;
+            }
+
+            let function = fix.functions.remove(index);
+            let map = HashMap::from([(var_name(function.name), target)]);
+            substitute(&mut fix.continuation, &map);
+            for sibling in fix.functions.iter_mut() {
+                substitute_in_function(sibling, &map);
+            }
+            self.changed = true;
+        }
+    }
+
+    fn beta_contract(&mut self, fix: &mut CPSFix) {
+        let mut index = 0;
+        while index < fix.functions.len() {
+            let name = var_name(fix.functions[index].name);
+            let function = &fix.functions[index];
+
+            let uses_in_scope = count_uses(&fix.continuation, name)
+                + fix
+                    .functions
+                    .iter()
+                    .map(|function| count_uses(&function.continuation, name))
+                    .sum::<usize>();
+
+            // Only inline functions that are called from exactly one place outside of themselves,
+            // and when inlining them can't accidentally capture or shadow anything.
+            let inlinable = uses_in_scope == 1
+                && count_uses(&function.continuation, name) == 0
+                && function
+                    .free_vars()
+                    .is_disjoint(&scope_bound_names(fix, index));
+
+            if inlinable {
+                let function = fix.functions.remove(index);
+                let mut inliner = Inliner {
+                    function: Some(function),
+                    name,
+                };
+                inliner.visit(&mut fix.continuation);
+                for sibling in fix.functions.iter_mut() {
+                    inliner.visit(&mut sibling.continuation);
+                }
+
+                if let Some(function) = inliner.function {
+                    // the one use wasn't a call we could inline, so put it back
+                    fix.functions.insert(index, function);
+                    index += 1;
+                } else {
+                    self.changed = true;
+                }
+            } else {
+                index += 1;
+            }
+        }
+    }
+}
+
+enum Folded {
+    Value(Value),
+    Branch(bool),
+}
+
+/// Replaces the single call to `name` with the body of `function`.
+struct Inliner {
+    function: Option<Function>,
+    name: InternedString,
+}
+
+impl Inliner {
+    fn visit(&mut self, expr: &mut CPSExpr) {
+        if self.function.is_none() {
+            return;
+        }
+
+        if let CPSExpr::Appl(appl) = expr {
+            if appl.function != Value::Var(self.name) {
+                return;
+            }
+
+            let function = self.function.as_ref().unwrap();
+            let params = function.params();
+
+            // Every parameter the body uses needs an argument, and the arguments can't be
+            // shadowed by anything the body binds.
+            let mut body_binds = HashSet::new();
+            bound_names(&function.continuation, &mut body_binds);
+            let missing_args = params[appl.args.len().min(params.len())..]
+                .iter()
+                .any(|param| count_uses(&function.continuation, var_name(*param)) > 0);
+            let captures = appl
+                .args
+                .iter()
+                .any(|arg| matches!(arg, Value::Var(name) if body_binds.contains(name)));
+            if missing_args || captures {
+                return;
+            }
+
+            let map = params
+                .iter()
+                .zip(appl.args.iter())
+                .map(|(param, arg)| (var_name(*param), *arg))
+                .collect();
+            let mut body = *self.function.take().unwrap().continuation;
+            substitute(&mut body, &map);
+            *expr = body;
+            return;
+        }
+
+        for child in children(expr) {
+            self.visit(child);
+        }
+    }
+}
+
+fn var_name(value: Value) -> InternedString {
+    match value {
+        Value::Var(name) => name,
+        _ => unreachable!("function names are always variables"),
+    }
+}
+
+/// If `function` does nothing but call another function with all of its own parameters, in
+/// order, returns the function it calls. Forwarding only some of them isn't enough, since
+/// replacing the function with the one it calls would change how many arguments it takes.
+fn forwards_to(function: &Function) -> Option<Value> {
+    let CPSExpr::Appl(appl) = function.continuation.as_ref() else {
+        return None;
+    };
+    let params = function.params();
+
+    let forwards = appl.args.len() == params.len()
+        && appl.args.iter().zip(params.iter()).all(|(a, p)| a == p)
+        && !params.contains(&appl.function);
+
+    (forwards && matches!(appl.function, Value::Var(_))).then_some(appl.function)
+}
+
+/// Everything bound in the scope of the `index`th function of `fix`, excluding that function.
+fn scope_bound_names(fix: &CPSFix, index: usize) -> HashSet<InternedString> {
+    let mut names = HashSet::new();
+    bound_names(&fix.continuation, &mut names);
+    for (i, function) in fix.functions.iter().enumerate() {
+        if i != index {
+            for param in function.params() {
+                names.insert(var_name(param));
+            }
+            bound_names(&function.continuation, &mut names);
+        }
+    }
+    names
+}
+
+/// The functions of `fix` that can be reached from its continuation.
+fn live_functions(fix: &CPSFix) -> HashSet<InternedString> {
+    let names: HashSet<InternedString> = fix.functions.iter().map(|f| var_name(f.name)).collect();
+    let mut live: HashSet<InternedString> = uses(&fix.continuation)
+        .into_iter()
+        .filter(|name| names.contains(name))
+        .collect();
+
+    let mut worklist: Vec<InternedString> = live.iter().copied().collect();
+    while let Some(name) = worklist.pop() {
+        let function = fix
+            .functions
+            .iter()
+            .find(|f| f.name == Value::Var(name))
+            .unwrap();
+        for used in uses(&function.continuation) {
+            if names.contains(&used) && live.insert(used) {
+                worklist.push(used);
+            }
+        }
+    }
+
+    live
+}
+
+/// Replaces `expr` with its (only) continuation.
+fn take_continuation(expr: &mut CPSExpr) {
+    let placeholder = CPSExpr::Halt(Value::Int(0));
+    *expr = match std::mem::replace(expr, placeholder) {
+        CPSExpr::Record(record) => *record.continuation,
+        CPSExpr::Select(select) => *select.continuation,
+        CPSExpr::Fix(fix) => *fix.continuation,
+        _ => unreachable!("only called on expressions with a single continuation"),
+    };
+}
+
+fn children(expr: &mut CPSExpr) -> Vec<&mut CPSExpr> {
+    match expr {
+        CPSExpr::Primop(primop) => primop.continuations.iter_mut().collect(),
+        CPSExpr::Record(record) => vec![&mut record.continuation],
+        CPSExpr::Select(select) => vec![&mut select.continuation],
+        CPSExpr::Fix(fix) => fix
+            .functions
+            .iter_mut()
+            .map(|function| function.continuation.as_mut())
+            .chain([fix.continuation.as_mut()])
+            .collect(),
+        CPSExpr::Appl(_) | CPSExpr::Halt(_) => vec![],
+    }
+}
+
+fn for_each_subexpr<'a>(expr: &'a CPSExpr, f: &mut dyn FnMut(&'a CPSExpr)) {
+    f(expr);
+    match expr {
+        CPSExpr::Primop(primop) => {
+            for continuation in primop.continuations.iter() {
+                for_each_subexpr(continuation, f);
+            }
+        }
+        CPSExpr::Record(record) => for_each_subexpr(&record.continuation, f),
+        CPSExpr::Select(select) => for_each_subexpr(&select.continuation, f),
+        CPSExpr::Fix(fix) => {
+            for function in fix.functions.iter() {
+                for_each_subexpr(&function.continuation, f);
+            }
+            for_each_subexpr(&fix.continuation, f);
+        }
+        CPSExpr::Appl(_) | CPSExpr::Halt(_) => {}
+    }
+}
+
+/// Every variable used anywhere in `expr`. This doesn't account for shadowing, so it may include
+/// variables that are bound inside of `expr`, which is fine since we only ever use it to be
+/// conservative.
+fn uses(expr: &CPSExpr) -> HashSet<InternedString> {
+    let mut names = HashSet::new();
+    for_each_subexpr(expr, &mut |expr| {
+        names.extend(expr.values().into_iter().filter_map(|value| match value {
+            Value::Var(name) => Some(name),
+            _ => None,
+        }));
+    });
+    names
+}
+
+/// How many times `name` is used anywhere in `expr`, again ignoring shadowing.
+fn count_uses(expr: &CPSExpr, name: InternedString) -> usize {
+    let mut count = 0;
+    for_each_subexpr(expr, &mut |expr| {
+        count += expr
+            .values()
+            .into_iter()
+            .filter(|value| *value == Value::Var(name))
+            .count();
+    });
+    count
+}
+
+/// Every name bound anywhere in `expr`.
+fn bound_names(expr: &CPSExpr, names: &mut HashSet<InternedString>) {
+    for_each_subexpr(expr, &mut |expr| match expr {
+        CPSExpr::Primop(primop) => {
+            names.insert(primop.name);
+        }
+        CPSExpr::Record(record) => {
+            names.insert(record.name);
+        }
+        CPSExpr::Select(select) => {
+            names.insert(select.result);
+        }
+        CPSExpr::Fix(fix) => {
+            for function in fix.functions.iter() {
+                names.insert(var_name(function.name));
+                names.extend(function.params().into_iter().map(var_name));
+            }
+        }
+        CPSExpr::Appl(_) | CPSExpr::Halt(_) => {}
+    });
+}
+
+/// Replaces variables according to `map`, respecting shadowing. The caller is responsible for
+/// making sure that none of the replacements get captured by something bound inside of `expr`.
+fn substitute(expr: &mut CPSExpr, map: &HashMap<InternedString, Value>) {
+    if map.is_empty() {
+        return;
+    }
+
+    let replace = |value: &mut Value| {
+        if let Value::Var(name) = value {
+            if let Some(replacement) = map.get(name) {
+                *value = *replacement;
+            }
+        }
+    };
+    let without = |names: &[InternedString]| -> HashMap<InternedString, Value> {
+        map.iter()
+            .filter(|(name, _)| !names.contains(name))
+            .map(|(name, value)| (*name, *value))
+            .collect()
+    };
+
+    match expr {
+        CPSExpr::Primop(primop) => {
+            replace(&mut primop.left);
+            replace(&mut primop.right);
+            let map = without(&[primop.name]);
+            for continuation in primop.continuations.iter_mut() {
+                substitute(continuation, &map);
+            }
+        }
+        CPSExpr::Record(record) => {
+            record.values.iter_mut().for_each(replace);
+            substitute(&mut record.continuation, &without(&[record.name]));
+        }
+        CPSExpr::Select(select) => {
+            replace(&mut select.record);
+            substitute(&mut select.continuation, &without(&[select.result]));
+        }
+        CPSExpr::Appl(appl) => {
+            replace(&mut appl.function);
+            appl.args.iter_mut().for_each(replace);
+        }
+        CPSExpr::Fix(fix) => {
+            let names: Vec<_> = fix.functions.iter().map(|f| var_name(f.name)).collect();
+            let map = without(&names);
+            for function in fix.functions.iter_mut() {
+                substitute_in_function(function, &map);
+            }
+            substitute(&mut fix.continuation, &map);
+        }
+        CPSExpr::Halt(value) => replace(value),
+    }
+}
+
+fn substitute_in_function(function: &mut Function, map: &HashMap<InternedString, Value>) {
+    let params: Vec<_> = function.params().into_iter().map(var_name).collect();
+    let map = map
+        .iter()
+        .filter(|(name, _)| !params.contains(name))
+        .map(|(name, value)| (*name, *value))
+        .collect();
+    substitute(&mut function.continuation, &map);
+}
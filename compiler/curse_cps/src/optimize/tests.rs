@@ -0,0 +1,236 @@
+use super::*;
+use crate::cpsexpr::{var, CPSAppl, CPSFix, CPSPrimop, CPSRecord, Value::*};
+
+fn name(s: &str) -> InternedString {
+    InternedString::get_or_intern(s)
+}
+
+fn only(pass: fn(&mut Passes)) -> Passes {
+    let mut passes = Passes::none();
+    pass(&mut passes);
+    passes
+}
+
+/// `fix k(x, _) = halt x in <body>`
+fn halting(body: CPSExpr) -> CPSExpr {
+    CPSFix::new(
+        vec![Function::new(
+            var("x"),
+            var("k"),
+            Int(0),
+            None,
+            Box::new(CPSExpr::Halt(var("x"))),
+        )],
+        Box::new(body),
+    )
+}
+
+#[test]
+fn constant_folding() {
+    let _interner = curse_test_support::lock();
+
+    // (1 + 2) < 4
+    let expr = CPSPrimop::new(
+        Primop::Plus,
+        Int(1),
+        Int(2),
+        name("t"),
+        vec![CPSPrimop::new(
+            Primop::Lt,
+            var("t"),
+            Int(4),
+            name("b"),
+            vec![CPSExpr::Halt(Int(1)), CPSExpr::Halt(Int(0))],
+        )],
+    );
+
+    assert_eq!(
+        optimize(expr, only(|p| p.constant_folding = true)),
+        CPSExpr::Halt(Int(1))
+    );
+}
+
+#[test]
+fn no_folding_division_by_zero() {
+    let _interner = curse_test_support::lock();
+
+    let expr = || {
+        CPSPrimop::new(
+            Primop::Div,
+            Int(1),
+            Int(0),
+            name("t"),
+            vec![CPSExpr::Halt(var("t"))],
+        )
+    };
+
+    assert_eq!(optimize(expr(), Passes::default()), expr());
+}
+
+#[test]
+fn dead_code() {
+    let _interner = curse_test_support::lock();
+
+    // neither the record nor `f` are ever used
+    let expr = CPSRecord::new(
+        vec![Int(1), Int(2)],
+        name("r"),
+        Box::new(CPSFix::new(
+            vec![Function::new(
+                var("a"),
+                var("f"),
+                var("b"),
+                Some(var("c")),
+                Box::new(CPSAppl::new(var("c"), vec![var("r")])),
+            )],
+            Box::new(CPSExpr::Halt(Int(3))),
+        )),
+    );
+
+    assert_eq!(
+        optimize(expr, only(|p| p.dead_code = true)),
+        CPSExpr::Halt(Int(3))
+    );
+}
+
+#[test]
+fn eta_reduction() {
+    let _interner = curse_test_support::lock();
+
+    // fix j(y, _) = k y in f 1 2 j
+    let expr = halting(CPSFix::new(
+        vec![Function::new(
+            var("y"),
+            var("j"),
+            Int(0),
+            None,
+            Box::new(CPSAppl::new(var("k"), vec![var("y")])),
+        )],
+        Box::new(CPSAppl::new(var("f"), vec![Int(1), Int(2), var("j")])),
+    ));
+
+    let expected = halting(CPSAppl::new(var("f"), vec![Int(1), Int(2), var("k")]));
+    assert_eq!(optimize(expr, only(|p| p.eta_reduction = true)), expected);
+}
+
+#[test]
+fn no_eta_reduction_when_dropping_params() {
+    let _interner = curse_test_support::lock();
+
+    // fix j(y, z) = k y in f 1 2 j
+    let expr = || {
+        halting(CPSFix::new(
+            vec![Function::new(
+                var("y"),
+                var("j"),
+                var("z"),
+                None,
+                Box::new(CPSAppl::new(var("k"), vec![var("y")])),
+            )],
+            Box::new(CPSAppl::new(var("f"), vec![Int(1), Int(2), var("j")])),
+        ))
+    };
+
+    assert_eq!(optimize(expr(), only(|p| p.eta_reduction = true)), expr());
+}
+
+#[test]
+fn beta_contraction() {
+    let _interner = curse_test_support::lock();
+
+    // fix f(a, b, c) = (t = a + b; c t) in f 1 2 k
+    let expr = halting(CPSFix::new(
+        vec![Function::new(
+            var("a"),
+            var("f"),
+            var("b"),
+            Some(var("c")),
+            Box::new(CPSPrimop::new(
+                Primop::Plus,
+                var("a"),
+                var("b"),
+                name("t"),
+                vec![CPSAppl::new(var("c"), vec![var("t")])],
+            )),
+        )],
+        Box::new(CPSAppl::new(var("f"), vec![Int(1), Int(2), var("k")])),
+    ));
+
+    // `k` is only used once too, so it gets inlined as well
+    let expected = CPSPrimop::new(
+        Primop::Plus,
+        Int(1),
+        Int(2),
+        name("t"),
+        vec![CPSExpr::Halt(var("t"))],
+    );
+    assert_eq!(
+        optimize(expr, only(|p| p.beta_contraction = true)),
+        expected
+    );
+}
+
+#[test]
+fn no_beta_contraction_when_captured() {
+    let _interner = curse_test_support::lock();
+
+    // fix f(a, _) = k a in (k = y; f k), where inlining `f` would make its `k` refer to the `k`
+    // bound at the call site instead of the halting continuation
+    let expr = || {
+        halting(CPSFix::new(
+            vec![Function::new(
+                var("a"),
+                var("f"),
+                Int(0),
+                None,
+                Box::new(CPSAppl::new(var("k"), vec![var("a")])),
+            )],
+            Box::new(CPSPrimop::new(
+                Primop::Semi,
+                Int(0),
+                var("y"),
+                name("k"),
+                vec![CPSAppl::new(var("f"), vec![var("k")])],
+            )),
+        ))
+    };
+
+    assert_eq!(
+        optimize(expr(), only(|p| p.beta_contraction = true)),
+        expr()
+    );
+}
+
+#[test]
+fn all_passes() {
+    let _interner = curse_test_support::lock();
+
+    // a `+` turned into a function, then applied to constants
+    let expr = halting(CPSFix::new(
+        vec![Function::new(
+            var("a"),
+            var("plus"),
+            var("b"),
+            Some(var("c")),
+            Box::new(CPSPrimop::new(
+                Primop::Plus,
+                var("a"),
+                var("b"),
+                name("t"),
+                vec![CPSAppl::new(var("c"), vec![var("t")])],
+            )),
+        )],
+        Box::new(CPSFix::new(
+            vec![Function::new(
+                var("r"),
+                var("j"),
+                Int(0),
+                None,
+                Box::new(CPSAppl::new(var("k"), vec![var("r")])),
+            )],
+            Box::new(CPSAppl::new(var("plus"), vec![Int(1), Int(2), var("j")])),
+        )),
+    ));
+
+    assert_eq!(optimize(expr, Passes::default()), CPSExpr::Halt(Int(3)));
+}
@@ -0,0 +1,525 @@
+//! A textual S-expression format for the CPS IR.
+//!
+//! Every `CPSExpr` prints as a list whose head says what kind of expression it is:
+//!
+//! ```text
+//! (halt v)
+//! (app f arg ...)
+//! (primop op name (left right) continuation ...)
+//! (record name (value ...) continuation)
+//! (select name record index continuation)
+//! (fix ((name left right [ret] body) ...) continuation)
+//! ```
+//!
+//! Values are variables, integers or double-quoted strings, and `op` is the lowercase name of a
+//! `Primop`, like `plus` or `eq`. Comments start with `;` and go until the end of the line.
+//! Printing and then parsing an expression always gives back the same expression, which makes it
+//! convenient for writing expected output in tests.
+
+use std::fmt;
+
+use curse_interner::InternedString;
+use miette::{Diagnostic, SourceSpan};
+use thiserror::Error;
+
+use crate::cpsexpr::{
+    CPSAppl, CPSExpr, CPSFix, CPSPrimop, CPSRecord, CPSSelect, Function, Primop, Value,
+};
+
+#[cfg(test)]
+mod tests;
+
+impl Primop {
+    pub(crate) fn name(self) -> &'static str {
+        match self {
+            Primop::Plus => "plus",
+            Primop::Times => "times",
+            Primop::Minus => "minus",
+            Primop::Div => "div",
+            Primop::Semi => "semi",
+            Primop::Mod => "mod",
+            Primop::Eq => "eq",
+            Primop::Lt => "lt",
+            Primop::Gt => "gt",
+            Primop::Le => "le",
+            Primop::Ge => "ge",
+            Primop::Record => "record",
+        }
+    }
+
+    fn from_name(name: &str) -> Option<Self> {
+        Some(match name {
+            "plus" => Primop::Plus,
+            "times" => Primop::Times,
+            "minus" => Primop::Minus,
+            "div" => Primop::Div,
+            "semi" => Primop::Semi,
+            "mod" => Primop::Mod,
+            "eq" => Primop::Eq,
+            "lt" => Primop::Lt,
+            "gt" => Primop::Gt,
+            "le" => Primop::Le,
+            "ge" => Primop::Ge,
+            "record" => Primop::Record,
+            _ => return None,
+        })
+    }
+
+    /// How many continuations this primop takes. Branching primops take the `true` continuation
+    /// first and the `false` continuation second.
+    fn arity(self) -> usize {
+        match self {
+            Primop::Eq | Primop::Lt | Primop::Gt | Primop::Le | Primop::Ge | Primop::Record => 2,
+            Primop::Plus
+            | Primop::Times
+            | Primop::Minus
+            | Primop::Div
+            | Primop::Semi
+            | Primop::Mod => 1,
+        }
+    }
+}
+
+impl fmt::Display for Value {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Var(name) => write!(f, "{name}"),
+            Value::Int(n) => write!(f, "{n}"),
+            Value::String(s) => {
+                f.write_str("\"")?;
+                for c in s.string().chars() {
+                    match c {
+                        '"' => f.write_str("\\\"")?,
+                        '\\' => f.write_str("\\\\")?,
+                        '\n' => f.write_str("\\n")?,
+                        c => write!(f, "{c}")?,
+                    }
+                }
+                f.write_str("\"")
+            }
+        }
+    }
+}
+
+impl fmt::Display for CPSExpr {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        print_expr(f, self, 0)
+    }
+}
+
+fn newline(f: &mut fmt::Formatter<'_>, indent: usize) -> fmt::Result {
+    write!(f, "\n{:indent$}", "")
+}
+
+fn print_expr(f: &mut fmt::Formatter<'_>, expr: &CPSExpr, indent: usize) -> fmt::Result {
+    match expr {
+        CPSExpr::Halt(value) => write!(f, "(halt {value})"),
+        CPSExpr::Appl(appl) => {
+            write!(f, "(app {}", appl.function)?;
+            for arg in appl.args.iter() {
+                write!(f, " {arg}")?;
+            }
+            f.write_str(")")
+        }
+        CPSExpr::Primop(primop) => {
+            write!(
+                f,
+                "(primop {} {} ({} {})",
+                primop.primop.name(),
+                primop.name,
+                primop.left,
+                primop.right
+            )?;
+            for continuation in primop.continuations.iter() {
+                newline(f, indent + 2)?;
+                print_expr(f, continuation, indent + 2)?;
+            }
+            f.write_str(")")
+        }
+        CPSExpr::Record(record) => {
+            write!(f, "(record {} (", record.name)?;
+            for (i, value) in record.values.iter().enumerate() {
+                if i > 0 {
+                    f.write_str(" ")?;
+                }
+                write!(f, "{value}")?;
+            }
+            f.write_str(")")?;
+            newline(f, indent + 2)?;
+            print_expr(f, &record.continuation, indent + 2)?;
+            f.write_str(")")
+        }
+        CPSExpr::Select(select) => {
+            write!(
+                f,
+                "(select {} {} {}",
+                select.result, select.record, select.index
+            )?;
+            newline(f, indent + 2)?;
+            print_expr(f, &select.continuation, indent + 2)?;
+            f.write_str(")")
+        }
+        CPSExpr::Fix(fix) => {
+            // functions line up after the `(fix (`
+            let functions_indent = indent + "(fix (".len();
+            f.write_str("(fix (")?;
+            for (i, function) in fix.functions.iter().enumerate() {
+                if i > 0 {
+                    newline(f, functions_indent)?;
+                }
+                write!(f, "({} {} {}", function.name, function.left, function.right)?;
+                if let Some(ret) = function.ret {
+                    write!(f, " {ret}")?;
+                }
+                newline(f, functions_indent + 2)?;
+                print_expr(f, &function.continuation, functions_indent + 2)?;
+                f.write_str(")")?;
+            }
+            f.write_str(")")?;
+            newline(f, indent + 2)?;
+            print_expr(f, &fix.continuation, indent + 2)?;
+            f.write_str(")")
+        }
+    }
+}
+
+#[derive(Debug, Diagnostic, Error, PartialEq, Eq)]
+pub enum ParseError {
+    #[error("Unexpected end of input")]
+    #[diagnostic(help("Check that every `(` has a matching `)`."))]
+    UnexpectedEof {
+        #[label("Input ends here")]
+        location: SourceSpan,
+    },
+
+    #[error("Unmatched closing parenthesis")]
+    #[diagnostic(help("Remove this `)`."))]
+    UnmatchedParen {
+        #[label("This doesn't close anything")]
+        location: SourceSpan,
+    },
+
+    #[error("Unterminated string")]
+    #[diagnostic(help("Add a closing `\"`."))]
+    UnterminatedString {
+        #[label("This string never ends")]
+        location: SourceSpan,
+    },
+
+    #[error("Expected {expected}")]
+    Expected {
+        expected: &'static str,
+        #[label("Expected {expected} here")]
+        span: SourceSpan,
+    },
+
+    #[error("Unknown primop `{name}`")]
+    #[diagnostic(help("Use one of `plus`, `times`, `minus`, `div`, `semi`, `mod`, `eq`, `lt`, `gt`, `le`, `ge` or `record`."))]
+    UnknownPrimop {
+        name: String,
+        #[label("This isn't a primop")]
+        span: SourceSpan,
+    },
+
+    #[error("Wrong number of continuations")]
+    #[diagnostic(help("`{primop}` takes {expected} continuation(s)."))]
+    ContinuationCount {
+        primop: &'static str,
+        expected: usize,
+        #[label("This has {found} continuation(s)")]
+        span: SourceSpan,
+        found: usize,
+    },
+
+    #[error("Trailing input")]
+    #[diagnostic(help("Only a single expression is allowed."))]
+    TrailingInput {
+        #[label("This comes after the expression")]
+        location: SourceSpan,
+    },
+}
+
+/// Parses the textual form of a `CPSExpr`.
+pub fn parse(input: &str) -> Result<CPSExpr, ParseError> {
+    let mut reader = Reader { input, pos: 0 };
+    let sexp = reader.sexp()?;
+    reader.skip_trivia();
+    if reader.pos < input.len() {
+        return Err(ParseError::TrailingInput {
+            location: (reader.pos..input.len()).into(),
+        });
+    }
+    expr(&sexp)
+}
+
+/// An untyped S-expression, which is then checked and turned into a `CPSExpr`.
+enum Sexp<'input> {
+    Atom(&'input str, SourceSpan),
+    String(String, SourceSpan),
+    List(Vec<Sexp<'input>>, SourceSpan),
+}
+
+impl Sexp<'_> {
+    fn span(&self) -> SourceSpan {
+        match self {
+            Sexp::Atom(_, span) | Sexp::String(_, span) | Sexp::List(_, span) => *span,
+        }
+    }
+}
+
+struct Reader<'input> {
+    input: &'input str,
+    pos: usize,
+}
+
+impl<'input> Reader<'input> {
+    fn rest(&self) -> &'input str {
+        &self.input[self.pos..]
+    }
+
+    fn skip_trivia(&mut self) {
+        loop {
+            let rest = self.rest();
+            let trimmed = rest.trim_start();
+            self.pos += rest.len() - trimmed.len();
+
+            if trimmed.starts_with(';') {
+                self.pos += trimmed.find('\n').unwrap_or(trimmed.len());
+            } else {
+                return;
+            }
+        }
+    }
+
+    fn sexp(&mut self) -> Result<Sexp<'input>, ParseError> {
+        self.skip_trivia();
+        let start = self.pos;
+
+        match self.rest().chars().next() {
+            None => Err(ParseError::UnexpectedEof {
+                location: start.into(),
+            }),
+            Some(')') => Err(ParseError::UnmatchedParen {
+                location: start.into(),
+            }),
+            Some('(') => {
+                self.pos += 1;
+                let mut items = vec![];
+                loop {
+                    self.skip_trivia();
+                    if self.rest().starts_with(')') {
+                        self.pos += 1;
+                        return Ok(Sexp::List(items, (start..self.pos).into()));
+                    }
+                    items.push(self.sexp()?);
+                }
+            }
+            Some('"') => {
+                self.pos += 1;
+                let mut string = String::new();
+                let mut chars = self.rest().char_indices();
+                loop {
+                    match chars.next() {
+                        None => {
+                            return Err(ParseError::UnterminatedString {
+                                location: start.into(),
+                            })
+                        }
+                        Some((i, '"')) => {
+                            self.pos += i + 1;
+                            return Ok(Sexp::String(string, (start..self.pos).into()));
+                        }
+                        Some((_, '\\')) => match chars.next() {
+                            Some((_, 'n')) => string.push('\n'),
+                            Some((_, c)) => string.push(c),
+                            None => {
+                                return Err(ParseError::UnterminatedString {
+                                    location: start.into(),
+                                })
+                            }
+                        },
+                        Some((_, c)) => string.push(c),
+                    }
+                }
+            }
+            Some(_) => {
+                let len = self
+                    .rest()
+                    .find(|c: char| c.is_whitespace() || "();\"".contains(c))
+                    .unwrap_or(self.rest().len());
+                self.pos += len;
+                Ok(Sexp::Atom(
+                    &self.input[start..self.pos],
+                    (start..self.pos).into(),
+                ))
+            }
+        }
+    }
+}
+
+fn expected(expected: &'static str, sexp: &Sexp) -> ParseError {
+    ParseError::Expected {
+        expected,
+        span: sexp.span(),
+    }
+}
+
+/// Splits a list into its items, requiring there to be at least `min` of them.
+fn list<'a, 'input>(
+    sexp: &'a Sexp<'input>,
+    min: usize,
+    what: &'static str,
+) -> Result<&'a [Sexp<'input>], ParseError> {
+    match sexp {
+        Sexp::List(items, _) if items.len() >= min => Ok(items),
+        Sexp::List(_, span) => Err(ParseError::Expected {
+            expected: what,
+            span: *span,
+        }),
+        _ => Err(expected(what, sexp)),
+    }
+}
+
+fn name(sexp: &Sexp) -> Result<InternedString, ParseError> {
+    match sexp {
+        Sexp::Atom(atom, _) if atom.parse::<u32>().is_err() => {
+            Ok(InternedString::get_or_intern(atom))
+        }
+        _ => Err(expected("a name", sexp)),
+    }
+}
+
+fn value(sexp: &Sexp) -> Result<Value, ParseError> {
+    match sexp {
+        Sexp::Atom(atom, _) => Ok(match atom.parse() {
+            Ok(n) => Value::Int(n),
+            Err(_) => Value::Var(InternedString::get_or_intern(atom)),
+        }),
+        Sexp::String(string, _) => Ok(Value::String(InternedString::get_or_intern(string))),
+        Sexp::List(..) => Err(expected("a value", sexp)),
+    }
+}
+
+fn expr(sexp: &Sexp) -> Result<CPSExpr, ParseError> {
+    let items = list(sexp, 1, "an expression")?;
+    let Sexp::Atom(head, _) = &items[0] else {
+        return Err(expected("an expression", sexp));
+    };
+
+    let exactly = |len: usize, what: &'static str| {
+        if items.len() == len {
+            Ok(())
+        } else {
+            Err(expected(what, sexp))
+        }
+    };
+
+    match *head {
+        "halt" => {
+            exactly(2, "`(halt value)`")?;
+            Ok(CPSExpr::Halt(value(&items[1])?))
+        }
+        "app" => {
+            let items = list(sexp, 2, "`(app function arg ...)`")?;
+            Ok(CPSAppl::new(
+                value(&items[1])?,
+                items[2..].iter().map(value).collect::<Result<_, _>>()?,
+            ))
+        }
+        "primop" => {
+            let items = list(sexp, 5, "`(primop op name (left right) continuation ...)`")?;
+            let primop = match &items[1] {
+                Sexp::Atom(op, span) => {
+                    Primop::from_name(op).ok_or_else(|| ParseError::UnknownPrimop {
+                        name: op.to_string(),
+                        span: *span,
+                    })?
+                }
+                other => return Err(expected("a primop", other)),
+            };
+            let operands = list(&items[3], 2, "`(left right)`")?;
+            if operands.len() != 2 {
+                return Err(expected("`(left right)`", &items[3]));
+            }
+
+            let continuations = &items[4..];
+            if continuations.len() != primop.arity() {
+                return Err(ParseError::ContinuationCount {
+                    primop: primop.name(),
+                    expected: primop.arity(),
+                    span: sexp.span(),
+                    found: continuations.len(),
+                });
+            }
+
+            Ok(CPSPrimop::new(
+                primop,
+                value(&operands[0])?,
+                value(&operands[1])?,
+                name(&items[2])?,
+                continuations.iter().map(expr).collect::<Result<_, _>>()?,
+            ))
+        }
+        "record" => {
+            exactly(4, "`(record name (value ...) continuation)`")?;
+            let values = list(&items[2], 0, "`(value ...)`")?;
+            Ok(CPSRecord::new(
+                values.iter().map(value).collect::<Result<_, _>>()?,
+                name(&items[1])?,
+                Box::new(expr(&items[3])?),
+            ))
+        }
+        "select" => {
+            exactly(5, "`(select name record index continuation)`")?;
+            let index = match &items[3] {
+                Sexp::Atom(atom, _) => atom.parse().ok(),
+                _ => None,
+            }
+            .ok_or_else(|| expected("an index", &items[3]))?;
+
+            Ok(CPSSelect::new(
+                index,
+                value(&items[2])?,
+                name(&items[1])?,
+                Box::new(expr(&items[4])?),
+            ))
+        }
+        "fix" => {
+            exactly(3, "`(fix (function ...) continuation)`")?;
+            let functions = list(&items[1], 0, "`(function ...)`")?
+                .iter()
+                .map(function)
+                .collect::<Result<_, _>>()?;
+            Ok(CPSFix::new(functions, Box::new(expr(&items[2])?)))
+        }
+        _ => Err(expected(
+            "`halt`, `app`, `primop`, `record`, `select` or `fix`",
+            &items[0],
+        )),
+    }
+}
+
+fn function(sexp: &Sexp) -> Result<Function, ParseError> {
+    const SHAPE: &str = "`(name left right [ret] body)`";
+    let items = list(sexp, 4, SHAPE)?;
+
+    let param = |sexp: &Sexp| match sexp {
+        // continuations use an integer as a dummy parameter
+        Sexp::Atom(..) => value(sexp),
+        _ => Err(expected("a parameter", sexp)),
+    };
+
+    let ret = match items.len() {
+        4 => None,
+        5 => Some(Value::Var(name(&items[3])?)),
+        _ => return Err(expected(SHAPE, sexp)),
+    };
+
+    Ok(Function::new(
+        param(&items[1])?,
+        Value::Var(name(&items[0])?),
+        param(&items[2])?,
+        ret,
+        Box::new(expr(items.last().unwrap())?),
+    ))
+}
@@ -0,0 +1,110 @@
+use super::*;
+use crate::cpsexpr::var;
+
+fn name(s: &str) -> InternedString {
+    InternedString::get_or_intern(s)
+}
+
+#[test]
+fn print() {
+    let _interner = curse_test_support::lock();
+
+    let expr = CPSFix::new(
+        vec![
+            Function::new(
+                var("x"),
+                var("f"),
+                var("y"),
+                Some(var("k")),
+                Box::new(CPSPrimop::new(
+                    Primop::Lt,
+                    var("x"),
+                    var("y"),
+                    name("b"),
+                    vec![
+                        CPSAppl::new(var("k"), vec![Value::Int(1)]),
+                        CPSAppl::new(var("k"), vec![Value::Int(0)]),
+                    ],
+                )),
+            ),
+            Function::new(
+                var("r"),
+                var("j"),
+                Value::Int(0),
+                None,
+                Box::new(CPSExpr::Halt(var("r"))),
+            ),
+        ],
+        Box::new(CPSRecord::new(
+            vec![Value::String(name("[Option, Some]")), Value::Int(3)],
+            name("c"),
+            Box::new(CPSSelect::new(
+                1,
+                var("c"),
+                name("v"),
+                Box::new(CPSAppl::new(
+                    var("f"),
+                    vec![var("v"), Value::Int(4), var("j")],
+                )),
+            )),
+        )),
+    );
+
+    let expected = r#"(fix ((f x y k
+        (primop lt b (x y)
+          (app k 1)
+          (app k 0)))
+      (j r 0
+        (halt r)))
+  (record c ("[Option, Some]" 3)
+    (select v c 1
+      (app f v 4 j))))"#;
+
+    assert_eq!(expr.to_string(), expected);
+    assert_eq!(parse(expected), Ok(expr));
+}
+
+#[test]
+fn round_trip() {
+    let _interner = curse_test_support::lock();
+
+    let input = r#"
+        ; comments and extra whitespace are fine
+        (primop semi t (0 "quote \" and backslash \\")
+          (fix ()
+            (halt t)))
+    "#;
+
+    let expr = parse(input).unwrap();
+    assert_eq!(parse(&expr.to_string()), Ok(expr));
+}
+
+#[test]
+fn errors() {
+    let _interner = curse_test_support::lock();
+
+    assert!(matches!(
+        parse("(halt x"),
+        Err(ParseError::UnexpectedEof { .. })
+    ));
+    assert!(matches!(
+        parse("(halt x))"),
+        Err(ParseError::TrailingInput { .. })
+    ));
+    assert!(matches!(
+        parse("(primop frob t (1 2) (halt t))"),
+        Err(ParseError::UnknownPrimop { .. })
+    ));
+    assert!(matches!(
+        parse("(primop eq t (1 2) (halt t))"),
+        Err(ParseError::ContinuationCount {
+            expected: 2,
+            found: 1,
+            ..
+        })
+    ));
+    assert!(matches!(
+        parse("(record 3 () (halt 0))"),
+        Err(ParseError::Expected { .. })
+    ));
+}
@@ -1,23 +1,18 @@
+use std::collections::HashMap;
+
 use curse_hir::hir::{self, ExprKind};
 use curse_interner::{Ident, InternedString};
 use curse_span::Span;
 
 use crate::{
     convert_expr,
-    cpsexpr::{var, CPSAppl, CPSExpr, CPSFix, CPSPrimop, CPSRecord, Function, Primop, Value::{*, self}},
-    reset_sym_counter,
+    cpsexpr::{var, CPSAppl, CPSExpr, CPSFix, CPSPrimop, CPSRecord, Function, Primop, Value::*},
+    reset_sym_counter, VARIANTS,
 };
 
-// MAKE SURE TO RUN AS `cargo test -- --test-threads=1`
-
-pub fn string(s: &str) -> Value {
-    Value::String(InternedString::get_or_intern(s))
-}
-
-
 #[test]
 fn records() {
-    let _interner = curse_interner::init();
+    let _interner = curse_test_support::lock();
     reset_sym_counter();
 
     let span = Span { start: 0, end: 0 };
@@ -93,7 +88,7 @@ fn records() {
 
 #[test]
 fn basics() {
-    let _interner = curse_interner::init();
+    let _interner = curse_test_support::lock();
     reset_sym_counter();
 
     let one_plus_one = hir::Expr {
@@ -159,7 +154,7 @@ fn basics() {
 
 #[test]
 fn appl() {
-    let _interner = curse_interner::init();
+    let _interner = curse_test_support::lock();
     reset_sym_counter();
 
     let span = Span { start: 0, end: 0 };
@@ -206,6 +201,7 @@ fn appl() {
             left: var("x__1_"),
             name: var("r__2_"),
             right: Int(0),
+            ret: None,
             continuation: Box::new(CPSExpr::Halt(var("x__1_"))),
         }],
         continuation: Box::new(CPSExpr::Fix(CPSFix {
@@ -213,6 +209,7 @@ fn appl() {
                 left: var("x__3_"),
                 name: var("r__4_"),
                 right: Int(0),
+                ret: None,
                 continuation: Box::new(CPSExpr::Appl(CPSAppl {
                     function: var("in"),
                     args: vec![var("x__3_"), var("sum"), var("r__2_")],
@@ -230,7 +227,7 @@ fn appl() {
 
 #[test]
 fn symb() {
-    let _interner = curse_interner::init();
+    let _interner = curse_test_support::lock();
     reset_sym_counter();
 
     let expr = hir::Expr {
@@ -244,12 +241,16 @@ fn symb() {
             left: var("x__1_"),
             name: var("f__3_"),
             right: var("y__2_"),
+            ret: Some(var("k__5_")),
             continuation: Box::new(CPSExpr::Primop(CPSPrimop {
                 primop: Primop::Plus,
                 left: var("x__1_"),
                 right: var("y__2_"),
                 name: InternedString::get_or_intern("t__4_"),
-                continuations: vec![CPSExpr::Halt(var("t__4_"))],
+                continuations: vec![CPSExpr::Appl(CPSAppl {
+                    function: var("k__5_"),
+                    args: vec![var("t__4_")],
+                })],
             })),
         }],
         continuation: Box::new(CPSExpr::Halt(var("f__3_"))),
@@ -260,7 +261,7 @@ fn symb() {
 
 #[test]
 fn ctor() {
-    let _interner = curse_interner::init();
+    let _interner = curse_test_support::lock();
     let span = Span { start: 0, end: 0 };
     reset_sym_counter();
 
@@ -281,6 +282,10 @@ fn ctor() {
         span,
     };
     let path = [Ident::new("Option", span), Ident::new("Some", span)];
+    VARIANTS.with(|cell| {
+        let key = (path[0].symbol, path[1].symbol);
+        *cell.borrow_mut() = HashMap::from([(key, 1)]);
+    });
     let ctor_expr = hir::Expr {
         kind: ExprKind::Constructor(hir::Constructor {
             path: &path,
@@ -296,7 +301,7 @@ fn ctor() {
         right: Int(4),
         name: InternedString::get_or_intern("t__2_"),
         continuations: vec![CPSExpr::Record(CPSRecord {
-            values: vec![string("[Option, Some]"), var("t__2_")],
+            values: vec![Int(1), var("t__2_")],
             name: InternedString::get_or_intern("ctor__1_"),
             continuation: Box::new(CPSExpr::Halt(var("ctor__1_"))),
         })],
@@ -307,7 +312,7 @@ fn ctor() {
 
 #[test]
 fn branching() {
-    let _interner = curse_interner::init();
+    let _interner = curse_test_support::lock();
     reset_sym_counter();
 
     let one_plus_one = hir::Expr {
@@ -369,6 +374,7 @@ fn branching() {
                         left: Var(x),
                         name: Var(k),
                         right: Var(y),
+                        ret: None,
                         continuation: Box::new(CPSExpr::Halt(Var(x))),
                     }],
                     continuation: Box::new(CPSExpr::Primop(CPSPrimop {
@@ -0,0 +1,108 @@
+//! Finds the parts of a program that CPS conversion can't handle yet, so that the backends built
+//! on it refuse to compile the program instead of panicking or computing the wrong thing.
+
+use curse_hir::hir::{self, Arm, ExprKind, ExprRef, PatKind, PatRef, Symbol};
+use curse_interner::InternedString;
+use miette::{Diagnostic, SourceSpan};
+use thiserror::Error;
+
+#[derive(Debug, Diagnostic, Error)]
+#[error("{what} can't be compiled yet")]
+#[diagnostic(help("the interpreter supports this, so run it with `curse run` instead"))]
+pub struct Unsupported {
+    pub what: &'static str,
+    /// The top level function that it's in, or the type of the `impl` that it's in, so that it
+    /// can be reported in the right file.
+    pub function: InternedString,
+    #[label("This isn't supported by the compiled backends")]
+    pub span: SourceSpan,
+}
+
+/// Everything in `program` that can't be converted to CPS, ordered by the functions and then the
+/// `impl`s they're in. Uses of traits that can't be carried out are only found while converting.
+pub fn find(program: &hir::Program<'_>) -> Vec<Unsupported> {
+    let mut defs: Vec<_> = program.function_defs.values().collect();
+    defs.sort_by_key(|def| def.ident.symbol.string().to_string());
+
+    let mut found = vec![];
+    for def in defs {
+        let mut finder = Finder {
+            function: def.ident.symbol,
+            found: &mut found,
+        };
+        finder.arms(def.arms);
+    }
+
+    let mut impl_defs: Vec<_> = program.impl_defs.values().collect();
+    impl_defs.sort_by_key(|def| (def.ty.symbol.string().to_string(), def.trait_.name()));
+    for def in impl_defs {
+        let mut finder = Finder {
+            function: def.ty.symbol,
+            found: &mut found,
+        };
+        finder.arms(def.arms);
+    }
+    found
+}
+
+struct Finder<'a> {
+    function: InternedString,
+    found: &'a mut Vec<Unsupported>,
+}
+
+impl Finder<'_> {
+    fn report(&mut self, what: &'static str, span: curse_span::Span) {
+        self.found.push(Unsupported {
+            what,
+            function: self.function,
+            span: span.start_len().into(),
+        });
+    }
+
+    fn arms(&mut self, arms: &[Arm<'_>]) {
+        for arm in arms {
+            for param in arm.params {
+                self.pat(param.pat);
+            }
+            self.expr(arm.body);
+        }
+    }
+
+    fn pat(&mut self, pat: PatRef<'_>) {
+        match pat.kind {
+            PatKind::Lit(_) | PatKind::Error => {}
+            // records are laid out by position, and where a field is depends on the ones that an
+            // open pattern leaves out
+            PatKind::Record(_, true) => self.report("Open record patterns", pat.span),
+            PatKind::Record(map, false) => {
+                for pat in map.entries.iter().filter_map(|(_, pat)| *pat) {
+                    self.pat(pat);
+                }
+            }
+            PatKind::Constructor(_, inner) => self.pat(inner),
+        }
+    }
+
+    fn expr(&mut self, expr: ExprRef<'_>) {
+        match expr.kind {
+            // for the same reason as open record patterns, finding a field needs the type of the
+            // record, which CPS conversion doesn't have
+            ExprKind::Symbol(Symbol::Dot) => self.report("Field access", expr.span),
+            ExprKind::Symbol(Symbol::DotDot) => self.report("`..`", expr.span),
+            ExprKind::Symbol(_) | ExprKind::Lit(_) | ExprKind::Error => {}
+            ExprKind::Record(map) => {
+                for expr in map.entries.iter().filter_map(|(_, expr)| *expr) {
+                    self.expr(expr);
+                }
+            }
+            ExprKind::Constructor(constructor) => self.expr(constructor.inner),
+            ExprKind::Closure(arms) => self.arms(arms),
+            ExprKind::Appl(appl) => {
+                for part in appl.parts {
+                    self.expr(part);
+                }
+            }
+            ExprKind::Region(_) => self.report("Regions", expr.span),
+        }
+    }
+}
@@ -0,0 +1,214 @@
+//! Runs every program in `examples/`, `curse_interpreter/project_euler/` and `tests/expected/`
+//! through both the CPS reference evaluator and the tree-walking interpreter, and checks that they
+//! agree. The CPS is also checked after running each optimization pass on its own, and after
+//! running all of them.
+
+use std::{fs, path::Path, thread};
+
+use bumpalo::Bump;
+use curse_cps::{
+    eval,
+    optimize::{optimize, Passes},
+};
+use curse_hir::hir;
+use curse_interner::InternedString;
+use curse_interpreter::value;
+use curse_test_support::lock;
+
+/// The part of a value that both evaluators can observe. Functions are opaque, booleans are
+/// integers and choice values are records of the position of their variant and the inner value,
+/// the same way the CPS conversion represents them.
+#[derive(Debug, PartialEq)]
+enum Observed {
+    Int(u32),
+    String(String),
+    Record(Vec<Observed>),
+    Function,
+}
+
+impl From<&eval::Value<'_>> for Observed {
+    fn from(value: &eval::Value<'_>) -> Self {
+        match value {
+            eval::Value::Int(n) => Observed::Int(*n),
+            eval::Value::String(s) => Observed::String(s.to_string()),
+            eval::Value::Record(values) => {
+                Observed::Record(values.iter().map(Into::into).collect())
+            }
+            eval::Value::Function(_) => Observed::Function,
+        }
+    }
+}
+
+/// Observes a value from the interpreter, which needs `program` to find the position of each
+/// variant that the CPS conversion uses as the tag.
+fn observe(program: &hir::Program, value: &value::Value<'_>) -> Observed {
+    match value {
+        value::Value::Integer(n) => Observed::Int(*n),
+        value::Value::Bool(b) => Observed::Int(*b as u32),
+        value::Value::Record(map) if map.entries.is_empty() => Observed::Int(0),
+        value::Value::Record(map) => Observed::Record(
+            map.entries
+                .iter()
+                .map(|(_, value)| observe(program, value))
+                .collect(),
+        ),
+        value::Value::Choice { tag, value } => {
+            let [choice, variant] = tag else {
+                unreachable!("variants are always named by their choice")
+            };
+            let index = program.choice_defs[&choice.symbol]
+                .variants
+                .entries
+                .iter()
+                .position(|(ident, _)| ident.symbol == variant.symbol)
+                .expect("the variant exists");
+            Observed::Record(vec![Observed::Int(index as u32), observe(program, value)])
+        }
+        value::Value::Struct { value, .. } => {
+            Observed::Record(vec![Observed::Int(0), observe(program, value)])
+        }
+        value::Value::Cell { value, .. } => observe(program, &value.borrow()),
+        value::Value::Function(..) | value::Value::Builtin(_) | value::Value::Method(_) => {
+            Observed::Function
+        }
+    }
+}
+
+/// Every combination of optimizations we check, along with a name for error messages.
+fn pipelines() -> Vec<(&'static str, Passes)> {
+    vec![
+        ("unoptimized", Passes::none()),
+        (
+            "beta contraction",
+            Passes {
+                beta_contraction: true,
+                ..Passes::none()
+            },
+        ),
+        (
+            "eta reduction",
+            Passes {
+                eta_reduction: true,
+                ..Passes::none()
+            },
+        ),
+        (
+            "dead code",
+            Passes {
+                dead_code: true,
+                ..Passes::none()
+            },
+        ),
+        (
+            "constant folding",
+            Passes {
+                constant_folding: true,
+                ..Passes::none()
+            },
+        ),
+        ("optimized", Passes::default()),
+    ]
+}
+
+/// Runs a single program through the interpreter and through the CPS evaluator after each
+/// pipeline, returning `None` if it isn't a runnable program in the first place.
+fn run(path: &str) -> Option<(Observed, Vec<(&'static str, Observed)>)> {
+    let parsed =
+        curse_test_support::parse_with(|interner| curse_interpreter::flatten_asts(interner, path));
+    let Ok(ast_program) = parsed else {
+        eprintln!("skipping {path}: doesn't parse");
+        return None;
+    };
+
+    let arena = Bump::new();
+    let (program, errors) = curse_test_support::lower_with_errors(&arena, &ast_program);
+    if !errors.is_empty() {
+        eprintln!("skipping {path}: doesn't lower");
+        return None;
+    }
+
+    let global = curse_mir::ctx::Global::default();
+    let mut typeck = curse_mir::ctx::Typeck::with_global(&global);
+    let Ok(typed) = curse_mir::check_program(&mut typeck, &program) else {
+        eprintln!("skipping {path}: doesn't type check");
+        return None;
+    };
+
+    let main = InternedString::get_or_intern("main");
+    if !program.function_defs.contains_key(&main) {
+        eprintln!("skipping {path}: no `main`");
+        return None;
+    }
+
+    let expected = curse_interpreter::evaluation::execute_program(&program)
+        .unwrap_or_else(|e| panic!("{path} failed in the interpreter: {e:?}"));
+
+    let actual = pipelines()
+        .into_iter()
+        .map(|(name, passes)| {
+            let cps = optimize(
+                curse_cps::convert_program(&program, &typed.dispatches).expect("converts to CPS"),
+                passes,
+            );
+            let actual = eval::eval(&cps)
+                .unwrap_or_else(|e| panic!("{path} failed in the CPS evaluator ({name}): {e:?}"));
+            (name, (&actual).into())
+        })
+        .collect();
+
+    Some((observe(&program, &expected), actual))
+}
+
+/// Checks every program in `dir` that can be run.
+fn agree(dir: &str) {
+    let _interner = lock();
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let interpreter_dir = manifest_dir.join("../curse_interpreter");
+
+    let mut programs: Vec<_> = fs::read_dir(manifest_dir.join(dir))
+        .expect("directory exists")
+        .map(|entry| entry.expect("readable entry").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "curse"))
+        .collect();
+    programs.sort();
+
+    // `dynamic_import`s are relative to the working directory, and the standard library lives
+    // next to the interpreter.
+    std::env::set_current_dir(&interpreter_dir).unwrap();
+
+    let mut ran = 0;
+    for program in programs {
+        let path = program.to_str().unwrap().to_string();
+        // the interpreter recurses a lot, so give it plenty of room
+        let result = thread::Builder::new()
+            .stack_size(1 << 30)
+            .spawn(move || run(&path))
+            .unwrap()
+            .join()
+            .unwrap();
+
+        if let Some((expected, actual)) = result {
+            for (name, actual) in actual {
+                assert_eq!(expected, actual, "{} ({name})", program.display());
+            }
+            ran += 1;
+        }
+    }
+
+    assert!(ran > 0, "no programs were run");
+}
+
+#[test]
+fn examples() {
+    agree("../../examples");
+}
+
+#[test]
+fn project_euler() {
+    agree("../curse_interpreter/project_euler");
+}
+
+#[test]
+fn expected_programs() {
+    agree("tests/expected");
+}
@@ -0,0 +1,70 @@
+//! Checks that CPS expressions are drawn for Graphviz with the functions of each `Fix` as
+//! clusters.
+
+use bumpalo::Bump;
+use curse_test_support::lock;
+
+const INPUT: &str = "
+fn add_to |x| |y| x + y
+
+fn main || 1 in (2 add_to ())
+";
+
+/// Draws [`INPUT`].
+fn dot() -> String {
+    let arena = Bump::new();
+    let program = curse_test_support::lower(&arena, INPUT);
+
+    // nothing here uses an `impl`, so there's nothing for type checking to resolve
+    let cps = curse_cps::convert_program(&program, &Default::default()).expect("converts to CPS");
+    let mut builder = curse_cps::dot::Builder::new();
+    builder.visit_expr(&cps);
+    let dot = builder.finish();
+    assert!(dot.starts_with("digraph cps {"), "{dot}");
+    dot
+}
+
+/// The line that the label of the cluster for the function `name` is on.
+fn cluster(dot: &str, name: &str) -> usize {
+    dot.lines()
+        .position(|line| line.trim_start().starts_with(&format!("label = \"{name} ")))
+        .unwrap_or_else(|| panic!("no cluster for {name} in {dot}"))
+}
+
+/// How far the `line`th line is indented.
+fn indent(dot: &str, line: usize) -> usize {
+    let line = dot.lines().nth(line).unwrap();
+    line.len() - line.trim_start().len()
+}
+
+#[test]
+fn top_level_functions_are_clusters_in_the_outer_fix() {
+    let _interner = lock();
+    let dot = dot();
+    assert_eq!(indent(&dot, cluster(&dot, "add_to")), 8, "{dot}");
+    assert_eq!(indent(&dot, cluster(&dot, "main")), 8, "{dot}");
+    assert!(dot.contains("[label = \"add_to\"]"), "{dot}");
+}
+
+#[test]
+fn closures_are_nested_in_the_cluster_of_their_function() {
+    let _interner = lock();
+    let dot = dot();
+    let lines: Vec<&str> = dot.lines().collect();
+    let add_to = cluster(&dot, "add_to");
+    let end = add_to
+        + lines[add_to..]
+            .iter()
+            .position(|line| *line == "    }")
+            .unwrap();
+    let nested = lines[add_to..end]
+        .iter()
+        .position(|line| line.trim_start().starts_with("subgraph cluster_"))
+        .map(|line| add_to + line)
+        .unwrap_or_else(|| panic!("no nested cluster in {dot}"));
+    assert_eq!(indent(&dot, nested), 8, "{dot}");
+    assert!(
+        lines[nested + 1].starts_with("            label = \"f__"),
+        "{dot}"
+    );
+}
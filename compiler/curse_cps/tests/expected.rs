@@ -0,0 +1,61 @@
+//! Converts every program in `tests/expected/` to CPS and compares the printed result against the
+//! `.cps` file next to it. Run with `UPDATE_EXPECT=1` to overwrite the `.cps` files with the
+//! current output instead.
+
+use std::{fs, path::Path};
+
+use bumpalo::Bump;
+use curse_cps::sexpr;
+use curse_test_support::lock;
+
+fn convert(input: &str) -> curse_cps::cpsexpr::CPSExpr {
+    let arena = Bump::new();
+    let program = curse_test_support::lower(&arena, input);
+
+    let global = curse_mir::ctx::Global::default();
+    let mut typeck = curse_mir::ctx::Typeck::with_global(&global);
+    let typed = curse_mir::check_program(&mut typeck, &program).expect("type checks");
+    curse_cps::convert_program(&program, &typed.dispatches).expect("converts to CPS")
+}
+
+#[test]
+fn expected_output() {
+    let _interner = lock();
+    let dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/expected");
+    let update = std::env::var_os("UPDATE_EXPECT").is_some();
+
+    let mut programs: Vec<_> = fs::read_dir(&dir)
+        .expect("directory exists")
+        .map(|entry| entry.expect("readable entry").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "curse"))
+        .collect();
+    programs.sort();
+    assert!(!programs.is_empty(), "no programs to check");
+
+    for program in programs {
+        let cps = convert(&fs::read_to_string(&program).unwrap());
+        let actual = format!("{cps}\n");
+
+        // the printed form should always parse back into the same thing
+        assert_eq!(sexpr::parse(&actual).as_ref(), Ok(&cps));
+
+        let expected_path = program.with_extension("cps");
+        if update {
+            fs::write(&expected_path, &actual).unwrap();
+            continue;
+        }
+
+        let expected = fs::read_to_string(&expected_path).unwrap_or_else(|_| {
+            panic!(
+                "{} is missing, run with `UPDATE_EXPECT=1` to create it",
+                expected_path.display()
+            )
+        });
+        assert_eq!(
+            expected,
+            actual,
+            "{} doesn't match, run with `UPDATE_EXPECT=1` to update it",
+            expected_path.display()
+        );
+    }
+}
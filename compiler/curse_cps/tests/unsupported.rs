@@ -0,0 +1,82 @@
+//! Checks that programs using things that CPS conversion can't handle yet are refused with the
+//! span of each of them, rather than panicking.
+
+use bumpalo::Bump;
+use curse_test_support::lock;
+
+const INPUT: &str = "fn in |x, f| x f {}
+
+fn get_x |r| r . x
+
+fn get_y |{ y, .. }| y
+
+fn main || 1 in |n| mut n { n + 1 }
+";
+
+/// Uses of traits are only checked while converting, once nothing else is in the way.
+const TRAITS: &str = "choice Money { Cents I32 }
+
+impl Eq Money |Money::Cents a, Money::Cents b| a = b
+
+fn nested || { m: Money::Cents 1 } = { m: Money::Cents 2 }
+
+trait Scale scale |Self, I32| Self
+
+impl Scale Money |Money::Cents a, n| Money::Cents (a * n)
+
+fn main || ((0 panic {}) scale 2); 0
+";
+
+/// What can't be converted in `input`, the name that it's reported under, and its text.
+fn unsupported(input: &str) -> Vec<(&'static str, String, &str)> {
+    let arena = Bump::new();
+    let program = curse_test_support::lower(&arena, input);
+
+    let global = curse_mir::ctx::Global::default();
+    let mut typeck = curse_mir::ctx::Typeck::with_global(&global);
+    let typed = curse_mir::check_program(&mut typeck, &program).expect("type checks");
+
+    let Err(unsupported) = curse_cps::convert_program(&program, &typed.dispatches) else {
+        panic!("converted a program with unsupported parts");
+    };
+    unsupported
+        .iter()
+        .map(|error| {
+            let text = &input[error.span.offset()..][..error.span.len()];
+            (error.what, error.function.to_string(), text)
+        })
+        .collect()
+}
+
+#[test]
+fn unsupported_syntax() {
+    let _interner = lock();
+    assert_eq!(
+        unsupported(INPUT),
+        [
+            ("Field access", "get_x".to_string(), "."),
+            ("Open record patterns", "get_y".to_string(), "{ y, .. }"),
+            ("Regions", "main".to_string(), "mut n { n + 1 }"),
+        ]
+    );
+}
+
+#[test]
+fn traits_used_on_types_that_nothing_decides() {
+    let _interner = lock();
+    assert_eq!(
+        unsupported(TRAITS),
+        [
+            (
+                "Methods used on types that nothing decides",
+                "main".to_string(),
+                "scale"
+            ),
+            (
+                "Comparing values with `impl`s inside",
+                "nested".to_string(),
+                "="
+            ),
+        ]
+    );
+}
@@ -0,0 +1,775 @@
+//! The query database, which makes checking incremental: it remembers the HIR and the types it's
+//! worked out for a program, so that after a change, it only lowers and type checks again what
+//! the change could have affected. It's what the language server checks programs with, since it
+//! checks them again on every keystroke.
+//!
+//! The contents of the files are the inputs, and everything else is worked out from them and
+//! remembered along with what it depended on:
+//!
+//! - Each file is parsed on its own, and only parsed again when it changes.
+//! - Each item of a file, like a function or a struct, is lowered on its own, and resolved at the
+//!   same time by finding the names it uses from outside of itself. It's only lowered again when
+//!   its text changes, when it moves, since the spans in the HIR have to be where it is now, or
+//!   when the structs, choices or traits that it could name change. So an edit only lowers again
+//!   what it changed and what comes after it in the same file.
+//! - The lowered items of a program, which is a file along with everything it imports, are put
+//!   together whenever any of its files change. That's where redefinitions, recursive structs and
+//!   escaping references are found, since they look across the whole program. It doesn't lower
+//!   anything itself, so it's quick.
+//! - Each strongly connected component of the call graph is type checked on its own. It's only
+//!   checked again when the text of one of its functions changes, when the type of a function
+//!   that it uses changes, or when the program's structs, choices or impls change. So changing
+//!   the body of a function checks it again, but only checks what uses it if its type changed.
+//! - Each `impl` is checked on its own in the same way, once the functions are done.
+//!
+//! Exhaustiveness and unreachable arms aren't checked at all, since the usefulness checker in
+//! `curse_mir` isn't built yet, so there's no query for it either.
+//!
+//! Type checked functions are remembered by their text rather than where they are, so one that
+//! only moved because something above it changed isn't checked again. Its errors are moved along with it,
+//! but the spans in its typed body are where it was when it was checked, and
+//! [`CheckedFunction::moved`] says how far to move them.
+//!
+//! The HIR and the types live in [`Arenas`] that the database borrows, so nothing that it works
+//! out is freed while it's around. [`Database::garbage`] says how much of that is out of date, and
+//! once it's too much, the inputs can be moved into a new database with new arenas, which only
+//! has to lower and type check everything again. The inputs also own the global string interner,
+//! so there can only be one database at a time.
+
+#![forbid(unsafe_code)]
+
+use bumpalo::Bump;
+use curse_ast::ast;
+use curse_ast_lowering::{Items, Lower, Lowerer, LoweringError};
+use curse_hir::hir::{ChoiceDef, FunctionDef, ImplDef, StructDef, Trait, TraitDef};
+use curse_interner::InternedString;
+use curse_mir::{ctx, Dispatches, Expr, LowerError, TypeTemplate};
+use curse_span::{HasSpan, Span};
+use std::{
+    collections::{hash_map::DefaultHasher, HashMap},
+    hash::{Hash, Hasher},
+    path::{Path, PathBuf},
+    rc::Rc,
+};
+
+/// Where the database keeps what it works out, which has to outlive it. Nothing in them is freed
+/// until they're dropped.
+#[derive(Default)]
+pub struct Arenas<'db> {
+    /// The types of everything that's checked.
+    global: ctx::Global<'db>,
+    /// The HIR of every item that's lowered.
+    hir: Bump,
+}
+
+/// The contents of the files that a database was given, along with what they parsed to. They
+/// don't borrow the database's arenas, so they can be moved into a new one.
+pub struct Inputs {
+    root: PathBuf,
+    files: HashMap<PathBuf, File>,
+}
+
+impl Inputs {
+    /// Makes empty inputs, where imports are relative to `root`. It replaces the global string
+    /// interner with its own.
+    pub fn new(root: PathBuf) -> Self {
+        curse_interner::init();
+        Inputs {
+            root,
+            files: HashMap::new(),
+        }
+    }
+}
+
+pub struct Database<'db> {
+    arenas: &'db Arenas<'db>,
+    ctx: ctx::Typeck<'db>,
+    /// The types of the builtins, which every program starts out with.
+    builtins: HashMap<InternedString, TypeTemplate<'db>>,
+    /// Imports are relative to this, the same as they're relative to wherever the interpreter is
+    /// run from.
+    root: PathBuf,
+    files: HashMap<PathBuf, File>,
+    /// Each item that's been lowered, keyed by its file and its name.
+    lowered: HashMap<(PathBuf, Item), Lowered<'db>>,
+    /// Keyed by the file that each program starts at.
+    programs: HashMap<PathBuf, Program<'db>>,
+    /// How many items have been lowered or type checked, including ones that are out of date now.
+    worked_out: usize,
+    /// What the last call to [`Database::check`] had to work out, rather than remembering it.
+    pub log: Vec<Event>,
+}
+
+/// Something that the database had to work out, because it didn't have it already or because
+/// what it depended on changed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Event {
+    /// A file was parsed.
+    Parsed(PathBuf),
+    /// An item of a file was lowered and resolved.
+    Lowered(PathBuf, Item),
+    /// The functions in a component of the call graph were type checked.
+    Checked(Vec<InternedString>),
+    /// The `impl` of a trait for a type was type checked.
+    CheckedImpl(Trait, InternedString),
+}
+
+/// A top level item of a file, by its name.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Item {
+    Function(InternedString),
+    Struct(InternedString),
+    Choice(InternedString),
+    Trait(InternedString),
+    /// By the names of its trait and its type, as they're written.
+    Impl(InternedString, InternedString),
+}
+
+struct File {
+    /// The contents of the file, or why it couldn't be read.
+    input: Result<Rc<str>, String>,
+    parsed: Option<Rc<Parsed>>,
+}
+
+/// A file after it's been parsed.
+#[derive(Debug)]
+pub struct Parsed {
+    pub input: Rc<str>,
+    pub program: ast::Program,
+    pub errors: Vec<curse_parse::Error>,
+}
+
+/// One of the files of a program.
+#[derive(Clone, Debug)]
+pub struct SourceFile {
+    pub path: PathBuf,
+    pub parsed: Rc<Parsed>,
+}
+
+/// A `dynamic_import` in one of the files of a program.
+#[derive(Clone, Debug)]
+pub struct Import {
+    /// Which of the program's files it's in.
+    pub file: usize,
+    /// The quoted name of the file.
+    pub span: Span,
+    /// Which of the program's files it imports, or why it couldn't be read.
+    pub imported: Result<usize, String>,
+}
+
+/// A top level function, after it's been type checked.
+#[derive(Debug)]
+pub struct CheckedFunction<'db> {
+    pub name: InternedString,
+    /// Which of the program's files it's in.
+    pub file: usize,
+    /// Its type, which it has even when it has errors.
+    pub template: TypeTemplate<'db>,
+    /// Its body, if it type checked.
+    pub expr: Option<Expr<'db>>,
+    /// How far the function has moved since it was checked, which has to be added to the spans
+    /// in `expr`.
+    pub moved: i64,
+    /// Its type errors, which have already been moved to where the function is now.
+    pub errors: Vec<LowerError>,
+}
+
+/// An `impl`, after it's been type checked.
+#[derive(Debug)]
+pub struct CheckedImpl<'db> {
+    pub trait_: Trait,
+    pub ty: InternedString,
+    /// Which of the program's files it's in.
+    pub file: usize,
+    /// Its body, if it type checked.
+    pub expr: Option<Expr<'db>>,
+    /// How far the `impl` has moved since it was checked, which has to be added to the spans in
+    /// `expr`.
+    pub moved: i64,
+    /// Its type errors, which have already been moved to where the `impl` is now.
+    pub errors: Vec<LowerError>,
+}
+
+/// Everything that's known about a program, as of the last time it was checked.
+#[derive(Debug)]
+pub struct Checked<'db> {
+    /// The file that the program starts at, followed by everything it imports.
+    pub files: Vec<SourceFile>,
+    pub imports: Vec<Import>,
+    pub lowering_errors: Vec<LoweringError>,
+    /// In the order they were checked, which is callees first.
+    pub functions: Vec<CheckedFunction<'db>>,
+    pub impls: Vec<CheckedImpl<'db>>,
+}
+
+/// An item, as it was lowered.
+struct Lowered<'db> {
+    /// The item's text and where it starts, along with the structs, choices and traits of the
+    /// program it was lowered in, hashed.
+    deps: u64,
+    def: Def<'db>,
+    /// The names it uses from outside of itself, which is what resolving it finds.
+    uses: Vec<InternedString>,
+    errors: Vec<LoweringError>,
+}
+
+/// A lowered item, or `None` for traits and impls that couldn't be lowered.
+#[derive(Copy, Clone)]
+enum Def<'db> {
+    Function(FunctionDef<'db>),
+    Struct(StructDef<'db>),
+    Choice(ChoiceDef<'db>),
+    Trait(Option<TraitDef<'db>>),
+    Impl(Option<ImplDef<'db>>),
+}
+
+/// A program, along with how each part of it was checked.
+struct Program<'db> {
+    checked: Checked<'db>,
+    components: HashMap<Vec<InternedString>, Component<'db>>,
+    impls: HashMap<(Trait, InternedString), Impl<'db>>,
+}
+
+/// The functions in a component of the call graph, as they were checked.
+struct Component<'db> {
+    deps: Deps,
+    /// Where each function started when it was checked.
+    starts: Vec<u32>,
+    functions: Vec<(TypeTemplate<'db>, Result<Expr<'db>, Vec<LowerError>>)>,
+}
+
+/// An `impl`, as it was checked.
+struct Impl<'db> {
+    deps: Deps,
+    /// Where the `impl` started when it was checked.
+    start: u32,
+    checked: Result<Expr<'db>, Vec<LowerError>>,
+}
+
+/// What type checking something depends on, so that it's checked again when any of it changes.
+#[derive(PartialEq, Eq)]
+struct Deps {
+    /// The file and text of each function, or of the `impl`.
+    texts: Vec<u64>,
+    /// The types of the names that it uses from outside of itself, printed, or `None` for names
+    /// that aren't defined.
+    uses: Vec<(InternedString, Option<String>)>,
+    /// The structs, choices, traits and impls of the program.
+    types: u64,
+}
+
+impl<'db> Database<'db> {
+    /// Makes an empty database, which puts what it works out in `arenas`. It replaces the global
+    /// string interner with its own.
+    pub fn new(arenas: &'db Arenas<'db>, root: PathBuf) -> Self {
+        Database::with_inputs(arenas, Inputs::new(root))
+    }
+
+    /// Makes a database that starts out with `inputs`, which puts what it works out in `arenas`.
+    /// The files don't have to be parsed again, but everything else does.
+    pub fn with_inputs(arenas: &'db Arenas<'db>, inputs: Inputs) -> Self {
+        let mut ctx = ctx::Typeck::with_global(&arenas.global);
+        let builtins = ctx.default_globals().collect();
+        Database {
+            arenas,
+            ctx,
+            builtins,
+            root: inputs.root,
+            files: inputs.files,
+            lowered: HashMap::new(),
+            programs: HashMap::new(),
+            worked_out: 0,
+            log: vec![],
+        }
+    }
+
+    /// Gives back the inputs, so that they can be moved into a new database with new arenas.
+    pub fn into_inputs(self) -> Inputs {
+        Inputs {
+            root: self.root,
+            files: self.files,
+        }
+    }
+
+    /// How many of the items that have been lowered or type checked are out of date, which are
+    /// still taking up space in the arenas.
+    pub fn garbage(&self) -> usize {
+        let checked: usize = self
+            .programs
+            .values()
+            .map(|program| program.components.len() + program.impls.len())
+            .sum();
+        self.worked_out - self.lowered.len() - checked
+    }
+
+    /// Sets the contents of the file at `path`, which are used instead of what's on disk.
+    pub fn set_input(&mut self, path: PathBuf, input: String) {
+        if let Some(File { input: Ok(old), .. }) = self.files.get(&path) {
+            if **old == *input {
+                return;
+            }
+        }
+        let file = File {
+            input: Ok(input.into()),
+            parsed: None,
+        };
+        self.files.insert(path, file);
+    }
+
+    /// Forgets the contents of the file at `path`, so that it's read from disk the next time it's
+    /// needed, along with the program that starts at it.
+    pub fn remove_input(&mut self, path: &Path) {
+        self.files.remove(path);
+        self.programs.remove(path);
+    }
+
+    /// The context that the types of checked functions are in, which they're printed with.
+    pub fn typeck(&self) -> &ctx::Typeck<'db> {
+        &self.ctx
+    }
+
+    /// Parses the file at `path`. Files that haven't been given with [`Database::set_input`] are
+    /// read from disk, but only the first time they're needed.
+    pub fn parse(&mut self, path: &Path) -> Result<Rc<Parsed>, String> {
+        let file = self
+            .files
+            .entry(path.to_path_buf())
+            .or_insert_with(|| File {
+                input: std::fs::read_to_string(path)
+                    .map(Rc::from)
+                    .map_err(|e| format!("couldn't read {}: {e}", path.display())),
+                parsed: None,
+            });
+        let input = file.input.clone()?;
+        if let Some(parsed) = &file.parsed {
+            return Ok(parsed.clone());
+        }
+
+        // The parser interns into the interner it's given, so the global one is lent to it.
+        let mut interner = curse_interner::replace(None).unwrap_or_default();
+        let mut parser = curse_parse::Parser::new(&mut interner);
+        let program = parser.parse_program(&input);
+        let errors = parser.errors;
+        curse_interner::replace(Some(interner));
+
+        self.log.push(Event::Parsed(path.to_path_buf()));
+        let parsed = Rc::new(Parsed {
+            input,
+            program,
+            errors,
+        });
+        file.parsed = Some(parsed.clone());
+        Ok(parsed)
+    }
+
+    /// Checks the program that starts at the file at `path`, which has to have been given with
+    /// [`Database::set_input`] or be on disk. Returns everything that's known about it, along
+    /// with the context to print its types with.
+    pub fn check(&mut self, path: &Path) -> (&Checked<'db>, &ctx::Typeck<'db>) {
+        self.log.clear();
+        let (files, imports) = self.files_of(path);
+
+        let unchanged = self.programs.get(path).is_some_and(|program| {
+            let old = &program.checked.files;
+            old.len() == files.len()
+                && old
+                    .iter()
+                    .zip(&files)
+                    .all(|(old, new)| old.path == new.path && Rc::ptr_eq(&old.parsed, &new.parsed))
+        });
+        if !unchanged {
+            let program = self.check_program(path, files, imports);
+            self.programs.insert(path.to_path_buf(), program);
+        }
+
+        (&self.programs[path].checked, &self.ctx)
+    }
+
+    /// Parses the file at `path` and everything it imports, in the order the interpreter reads
+    /// them in. Each file is only read once, so that files that import each other don't go on
+    /// forever.
+    fn files_of(&mut self, path: &Path) -> (Vec<SourceFile>, Vec<Import>) {
+        let parsed = self.parse(path).unwrap_or_else(|_| {
+            Rc::new(Parsed {
+                input: "".into(),
+                program: ast::Program::default(),
+                errors: vec![],
+            })
+        });
+        let mut files = vec![SourceFile {
+            path: path.to_path_buf(),
+            parsed,
+        }];
+        let mut seen = HashMap::from([(path.to_path_buf(), 0)]);
+        let mut imports = vec![];
+
+        let mut index = 0;
+        while index < files.len() {
+            let parsed = files[index].parsed.clone();
+            for import in &parsed.program.dynamic_imports {
+                let name = import.file_string.symbol.to_string();
+                // trim off quotes
+                let path = self.root.join(&name[1..name.len() - 1]);
+                let imported = match seen.get(&path) {
+                    Some(&file) => Ok(file),
+                    None => self.parse(&path).map(|parsed| {
+                        seen.insert(path.clone(), files.len());
+                        files.push(SourceFile { path, parsed });
+                        files.len() - 1
+                    }),
+                };
+                imports.push(Import {
+                    file: index,
+                    span: import.file_string.span,
+                    imported,
+                });
+            }
+            index += 1;
+        }
+
+        (files, imports)
+    }
+
+    /// Lowers and type checks the program made of `files`, reusing whatever was lowered or
+    /// checked before that still depends on the same things.
+    fn check_program(
+        &mut self,
+        path: &Path,
+        files: Vec<SourceFile>,
+        imports: Vec<Import>,
+    ) -> Program<'db> {
+        let mut old = self.programs.remove(path);
+
+        // Lowering an item depends on the names of the structs and choices that it could refer
+        // to, how many generic parameters they have and where they are, and on the names of the
+        // traits. Type checking depends on all of the structs, choices and traits, and on which
+        // impls there are.
+        let mut lowerer = Lowerer::new(&self.arenas.hir);
+        let mut names = DefaultHasher::new();
+        let mut types = DefaultHasher::new();
+        for file in &files {
+            let program = &file.parsed.program;
+            let text = |span: Span| &file.parsed.input[span.start as usize..span.end as usize];
+            lowerer.declare(program);
+            file.path.hash(&mut names);
+            let struct_names = program
+                .struct_defs
+                .iter()
+                .map(|def| (def.ident, &def.generic_params));
+            let choice_names = program
+                .choice_defs
+                .iter()
+                .map(|def| (def.ident, &def.generic_params));
+            for (ident, generic_params) in struct_names.chain(choice_names) {
+                let arity = generic_params
+                    .as_ref()
+                    .map_or(0, |params| params.iter_params().count());
+                (ident, arity).hash(&mut names);
+            }
+            for def in &program.trait_defs {
+                def.ident.symbol.hash(&mut names);
+                text(def.span()).hash(&mut types);
+            }
+            for def in &program.impl_defs {
+                (def.trait_.symbol, def.ty.symbol).hash(&mut types);
+            }
+            for def in &program.struct_defs {
+                text(def.span()).hash(&mut types);
+            }
+            for def in &program.choice_defs {
+                text(def.span()).hash(&mut types);
+            }
+        }
+        let names = names.finish();
+        let types = types.finish();
+
+        // The first definition of a name is the one that's kept, the same as in linking.
+        let mut items = Items::default();
+        let mut lowering_errors = vec![];
+        let mut function_files = HashMap::new();
+        let mut function_uses = HashMap::new();
+        let mut impl_files = HashMap::new();
+        let mut impl_uses = HashMap::new();
+        for (index, file) in files.iter().enumerate() {
+            let program = &file.parsed.program;
+            for def in &program.function_defs {
+                let item = Item::Function(def.ident.symbol);
+                let lowered = self.lower(&mut lowerer, file, item, def.span(), names, |lowerer| {
+                    Def::Function(def.lower(lowerer))
+                });
+                lowering_errors.extend_from_slice(&lowered.errors);
+                if let Def::Function(def) = lowered.def {
+                    items.function_defs.push(def);
+                }
+                function_files.entry(def.ident.symbol).or_insert(index);
+                function_uses
+                    .entry(def.ident.symbol)
+                    .or_insert_with(|| lowered.uses.clone());
+            }
+            for def in &program.struct_defs {
+                let item = Item::Struct(def.ident.symbol);
+                let lowered = self.lower(&mut lowerer, file, item, def.span(), names, |lowerer| {
+                    Def::Struct(def.lower(lowerer))
+                });
+                lowering_errors.extend_from_slice(&lowered.errors);
+                if let Def::Struct(def) = lowered.def {
+                    items.struct_defs.push(def);
+                }
+            }
+            for def in &program.choice_defs {
+                let item = Item::Choice(def.ident.symbol);
+                let lowered = self.lower(&mut lowerer, file, item, def.span(), names, |lowerer| {
+                    Def::Choice(def.lower(lowerer))
+                });
+                lowering_errors.extend_from_slice(&lowered.errors);
+                if let Def::Choice(def) = lowered.def {
+                    items.choice_defs.push(def);
+                }
+            }
+            for def in &program.trait_defs {
+                let item = Item::Trait(def.ident.symbol);
+                let lowered = self.lower(&mut lowerer, file, item, def.span(), names, |lowerer| {
+                    Def::Trait(def.lower(lowerer))
+                });
+                lowering_errors.extend_from_slice(&lowered.errors);
+                if let Def::Trait(Some(def)) = lowered.def {
+                    items.trait_defs.push(def);
+                }
+            }
+            for def in &program.impl_defs {
+                let item = Item::Impl(def.trait_.symbol, def.ty.symbol);
+                let lowered = self.lower(&mut lowerer, file, item, def.span(), names, |lowerer| {
+                    Def::Impl(def.lower(lowerer))
+                });
+                lowering_errors.extend_from_slice(&lowered.errors);
+                if let Def::Impl(Some(def)) = lowered.def {
+                    let key = (def.trait_, def.ty.symbol);
+                    items.impl_defs.push(def);
+                    impl_files.entry(key).or_insert(index);
+                    impl_uses.entry(key).or_insert_with(|| lowered.uses.clone());
+                }
+            }
+        }
+        let hir_program = items.link(&mut lowering_errors);
+
+        let fingerprint = |file: usize, span: Span| {
+            let file = &files[file];
+            let mut hasher = DefaultHasher::new();
+            file.path.hash(&mut hasher);
+            file.parsed.input[span.start as usize..span.end as usize].hash(&mut hasher);
+            hasher.finish()
+        };
+
+        let mut globals = self.builtins.clone();
+        globals.extend(curse_mir::method_globals(&mut self.ctx, &hir_program));
+        let mut components = HashMap::new();
+        let mut functions = vec![];
+        let call_graph = curse_mir::call_graph_components_with(&hir_program, |def| {
+            &function_uses[&def.ident.symbol]
+        });
+        for component in call_graph {
+            let names: Vec<_> = component.iter().map(|def| def.ident.symbol).collect();
+            let files: Vec<_> = names.iter().map(|name| function_files[name]).collect();
+            let starts: Vec<_> = component.iter().map(|def| def.span.start).collect();
+            let deps = Deps {
+                texts: component
+                    .iter()
+                    .zip(&files)
+                    .map(|(def, &file)| fingerprint(file, def.span))
+                    .collect(),
+                uses: self.uses(
+                    &globals,
+                    names.iter().map(|name| &function_uses[name][..]),
+                    &names,
+                ),
+                types,
+            };
+
+            let old = old
+                .as_mut()
+                .and_then(|old| old.components.remove(&names))
+                .filter(|old| old.deps == deps);
+            let component = match old {
+                Some(old) => {
+                    for (name, (template, _)) in names.iter().zip(&old.functions) {
+                        globals.insert(*name, template.clone());
+                    }
+                    old
+                }
+                None => {
+                    self.log.push(Event::Checked(names.clone()));
+                    self.worked_out += 1;
+                    // Only the compiled backends need to know which `impl`s get
+                    // called, so what they are isn't kept.
+                    let checked = curse_mir::check_component(
+                        &mut self.ctx,
+                        &hir_program,
+                        &mut globals,
+                        &component,
+                        &mut Dispatches::default(),
+                    );
+                    let functions = checked
+                        .into_iter()
+                        .map(|(name, checked)| (globals[&name].clone(), checked))
+                        .collect();
+                    Component {
+                        deps,
+                        starts: starts.clone(),
+                        functions,
+                    }
+                }
+            };
+
+            for (i, (template, checked)) in component.functions.iter().enumerate() {
+                let moved = i64::from(starts[i]) - i64::from(component.starts[i]);
+                let (expr, errors) = moved_by(checked, moved);
+                functions.push(CheckedFunction {
+                    name: names[i],
+                    file: files[i],
+                    template: template.clone(),
+                    expr,
+                    moved,
+                    errors,
+                });
+            }
+            components.insert(names, component);
+        }
+
+        let mut impl_defs: Vec<_> = hir_program.impl_defs.values().collect();
+        impl_defs.sort_by_key(|def| (def.ty.symbol.string().to_string(), def.trait_.name()));
+
+        let mut impls = vec![];
+        let mut checked_impls = HashMap::new();
+        for def in impl_defs {
+            let key = (def.trait_, def.ty.symbol);
+            let file = impl_files[&key];
+            let deps = Deps {
+                texts: vec![fingerprint(file, def.span)],
+                uses: self.uses(&globals, [&impl_uses[&key][..]], &[]),
+                types,
+            };
+
+            let old = old
+                .as_mut()
+                .and_then(|old| old.impls.remove(&key))
+                .filter(|old| old.deps == deps);
+            let checked = old.unwrap_or_else(|| {
+                self.log.push(Event::CheckedImpl(def.trait_, def.ty.symbol));
+                self.worked_out += 1;
+                Impl {
+                    deps,
+                    start: def.span.start,
+                    checked: curse_mir::check_impl(
+                        &mut self.ctx,
+                        &hir_program,
+                        &globals,
+                        def,
+                        &mut Dispatches::default(),
+                    ),
+                }
+            });
+
+            let moved = i64::from(def.span.start) - i64::from(checked.start);
+            let (expr, errors) = moved_by(&checked.checked, moved);
+            impls.push(CheckedImpl {
+                trait_: def.trait_,
+                ty: def.ty.symbol,
+                file,
+                expr,
+                moved,
+                errors,
+            });
+            checked_impls.insert(key, checked);
+        }
+
+        Program {
+            checked: Checked {
+                files,
+                imports,
+                lowering_errors,
+                functions,
+                impls,
+            },
+            components,
+            impls: checked_impls,
+        }
+    }
+
+    /// Lowers an item of `file` and resolves the names that it uses, unless that's already been
+    /// done since it last changed. `names` is what the program's structs, choices and traits are
+    /// hashed to.
+    fn lower(
+        &mut self,
+        lowerer: &mut Lowerer<'db>,
+        file: &SourceFile,
+        item: Item,
+        span: Span,
+        names: u64,
+        lower: impl FnOnce(&mut Lowerer<'db>) -> Def<'db>,
+    ) -> &Lowered<'db> {
+        let mut hasher = DefaultHasher::new();
+        file.parsed.input[span.start as usize..span.end as usize].hash(&mut hasher);
+        (span.start, names).hash(&mut hasher);
+        let deps = hasher.finish();
+
+        let key = (file.path.clone(), item);
+        if self.lowered.get(&key).is_none_or(|old| old.deps != deps) {
+            self.log.push(Event::Lowered(key.0.clone(), key.1.clone()));
+            self.worked_out += 1;
+            let def = lower(lowerer);
+            let uses = match def {
+                Def::Function(def) => curse_mir::free_names(def.arms),
+                Def::Impl(Some(def)) => curse_mir::free_names(def.arms),
+                _ => vec![],
+            };
+            let lowered = Lowered {
+                deps,
+                def,
+                uses,
+                errors: std::mem::take(&mut lowerer.errors),
+            };
+            self.lowered.insert(key.clone(), lowered);
+        }
+        &self.lowered[&key]
+    }
+
+    /// The types of the names that bodies use, given the names that each of them uses, other
+    /// than the ones in `defined`.
+    fn uses<'a>(
+        &self,
+        globals: &HashMap<InternedString, TypeTemplate<'db>>,
+        bodies: impl IntoIterator<Item = &'a [InternedString]>,
+        defined: &[InternedString],
+    ) -> Vec<(InternedString, Option<String>)> {
+        let mut uses = vec![];
+        for names in bodies {
+            for &name in names {
+                if !defined.contains(&name) && !uses.iter().any(|&(used, _)| used == name) {
+                    let ty = globals
+                        .get(&name)
+                        .map(|template| template.display(&self.ctx).to_string());
+                    uses.push((name, ty));
+                }
+            }
+        }
+        uses
+    }
+}
+
+/// Splits what checking something gave back into its body and its errors, moving the errors
+/// `moved` bytes.
+fn moved_by<'db>(
+    checked: &Result<Expr<'db>, Vec<LowerError>>,
+    moved: i64,
+) -> (Option<Expr<'db>>, Vec<LowerError>) {
+    match checked {
+        Ok(expr) => (Some(*expr), vec![]),
+        Err(errors) => {
+            let mut errors = errors.clone();
+            for error in &mut errors {
+                error.shift(moved);
+            }
+            (None, errors)
+        }
+    }
+}
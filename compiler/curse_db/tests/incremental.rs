@@ -0,0 +1,252 @@
+//! Checks that after a change, the database only works out again what the change could have
+//! affected.
+
+use curse_db::{Arenas, Checked, Database, Event, Item};
+use curse_mir::LowerError;
+use curse_span::Span;
+use curse_test_support::{lock, text};
+use std::path::{Path, PathBuf};
+
+const LIB: &str = "struct Meters I32
+
+impl Add Meters |Meters a, Meters b| Meters (a + b)
+
+fn double |x| x + x
+";
+
+const MAIN: &str = "dynamic_import \"lib.curse\"
+
+fn quad |x| x double {} double {}
+
+fn main || 1 quad {}
+
+fn broken || 1 + true
+";
+
+/// What the last check had to work out, sorted since the order of the components isn't the
+/// point.
+fn log(db: &Database<'_>) -> Vec<String> {
+    let mut log: Vec<_> = db
+        .log
+        .iter()
+        .map(|event| match event {
+            Event::Parsed(path) => format!("parse {}", path.file_name().unwrap().to_string_lossy()),
+            Event::Lowered(path, item) => {
+                let item = match item {
+                    Item::Function(name)
+                    | Item::Struct(name)
+                    | Item::Choice(name)
+                    | Item::Trait(name) => name.to_string(),
+                    Item::Impl(trait_, ty) => format!("impl {trait_} {ty}"),
+                };
+                let file = path.file_name().unwrap().to_string_lossy();
+                format!("lower {file} {item}")
+            }
+            Event::Checked(names) => {
+                let names: Vec<_> = names.iter().map(ToString::to_string).collect();
+                format!("check {}", names.join(" "))
+            }
+            Event::CheckedImpl(trait_, ty) => format!("check impl {} {ty}", trait_.name()),
+        })
+        .collect();
+    log.sort();
+    log
+}
+
+fn function<'a, 'db>(checked: &'a Checked<'db>, name: &str) -> &'a curse_db::CheckedFunction<'db> {
+    checked
+        .functions
+        .iter()
+        .find(|function| function.name.to_string() == name)
+        .unwrap_or_else(|| panic!("{name}"))
+}
+
+const ROOT: &str = "/project";
+
+/// A database with [`LIB`] and [`MAIN`] as its inputs, and the path to `main.curse`.
+fn database<'db>(arenas: &'db Arenas<'db>) -> (Database<'db>, PathBuf) {
+    let root = Path::new(ROOT);
+    let main = root.join("main.curse");
+    let mut db = Database::new(arenas, root.to_path_buf());
+    db.set_input(root.join("lib.curse"), LIB.to_string());
+    db.set_input(main.clone(), MAIN.to_string());
+    (db, main)
+}
+
+/// Like [`database`], but checked once already.
+fn checked_database<'db>(arenas: &'db Arenas<'db>) -> (Database<'db>, PathBuf) {
+    let (mut db, main) = database(arenas);
+    db.check(&main);
+    (db, main)
+}
+
+/// Everything that lowering and checking the program with `struct Feet` added to `lib.curse`
+/// does.
+const EVERYTHING: [&str; 12] = [
+    "check broken",
+    "check double",
+    "check impl Add Meters",
+    "check main",
+    "check quad",
+    "lower lib.curse Feet",
+    "lower lib.curse Meters",
+    "lower lib.curse double",
+    "lower lib.curse impl Add Meters",
+    "lower main.curse broken",
+    "lower main.curse main",
+    "lower main.curse quad",
+];
+
+#[test]
+fn everything_is_worked_out_the_first_time() {
+    let _interner = lock();
+    let arenas = Arenas::default();
+    let (mut db, main) = database(&arenas);
+    let (checked, ctx) = db.check(&main);
+    assert_eq!(checked.files.len(), 2);
+    let types: Vec<_> = ["double", "quad", "main"]
+        .map(|name| function(checked, name).template.display(ctx).to_string())
+        .into();
+    assert_eq!(
+        types,
+        [
+            "(A {} -> A) where Add A",
+            "(A {} -> A) where Add A",
+            "({} {} -> I32)"
+        ]
+    );
+    assert_eq!(
+        log(&db),
+        [
+            "check broken",
+            "check double",
+            "check impl Add Meters",
+            "check main",
+            "check quad",
+            "lower lib.curse Meters",
+            "lower lib.curse double",
+            "lower lib.curse impl Add Meters",
+            "lower main.curse broken",
+            "lower main.curse main",
+            "lower main.curse quad",
+            "parse lib.curse",
+            "parse main.curse",
+        ]
+    );
+    assert_eq!(db.garbage(), 0);
+}
+
+#[test]
+fn nothing_is_worked_out_when_nothing_changed() {
+    let _interner = lock();
+    let arenas = Arenas::default();
+    let (mut db, main) = checked_database(&arenas);
+    db.check(&main);
+    assert_eq!(log(&db), [] as [&str; 0]);
+}
+
+#[test]
+fn changing_a_body_only_works_out_that_function() {
+    let _interner = lock();
+    let arenas = Arenas::default();
+    let (mut db, main) = checked_database(&arenas);
+    db.set_input(main.clone(), MAIN.replace("1 quad", "2 quad"));
+    db.check(&main);
+    assert_eq!(
+        log(&db),
+        ["check main", "lower main.curse main", "parse main.curse"]
+    );
+    // the old lowered and checked `main` are out of date
+    assert_eq!(db.garbage(), 2);
+}
+
+#[test]
+fn moved_functions_keep_their_types_and_errors() {
+    let _interner = lock();
+    let arenas = Arenas::default();
+    let (mut db, main) = checked_database(&arenas);
+    let input = MAIN.replace("x double {} double", "(x double {}) double");
+    db.set_input(main.clone(), input.clone());
+    let (checked, _) = db.check(&main);
+    let broken = function(checked, "broken");
+    let [LowerError::Unify {
+        ty1_span, ty2_span, ..
+    }] = &broken.errors[..]
+    else {
+        panic!("{:?}", broken.errors);
+    };
+    let spans = [ty1_span, ty2_span].map(|span| &input[span.offset()..][..span.len()]);
+    assert!(spans.contains(&"true"), "{spans:?}");
+    let main_function = function(checked, "main");
+    let body = main_function.expr.unwrap().span;
+    let moved = Span {
+        start: (i64::from(body.start) + main_function.moved) as u32,
+        end: (i64::from(body.end) + main_function.moved) as u32,
+    };
+    assert_eq!(text(&input, moved), "fn main || 1 quad {}");
+    // they're lowered again, since their spans have to be where they are now
+    assert_eq!(
+        log(&db),
+        [
+            "check quad",
+            "lower main.curse broken",
+            "lower main.curse main",
+            "lower main.curse quad",
+            "parse main.curse"
+        ]
+    );
+}
+
+#[test]
+fn changed_types_are_checked_again_as_far_as_they_keep_changing() {
+    let _interner = lock();
+    let arenas = Arenas::default();
+    let (mut db, main) = checked_database(&arenas);
+    db.set_input(Path::new(ROOT).join("lib.curse"), LIB.replace("x + x", "x"));
+    let (checked, ctx) = db.check(&main);
+    let quad = function(checked, "quad").template.display(ctx).to_string();
+    assert_eq!(quad, "(A {} -> A)");
+    assert_eq!(
+        log(&db),
+        [
+            "check double",
+            "check main",
+            "check quad",
+            "lower lib.curse double",
+            "parse lib.curse"
+        ]
+    );
+}
+
+#[test]
+fn changing_the_types_in_the_program_works_out_everything() {
+    let _interner = lock();
+    let arenas = Arenas::default();
+    let (mut db, main) = checked_database(&arenas);
+    db.set_input(
+        Path::new(ROOT).join("lib.curse"),
+        format!("{LIB}\nstruct Feet I32\n"),
+    );
+    db.check(&main);
+    assert_eq!(log(&db), [&EVERYTHING[..], &["parse lib.curse"]].concat());
+}
+
+#[test]
+fn new_databases_dont_parse_the_same_inputs_again() {
+    let _interner = lock();
+    let arenas = Arenas::default();
+    let (mut db, main) = checked_database(&arenas);
+    db.set_input(
+        Path::new(ROOT).join("lib.curse"),
+        format!("{LIB}\nstruct Feet I32\n"),
+    );
+    db.check(&main);
+    assert!(db.garbage() > 0);
+
+    // and start out with nothing that's out of date
+    let arenas = Arenas::default();
+    let mut db = Database::with_inputs(&arenas, db.into_inputs());
+    db.check(&main);
+    assert_eq!(log(&db), EVERYTHING);
+    assert_eq!(db.garbage(), 0);
+}
@@ -0,0 +1,859 @@
+//! The Curse formatter, which lays a program out the one way it's meant to look, keeping its
+//! comments.
+//!
+//! Everything goes on one line when it fits, and otherwise it's broken up the same way every time:
+//! - piecewise closures and `choice` variants always get a line for each arm or variant, ending
+//!   with a comma
+//! - records get a line for each field, ending with a comma
+//! - long application chains get a line for each function applied, so `a f b g c` becomes `a`,
+//!   then `f b` and `g c` under it, though symbols like `+` stay on the line before
+//! - a closure applied at the end of a chain stays on the line, with its body continuing under the
+//!   chain, so that `4 + 5 in |x|` reads like a `let`
+
+#![forbid(unsafe_code)]
+
+use curse_ast::ast::{
+    bikeshed, Appl, Arm, Attribute, ChoiceDef, Closure, Constructor, Expr, Field, FunctionDef,
+    GenericArgs, GenericParams, ImplDef, Lit, MethodSignature, Param, Paren, Pat, Path, Record,
+    Region, RegionKind, StructDef, Symbol, TraitDef, Type, VariantDef, Variants,
+};
+use curse_ast::cst::{Cst, Tokens, Trivia, TriviaKind};
+use curse_span::{HasSpan, Span};
+use std::mem;
+
+/// How wide lines can get before they're broken up.
+pub const WIDTH: usize = 80;
+
+const INDENT: usize = 4;
+
+/// Formats a program that parsed without syntax errors. Anything that didn't parse is kept the
+/// way it was written, though that might lose the comments inside it.
+pub fn format(cst: &Cst<'_>) -> String {
+    let program = &cst.program;
+    let mut items: Vec<Item<'_>> = program
+        .function_defs
+        .iter()
+        .map(Item::Function)
+        .chain(program.struct_defs.iter().map(Item::Struct))
+        .chain(program.choice_defs.iter().map(Item::Choice))
+        .chain(program.impl_defs.iter().map(Item::Impl))
+        .chain(program.trait_defs.iter().map(Item::Trait))
+        .chain(program.dynamic_imports.iter().map(Item::Import))
+        .collect();
+    items.sort_by_key(Item::start);
+
+    let mut formatter = Formatter {
+        tokens: &cst.tokens,
+        out: String::new(),
+        indent: 0,
+        flat: false,
+        line_ended: false,
+    };
+    for (index, item) in items.iter().enumerate() {
+        match (index.checked_sub(1).map(|before| items[before]), item) {
+            (None, _) => {}
+            // imports are kept together
+            (Some(Item::Import(_)), Item::Import(_)) => formatter.newline(),
+            (Some(_), _) => formatter.blank_line(),
+        }
+        item.format(&mut formatter);
+    }
+    formatter.comments(cst.tokens.dangling());
+
+    let mut out = formatter.out.trim_end().to_string();
+    if !out.is_empty() {
+        out.push('\n');
+    }
+    out
+}
+
+#[derive(Copy, Clone)]
+enum Item<'ast> {
+    Function(&'ast FunctionDef),
+    Struct(&'ast StructDef),
+    Choice(&'ast ChoiceDef),
+    Impl(&'ast ImplDef),
+    Trait(&'ast TraitDef),
+    Import(&'ast bikeshed::DynamicImport),
+}
+
+impl Item<'_> {
+    fn start(&self) -> u32 {
+        match self {
+            Item::Function(def) => def.start(),
+            Item::Struct(def) => def.start(),
+            Item::Choice(def) => def.start(),
+            Item::Impl(def) => def.start(),
+            Item::Trait(def) => def.start(),
+            Item::Import(import) => import.dynamic_import.start(),
+        }
+    }
+
+    fn format(&self, f: &mut Formatter<'_, '_>) {
+        match self {
+            Item::Function(def) => def.format(f),
+            Item::Struct(def) => def.format(f),
+            Item::Choice(def) => def.format(f),
+            Item::Impl(def) => def.format(f),
+            Item::Trait(def) => def.format(f),
+            Item::Import(import) => {
+                f.token(import.dynamic_import);
+                f.space();
+                f.token(import.file_string);
+            }
+        }
+    }
+}
+
+/// What has to fit for the flat way of writing something to be used.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Fit {
+    /// It has to be on one line.
+    OneLine,
+    /// Every line it's on has to fit, since something inside it, like a piecewise closure, has
+    /// lines of its own.
+    EveryLine,
+}
+
+/// How a chain of applications is laid out.
+#[derive(Copy, Clone, PartialEq, Eq)]
+enum Layout {
+    /// On one line.
+    Glued,
+    /// On one line, except for the right-hand side, like `a f {` with the fields of a record on
+    /// the lines after it. Only used for a single application.
+    Hugged,
+    /// With a line for each function.
+    Broken,
+}
+
+/// Whether `expr` can be broken up on its own at the end of a line, like `{` or `(`.
+fn hugs(expr: &Expr) -> bool {
+    match expr {
+        Expr::Record(_) | Expr::Paren(_) => true,
+        Expr::Closure(closure) => matches!(**closure, Closure::Piecewise(..)),
+        Expr::Constructor(constructor) => hugs(&constructor.inner),
+        _ => false,
+    }
+}
+
+struct Formatter<'a, 'input> {
+    tokens: &'a Tokens<'input>,
+    out: String,
+    indent: usize,
+    /// Set while trying to fit something on one line, so that everything inside it is flat too.
+    flat: bool,
+    /// Set after a comment, so that whatever comes next goes on a new line.
+    line_ended: bool,
+}
+
+impl Formatter<'_, '_> {
+    /// Writes a token the way it was written, along with the comments around it.
+    fn token(&mut self, token: impl HasSpan) {
+        let span = token.span();
+        self.comments(self.tokens.leading(span));
+        self.text(self.tokens.text(span));
+        self.trailing(span);
+    }
+
+    /// Leaves out a token that isn't needed, like a trailing comma, but keeps its comments.
+    fn omit(&mut self, token: impl HasSpan) {
+        let span = token.span();
+        self.comments(self.tokens.leading(span));
+        self.trailing(span);
+    }
+
+    /// Writes something that didn't parse the way it was written.
+    fn verbatim(&mut self, span: Span) {
+        self.text(self.tokens.text(span));
+    }
+
+    fn text(&mut self, text: &str) {
+        if self.line_ended {
+            self.newline();
+        }
+        self.out.push_str(text);
+    }
+
+    /// Writes the comments before a token, each on its own line, keeping blank lines around them.
+    fn comments(&mut self, trivia: &[Trivia<'_>]) {
+        let mut blank = false;
+        let mut any = false;
+        for trivia in trivia {
+            match trivia.kind {
+                TriviaKind::Whitespace => {
+                    blank = trivia.literal.literal.matches('\n').count() > 1;
+                }
+                TriviaKind::Comment | TriviaKind::Unknown => {
+                    if blank {
+                        self.blank_line();
+                    } else if !self.at_line_start() {
+                        self.newline();
+                    }
+                    self.out.push_str(trivia.literal.literal);
+                    self.newline();
+                    any = true;
+                    blank = false;
+                }
+            }
+        }
+        if any && blank {
+            self.blank_line();
+        }
+    }
+
+    /// Writes the comments after a token on the same line.
+    fn trailing(&mut self, span: Span) {
+        for trivia in self.tokens.trailing(span) {
+            if trivia.kind == TriviaKind::Comment {
+                self.out.push(' ');
+                self.out.push_str(trivia.literal.literal);
+                self.line_ended = true;
+            }
+        }
+    }
+
+    fn space(&mut self) {
+        if self.line_ended {
+            self.newline();
+        } else {
+            self.out.push(' ');
+        }
+    }
+
+    fn newline(&mut self) {
+        self.out.truncate(self.out.trim_end_matches(' ').len());
+        self.out.push('\n');
+        self.out.extend(std::iter::repeat_n(' ', self.indent));
+        self.line_ended = false;
+    }
+
+    fn blank_line(&mut self) {
+        self.out.truncate(self.out.trim_end_matches(' ').len());
+        if self.out.is_empty() {
+            return;
+        }
+        while !self.out.ends_with("\n\n") {
+            self.out.push('\n');
+        }
+        self.out.extend(std::iter::repeat_n(' ', self.indent));
+        self.line_ended = false;
+    }
+
+    fn at_line_start(&self) -> bool {
+        let out = self.out.trim_end_matches(' ');
+        out.is_empty() || out.ends_with('\n')
+    }
+
+    fn indented(&mut self, f: impl FnOnce(&mut Self)) {
+        self.indent += INDENT;
+        f(self);
+        self.indent -= INDENT;
+    }
+
+    /// Writes lines that are broken up no matter what's around them, like the arms of a piecewise
+    /// closure, so that what's in them can be flat or not on its own.
+    fn unflattened(&mut self, f: impl FnOnce(&mut Self)) {
+        let flat = mem::replace(&mut self.flat, false);
+        f(self);
+        self.flat = flat;
+    }
+
+    /// Writes `flat` if it fits, and otherwise `broken`.
+    fn either(&mut self, fit: Fit, flat: impl FnOnce(&mut Self), broken: impl FnOnce(&mut Self)) {
+        if self.flat {
+            return flat(self);
+        }
+        self.attempt(
+            fit,
+            |f| {
+                f.flat = true;
+                flat(f);
+                f.flat = false;
+            },
+            broken,
+        );
+    }
+
+    /// Writes `first` if it fits, and otherwise `second`.
+    fn attempt(&mut self, fit: Fit, first: impl FnOnce(&mut Self), second: impl FnOnce(&mut Self)) {
+        let (len, line_ended) = (self.out.len(), self.line_ended);
+        first(self);
+        if self.fits(len, fit) {
+            return;
+        }
+
+        self.out.truncate(len);
+        self.line_ended = line_ended;
+        second(self);
+    }
+
+    /// Whether everything written since `from` fits.
+    fn fits(&self, from: usize, fit: Fit) -> bool {
+        let line_start = self.out[..from].rfind('\n').map_or(0, |index| index + 1);
+        !(fit == Fit::OneLine && self.out[from..].contains('\n'))
+            && self.out[line_start..]
+                .lines()
+                .all(|line| line.chars().count() <= WIDTH)
+    }
+
+    /// Writes an expression at the start of its own line, where a long chain can be broken up.
+    /// When there's a `;` in the chain, it gets a line for each `;` first, and each of those lines
+    /// is only broken up further if it's still too long.
+    fn block(&mut self, expr: &Expr) {
+        let mut links = vec![];
+        let mut first = expr;
+        while let Expr::Appl(appl) = first {
+            links.push(&**appl);
+            first = &appl.lhs;
+        }
+        links.reverse();
+
+        let is_semi = |appl: &&Appl| matches!(appl.fun, Expr::Symbol(Symbol::Semi(_)));
+        if !links.iter().any(is_semi) {
+            return self.sequenced(first, &links);
+        }
+
+        self.either(
+            Fit::EveryLine,
+            |f| f.links(first, &links, Layout::Glued),
+            |f| {
+                let mut first = first;
+                let mut start = 0;
+                for (index, appl) in links.iter().enumerate() {
+                    if is_semi(appl) {
+                        f.sequenced(first, &links[start..index]);
+                        f.newline();
+                        appl.fun.format(f);
+                        f.space();
+                        first = &appl.rhs;
+                        start = index + 1;
+                    }
+                }
+                f.sequenced(first, &links[start..]);
+            },
+        );
+    }
+
+    /// Writes a chain without any `;`s in it, on one line if it fits. Otherwise, if it's a single
+    /// application of something that can be broken up on its own, like `a f {` with a record,
+    /// that's tried before breaking up the chain. Longer chains are always broken up, since
+    /// hugging only their last link would leave the rest crammed on one line.
+    fn sequenced(&mut self, first: &Expr, links: &[&Appl]) {
+        let Some(last) = links.last() else {
+            return first.format(self);
+        };
+
+        self.either(
+            Fit::EveryLine,
+            |f| f.links(first, links, Layout::Glued),
+            |f| {
+                if links.len() == 1 && hugs(&last.rhs) {
+                    f.attempt(
+                        Fit::EveryLine,
+                        |f| f.links(first, links, Layout::Hugged),
+                        |f| f.links(first, links, Layout::Broken),
+                    );
+                } else {
+                    f.links(first, links, Layout::Broken);
+                }
+            },
+        );
+    }
+
+    /// Writes `first` and then each function and right-hand side applied to it. When the chain is
+    /// broken, the first function stays on the line if `first` is short, like `x in f`, and so does
+    /// a closure with parameters at the end, like `x in |y|`, since its body continues the chain.
+    fn links(&mut self, first: &Expr, links: &[&Appl], layout: Layout) {
+        let flat = self.flat;
+        self.flat |= layout == Layout::Hugged;
+        first.format(self);
+        for (index, appl) in links.iter().enumerate() {
+            let last = index + 1 == links.len();
+            let binds = match &appl.rhs {
+                Expr::Closure(closure) if last => match &**closure {
+                    Closure::NonPiecewise(arm) if arm.iter_params().len() > 0 => Some(arm),
+                    _ => None,
+                },
+                _ => None,
+            };
+            let named = matches!(
+                appl.fun,
+                Expr::Lit(Lit::Ident(_)) | Expr::Symbol(Symbol::Semi(_))
+            );
+            let short = index == 0 && matches!(first, Expr::Lit(_));
+            if layout == Layout::Broken && named && binds.is_none() && !short {
+                self.newline();
+            } else {
+                self.space();
+            }
+            appl.fun.format(self);
+            self.space();
+            match binds {
+                Some(arm) => self.arm(arm, layout == Layout::Broken),
+                None if last && layout == Layout::Hugged => {
+                    self.unflattened(|f| appl.rhs.format(f));
+                }
+                None => appl.rhs.format(self),
+            }
+        }
+        self.flat = flat;
+    }
+
+    /// Writes an arm, with its body on the next line if it doesn't fit. The body is indented,
+    /// unless it `continues` a chain.
+    fn arm(&mut self, arm: &Arm, continues: bool) {
+        self.token(arm.open);
+        for (index, (param, comma)) in arm.params.iter().enumerate() {
+            param.format(self);
+            if index + 1 == arm.params.len() && arm.last.is_none() {
+                self.omit(comma);
+            } else {
+                self.token(comma);
+                self.space();
+            }
+        }
+        if let Some(param) = &arm.last {
+            param.format(self);
+        }
+        self.token(arm.close);
+
+        match &arm.body {
+            // these start on the same line, and break up on their own
+            Expr::Paren(_) | Expr::Record(_) => {
+                self.space();
+                arm.body.format(self);
+            }
+            Expr::Closure(closure) => {
+                self.space();
+                match &**closure {
+                    Closure::NonPiecewise(arm) => self.arm(arm, continues),
+                    closure => closure.format(self),
+                }
+            }
+            body => {
+                // like `(a < b) in (`, where the arms are on their own lines anyway
+                let fit = match body {
+                    Expr::Appl(appl) => match &appl.rhs {
+                        Expr::Closure(closure) if matches!(**closure, Closure::Piecewise(..)) => {
+                            Fit::EveryLine
+                        }
+                        _ => Fit::OneLine,
+                    },
+                    _ => Fit::OneLine,
+                };
+                self.either(
+                    fit,
+                    |f| {
+                        f.space();
+                        body.format(f);
+                    },
+                    |f| {
+                        if continues {
+                            f.newline();
+                            f.block(body);
+                        } else {
+                            f.indented(|f| {
+                                f.newline();
+                                f.block(body);
+                            });
+                        }
+                    },
+                );
+            }
+        }
+    }
+}
+
+trait Format {
+    fn format(&self, f: &mut Formatter<'_, '_>);
+}
+
+impl Format for FunctionDef {
+    fn format(&self, f: &mut Formatter<'_, '_>) {
+        for attribute in &self.attributes {
+            attribute.format(f);
+            f.newline();
+        }
+        f.token(self.fn_);
+        f.space();
+        f.token(self.ident);
+        f.space();
+        self.function.format(f);
+    }
+}
+
+impl Format for Attribute {
+    fn format(&self, f: &mut Formatter<'_, '_>) {
+        f.token(self.pound);
+        f.token(self.lbracket);
+        f.token(self.ident);
+        f.token(self.rbracket);
+    }
+}
+
+impl Format for StructDef {
+    fn format(&self, f: &mut Formatter<'_, '_>) {
+        f.token(self.struct_);
+        f.space();
+        f.token(self.ident);
+        if let Some(generic_params) = &self.generic_params {
+            f.space();
+            generic_params.format(f);
+        }
+        f.space();
+        self.ty.format(f);
+    }
+}
+
+impl Format for ChoiceDef {
+    fn format(&self, f: &mut Formatter<'_, '_>) {
+        f.token(self.choice);
+        f.space();
+        f.token(self.ident);
+        if let Some(generic_params) = &self.generic_params {
+            f.space();
+            generic_params.format(f);
+        }
+        f.space();
+        self.variants.format(f);
+    }
+}
+
+impl Format for Variants {
+    fn format(&self, f: &mut Formatter<'_, '_>) {
+        f.token(self.lbrace);
+        if !self.variants.is_empty() || self.last.is_some() {
+            f.unflattened(|f| {
+                f.indented(|f| {
+                    for (variant, comma) in &self.variants {
+                        f.newline();
+                        variant.format(f);
+                        f.token(comma);
+                    }
+                    if let Some(variant) = &self.last {
+                        f.newline();
+                        variant.format(f);
+                        f.text(",");
+                    }
+                });
+                f.newline();
+            });
+        }
+        f.token(self.rbrace);
+    }
+}
+
+impl Format for VariantDef {
+    fn format(&self, f: &mut Formatter<'_, '_>) {
+        f.token(self.ident);
+        f.space();
+        self.ty.format(f);
+    }
+}
+
+impl Format for ImplDef {
+    fn format(&self, f: &mut Formatter<'_, '_>) {
+        f.token(self.impl_);
+        f.space();
+        f.token(self.trait_);
+        f.space();
+        f.token(self.ty);
+        f.space();
+        self.function.format(f);
+    }
+}
+
+impl Format for TraitDef {
+    fn format(&self, f: &mut Formatter<'_, '_>) {
+        f.token(self.trait_);
+        f.space();
+        f.token(self.ident);
+        f.space();
+        f.token(self.method);
+        f.space();
+        self.signature.format(f);
+    }
+}
+
+impl Format for MethodSignature {
+    fn format(&self, f: &mut Formatter<'_, '_>) {
+        f.token(self.open);
+        self.lhs.format(f);
+        f.token(self.comma);
+        f.space();
+        self.rhs.format(f);
+        f.token(self.close);
+        f.space();
+        self.output.format(f);
+    }
+}
+
+impl Format for GenericParams {
+    fn format(&self, f: &mut Formatter<'_, '_>) {
+        f.token(self.open);
+        for (param, star) in &self.params {
+            f.token(param);
+            f.space();
+            f.token(star);
+            f.space();
+        }
+        f.token(self.last);
+        f.token(self.close);
+    }
+}
+
+impl Format for Closure {
+    fn format(&self, f: &mut Formatter<'_, '_>) {
+        match self {
+            Closure::NonPiecewise(arm) => f.arm(arm, false),
+            Closure::Piecewise(lparen, arms, last, rparen) => {
+                f.token(lparen);
+                f.unflattened(|f| {
+                    f.indented(|f| {
+                        for (arm, comma) in arms {
+                            f.newline();
+                            f.arm(arm, false);
+                            f.token(comma);
+                        }
+                        if let Some(arm) = last {
+                            f.newline();
+                            f.arm(arm, false);
+                            f.text(",");
+                        }
+                    });
+                    f.newline();
+                });
+                f.token(rparen);
+            }
+            Closure::Empty(lparen, rparen) => {
+                f.token(lparen);
+                f.token(rparen);
+            }
+            Closure::Error(span) => f.verbatim(*span),
+        }
+    }
+}
+
+impl Format for Param {
+    fn format(&self, f: &mut Formatter<'_, '_>) {
+        self.pat.format(f);
+        if let Some((colon, ty)) = &self.ascription {
+            f.token(colon);
+            f.space();
+            ty.format(f);
+        }
+    }
+}
+
+impl Format for Expr {
+    fn format(&self, f: &mut Formatter<'_, '_>) {
+        match self {
+            Expr::Paren(paren) => paren.format(f),
+            Expr::Symbol(symbol) => f.token(symbol),
+            Expr::Lit(lit) => f.token(lit),
+            Expr::Record(record) => record.format(f),
+            Expr::Constructor(constructor) => constructor.format(f),
+            Expr::Closure(closure) => closure.format(f),
+            Expr::Appl(_) => {
+                let mut links = vec![];
+                let mut first = self;
+                while let Expr::Appl(appl) = first {
+                    links.push(&**appl);
+                    first = &appl.lhs;
+                }
+                links.reverse();
+                f.links(first, &links, Layout::Glued);
+            }
+            Expr::Region(region) => region.format(f),
+            Expr::Error(span) => f.verbatim(*span),
+        }
+    }
+}
+
+impl Format for Paren {
+    fn format(&self, f: &mut Formatter<'_, '_>) {
+        f.token(self.lparen);
+        f.either(
+            Fit::EveryLine,
+            |f| self.expr.format(f),
+            |f| {
+                match &self.expr {
+                    // like `(|x|`, with the body under it
+                    Expr::Closure(closure) if matches!(**closure, Closure::NonPiecewise(_)) => {
+                        closure.format(f);
+                    }
+                    expr => f.indented(|f| {
+                        f.newline();
+                        f.block(expr);
+                    }),
+                }
+                f.newline();
+            },
+        );
+        f.token(self.rparen);
+    }
+}
+
+impl Format for Region {
+    fn format(&self, f: &mut Formatter<'_, '_>) {
+        match self.kind {
+            RegionKind::Ref(ref_) => f.token(ref_),
+            RegionKind::Mut(mut_) => f.token(mut_),
+            RegionKind::RefMut(ref_, mut_) => {
+                f.token(ref_);
+                f.space();
+                f.token(mut_);
+            }
+        }
+        f.space();
+        self.pat.format(f);
+        f.space();
+        f.token(self.lbrace);
+        f.either(
+            Fit::OneLine,
+            |f| {
+                f.space();
+                self.body.format(f);
+                f.space();
+            },
+            |f| {
+                f.indented(|f| {
+                    f.newline();
+                    f.block(&self.body);
+                });
+                f.newline();
+            },
+        );
+        f.token(self.rbrace);
+    }
+}
+
+impl<T: Format> Format for Record<T> {
+    fn format(&self, f: &mut Formatter<'_, '_>) {
+        f.token(self.lbrace);
+        if self.fields.is_empty() && self.trailing.is_none() && self.rest.is_none() {
+            return f.token(self.rbrace);
+        }
+
+        f.either(
+            Fit::OneLine,
+            |f| {
+                f.space();
+                for (index, (field, comma)) in self.fields.iter().enumerate() {
+                    field.format(f);
+                    if index + 1 == self.fields.len()
+                        && self.trailing.is_none()
+                        && self.rest.is_none()
+                    {
+                        f.omit(comma);
+                    } else {
+                        f.token(comma);
+                        f.space();
+                    }
+                }
+                if let Some(field) = &self.trailing {
+                    field.format(f);
+                }
+                if let Some(rest) = self.rest {
+                    f.token(rest);
+                }
+                f.space();
+            },
+            |f| {
+                f.indented(|f| {
+                    for (field, comma) in &self.fields {
+                        f.newline();
+                        field.format(f);
+                        f.token(comma);
+                    }
+                    if let Some(field) = &self.trailing {
+                        f.newline();
+                        field.format(f);
+                        f.text(",");
+                    }
+                    if let Some(rest) = self.rest {
+                        f.newline();
+                        f.token(rest);
+                    }
+                });
+                f.newline();
+            },
+        );
+        f.token(self.rbrace);
+    }
+}
+
+impl<T: Format> Format for Field<T> {
+    fn format(&self, f: &mut Formatter<'_, '_>) {
+        f.token(self.ident);
+        if let Some((colon, value)) = &self.value {
+            f.token(colon);
+            f.space();
+            value.format(f);
+        }
+    }
+}
+
+impl<T: Format> Format for Constructor<T> {
+    fn format(&self, f: &mut Formatter<'_, '_>) {
+        self.path.format(f);
+        f.space();
+        self.inner.format(f);
+    }
+}
+
+impl Format for Path {
+    fn format(&self, f: &mut Formatter<'_, '_>) {
+        for (part, colon_colon) in &self.parts {
+            f.token(part);
+            f.token(colon_colon);
+        }
+        f.token(self.ident);
+    }
+}
+
+impl Format for Pat {
+    fn format(&self, f: &mut Formatter<'_, '_>) {
+        match self {
+            Pat::Lit(lit) => f.token(lit),
+            Pat::Record(record) => record.format(f),
+            Pat::Constructor(constructor) => constructor.format(f),
+        }
+    }
+}
+
+impl Format for Type {
+    fn format(&self, f: &mut Formatter<'_, '_>) {
+        match self {
+            Type::Named(named) => {
+                named.path.format(f);
+                if let Some(generic_args) = &named.generic_args {
+                    f.space();
+                    generic_args.format(f);
+                }
+            }
+            Type::Record(record) => record.format(f),
+            Type::Error(span) => f.verbatim(*span),
+        }
+    }
+}
+
+impl Format for GenericArgs {
+    fn format(&self, f: &mut Formatter<'_, '_>) {
+        match self {
+            GenericArgs::Single(ty) => ty.format(f),
+            GenericArgs::CartesianProduct(lparen, types, last, rparen) => {
+                f.token(lparen);
+                for (ty, star) in types {
+                    ty.format(f);
+                    f.space();
+                    f.token(star);
+                    f.space();
+                }
+                last.format(f);
+                f.token(rparen);
+            }
+        }
+    }
+}
@@ -0,0 +1,174 @@
+//! Checks the layout the formatter picks, and that formatting keeps every comment and is stable,
+//! so formatting something that's already formatted changes nothing.
+
+use curse_ast::cst::{Cst, TriviaKind};
+use curse_interner::StringInterner;
+use std::path::Path;
+
+/// Parses `input`, returning `None` if it has syntax errors.
+fn parse(input: &str) -> Option<Cst<'_>> {
+    let mut interner = StringInterner::new();
+    let mut parser = curse_parse::Parser::new(&mut interner);
+    let cst = parser.parse_cst(input);
+    parser.errors.is_empty().then_some(cst)
+}
+
+fn fmt(input: &str) -> String {
+    let cst = parse(input).unwrap_or_else(|| panic!("{input}"));
+    curse_fmt::format(&cst)
+}
+
+fn comments(cst: &Cst<'_>) -> Vec<String> {
+    let mut comments = vec![];
+    let tokens = &cst.tokens;
+    let mut gaps = vec![tokens.dangling()];
+    for &span in tokens.spans() {
+        gaps.push(tokens.leading(span));
+        gaps.push(tokens.trailing(span));
+    }
+    for gap in gaps {
+        for trivia in gap {
+            if trivia.kind == TriviaKind::Comment {
+                comments.push(trivia.literal.literal.to_string());
+            }
+        }
+    }
+    comments.sort();
+    comments
+}
+
+fn curse_files(dir: &Path, files: &mut Vec<String>) {
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            curse_files(&path, files);
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "curse")
+        {
+            files.push(std::fs::read_to_string(path).unwrap());
+        }
+    }
+}
+
+#[test]
+fn format() {
+    // piecewise closures and variants get a line each, while short records stay on one
+    let input = "
+// the sign of a number
+fn sign (|0| 0,|n| (n < 0) then_else { a: 0 - 1, b: 1 })
+struct Point {x: I32, y: I32}
+choice Shape { Circle { radius: I32 }, Square I32, }
+";
+    let expected = "\
+// the sign of a number
+fn sign (
+    |0| 0,
+    |n| (n < 0) then_else { a: 0 - 1, b: 1 },
+)
+
+struct Point { x: I32, y: I32 }
+
+choice Shape {
+    Circle { radius: I32 },
+    Square I32,
+}
+";
+    assert_eq!(fmt(input), expected);
+
+    // long chains get a line for each function, and so do long records
+    let input = "
+fn long || 1 range 100 filter (|x| (x % 3 = 0) or (x % 5 = 0)) map (|x| x * 2) in sum // total
+fn point || { first_coordinate: 1000000, second_coordinate: 2000000, third_coordinate: 3, }
+";
+    let expected = "\
+fn long ||
+    1 range 100
+    filter (|x| (x % 3 = 0) or (x % 5 = 0))
+    map (|x| x * 2)
+    in sum // total
+
+fn point || {
+    first_coordinate: 1000000,
+    second_coordinate: 2000000,
+    third_coordinate: 3,
+}
+";
+    assert_eq!(fmt(input), expected);
+
+    // short chains stay on one line, dropping the trailing comma, and a closure at the end
+    // reads like a `let`
+    let input = "
+fn point |p| { x: 1, y: 2, }   // trailing
+
+
+fn let_in || 4 + 5 in |x| x * 2
+fn seq || (1 assert_eq 1) ; (2 assert_eq 2)
+
+// dangling at the end
+";
+    let expected = "\
+fn point |p| { x: 1, y: 2 } // trailing
+
+fn let_in || 4 + 5 in |x| x * 2
+
+fn seq || (1 assert_eq 1) ; (2 assert_eq 2)
+
+// dangling at the end
+";
+    assert_eq!(fmt(input), expected);
+
+    // a long `;` sequence gets a line for each step
+    let input = "
+fn steps || (1 + 2 + 3 + 4 assert_eq 10) ; (5 + 6 + 7 + 8 assert_eq 26) ; (9 assert_eq 9)
+";
+    let expected = "\
+fn steps ||
+    (1 + 2 + 3 + 4 assert_eq 10)
+    ; (5 + 6 + 7 + 8 assert_eq 26)
+    ; (9 assert_eq 9)
+";
+    assert_eq!(fmt(input), expected);
+
+    // a chain ending in a parenthesized closure still gets a line for each function applied,
+    // the same as any other long chain
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../..");
+    let input = std::fs::read_to_string(
+        root.join("compiler/curse_interpreter/project_euler/problem3.curse"),
+    )
+    .unwrap();
+    let expected = "\
+dynamic_import \"std.curse\"
+
+fn largest_prime_factor |n|
+    { i: 2, n } rec |loop| (
+        |{ i, n: 1 }| i,
+        |{ i, n }|
+            n % i = 0
+            then_do (|| { i: i, n: n / i } in loop)
+            else_do (|| { i: i + 1, n } in loop),
+    )
+
+// actual integer too big unfortunately
+// largest integer that isn't too big and doesn't cause a stack overflow
+// gets the right answer in about 1ms though
+fn main || 60085 in largest_prime_factor
+";
+    assert_eq!(fmt(&input), expected);
+
+    // everything in the repo keeps its comments, still parses, and is formatted once formatted
+    let mut files = vec![];
+    curse_files(&root.join("examples"), &mut files);
+    curse_files(&root.join("compiler/curse_interpreter"), &mut files);
+    assert!(!files.is_empty());
+    for input in &files {
+        // some of the old examples don't parse anymore
+        let Some(cst) = parse(input) else {
+            continue;
+        };
+        let formatted = curse_fmt::format(&cst);
+        let reparsed = parse(&formatted).unwrap_or_else(|| panic!("{formatted}"));
+        assert_eq!(comments(&reparsed), comments(&cst), "{formatted}");
+        assert_eq!(curse_fmt::format(&reparsed), formatted);
+    }
+}
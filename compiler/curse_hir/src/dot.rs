@@ -0,0 +1,234 @@
+//! Utilities for writing the HIR in dot format for Graphviz.
+//!
+//! Every expression is a node labelled with what it is and its span, and
+//! closures have a node for each of their arms, labelled with the patterns.
+use crate::hir::{
+    ExprKind, ExprRef, FunctionDef, Lit, Param, Pat, PatKind, Program, RegionKind, Type, TypeKind,
+};
+use curse_span::Span;
+use std::fmt::{self, Write as _};
+
+pub struct Builder {
+    count: u32,
+    out: String,
+}
+
+impl Default for Builder {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Builder {
+    pub fn new() -> Self {
+        let out = String::with_capacity(2048) + "digraph hir {\n    node [shape = box]";
+
+        Builder { count: 0, out }
+    }
+
+    /// Draws every function in `program`, in alphabetical order.
+    pub fn visit_program(&mut self, program: &Program<'_>) {
+        let mut defs: Vec<_> = program.function_defs.values().collect();
+        defs.sort_by_key(|def| def.ident.symbol.string().to_string());
+        for def in defs {
+            self.visit_function(def);
+        }
+    }
+
+    pub fn visit_function(&mut self, def: &FunctionDef<'_>) {
+        let id = self.node(&format!("fn {}", def.ident), def.span);
+        for arm in def.arms {
+            self.visit_arm(arm.params, arm.body, id);
+        }
+    }
+
+    pub fn visit_expr(&mut self, expr: ExprRef<'_>, parent: Option<u32>, name: &str) -> u32 {
+        let id = match expr.kind {
+            ExprKind::Symbol(symbol) => self.node(symbol.as_str(), expr.span),
+            ExprKind::Lit(lit) => self.node(&lit_label(lit), expr.span),
+            ExprKind::Record(map) => {
+                let id = self.node("record", expr.span);
+                for &(ident, value) in map.entries {
+                    match value {
+                        Some(value) => {
+                            self.visit_expr(value, Some(id), &ident.to_string());
+                        }
+                        None => {
+                            let field = self.node(&ident.to_string(), ident.span);
+                            self.edge(id, field, &ident.to_string());
+                        }
+                    }
+                }
+                id
+            }
+            ExprKind::Constructor(constructor) => {
+                let id = self.node(&path_label(constructor.path), expr.span);
+                self.visit_expr(constructor.inner, Some(id), "");
+                id
+            }
+            ExprKind::Closure(arms) => {
+                let id = self.node("closure", expr.span);
+                for arm in arms {
+                    self.visit_arm(arm.params, arm.body, id);
+                }
+                id
+            }
+            ExprKind::Appl(appl) => {
+                let id = self.node("appl", expr.span);
+                self.visit_expr(appl.lhs(), Some(id), "lhs");
+                self.visit_expr(appl.fun(), Some(id), "fun");
+                self.visit_expr(appl.rhs(), Some(id), "rhs");
+                id
+            }
+            ExprKind::Region(region) => {
+                let kind = match region.kind {
+                    RegionKind::Ref => "ref",
+                    RegionKind::Mut => "mut",
+                    RegionKind::RefMut => "ref mut",
+                };
+                let mut label = kind.to_string();
+                for ident in region.shadows {
+                    write!(label, " {ident}").unwrap();
+                }
+                let id = self.node(&label, expr.span);
+                self.visit_expr(region.body, Some(id), "");
+                id
+            }
+            ExprKind::Error => self.node("<error>", expr.span),
+        };
+
+        if let Some(parent) = parent {
+            self.edge(parent, id, name);
+        }
+        id
+    }
+
+    fn visit_arm(&mut self, params: &[Param<'_>], body: ExprRef<'_>, parent: u32) {
+        let mut label = String::from("|");
+        for (i, param) in params.iter().enumerate() {
+            if i > 0 {
+                label += ", ";
+            }
+            write!(label, "{}", PatLabel(param.pat)).unwrap();
+            if let Some(ty) = param.ascription {
+                write!(label, ": {}", TypeLabel(ty)).unwrap();
+            }
+        }
+        label += "|";
+
+        let span = Span {
+            start: params
+                .first()
+                .map_or(body.span.start, |param| param.pat.span.start),
+            end: body.span.end,
+        };
+        let id = self.node(&label, span);
+        self.edge(parent, id, "");
+        self.visit_expr(body, Some(id), "");
+    }
+
+    fn node(&mut self, label: &str, span: Span) -> u32 {
+        self.count += 1;
+        let id = self.count;
+        let label = format!("{label}\n{span:?}");
+        write!(self.out, "\n    p{id}[label = {label:?}]").unwrap();
+        id
+    }
+
+    fn edge(&mut self, from: u32, to: u32, label: &str) {
+        write!(self.out, "\n    p{from} -> p{to}[label = {label:?}]").unwrap();
+    }
+
+    pub fn finish(self) -> String {
+        self.out + "\n}\n"
+    }
+}
+
+fn lit_label(lit: Lit) -> String {
+    match lit {
+        Lit::Integer(n) => n.to_string(),
+        Lit::Ident(ident) => ident.to_string(),
+        Lit::Bool(b) => b.to_string(),
+    }
+}
+
+fn path_label(path: &[curse_interner::Ident]) -> String {
+    path.iter()
+        .map(|ident| ident.to_string())
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+/// Writes a pattern the way it would be written in the source.
+struct PatLabel<'a, 'hir>(&'a Pat<'hir>);
+
+impl fmt::Display for PatLabel<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0.kind {
+            PatKind::Lit(lit) => f.write_str(&lit_label(*lit)),
+            PatKind::Record(map, false) if map.entries.is_empty() => f.write_str("{}"),
+            PatKind::Record(map, open) => {
+                f.write_str("{ ")?;
+                for (i, (ident, pat)) in map.entries.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    match pat {
+                        Some(pat) => write!(f, "{ident}: {}", PatLabel(pat))?,
+                        None => write!(f, "{ident}")?,
+                    }
+                }
+                match (open, map.entries.is_empty()) {
+                    (false, _) => f.write_str(" }"),
+                    (true, true) => f.write_str(".. }"),
+                    (true, false) => f.write_str(", .. }"),
+                }
+            }
+            PatKind::Constructor(path, inner) => {
+                write!(f, "{} {}", path_label(path), PatLabel(inner))
+            }
+            PatKind::Error => f.write_str("<error>"),
+        }
+    }
+}
+
+/// Writes a type the way it would be written in the source.
+struct TypeLabel<'a, 'hir>(&'a Type<'hir>);
+
+impl fmt::Display for TypeLabel<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.0.kind {
+            TypeKind::Named { path, generic_args } => {
+                f.write_str(&path_label(path))?;
+                match generic_args {
+                    [] => Ok(()),
+                    [arg] => write!(f, " {}", TypeLabel(arg)),
+                    args => {
+                        f.write_str(" (")?;
+                        for (i, arg) in args.iter().enumerate() {
+                            if i > 0 {
+                                f.write_str(" * ")?;
+                            }
+                            write!(f, "{}", TypeLabel(arg))?;
+                        }
+                        f.write_str(")")
+                    }
+                }
+            }
+            TypeKind::Generic { name, .. } => write!(f, "{name}"),
+            TypeKind::Record(map) if map.entries.is_empty() => f.write_str("{}"),
+            TypeKind::Record(map) => {
+                f.write_str("{ ")?;
+                for (i, (ident, ty)) in map.entries.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{ident}: {}", TypeLabel(ty))?;
+                }
+                f.write_str(" }")
+            }
+            TypeKind::Primitive(primitive) => write!(f, "{primitive:?}"),
+            TypeKind::Error => f.write_str("<error>"),
+        }
+    }
+}
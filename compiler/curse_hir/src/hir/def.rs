@@ -1,9 +1,10 @@
 use crate::hir::{Arm, Map, TypeRef};
-use curse_interner::Ident;
+use curse_interner::{Ident, InternedString};
 use curse_span::{HasSpan, Span};
 
-#[derive(Debug)]
+#[derive(Copy, Clone, Debug)]
 pub struct FunctionDef<'hir> {
+    pub attributes: &'hir [Attribute],
     pub ident: Ident,
     pub generic_params: &'hir [Ident],
     pub ty: Option<TypeRef<'hir>>,
@@ -11,7 +12,108 @@ pub struct FunctionDef<'hir> {
     pub span: Span,
 }
 
-#[derive(Debug)]
+/// Something that changes how the compiler treats a definition, e.g. `#[tail_recursive]`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Attribute {
+    pub kind: AttributeKind,
+    pub span: Span,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum AttributeKind {
+    /// Every recursive call in the function has to be a tail call.
+    TailRecursive,
+    /// The function is a test, which `curse test` runs.
+    Test,
+}
+
+/// A trait that a user type can implement, either in order to be used with some of the builtin
+/// symbols, or one that the program declares itself.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Trait {
+    /// `+`
+    Add,
+    /// `-`
+    Sub,
+    /// `*`
+    Mul,
+    /// `/`
+    Div,
+    /// `%`
+    Rem,
+    /// `=`
+    Eq,
+    /// `<`, which `>`, `<=` and `>=` are defined in terms of.
+    Ord,
+    /// A trait from a `trait` definition, by its name.
+    Declared(InternedString),
+}
+
+impl Trait {
+    pub const ALL: [(&'static str, Trait); 7] = [
+        ("Add", Trait::Add),
+        ("Sub", Trait::Sub),
+        ("Mul", Trait::Mul),
+        ("Div", Trait::Div),
+        ("Rem", Trait::Rem),
+        ("Eq", Trait::Eq),
+        ("Ord", Trait::Ord),
+    ];
+
+    /// The builtin trait called `name`.
+    pub fn named(name: &str) -> Option<Trait> {
+        Trait::ALL
+            .into_iter()
+            .find_map(|(trait_name, trait_)| (trait_name == name).then_some(trait_))
+    }
+
+    pub fn name(self) -> String {
+        match self {
+            Trait::Declared(name) => name.to_string(),
+            _ => Trait::ALL
+                .into_iter()
+                .find_map(|(name, trait_)| (trait_ == self).then_some(name))
+                .expect("every builtin trait has a name")
+                .to_string(),
+        }
+    }
+
+    /// Whether the symbols for this trait give back a `Bool` rather than a value of the type that
+    /// implements it.
+    pub fn is_comparison(self) -> bool {
+        matches!(self, Trait::Eq | Trait::Ord)
+    }
+}
+
+/// An implementation of a trait for a type, e.g. `impl Add Point |a, b| ...`.
+///
+/// The implementation is a function that takes a value of the type on each side. It returns a
+/// value of the type for the arithmetic traits, or a `Bool` for `Eq` and `Ord`.
+#[derive(Copy, Clone, Debug)]
+pub struct ImplDef<'hir> {
+    pub trait_: Trait,
+    pub trait_span: Span,
+    pub ty: Ident,
+    pub arms: &'hir [Arm<'hir>],
+    pub span: Span,
+}
+
+/// A trait that the program declares, e.g. `trait Combine combine |Self, Self| Self`.
+///
+/// It has a single method, which is called like any other function. The method calls the `impl`
+/// for the type of its left argument, so `lhs` is always `Self`, which is the only generic
+/// parameter of the types of the method.
+#[derive(Copy, Clone, Debug)]
+pub struct TraitDef<'hir> {
+    pub ident: Ident,
+    pub method: Ident,
+    pub lhs: TypeRef<'hir>,
+    pub rhs: TypeRef<'hir>,
+    pub output: TypeRef<'hir>,
+    pub span: Span,
+}
+
+#[derive(Copy, Clone, Debug)]
 pub struct StructDef<'hir> {
     pub ident: Ident,
     pub generic_params: &'hir [Ident],
@@ -19,7 +121,7 @@ pub struct StructDef<'hir> {
     pub span: Span,
 }
 
-#[derive(Debug)]
+#[derive(Copy, Clone, Debug)]
 pub struct ChoiceDef<'hir> {
     pub ident: Ident,
     pub generic_params: &'hir [Ident],
@@ -37,6 +139,36 @@ impl HasSpan for FunctionDef<'_> {
     }
 }
 
+impl HasSpan for Attribute {
+    fn start(&self) -> u32 {
+        self.span.start
+    }
+
+    fn end(&self) -> u32 {
+        self.span.end
+    }
+}
+
+impl HasSpan for ImplDef<'_> {
+    fn start(&self) -> u32 {
+        self.span.start
+    }
+
+    fn end(&self) -> u32 {
+        self.span.end
+    }
+}
+
+impl HasSpan for TraitDef<'_> {
+    fn start(&self) -> u32 {
+        self.span.start
+    }
+
+    fn end(&self) -> u32 {
+        self.span.end
+    }
+}
+
 impl HasSpan for StructDef<'_> {
     fn start(&self) -> u32 {
         self.span.start
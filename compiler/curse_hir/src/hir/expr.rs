@@ -1,4 +1,4 @@
-use crate::hir::{Constructor, Lit, Map, PatRef, TypeRef};
+use crate::hir::{Constructor, Lit, Map, PatRef, Trait, TypeRef};
 use curse_interner::Ident;
 use curse_span::{HasSpan, Span};
 use std::fmt;
@@ -46,6 +46,40 @@ pub enum Symbol {
     Ge,
 }
 
+impl Symbol {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Symbol::Plus => "+",
+            Symbol::Minus => "-",
+            Symbol::Star => "*",
+            Symbol::Dot => ".",
+            Symbol::DotDot => "..",
+            Symbol::Semi => ";",
+            Symbol::Percent => "%",
+            Symbol::Slash => "/",
+            Symbol::Eq => "=",
+            Symbol::Lt => "<",
+            Symbol::Gt => ">",
+            Symbol::Le => "<=",
+            Symbol::Ge => ">=",
+        }
+    }
+
+    /// The trait that a type has to implement for this symbol to work on it.
+    pub fn trait_(&self) -> Option<Trait> {
+        match self {
+            Symbol::Plus => Some(Trait::Add),
+            Symbol::Minus => Some(Trait::Sub),
+            Symbol::Star => Some(Trait::Mul),
+            Symbol::Slash => Some(Trait::Div),
+            Symbol::Percent => Some(Trait::Rem),
+            Symbol::Eq => Some(Trait::Eq),
+            Symbol::Lt | Symbol::Gt | Symbol::Le | Symbol::Ge => Some(Trait::Ord),
+            Symbol::Dot | Symbol::DotDot | Symbol::Semi => None,
+        }
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq)]
 pub struct Arm<'hir> {
     pub params: &'hir [Param<'hir>],
@@ -4,7 +4,7 @@ mod map;
 mod pat;
 mod ty;
 mod program {
-    use crate::hir::{ChoiceDef, FunctionDef, StructDef};
+    use crate::hir::{ChoiceDef, FunctionDef, ImplDef, StructDef, Trait, TraitDef};
     use curse_interner::{Ident, InternedString};
     use std::collections::{HashMap, HashSet};
 
@@ -13,8 +13,21 @@ mod program {
         pub function_defs: HashMap<InternedString, FunctionDef<'hir>>,
         pub struct_defs: HashMap<InternedString, StructDef<'hir>>,
         pub choice_defs: HashMap<InternedString, ChoiceDef<'hir>>,
+        /// Keyed by the trait and the name of the type it's implemented for.
+        pub impl_defs: HashMap<(Trait, InternedString), ImplDef<'hir>>,
+        /// Keyed by the name of the trait.
+        pub trait_defs: HashMap<InternedString, TraitDef<'hir>>,
         pub dynamic_imports: HashSet<Ident>,
     }
+
+    impl<'hir> Program<'hir> {
+        /// The declared trait whose method is called `name`.
+        pub fn method(&self, name: InternedString) -> Option<&TraitDef<'hir>> {
+            self.trait_defs
+                .values()
+                .find(|def| def.method.symbol == name)
+        }
+    }
 }
 mod shared {
     use curse_interner::Ident;
@@ -26,6 +39,8 @@ mod shared {
         Bool(bool),
     }
 
+    /// A choice variant like `Option::Some 5`, or a struct like `Id 5`, which
+    /// is a path with only the struct's name.
     #[derive(Copy, Clone, Debug, PartialEq, Eq)]
     pub struct Constructor<'hir, T> {
         pub path: Path<'hir>,
@@ -35,7 +50,9 @@ mod shared {
     pub type Path<'hir> = &'hir [Ident];
 }
 
-pub use def::{ChoiceDef, FunctionDef, StructDef};
+pub use def::{
+    Attribute, AttributeKind, ChoiceDef, FunctionDef, ImplDef, StructDef, Trait, TraitDef,
+};
 pub use expr::{Appl, Arm, Expr, ExprKind, ExprRef, Param, Region, RegionKind, Symbol};
 pub use map::Map;
 pub use pat::{Pat, PatKind, PatRef};
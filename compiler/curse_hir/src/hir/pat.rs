@@ -12,7 +12,10 @@ pub struct Pat<'hir> {
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum PatKind<'hir> {
     Lit(Lit),
-    Record(Map<'hir, Option<PatRef<'hir>>>),
+    /// A record pattern, which also matches records with other fields when the `bool` is set,
+    /// i.e. when it ends in `..`.
+    Record(Map<'hir, Option<PatRef<'hir>>>, bool),
+    /// A choice variant like `Option::Some x`, or a struct like `Id n`.
     Constructor(&'hir [Ident], PatRef<'hir>),
     Error,
 }
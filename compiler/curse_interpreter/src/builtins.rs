@@ -1,36 +1,139 @@
 use std::rc::Rc;
 
+use curse_span::Span;
+
 use crate::{
     error::EvalError,
     value::{Value, ValueRef},
 };
 
 macro_rules! binary_operation {
-    ($name:ident = $rettype:ident: $lhs:ident $op:tt $rhs:ident) => {
+    ($name:ident = $rettype:ident: |$lhs:ident, $rhs:ident| $result:expr) => {
         pub fn $name<'hir>(
             lhs: ValueRef<'hir>,
             rhs: ValueRef<'hir>,
         ) -> Result<ValueRef<'hir>, EvalError> {
             match (lhs.as_ref(), rhs.as_ref()) {
-                (Value::Integer($lhs), Value::Integer($rhs)) => Ok(Rc::new(Value::$rettype($lhs $op $rhs))),
+                (Value::Integer($lhs), Value::Integer($rhs)) => {
+                    Ok(Rc::new(Value::$rettype($result)))
+                }
                 _ => Err(EvalError::TypeMismatch),
             }
         }
     };
 }
 
-binary_operation!(add = Integer: n + m);
-binary_operation!(mul = Integer: n * m);
-binary_operation!(sub = Integer: n - m);
-binary_operation!(div = Integer: n / m);
-binary_operation!(modulo = Integer: n % m);
+// Integers wrap around the same way they do in the compiled backends.
+binary_operation!(add = Integer: |n, m| n.wrapping_add(*m));
+binary_operation!(mul = Integer: |n, m| n.wrapping_mul(*m));
+binary_operation!(sub = Integer: |n, m| n.wrapping_sub(*m));
+binary_operation!(div = Integer: |n, m| n.checked_div(*m).ok_or(EvalError::DivideByZero)?);
+binary_operation!(modulo = Integer: |n, m| n.checked_rem(*m).ok_or(EvalError::DivideByZero)?);
+
+/// `=`, which compares values structurally.
+pub fn eq<'hir>(lhs: ValueRef<'hir>, rhs: ValueRef<'hir>) -> Result<ValueRef<'hir>, EvalError> {
+    Ok(Rc::new(Value::Bool(equal(&lhs, &rhs)?)))
+}
 
-binary_operation!(eq = Bool: n == m);
-binary_operation!(lt = Bool: n < m);
-binary_operation!(gt = Bool: n > m);
-binary_operation!(le = Bool: n <= m);
-binary_operation!(ge = Bool: n >= m);
+binary_operation!(lt = Bool: |n, m| n < m);
+binary_operation!(gt = Bool: |n, m| n > m);
+binary_operation!(le = Bool: |n, m| n <= m);
+binary_operation!(ge = Bool: |n, m| n >= m);
 
 pub fn semi<'hir>(_lhs: ValueRef<'hir>, rhs: ValueRef<'hir>) -> Result<ValueRef<'hir>, EvalError> {
     Ok(rhs)
 }
+
+/// Builtins that check something while a program runs, which are called by name rather than
+/// being operators. They're only used when nothing else has the same name, so a program can
+/// shadow them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Assertion {
+    /// `cond assert {}` fails unless `cond` is `true`.
+    Assert,
+    /// `left assert_eq right` fails unless both sides are structurally equal.
+    AssertEq,
+    /// `value panic {}` always fails, showing `value`.
+    Panic,
+}
+
+impl Assertion {
+    pub const ALL: [(&'static str, Assertion); 3] = [
+        ("assert", Assertion::Assert),
+        ("assert_eq", Assertion::AssertEq),
+        ("panic", Assertion::Panic),
+    ];
+
+    pub fn named(name: &str) -> Option<Assertion> {
+        Assertion::ALL
+            .into_iter()
+            .find_map(|(assertion_name, assertion)| (assertion_name == name).then_some(assertion))
+    }
+}
+
+/// Runs an assertion that was applied at `span`, returning `{}` if it holds.
+pub fn check<'hir>(
+    assertion: Assertion,
+    lhs: ValueRef<'hir>,
+    rhs: ValueRef<'hir>,
+    span: Span,
+) -> Result<ValueRef<'hir>, EvalError> {
+    match assertion {
+        Assertion::Assert => match (lhs.as_ref(), rhs.is_null()) {
+            (Value::Bool(true), true) => Ok(rhs),
+            (Value::Bool(false), true) => Err(EvalError::AssertionFailed {
+                span: span.start_len().into(),
+            }),
+            _ => Err(EvalError::TypeMismatch),
+        },
+        Assertion::AssertEq => {
+            if equal(&lhs, &rhs)? {
+                Ok(Rc::new(Value::default()))
+            } else {
+                Err(EvalError::AssertEqFailed {
+                    left: format!("{lhs:?}"),
+                    right: format!("{rhs:?}"),
+                    span: span.start_len().into(),
+                })
+            }
+        }
+        Assertion::Panic if rhs.is_null() => Err(EvalError::Panicked {
+            value: format!("{lhs:?}"),
+            span: span.start_len().into(),
+        }),
+        Assertion::Panic => Err(EvalError::TypeMismatch),
+    }
+}
+
+/// Compares two values field by field. Functions can't be compared.
+fn equal(lhs: &Value<'_>, rhs: &Value<'_>) -> Result<bool, EvalError> {
+    match (lhs, rhs) {
+        (Value::Integer(n), Value::Integer(m)) => Ok(n == m),
+        (Value::Bool(a), Value::Bool(b)) => Ok(a == b),
+        (Value::Record(a), Value::Record(b)) => {
+            if a.entries.len() != b.entries.len() {
+                return Ok(false);
+            }
+            for ((a_name, a), (b_name, b)) in a.entries.iter().zip(&b.entries) {
+                if a_name != b_name || !equal(a, b)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+        (
+            Value::Choice { tag, value },
+            Value::Choice {
+                tag: other_tag,
+                value: other_value,
+            },
+        ) => Ok(tag == other_tag && equal(value, other_value)?),
+        (
+            Value::Struct { value, .. },
+            Value::Struct {
+                value: other_value, ..
+            },
+        ) => equal(value, other_value),
+        _ => Err(EvalError::TypeMismatch),
+    }
+}
@@ -0,0 +1,740 @@
+//! Compiles HIR into bytecode for the [`vm`](crate::vm).
+//!
+//! Every closure, including the top level functions, becomes a [`Proto`]: a flat sequence of
+//! instructions for a stack machine. Variables are resolved ahead of time. Bindings from patterns
+//! live in numbered slots of the call frame, variables from enclosing closures are copied into the
+//! closure as upvalues when it's created, and anything else refers to a top level function.
+//! Regions bind the names they shadow to cells in slots of their own, which closures capture like
+//! anything else, so they share the cell rather than copying what's in it.
+//!
+//! The arms of a function are compiled into pattern tests that jump to the next arm as soon as
+//! anything fails to match. When several arms match on the constructor of their first argument,
+//! the function starts with a jump table that skips straight to the first arm that could match.
+
+use std::collections::HashMap;
+
+use curse_hir::hir::{
+    self, Appl, Arm, ExprKind, ExprRef, Lit, PatKind, PatRef, Region, RegionKind, Symbol, Trait,
+};
+use curse_interner::{Ident, InternedString};
+use curse_span::Span;
+
+use crate::{builtins::Assertion, error::EvalError};
+
+#[derive(Copy, Clone, Debug)]
+pub enum Op<'hir> {
+    Integer(u32),
+    Bool(bool),
+    /// Pushes an operator, for when it's used as a value.
+    Builtin(Symbol),
+    /// Pushes the value in a slot of the current frame, or what's in it if it's a cell.
+    Local(u32),
+    /// Pushes one of the values captured by the current closure, or what's in it if it's a cell.
+    Upvalue(u32),
+    /// Pushes a top level function.
+    Global(u32),
+    /// Fails with [`EvalError::UnboundVariable`].
+    Unbound(Ident),
+    /// Pops a value for each field, the last field on top, and pushes a record of them.
+    Record(&'hir [(Ident, Option<ExprRef<'hir>>)]),
+    /// Pops a record and pushes the value of one of its fields.
+    Field(Ident),
+    /// Pops a value and pushes it wrapped in a choice variant.
+    Choice(hir::Path<'hir>),
+    /// Pops a value and pushes it wrapped in a struct.
+    Struct(Ident),
+    /// Pushes a new closure for a proto, capturing its upvalues from the current frame.
+    Closure(u32),
+    /// Pops two values and applies an operator to them.
+    Binary(Symbol),
+    /// Pops a right argument and a left argument, and checks an assertion that was applied at
+    /// `span` on them.
+    Assert {
+        assertion: Assertion,
+        span: Span,
+    },
+    /// Pops a right argument, a function and a left argument, and calls the function.
+    Call,
+    /// Like [`Op::Call`], but replaces the current frame instead of returning to it.
+    TailCall,
+    /// Pops a right argument and a left argument, and calls a top level function.
+    CallGlobal(u32),
+    /// Like [`Op::CallGlobal`], but replaces the current frame instead of returning to it.
+    TailCallGlobal(u32),
+    /// Pops a value and returns it to the caller.
+    Return,
+    /// Pops a value into a slot.
+    Store(u32),
+    /// Starts a region by putting a cell in `slot` for a name that it shadows, which holds what
+    /// the name was bound to. `ref` and `ref mut` regions share the cell if the name is already
+    /// bound to one, while `mut` regions copy what's in it.
+    OpenRegion {
+        from: Capture,
+        slot: u32,
+        kind: RegionKind,
+    },
+    /// Ends a region by writing what's in the cell in `slot` back to where the name was bound
+    /// before. That's into its cell if it was bound to one, and otherwise into `slot` itself,
+    /// which the name stays bound to.
+    CloseRegion {
+        from: Capture,
+        slot: u32,
+    },
+    /// Pushes the cell that a name is bound to, so that it can be assigned to. Fails with
+    /// [`EvalError::NotAssignable`] unless it's from a `mut` or `ref mut` region.
+    Cell {
+        from: Capture,
+        span: Span,
+    },
+    /// Pops a value and a cell, puts the value in the cell, and pushes `{}`.
+    Assign,
+    /// Fails with [`EvalError::NotAssignable`], for assigning to something that isn't a name.
+    NotAssignable(Span),
+    /// Jumps to where the jump table of the proto says to go for the value in a slot.
+    Switch {
+        slot: u32,
+        table: u32,
+    },
+    /// Fails unless the value in a slot is this integer.
+    TestInteger {
+        slot: u32,
+        value: u32,
+        fail: u32,
+    },
+    /// Fails unless the value in a slot is this bool.
+    TestBool {
+        slot: u32,
+        value: bool,
+        fail: u32,
+    },
+    /// Fails unless the value in a slot is `{}`.
+    TestNull {
+        slot: u32,
+        fail: u32,
+    },
+    /// Pops a value, and fails unless it's this integer.
+    MatchInteger {
+        value: u32,
+        fail: u32,
+    },
+    /// Pops a value, and fails unless it's this bool.
+    MatchBool {
+        value: bool,
+        fail: u32,
+    },
+    /// Pops a value, and fails unless it's a record with at least these fields if the pattern is
+    /// `open`, or with exactly these fields otherwise. Otherwise pushes the values of the fields,
+    /// with the first one on top.
+    MatchRecord {
+        fields: &'hir [(Ident, Option<PatRef<'hir>>)],
+        open: bool,
+        fail: u32,
+    },
+    /// Pops a value, and fails unless it's a choice with this tag. Otherwise pushes what's inside.
+    MatchChoice {
+        tag: hir::Path<'hir>,
+        fail: u32,
+    },
+    /// Pops a value, and fails unless it's a struct with this name. Otherwise pushes what's inside.
+    MatchStruct {
+        name: Ident,
+        fail: u32,
+    },
+    /// Always fails. Failing throws away everything that the arm pushed and jumps to `fail`.
+    Fail(u32),
+    /// Fails with [`EvalError::PatternMatchRefuted`].
+    Refuted,
+    /// Fails with [`EvalError::SyntaxError`], for an expression that couldn't be parsed.
+    SyntaxError(Span),
+    /// Fails with [`EvalError::Unsupported`], for things that the VM doesn't support yet.
+    Unsupported(&'static str, Span),
+}
+
+impl Op<'_> {
+    fn set_fail(&mut self, target: u32) {
+        match self {
+            Op::TestInteger { fail, .. }
+            | Op::TestBool { fail, .. }
+            | Op::TestNull { fail, .. }
+            | Op::MatchInteger { fail, .. }
+            | Op::MatchBool { fail, .. }
+            | Op::MatchRecord { fail, .. }
+            | Op::MatchChoice { fail, .. }
+            | Op::MatchStruct { fail, .. }
+            | Op::Fail(fail) => *fail = target,
+            _ => unreachable!("only pattern tests can fail"),
+        }
+    }
+}
+
+/// Where a variable is from inside of a function, which is also where a closure gets one of its
+/// upvalues from when it's created.
+#[derive(Copy, Clone, Debug)]
+pub enum Capture {
+    Local(u32),
+    Upvalue(u32),
+}
+
+/// Where to go for each constructor, keyed by the last part of its path. Arms still check the
+/// whole path, so constructors of different choices with the same name can share an entry.
+#[derive(Debug, Default)]
+pub struct JumpTable {
+    pub targets: HashMap<InternedString, u32>,
+    pub default: u32,
+}
+
+/// The code for a function.
+#[derive(Debug, Default)]
+pub struct Proto<'hir> {
+    pub code: Vec<Op<'hir>>,
+    /// How many slots a frame needs, including the two for the arguments.
+    pub slots: u32,
+    pub captures: Vec<Capture>,
+    pub tables: Vec<JumpTable>,
+}
+
+#[derive(Debug)]
+pub struct Program<'hir> {
+    pub protos: Vec<Proto<'hir>>,
+    /// The proto of each top level function.
+    pub globals: Vec<u32>,
+    /// Which of the top level functions is `main`.
+    pub main: u32,
+    /// The position of each variant in its choice, keyed by the name of the choice and the
+    /// variant, which orders choices when they're compared.
+    pub variants: HashMap<(InternedString, InternedString), u32>,
+    /// The type that each constructor makes, keyed by the name of the variant or struct.
+    pub constructors: HashMap<InternedString, InternedString>,
+    /// The proto of each `impl`, keyed by the trait and the name of the type.
+    pub impls: HashMap<(Trait, InternedString), u32>,
+    /// The trait of each method of a declared trait, which are the globals after the top level
+    /// functions.
+    pub methods: Vec<Trait>,
+}
+
+pub fn compile<'hir>(program: &hir::Program<'hir>) -> Result<Program<'hir>, EvalError> {
+    let mut compiler = Compiler {
+        protos: vec![],
+        globals: HashMap::new(),
+        scopes: vec![],
+        assign: InternedString::get_or_intern("assign"),
+    };
+
+    let defs: Vec<_> = program.function_defs.iter().collect();
+    for (index, (name, _)) in defs.iter().enumerate() {
+        compiler.globals.insert(**name, index as u32);
+    }
+    let methods: Vec<_> = program.trait_defs.iter().collect();
+    for (index, (_, def)) in methods.iter().enumerate() {
+        compiler
+            .globals
+            .insert(def.method.symbol, (defs.len() + index) as u32);
+    }
+    let methods = methods
+        .into_iter()
+        .map(|(name, _)| Trait::Declared(*name))
+        .collect();
+
+    let globals = defs
+        .iter()
+        .map(|(_, def)| compiler.function(def.arms))
+        .collect();
+
+    let impls = program
+        .impl_defs
+        .iter()
+        .map(|(key, def)| (*key, compiler.function(def.arms)))
+        .collect();
+
+    let main = compiler
+        .globals
+        .get(&InternedString::get_or_intern("main"))
+        .copied()
+        .filter(|&index| (index as usize) < defs.len())
+        .ok_or(EvalError::MissingMain)?;
+
+    let variants = program
+        .choice_defs
+        .iter()
+        .flat_map(|(name, def)| {
+            def.variants
+                .entries
+                .iter()
+                .enumerate()
+                .map(|(index, (variant, _))| ((*name, variant.symbol), index as u32))
+        })
+        .collect();
+
+    let constructors = program
+        .choice_defs
+        .iter()
+        .flat_map(|(name, def)| {
+            def.variants
+                .entries
+                .iter()
+                .map(|(variant, _)| (variant.symbol, *name))
+        })
+        .chain(program.struct_defs.keys().map(|name| (*name, *name)))
+        .collect();
+
+    Ok(Program {
+        protos: compiler.protos,
+        globals,
+        main,
+        variants,
+        constructors,
+        impls,
+        methods,
+    })
+}
+
+/// What's in scope while compiling a function.
+#[derive(Default)]
+struct Scope {
+    /// The slots of the bindings of the arm being compiled.
+    locals: HashMap<InternedString, u32>,
+    upvalues: HashMap<InternedString, u32>,
+    captures: Vec<Capture>,
+    /// The next slot for a binding in the arm being compiled.
+    next_slot: u32,
+    slots: u32,
+}
+
+struct Compiler<'hir> {
+    protos: Vec<Proto<'hir>>,
+    globals: HashMap<InternedString, u32>,
+    /// The functions being compiled, innermost last.
+    scopes: Vec<Scope>,
+    assign: InternedString,
+}
+
+impl<'hir> Compiler<'hir> {
+    /// Compiles a function, returning the index of its proto.
+    fn function(&mut self, arms: &'hir [Arm<'hir>]) -> u32 {
+        let index = self.protos.len();
+        self.protos.push(Proto::default());
+        self.scopes.push(Scope {
+            slots: 2,
+            ..Scope::default()
+        });
+
+        let mut code = vec![];
+        let mut tables = vec![];
+        let table = self.jump_table(arms);
+        if table.is_some() {
+            code.push(Op::Switch { slot: 0, table: 0 });
+        }
+
+        let mut starts = vec![];
+        for arm in arms {
+            starts.push(code.len() as u32);
+            let scope = self.scope();
+            scope.locals.clear();
+            scope.next_slot = 2;
+
+            // the patterns of the arm, and the argument each one is matched against
+            let params: &[_] = match arm.params {
+                [] => &[(0, None), (1, None)],
+                [left] => &[(0, Some(left.pat)), (1, None)],
+                [left, right] => &[(0, Some(left.pat)), (1, Some(right.pat))],
+                _ => &[],
+            };
+
+            let mut fails = vec![];
+            if params.is_empty() {
+                fails.push(code.len());
+                code.push(Op::Fail(0));
+            }
+            for (slot, pat) in params {
+                self.param(*slot, *pat, &mut code, &mut fails);
+            }
+
+            self.expr(arm.body, true, &mut code);
+            code.push(Op::Return);
+
+            let next = code.len() as u32;
+            for fail in fails {
+                code[fail].set_fail(next);
+            }
+        }
+        let refuted = code.len() as u32;
+        code.push(Op::Refuted);
+
+        if let Some(table) = table {
+            // past the last arm is where the refutation is
+            let start = |arm: u32| starts.get(arm as usize).copied().unwrap_or(refuted);
+            tables.push(JumpTable {
+                targets: table
+                    .targets
+                    .into_iter()
+                    .map(|(tag, arm)| (tag, start(arm)))
+                    .collect(),
+                default: start(table.default),
+            });
+        }
+
+        let scope = self.scopes.pop().unwrap();
+        self.protos[index] = Proto {
+            code,
+            slots: scope.slots,
+            captures: scope.captures,
+            tables,
+        };
+        index as u32
+    }
+
+    /// Works out which arm to start at for each constructor that the first argument could be, if
+    /// enough of the arms match on one to make it worthwhile. The targets of the table are the
+    /// indices of arms rather than instructions.
+    fn jump_table(&self, arms: &[Arm<'hir>]) -> Option<JumpTable> {
+        let tags: Vec<_> = arms
+            .iter()
+            .map(
+                |arm| match arm.params.first().map(|param| &param.pat.kind) {
+                    Some(PatKind::Constructor([_, variant], _)) => Some(variant.symbol),
+                    // a struct is the only thing of its type, so there's nothing to jump between
+                    _ => None,
+                },
+            )
+            .collect();
+
+        if tags.iter().flatten().count() < 2 {
+            return None;
+        }
+
+        // the first arm that doesn't match on a constructor could match anything
+        let default = tags.iter().position(Option::is_none).unwrap_or(arms.len());
+        let mut targets = HashMap::new();
+        for (arm, tag) in tags.iter().enumerate().take(default) {
+            if let Some(tag) = tag {
+                targets.entry(*tag).or_insert(arm as u32);
+            }
+        }
+
+        Some(JumpTable {
+            targets,
+            default: default as u32,
+        })
+    }
+
+    fn scope(&mut self) -> &mut Scope {
+        self.scopes.last_mut().expect("inside a function")
+    }
+
+    /// A slot that nothing in the arm being compiled uses yet.
+    fn slot(&mut self) -> u32 {
+        let scope = self.scope();
+        let slot = scope.next_slot;
+        scope.next_slot += 1;
+        scope.slots = scope.slots.max(slot + 1);
+        slot
+    }
+
+    /// The slot for a binding in the arm being compiled. Binding the same name twice in an arm
+    /// reuses the slot, so the later binding wins.
+    fn bind(&mut self, name: InternedString) -> u32 {
+        let slot = match self.scope().locals.get(&name) {
+            Some(slot) => *slot,
+            None => self.slot(),
+        };
+        self.scope().locals.insert(name, slot);
+        slot
+    }
+
+    /// Compiles matching `pat` against the argument in `slot`, where a missing pattern means that
+    /// the argument has to be `{}`. The simple patterns that most arms start with are checked
+    /// where the argument is, without copying it onto the stack.
+    fn param(
+        &mut self,
+        slot: u32,
+        pat: Option<PatRef<'hir>>,
+        code: &mut Vec<Op<'hir>>,
+        fails: &mut Vec<usize>,
+    ) {
+        let Some(pat) = pat else {
+            fails.push(code.len());
+            code.push(Op::TestNull { slot, fail: 0 });
+            return;
+        };
+
+        match pat.kind {
+            // the argument already has a slot, so just use that
+            PatKind::Lit(Lit::Ident(ident)) => {
+                self.scope().locals.insert(ident.symbol, slot);
+            }
+            PatKind::Lit(Lit::Integer(value)) => {
+                fails.push(code.len());
+                code.push(Op::TestInteger {
+                    slot,
+                    value,
+                    fail: 0,
+                });
+            }
+            PatKind::Lit(Lit::Bool(value)) => {
+                fails.push(code.len());
+                code.push(Op::TestBool {
+                    slot,
+                    value,
+                    fail: 0,
+                });
+            }
+            PatKind::Record(map, false) if map.entries.is_empty() => {
+                fails.push(code.len());
+                code.push(Op::TestNull { slot, fail: 0 });
+            }
+            _ => {
+                code.push(Op::Local(slot));
+                self.pattern(pat, code, fails);
+            }
+        }
+    }
+
+    fn variable(&mut self, ident: Ident) -> Op<'hir> {
+        let depth = self.scopes.len() - 1;
+        if let Some(op) = self.resolve(depth, ident.symbol) {
+            return op;
+        }
+
+        match self.globals.get(&ident.symbol) {
+            Some(index) => Op::Global(*index),
+            None => Op::Unbound(ident),
+        }
+    }
+
+    /// Where a variable that isn't a top level function is, if it's bound at all.
+    fn place(&mut self, name: InternedString) -> Option<Capture> {
+        match self.resolve(self.scopes.len() - 1, name)? {
+            Op::Local(slot) => Some(Capture::Local(slot)),
+            Op::Upvalue(index) => Some(Capture::Upvalue(index)),
+            _ => unreachable!("only locals and upvalues are resolved"),
+        }
+    }
+
+    /// Looks up a variable in the function at `depth` and the ones around it, capturing it in
+    /// every function in between if it's found.
+    fn resolve(&mut self, depth: usize, name: InternedString) -> Option<Op<'hir>> {
+        let scope = &self.scopes[depth];
+        if let Some(slot) = scope.locals.get(&name) {
+            return Some(Op::Local(*slot));
+        }
+        if let Some(index) = scope.upvalues.get(&name) {
+            return Some(Op::Upvalue(*index));
+        }
+        if depth == 0 {
+            return None;
+        }
+
+        let capture = match self.resolve(depth - 1, name)? {
+            Op::Local(slot) => Capture::Local(slot),
+            Op::Upvalue(index) => Capture::Upvalue(index),
+            _ => unreachable!("only locals and upvalues are resolved"),
+        };
+        let scope = &mut self.scopes[depth];
+        let index = scope.captures.len() as u32;
+        scope.captures.push(capture);
+        scope.upvalues.insert(name, index);
+        Some(Op::Upvalue(index))
+    }
+
+    /// Compiles matching `pat` against the value on top of the stack. The indices of any tests
+    /// are added to `fails` so they can be pointed at the next arm.
+    fn pattern(&mut self, pat: PatRef<'hir>, code: &mut Vec<Op<'hir>>, fails: &mut Vec<usize>) {
+        match pat.kind {
+            PatKind::Lit(Lit::Ident(ident)) => {
+                let slot = self.bind(ident.symbol);
+                code.push(Op::Store(slot));
+            }
+            PatKind::Lit(Lit::Integer(value)) => {
+                fails.push(code.len());
+                code.push(Op::MatchInteger { value, fail: 0 });
+            }
+            PatKind::Lit(Lit::Bool(value)) => {
+                fails.push(code.len());
+                code.push(Op::MatchBool { value, fail: 0 });
+            }
+            PatKind::Record(map, open) => {
+                fails.push(code.len());
+                code.push(Op::MatchRecord {
+                    fields: map.entries,
+                    open,
+                    fail: 0,
+                });
+                for (name, pat) in map.entries {
+                    match pat {
+                        Some(pat) => self.pattern(pat, code, fails),
+                        None => {
+                            let slot = self.bind(name.symbol);
+                            code.push(Op::Store(slot));
+                        }
+                    }
+                }
+            }
+            PatKind::Constructor(tag, inner) => {
+                fails.push(code.len());
+                code.push(match tag {
+                    [name] => Op::MatchStruct {
+                        name: *name,
+                        fail: 0,
+                    },
+                    _ => Op::MatchChoice { tag, fail: 0 },
+                });
+                self.pattern(inner, code, fails);
+            }
+            PatKind::Error => {
+                fails.push(code.len());
+                code.push(Op::Fail(0));
+            }
+        }
+    }
+
+    /// The single instruction that `expr` compiles to, if it's just a variable.
+    fn expr_op(&mut self, expr: ExprRef<'hir>) -> Option<Op<'hir>> {
+        match expr.kind {
+            ExprKind::Lit(Lit::Ident(ident)) => Some(self.variable(ident)),
+            _ => None,
+        }
+    }
+
+    /// Compiles `expr`, which leaves its value on top of the stack. If it's in `tail` position,
+    /// a call can replace the current frame.
+    fn expr(&mut self, expr: ExprRef<'hir>, tail: bool, code: &mut Vec<Op<'hir>>) {
+        match expr.kind {
+            ExprKind::Symbol(Symbol::Dot) => {
+                code.push(Op::Unsupported("`.` as a value", expr.span))
+            }
+            ExprKind::Symbol(Symbol::DotDot) => code.push(Op::Unsupported("`..`", expr.span)),
+            ExprKind::Symbol(symbol) => code.push(Op::Builtin(symbol)),
+            ExprKind::Lit(Lit::Integer(int)) => code.push(Op::Integer(int)),
+            ExprKind::Lit(Lit::Bool(bool)) => code.push(Op::Bool(bool)),
+            ExprKind::Lit(Lit::Ident(ident)) => {
+                let op = self.variable(ident);
+                code.push(op);
+            }
+            ExprKind::Record(map) => {
+                for (ident, expr) in map.entries {
+                    match expr {
+                        Some(expr) => self.expr(expr, false, code),
+                        None => {
+                            let op = self.variable(*ident);
+                            code.push(op);
+                        }
+                    }
+                }
+                code.push(Op::Record(map.entries));
+            }
+            ExprKind::Constructor(constructor) => {
+                self.expr(constructor.inner, false, code);
+                code.push(match constructor.path {
+                    [name] => Op::Struct(*name),
+                    tag => Op::Choice(tag),
+                });
+            }
+            ExprKind::Closure(arms) => {
+                let proto = self.function(arms);
+                code.push(Op::Closure(proto));
+            }
+            ExprKind::Appl(appl) => {
+                // the left side of `assign` names a cell rather than reading what's in it
+                if let Some(Op::Unbound(ident)) = self.expr_op(appl.fun()) {
+                    if ident.symbol == self.assign {
+                        return self.assign(appl, code);
+                    }
+                }
+                self.expr(appl.lhs(), false, code);
+                match (appl.fun().kind, appl.rhs().kind) {
+                    // `record . name` is a field rather than a variable called `name`
+                    (ExprKind::Symbol(Symbol::Dot), ExprKind::Lit(Lit::Ident(name))) => {
+                        code.push(Op::Field(name));
+                    }
+                    // operators can't fail to be called, so skip making them into values
+                    (ExprKind::Symbol(symbol), _)
+                        if !matches!(symbol, Symbol::Dot | Symbol::DotDot) =>
+                    {
+                        self.expr(appl.rhs(), false, code);
+                        code.push(Op::Binary(symbol));
+                    }
+                    _ => match self.expr_op(appl.fun()) {
+                        // looking up a top level function can't fail either
+                        Some(Op::Global(index)) => {
+                            self.expr(appl.rhs(), false, code);
+                            code.push(if tail {
+                                Op::TailCallGlobal(index)
+                            } else {
+                                Op::CallGlobal(index)
+                            });
+                        }
+                        // names that aren't bound to anything might be assertions
+                        Some(Op::Unbound(ident)) => {
+                            match Assertion::named(&ident.symbol.string()) {
+                                Some(assertion) => {
+                                    self.expr(appl.rhs(), false, code);
+                                    code.push(Op::Assert {
+                                        assertion,
+                                        span: expr.span,
+                                    });
+                                }
+                                // fails right away, so there's no point in compiling the rest
+                                None => code.push(Op::Unbound(ident)),
+                            }
+                        }
+                        _ => {
+                            self.expr(appl.fun(), false, code);
+                            self.expr(appl.rhs(), false, code);
+                            code.push(if tail { Op::TailCall } else { Op::Call });
+                        }
+                    },
+                }
+            }
+            ExprKind::Region(region) => self.region(region, code),
+            ExprKind::Error => code.push(Op::SyntaxError(expr.span)),
+        }
+    }
+
+    /// Compiles `name assign value`, which replaces what's in the cell that a `mut` or `ref mut`
+    /// region bound `name` to.
+    fn assign(&mut self, appl: Appl<'hir>, code: &mut Vec<Op<'hir>>) {
+        let from = match appl.lhs().kind {
+            ExprKind::Lit(Lit::Ident(ident)) => self.place(ident.symbol),
+            _ => None,
+        };
+        let Some(from) = from else {
+            code.push(Op::NotAssignable(appl.lhs().span));
+            return;
+        };
+
+        code.push(Op::Cell {
+            from,
+            span: appl.lhs().span,
+        });
+        self.expr(appl.rhs(), false, code);
+        code.push(Op::Assign);
+    }
+
+    /// Compiles a region, which binds each name that it shadows to a cell in a slot of its own
+    /// for the rest of the region, and writes what's in the cells back when it ends, the same as
+    /// the tree-walking [`evaluation`](crate::evaluation) does.
+    fn region(&mut self, region: Region<'hir>, code: &mut Vec<Op<'hir>>) {
+        let mut cells = Vec::with_capacity(region.shadows.len());
+        for ident in region.shadows {
+            let Some(from) = self.place(ident.symbol) else {
+                // fails right away, so there's no point in compiling the rest
+                code.push(Op::Unbound(*ident));
+                return;
+            };
+            let slot = self.slot();
+            code.push(Op::OpenRegion {
+                from,
+                slot,
+                kind: region.kind,
+            });
+            self.scope().locals.insert(ident.symbol, slot);
+            cells.push((from, slot));
+        }
+
+        // the cells are written back after the body, so it can't be a tail call
+        self.expr(region.body, false, code);
+        for (from, slot) in cells.into_iter().rev() {
+            code.push(Op::CloseRegion { from, slot });
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use curse_span::Span;
-use miette::Diagnostic;
+use miette::{Diagnostic, SourceSpan};
 use thiserror::Error;
 
 #[derive(Debug, Diagnostic, Error)]
@@ -21,4 +21,54 @@ pub enum EvalError {
 
     #[error("Missing field in record")]
     MissingField,
+
+    #[error("Division by zero")]
+    DivideByZero,
+
+    #[error("Can't run code with a syntax error")]
+    #[diagnostic(help("fix the syntax error that was reported when this was parsed"))]
+    SyntaxError {
+        #[label("This couldn't be parsed")]
+        span: SourceSpan,
+    },
+
+    #[error("{what} can't be run yet")]
+    Unsupported {
+        what: &'static str,
+
+        #[label("This isn't supported")]
+        span: SourceSpan,
+    },
+
+    #[error("Can't assign to this")]
+    #[diagnostic(help("only names bound by `mut` and `ref mut` regions can be assigned to"))]
+    NotAssignable {
+        #[label("This isn't mutable")]
+        span: SourceSpan,
+    },
+
+    #[error("Assertion failed")]
+    AssertionFailed {
+        #[label("This was false")]
+        span: SourceSpan,
+    },
+
+    #[error("Assertion failed: the two sides aren't equal")]
+    #[diagnostic(help("left: {left}\nright: {right}"))]
+    AssertEqFailed {
+        left: String,
+        right: String,
+
+        #[label("These aren't equal")]
+        span: SourceSpan,
+    },
+
+    #[error("Panicked")]
+    #[diagnostic(help("value: {value}"))]
+    Panicked {
+        value: String,
+
+        #[label("Panicked here")]
+        span: SourceSpan,
+    },
 }
@@ -1,18 +1,31 @@
-use std::{collections::HashMap, rc::Rc};
+use std::{cell::RefCell, cmp::Ordering, collections::HashMap, rc::Rc};
 
-use crate::builtins;
+use crate::builtins::{self, Assertion};
 use crate::error::EvalError;
 use crate::value::{OwnedMap, Value, ValueRef};
-use curse_hir::hir::{self, ExprKind, ExprRef, Lit, PatKind, PatRef, Program};
-use curse_interner::InternedString;
+use curse_hir::hir::{
+    self, Appl, ExprKind, ExprRef, Lit, Map, PatKind, PatRef, Program, Region, RegionKind, Symbol,
+    Trait,
+};
+use curse_interner::{Ident, InternedString};
 
 // globally available functions, both regular named functions as well as type constructors
 pub struct GlobalBindings<'hir> {
     // name of function => the function as a curse `Value`
     functions: HashMap<InternedString, ValueRef<'hir>>,
 
-    // name of variant => name of enum
+    // name of variant => name of enum, and name of struct => itself
     constructors: HashMap<InternedString, InternedString>,
+
+    // (name of enum, name of variant) => position of the variant in the enum
+    variants: HashMap<(InternedString, InternedString), usize>,
+
+    // (trait, name of type) => the function that implements the trait
+    impls: HashMap<(Trait, InternedString), ValueRef<'hir>>,
+
+    // builtins that are looked up by name when nothing else has that name
+    assertions: [(InternedString, Assertion); 3],
+    assign: InternedString,
 }
 
 impl<'hir> GlobalBindings<'hir> {
@@ -20,6 +33,11 @@ impl<'hir> GlobalBindings<'hir> {
         Self {
             functions: HashMap::new(),
             constructors: HashMap::new(),
+            variants: HashMap::new(),
+            impls: HashMap::new(),
+            assertions: Assertion::ALL
+                .map(|(name, assertion)| (InternedString::get_or_intern(name), assertion)),
+            assign: InternedString::get_or_intern("assign"),
         }
     }
 }
@@ -43,17 +61,16 @@ fn eval_expr<'hir>(
         ExprKind::Symbol(hir::Symbol::Le) => Ok(Rc::new(Value::Builtin(builtins::le))),
         ExprKind::Symbol(hir::Symbol::Ge) => Ok(Rc::new(Value::Builtin(builtins::ge))),
         ExprKind::Symbol(hir::Symbol::Semi) => Ok(Rc::new(Value::Builtin(builtins::semi))),
-        ExprKind::Symbol(hir::Symbol::Dot) => unimplemented!(),
-        ExprKind::Symbol(hir::Symbol::DotDot) => unimplemented!(),
+        ExprKind::Symbol(hir::Symbol::Dot) => Err(EvalError::Unsupported {
+            what: "`.` as a value",
+            span: expr.span.start_len().into(),
+        }),
+        ExprKind::Symbol(hir::Symbol::DotDot) => Err(EvalError::Unsupported {
+            what: "`..`",
+            span: expr.span.start_len().into(),
+        }),
         ExprKind::Lit(Lit::Integer(int)) => Ok(Rc::new(Value::Integer(int))),
-        ExprKind::Lit(Lit::Ident(ident)) => local_state
-            .get(&ident.symbol)
-            .or(global_state.functions.get(&ident.symbol))
-            .ok_or(EvalError::UnboundVariable {
-                literal: ident.to_string(),
-                span: ident.span,
-            })
-            .cloned(),
+        ExprKind::Lit(Lit::Ident(ident)) => lookup(ident, global_state, local_state),
         ExprKind::Lit(Lit::Bool(bool)) => Ok(Rc::new(Value::Bool(bool))),
         ExprKind::Record(map) => Ok(Rc::new(Value::Record(OwnedMap::new(
             map.entries
@@ -63,36 +80,381 @@ fn eval_expr<'hir>(
                     // expr
                     Some(expr) => Ok((*ident, eval_expr(expr, global_state, local_state)?)),
                     // otherwise look up the ident in the environment
-                    None => Ok((
-                        *ident,
-                        local_state
-                            .get(&ident.symbol)
-                            .or(global_state.functions.get(&ident.symbol))
-                            .ok_or_else(|| EvalError::UnboundVariable {
-                                literal: ident.to_string(),
-                                span: ident.span,
-                            })
-                            .cloned()?,
-                    )),
+                    None => Ok((*ident, lookup(*ident, global_state, local_state)?)),
                 })
                 .collect::<Result<_, _>>()?,
         )))),
-        ExprKind::Constructor(constructor) => Ok(Rc::new(Value::Choice {
-            tag: constructor.path,
-            value: eval_expr(constructor.inner, global_state, local_state)?,
-        })),
+        ExprKind::Constructor(constructor) => {
+            let value = eval_expr(constructor.inner, global_state, local_state)?;
+            Ok(Rc::new(match constructor.path {
+                [name] => Value::Struct { name: *name, value },
+                tag => Value::Choice { tag, value },
+            }))
+        }
         ExprKind::Closure(arms) => Ok(Rc::new(Value::Function(arms, local_state.clone()))),
         ExprKind::Appl(appl) => {
+            // the left side of `assign` names a cell rather than reading what's in it
+            if unbound_name(appl.fun(), global_state, local_state) == Some(global_state.assign) {
+                return assign(&appl, global_state, local_state);
+            }
             let lhs = eval_expr(appl.lhs(), global_state, local_state)?;
+            // `record . name` looks up a field rather than a variable called `name`
+            if let (ExprKind::Symbol(Symbol::Dot), ExprKind::Lit(Lit::Ident(name))) =
+                (appl.fun().kind, appl.rhs().kind)
+            {
+                return match lhs.as_ref() {
+                    Value::Record(map) => map.get(name).cloned().ok_or(EvalError::MissingField),
+                    _ => Err(EvalError::TypeMismatch),
+                };
+            }
+            if let Some(assertion) = assertion(appl.fun(), global_state, local_state) {
+                let rhs = eval_expr(appl.rhs(), global_state, local_state)?;
+                return builtins::check(assertion, lhs, rhs, expr.span);
+            }
             let fun = eval_expr(appl.fun(), global_state, local_state)?;
             let rhs = eval_expr(appl.rhs(), global_state, local_state)?;
+            if let ExprKind::Symbol(symbol) = appl.fun().kind {
+                if let Some(result) = call_impl(symbol, &lhs, &rhs, global_state) {
+                    return result;
+                }
+                if let Some(result) = call_comparison(symbol, &lhs, &rhs, global_state) {
+                    return result;
+                }
+            }
             call_function(lhs, fun, rhs, global_state)
         }
-        ExprKind::Region(_) => todo!("Regions"),
-        ExprKind::Error => todo!("error handling"),
+        ExprKind::Region(region) => eval_region(&region, global_state, local_state),
+        ExprKind::Error => Err(EvalError::SyntaxError {
+            span: expr.span.start_len().into(),
+        }),
+    }
+}
+
+/// Looks up the value of a variable, which is whatever is in the cell if a region bound it to one.
+fn lookup<'hir>(
+    ident: Ident,
+    global_state: &GlobalBindings<'hir>,
+    local_state: &Bindings<'hir>,
+) -> Result<ValueRef<'hir>, EvalError> {
+    let value = local_state
+        .get(&ident.symbol)
+        .or(global_state.functions.get(&ident.symbol))
+        .ok_or_else(|| EvalError::UnboundVariable {
+            literal: ident.to_string(),
+            span: ident.span,
+        })?;
+
+    Ok(match value.as_ref() {
+        Value::Cell { value, .. } => value.borrow().clone(),
+        _ => value.clone(),
+    })
+}
+
+/// Returns the name that `fun` refers to if it isn't bound to anything, which could make it a
+/// builtin.
+fn unbound_name(
+    fun: ExprRef<'_>,
+    global_state: &GlobalBindings<'_>,
+    local_state: &Bindings<'_>,
+) -> Option<InternedString> {
+    let ExprKind::Lit(Lit::Ident(ident)) = fun.kind else {
+        return None;
+    };
+
+    (!local_state.contains_key(&ident.symbol)
+        && !global_state.functions.contains_key(&ident.symbol))
+    .then_some(ident.symbol)
+}
+
+/// Returns the assertion that `fun` names, unless something else has that name.
+fn assertion<'hir>(
+    fun: ExprRef<'hir>,
+    global_state: &GlobalBindings<'hir>,
+    local_state: &Bindings<'hir>,
+) -> Option<Assertion> {
+    let name = unbound_name(fun, global_state, local_state)?;
+    global_state
+        .assertions
+        .iter()
+        .find_map(|(assertion_name, assertion)| (*assertion_name == name).then_some(*assertion))
+}
+
+/// Evaluates `name assign value`, which replaces what's in the cell that a `mut` or `ref mut`
+/// region bound `name` to, giving back `{}`.
+fn assign<'hir>(
+    appl: &Appl<'hir>,
+    global_state: &GlobalBindings<'hir>,
+    local_state: &mut Bindings<'hir>,
+) -> Result<ValueRef<'hir>, EvalError> {
+    let cell = match appl.lhs().kind {
+        ExprKind::Lit(Lit::Ident(ident)) => match local_state.get(&ident.symbol).map(Rc::as_ref) {
+            Some(Value::Cell {
+                value,
+                mutable: true,
+            }) => Some(value.clone()),
+            _ => None,
+        },
+        _ => None,
+    };
+    let Some(cell) = cell else {
+        return Err(EvalError::NotAssignable {
+            span: appl.lhs().span.start_len().into(),
+        });
+    };
+
+    let value = eval_expr(appl.rhs(), global_state, local_state)?;
+    *cell.borrow_mut() = value;
+    Ok(Rc::new(Value::default()))
+}
+
+/// Evaluates a region, which binds each name that it shadows to a cell for the rest of the region
+/// and writes what's in the cell back to the outer name when it ends.
+///
+/// `ref` and `ref mut` share the cell of a name that's already bound to one, so they see changes
+/// as soon as they're made, while `mut` works on a copy until the region ends. Only the cells of
+/// `mut` and `ref mut` regions can be assigned to, but a `ref` region still writes back the changes
+/// that regions inside of it made.
+fn eval_region<'hir>(
+    region: &Region<'hir>,
+    global_state: &GlobalBindings<'hir>,
+    local_state: &mut Bindings<'hir>,
+) -> Result<ValueRef<'hir>, EvalError> {
+    let mut outer = Vec::with_capacity(region.shadows.len());
+    for ident in region.shadows {
+        let binding =
+            local_state
+                .get(&ident.symbol)
+                .cloned()
+                .ok_or_else(|| EvalError::UnboundVariable {
+                    literal: ident.to_string(),
+                    span: ident.span,
+                })?;
+        let cell = match (region.kind, binding.as_ref()) {
+            (RegionKind::Ref | RegionKind::RefMut, Value::Cell { value, .. }) => value.clone(),
+            (RegionKind::Mut, Value::Cell { value, .. }) => {
+                Rc::new(RefCell::new(value.borrow().clone()))
+            }
+            _ => Rc::new(RefCell::new(binding.clone())),
+        };
+
+        local_state.insert(
+            ident.symbol,
+            Rc::new(Value::Cell {
+                value: cell.clone(),
+                mutable: region.kind != RegionKind::Ref,
+            }),
+        );
+        outer.push((ident.symbol, binding, cell));
+    }
+
+    let result = eval_expr(region.body, global_state, local_state);
+
+    for (name, binding, cell) in outer.into_iter().rev() {
+        let value = cell.borrow().clone();
+        match binding.as_ref() {
+            Value::Cell { value: outer, .. } => {
+                *outer.borrow_mut() = value;
+                local_state.insert(name, binding);
+            }
+            _ => {
+                local_state.insert(name, value);
+            }
+        }
+    }
+
+    result
+}
+
+/// The impl of `trait_` for the type of `lhs`, or `None` if `lhs` isn't a value of a choice or
+/// struct with such an impl.
+fn impl_for<'hir>(
+    trait_: Trait,
+    lhs: &ValueRef<'hir>,
+    global_state: &GlobalBindings<'hir>,
+) -> Option<ValueRef<'hir>> {
+    let constructor = match lhs.as_ref() {
+        Value::Choice { tag, .. } => tag.last()?,
+        Value::Struct { name, .. } => name,
+        _ => return None,
+    };
+    let ty = global_state.constructors.get(&constructor.symbol)?;
+    global_state.impls.get(&(trait_, *ty)).cloned()
+}
+
+/// Calls the impl that overloads `symbol` for the type of `lhs`, or returns `None` if `lhs`
+/// isn't a value of a choice or struct with such an impl.
+///
+/// `Ord` impls only define `<`, so the other comparisons swap the arguments or negate the result.
+fn call_impl<'hir>(
+    symbol: Symbol,
+    lhs: &ValueRef<'hir>,
+    rhs: &ValueRef<'hir>,
+    global_state: &GlobalBindings<'hir>,
+) -> Option<Result<ValueRef<'hir>, EvalError>> {
+    let function = impl_for(symbol.trait_()?, lhs, global_state)?;
+
+    let (left, right, negate) = match symbol {
+        Symbol::Gt => (rhs, lhs, false),
+        Symbol::Le => (rhs, lhs, true),
+        Symbol::Ge => (lhs, rhs, true),
+        _ => (lhs, rhs, false),
+    };
+    let result = call_function(left.clone(), function, right.clone(), global_state);
+    if !negate {
+        return Some(result);
+    }
+    Some(result.and_then(|value| match value.as_ref() {
+        Value::Bool(b) => Ok(Rc::new(Value::Bool(!b))),
+        _ => Err(EvalError::TypeMismatch),
+    }))
+}
+
+/// Calls the impl that overloads `symbol` like [`call_impl`], for a symbol that gives back a
+/// `Bool`.
+fn call_bool_impl<'hir>(
+    symbol: Symbol,
+    lhs: &ValueRef<'hir>,
+    rhs: &ValueRef<'hir>,
+    global_state: &GlobalBindings<'hir>,
+) -> Option<Result<bool, EvalError>> {
+    let result = call_impl(symbol, lhs, rhs, global_state)?;
+    Some(result.and_then(|value| match value.as_ref() {
+        Value::Bool(b) => Ok(*b),
+        _ => Err(EvalError::TypeMismatch),
+    }))
+}
+
+/// Applies `=` or one of the comparisons, or returns `None` if `symbol` is something else.
+fn call_comparison<'hir>(
+    symbol: Symbol,
+    lhs: &ValueRef<'hir>,
+    rhs: &ValueRef<'hir>,
+    global_state: &GlobalBindings<'hir>,
+) -> Option<Result<ValueRef<'hir>, EvalError>> {
+    let result = match symbol {
+        Symbol::Eq => equal(lhs, rhs, global_state),
+        Symbol::Lt => compare(lhs, rhs, global_state).map(Ordering::is_lt),
+        Symbol::Gt => compare(lhs, rhs, global_state).map(Ordering::is_gt),
+        Symbol::Le => compare(lhs, rhs, global_state).map(Ordering::is_le),
+        Symbol::Ge => compare(lhs, rhs, global_state).map(Ordering::is_ge),
+        _ => return None,
+    };
+    Some(result.map(|b| Rc::new(Value::Bool(b))))
+}
+
+/// Compares two values field by field, using the `impl Eq` of any choice or struct that has one.
+/// Functions can't be compared.
+fn equal<'hir>(
+    lhs: &ValueRef<'hir>,
+    rhs: &ValueRef<'hir>,
+    global_state: &GlobalBindings<'hir>,
+) -> Result<bool, EvalError> {
+    if let Some(result) = call_bool_impl(Symbol::Eq, lhs, rhs, global_state) {
+        return result;
+    }
+
+    match (lhs.as_ref(), rhs.as_ref()) {
+        (Value::Integer(n), Value::Integer(m)) => Ok(n == m),
+        (Value::Bool(a), Value::Bool(b)) => Ok(a == b),
+        (Value::Record(a), Value::Record(b)) => {
+            if a.entries.len() != b.entries.len() {
+                return Ok(false);
+            }
+            for ((a_name, a), (b_name, b)) in a.entries.iter().zip(&b.entries) {
+                if a_name != b_name || !equal(a, b, global_state)? {
+                    return Ok(false);
+                }
+            }
+            Ok(true)
+        }
+        (
+            Value::Choice { tag, value },
+            Value::Choice {
+                tag: other_tag,
+                value: other_value,
+            },
+        ) => Ok(tag == other_tag && equal(value, other_value, global_state)?),
+        (
+            Value::Struct { value, .. },
+            Value::Struct {
+                value: other_value, ..
+            },
+        ) => equal(value, other_value, global_state),
+        _ => Err(EvalError::TypeMismatch),
+    }
+}
+
+/// Orders two values: `false` comes before `true`, records are ordered by their fields in the
+/// order of their names, choices by the position of their variants and then by their payloads,
+/// and structs by their payloads, unless the type has an `impl Ord`. Functions can't be compared.
+fn compare<'hir>(
+    lhs: &ValueRef<'hir>,
+    rhs: &ValueRef<'hir>,
+    global_state: &GlobalBindings<'hir>,
+) -> Result<Ordering, EvalError> {
+    if let Some(less) = call_bool_impl(Symbol::Lt, lhs, rhs, global_state) {
+        if less? {
+            return Ok(Ordering::Less);
+        }
+        let greater = call_bool_impl(Symbol::Lt, rhs, lhs, global_state)
+            .expect("both sides have the same type")?;
+        return Ok(if greater {
+            Ordering::Greater
+        } else {
+            Ordering::Equal
+        });
+    }
+
+    match (lhs.as_ref(), rhs.as_ref()) {
+        (Value::Integer(n), Value::Integer(m)) => Ok(n.cmp(m)),
+        (Value::Bool(a), Value::Bool(b)) => Ok(a.cmp(b)),
+        (Value::Record(a), Value::Record(b)) if a.entries.len() == b.entries.len() => {
+            for ((a_name, a), (b_name, b)) in a.entries.iter().zip(&b.entries) {
+                if a_name != b_name {
+                    return Err(EvalError::TypeMismatch);
+                }
+                match compare(a, b, global_state)? {
+                    Ordering::Equal => {}
+                    ordering => return Ok(ordering),
+                }
+            }
+            Ok(Ordering::Equal)
+        }
+        (
+            Value::Choice { tag, value },
+            Value::Choice {
+                tag: other_tag,
+                value: other_value,
+            },
+        ) => {
+            let index = variant_index(tag, global_state)?;
+            match index.cmp(&variant_index(other_tag, global_state)?) {
+                Ordering::Equal => compare(value, other_value, global_state),
+                ordering => Ok(ordering),
+            }
+        }
+        (
+            Value::Struct { value, .. },
+            Value::Struct {
+                value: other_value, ..
+            },
+        ) => compare(value, other_value, global_state),
+        _ => Err(EvalError::TypeMismatch),
     }
 }
 
+/// The position of the variant that `tag` names in its choice.
+fn variant_index(tag: &[Ident], global_state: &GlobalBindings<'_>) -> Result<usize, EvalError> {
+    let [choice, variant] = tag else {
+        return Err(EvalError::TypeMismatch);
+    };
+    global_state
+        .variants
+        .get(&(choice.symbol, variant.symbol))
+        .copied()
+        .ok_or(EvalError::TypeMismatch)
+}
+
 fn call_function<'hir>(
     left: ValueRef<'hir>,
     function: ValueRef<'hir>,
@@ -131,22 +493,25 @@ fn call_function<'hir>(
             }
         }
         Value::Builtin(builtin) => builtin(left, right),
+        Value::Method(trait_) => {
+            let function = impl_for(*trait_, &left, global_state).ok_or(EvalError::TypeMismatch)?;
+            call_function(left, function, right, global_state)
+        }
         _ => Err(EvalError::TypeMismatch),
     }
 }
 
 fn check_pattern<'hir>(value: &Value, pattern: PatRef<'hir>) -> bool {
     match (&pattern.kind, value) {
-        (PatKind::Record(pattern_map), Value::Record(value_map)) => {
-            if pattern_map.entries.len() != value_map.entries.len() {
-                false
-            } else {
-                pattern_map.entries.iter().zip(&value_map.entries).all(
-                    |((_, opt_pat), (_, val))| match opt_pat {
+        (PatKind::Record(pattern_map, open), Value::Record(value_map)) => {
+            match record_fields(pattern_map, *open, value_map) {
+                Some(values) => pattern_map.entries.iter().zip(values).all(
+                    |((_, opt_pat), val)| match opt_pat {
                         Some(pat) => check_pattern(val.as_ref(), pat),
                         None => true,
                     },
-                )
+                ),
+                None => false,
             }
         }
         (
@@ -162,6 +527,9 @@ fn check_pattern<'hir>(value: &Value, pattern: PatRef<'hir>) -> bool {
                 false
             }
         }
+        (PatKind::Constructor([pat_name], pattern), Value::Struct { name, value }) => {
+            pat_name.symbol == name.symbol && check_pattern(value, pattern)
+        }
         (PatKind::Lit(Lit::Integer(n)), Value::Integer(m)) => n == m,
         (PatKind::Lit(Lit::Bool(b1)), Value::Bool(b2)) => b1 == b2,
         (PatKind::Lit(Lit::Ident(_)), _) => true,
@@ -169,6 +537,25 @@ fn check_pattern<'hir>(value: &Value, pattern: PatRef<'hir>) -> bool {
     }
 }
 
+/// The values that the fields of a record pattern match against, in the same order, or `None` if
+/// the record doesn't fit the pattern. Fields are found by name, so `|{ b, a }|` binds the same
+/// values as `|{ a, b }|`. An `open` pattern can leave out some of the record's fields, but
+/// otherwise the pattern has to name all of them.
+fn record_fields<'v, 'hir, 'val>(
+    pattern_map: &Map<'hir, Option<PatRef<'hir>>>,
+    open: bool,
+    value_map: &'v OwnedMap<ValueRef<'val>>,
+) -> Option<Vec<&'v ValueRef<'val>>> {
+    if !open && pattern_map.entries.len() != value_map.entries.len() {
+        return None;
+    }
+    pattern_map
+        .entries
+        .iter()
+        .map(|(name, _)| value_map.get(*name))
+        .collect()
+}
+
 fn match_pattern<'hir>(
     value: ValueRef<'hir>,
     pattern: PatRef<'hir>,
@@ -179,13 +566,11 @@ fn match_pattern<'hir>(
         Ok(())
     } else {
         match (&pattern.kind, value.as_ref()) {
-            (PatKind::Record(pattern_map), Value::Record(value_map)) => {
-                if pattern_map.entries.len() == value_map.entries.len() {
+            (PatKind::Record(pattern_map, open), Value::Record(value_map)) => {
+                if let Some(values) = record_fields(pattern_map, *open, value_map) {
                     // if there's no pattern after the name, binds value to the name, otherwise
                     // matches on the pattern
-                    for ((name, opt_pat), (_, val)) in
-                        pattern_map.entries.iter().zip(&value_map.entries)
-                    {
+                    for ((name, opt_pat), val) in pattern_map.entries.iter().zip(values) {
                         if let Some(pat) = opt_pat {
                             match_pattern(Rc::clone(&val), pat, local_state)?;
                         } else {
@@ -213,6 +598,11 @@ fn match_pattern<'hir>(
                     Err(EvalError::FailedPatternMatch)
                 }
             }
+            (PatKind::Constructor([pat_name], pattern), Value::Struct { name, value })
+                if pat_name.symbol == name.symbol =>
+            {
+                match_pattern(value.clone(), pattern, local_state)
+            }
             (PatKind::Lit(Lit::Integer(n)), Value::Integer(m)) => (*n == *m)
                 .then_some(())
                 .ok_or(EvalError::FailedPatternMatch),
@@ -228,6 +618,16 @@ fn match_pattern<'hir>(
 }
 
 pub fn execute_program<'hir>(program: &Program<'hir>) -> Result<ValueRef<'hir>, EvalError> {
+    execute_function(program, InternedString::get_or_intern("main"))
+        .unwrap_or(Err(EvalError::MissingMain))
+}
+
+/// Calls the top level function `name` with `{}` on both sides, or returns `None` if there's no
+/// such function.
+pub fn execute_function<'hir>(
+    program: &Program<'hir>,
+    name: InternedString,
+) -> Option<Result<ValueRef<'hir>, EvalError>> {
     let mut global_state = GlobalBindings::new();
 
     for (name, def) in &program.function_defs {
@@ -236,20 +636,35 @@ pub fn execute_program<'hir>(program: &Program<'hir>) -> Result<ValueRef<'hir>,
             .insert(*name, Rc::new(Value::Function(def.arms, HashMap::new())));
     }
 
+    for (name, def) in &program.trait_defs {
+        global_state.functions.insert(
+            def.method.symbol,
+            Rc::new(Value::Method(Trait::Declared(*name))),
+        );
+    }
+
     for (name, def) in &program.choice_defs {
-        for (variant, _) in def.variants.entries {
+        for (index, (variant, _)) in def.variants.entries.iter().enumerate() {
             global_state.constructors.insert(variant.symbol, *name);
+            global_state.variants.insert((*name, variant.symbol), index);
         }
     }
 
-    call_function(
-        Rc::new(Value::default()),
+    for name in program.struct_defs.keys() {
+        global_state.constructors.insert(*name, *name);
+    }
+
+    for (key, def) in &program.impl_defs {
         global_state
-            .functions
-            .get(&InternedString::get_or_intern("main"))
-            .ok_or(EvalError::MissingMain)
-            .cloned()?,
+            .impls
+            .insert(*key, Rc::new(Value::Function(def.arms, HashMap::new())));
+    }
+
+    let function = global_state.functions.get(&name)?.clone();
+    Some(call_function(
+        Rc::new(Value::default()),
+        function,
         Rc::new(Value::default()),
         &global_state,
-    )
+    ))
 }
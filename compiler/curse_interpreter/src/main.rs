@@ -1,43 +1,8 @@
 use std::io;
 
 use bumpalo::Bump;
-use curse_ast::ast;
 use curse_interner::StringInterner;
-
-mod builtins;
-mod error;
-mod evaluation;
-mod value;
-
-// TODO(william): better return value
-fn flatten_asts(interner: &mut StringInterner, filepath: &str) -> io::Result<ast::Program> {
-    let input = std::fs::read_to_string(filepath).expect("file read error");
-
-    let mut parser = curse_parse::Parser::new(interner);
-
-    let mut ast_program = parser.parse_program(&input);
-    if !parser.errors.is_empty() {
-        eprintln!("{:?}", parser.errors);
-        return Err(io::Error::new(io::ErrorKind::Other, "errors!!!"));
-    }
-
-    for import in ast_program.dynamic_imports.iter() {
-        let s: &str = import
-            .file_string
-            .symbol
-            .string_in(interner)
-            .expect("file name in interner");
-        // trim off quotes
-        let s = s[1..s.len() - 1].to_string();
-        let other_program = flatten_asts(interner, &s)?;
-        ast_program
-            .function_defs
-            .extend(other_program.function_defs);
-        ast_program.choice_defs.extend(other_program.choice_defs);
-        ast_program.struct_defs.extend(other_program.struct_defs);
-    }
-    Ok(ast_program)
-}
+use curse_interpreter::{evaluation, flatten_asts};
 
 pub fn main() -> io::Result<()> {
     let file_name = std::env::args()
@@ -0,0 +1,60 @@
+//! Finds and runs the `#[test]` functions of a program.
+//!
+//! Each test is called with `{}` on both sides by a fresh interpreter, and passes if it finishes
+//! without an error. A test that makes the interpreter panic, which would be a bug in the
+//! interpreter, is reported as a failure instead of taking the rest of the tests down with it.
+
+use std::panic::{self, AssertUnwindSafe};
+
+use curse_hir::hir::{AttributeKind, FunctionDef, Program};
+
+use crate::{error::EvalError, evaluation};
+
+/// Why a test failed.
+#[derive(Debug)]
+pub enum Failure {
+    /// The test stopped with an error, like a failed assertion.
+    Error(EvalError),
+    /// The interpreter panicked with this message.
+    Crashed(String),
+}
+
+/// The `#[test]` functions in `program` whose names contain `filter`, sorted by name.
+pub fn tests<'a, 'hir>(program: &'a Program<'hir>, filter: &str) -> Vec<&'a FunctionDef<'hir>> {
+    let mut tests: Vec<_> = program
+        .function_defs
+        .values()
+        .filter(|def| {
+            def.attributes
+                .iter()
+                .any(|attribute| attribute.kind == AttributeKind::Test)
+        })
+        .filter(|def| def.ident.symbol.string().contains(filter))
+        .collect();
+    tests.sort_by_key(|def| def.ident.symbol.string().to_string());
+    tests
+}
+
+/// Runs one of the tests of `program`.
+pub fn run(program: &Program<'_>, test: &FunctionDef<'_>) -> Result<(), Failure> {
+    // the message is part of the failure, so don't print it as well
+    let hook = panic::take_hook();
+    panic::set_hook(Box::new(|_| {}));
+    let result = panic::catch_unwind(AssertUnwindSafe(|| {
+        evaluation::execute_function(program, test.ident.symbol)
+            .expect("tests are top level functions")
+            .map(drop)
+    }));
+    panic::set_hook(hook);
+
+    match result {
+        Ok(result) => result.map_err(Failure::Error),
+        Err(payload) => Err(Failure::Crashed(
+            payload
+                .downcast_ref::<&str>()
+                .map(|message| message.to_string())
+                .or_else(|| payload.downcast_ref::<String>().cloned())
+                .unwrap_or_else(|| "<unknown>".to_string()),
+        )),
+    }
+}
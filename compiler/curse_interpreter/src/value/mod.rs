@@ -1,6 +1,6 @@
 use curse_hir::hir::{self, Arm};
 use curse_interner::Ident;
-use std::{fmt, rc::Rc};
+use std::{cell::RefCell, fmt, rc::Rc};
 
 use crate::{error::EvalError, evaluation::Bindings};
 
@@ -19,7 +19,19 @@ pub enum Value<'hir> {
         tag: &'hir [Ident],
         value: ValueRef<'hir>,
     },
+    Struct {
+        name: Ident,
+        value: ValueRef<'hir>,
+    },
     Builtin(Builtin<'hir>),
+    /// The method of a declared trait, which calls the impl for the type of its left argument.
+    Method(hir::Trait),
+    // what a region binds a name to, which is never a value on its own since reading the name
+    // gives back what's inside. Only cells from `mut` and `ref mut` regions can be assigned to
+    Cell {
+        value: Rc<RefCell<ValueRef<'hir>>>,
+        mutable: bool,
+    },
 }
 
 impl Value<'_> {
@@ -40,25 +52,26 @@ impl fmt::Debug for Value<'_> {
             Bool(bool) => write!(f, "{bool}"),
             Function(..) => write!(f, "<function>"),
             Builtin(_) => write!(f, "<builtin>"),
+            Method(trait_) => write!(f, "<method of {}>", trait_.name()),
             Record(map) => write!(f, "{map:#?}"),
-            Choice { tag, value } => {
-                // temporary hack until we formalize things
-                struct PathDisplay<'a>(&'a [Ident]);
-                impl fmt::Debug for PathDisplay<'_> {
-                    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-                        let (last, parts) =
-                            self.0.split_last().expect("at least 1 part in the path");
+            Choice { tag, value } => write!(f, "{:?} {value:?}", PathDisplay(tag)),
+            Struct { name, value } => write!(f, "{name} {value:?}"),
+            Cell { value, .. } => write!(f, "{:?}", value.borrow()),
+        }
+    }
+}
+
+// temporary hack until we formalize things
+pub(crate) struct PathDisplay<'a>(pub &'a [Ident]);
 
-                        for part in parts {
-                            write!(f, "{part}::")?;
-                        }
-                        write!(f, "{last}")
-                    }
-                }
+impl fmt::Debug for PathDisplay<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let (last, parts) = self.0.split_last().expect("at least 1 part in the path");
 
-                write!(f, "{:?} {value:?}", PathDisplay(tag))
-            }
+        for part in parts {
+            write!(f, "{part}::")?;
         }
+        write!(f, "{last}")
     }
 }
 
@@ -87,6 +100,13 @@ impl<'hir, T> OwnedMap<T> {
     pub fn new(entries: Vec<(Ident, T)>) -> Self {
         OwnedMap { entries }
     }
+
+    /// The value of the field called `name`.
+    pub fn get(&self, name: Ident) -> Option<&T> {
+        self.entries
+            .iter()
+            .find_map(|(field, value)| (*field == name).then_some(value))
+    }
 }
 
 impl<T> Default for OwnedMap<T> {
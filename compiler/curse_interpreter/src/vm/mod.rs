@@ -0,0 +1,688 @@
+//! A stack machine that runs the [`bytecode`](crate::bytecode), which is a good deal faster than
+//! walking the HIR in [`evaluation`](crate::evaluation) but otherwise behaves the same.
+//!
+//! Calls push a frame onto a stack of their own instead of recursing, and calls in tail position
+//! replace the current frame, so deep recursion doesn't overflow the native stack.
+
+use std::{cell::RefCell, cmp::Ordering, fmt, rc::Rc};
+
+use curse_hir::hir::{self, RegionKind, Symbol, Trait};
+use curse_interner::Ident;
+use curse_span::Span;
+
+use crate::{
+    builtins::Assertion,
+    bytecode::{self, Capture, Op, Proto},
+    error::EvalError,
+    value::{OwnedMap, PathDisplay},
+};
+
+/// A value in the VM, which mirrors [`crate::value::Value`] but doesn't allocate integers and
+/// bools, and whose functions are compiled.
+#[derive(Clone)]
+pub enum Value<'hir> {
+    Integer(u32),
+    Bool(bool),
+    Closure(Rc<Closure<'hir>>),
+    Record(Rc<OwnedMap<Value<'hir>>>),
+    Choice(Rc<Choice<'hir>>),
+    Struct(Rc<Struct<'hir>>),
+    Builtin(Symbol),
+    /// The method of a declared trait, which calls the impl for the type of its left argument.
+    Method(Trait),
+    /// What a region binds a name to, which is only ever in a slot or an upvalue since reading
+    /// the name gives back what's inside. Only cells from `mut` and `ref mut` regions can be
+    /// assigned to.
+    Cell {
+        value: Rc<RefCell<Value<'hir>>>,
+        mutable: bool,
+    },
+}
+
+impl<'hir> Value<'hir> {
+    /// The value, or what's in it if it's a cell.
+    #[inline]
+    fn load(&self) -> Value<'hir> {
+        match self {
+            Value::Cell { value, .. } => value.borrow().clone(),
+            value => value.clone(),
+        }
+    }
+}
+
+pub struct Closure<'hir> {
+    proto: u32,
+    upvalues: Box<[Value<'hir>]>,
+}
+
+pub struct Choice<'hir> {
+    pub tag: hir::Path<'hir>,
+    pub value: Value<'hir>,
+}
+
+pub struct Struct<'hir> {
+    pub name: Ident,
+    pub value: Value<'hir>,
+}
+
+// Formats the same way as the tree-walking interpreter's values, so the two can be compared.
+impl fmt::Debug for Value<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Value::Integer(int) => write!(f, "{int}"),
+            Value::Bool(bool) => write!(f, "{bool}"),
+            Value::Closure(_) => write!(f, "<function>"),
+            Value::Builtin(_) => write!(f, "<builtin>"),
+            Value::Method(trait_) => write!(f, "<method of {}>", trait_.name()),
+            Value::Record(map) => write!(f, "{map:#?}"),
+            Value::Choice(choice) => write!(f, "{:?} {:?}", PathDisplay(choice.tag), choice.value),
+            Value::Struct(s) => write!(f, "{} {:?}", s.name, s.value),
+            Value::Cell { value, .. } => write!(f, "{:?}", value.borrow()),
+        }
+    }
+}
+
+pub fn execute_program<'hir>(program: &hir::Program<'hir>) -> Result<Value<'hir>, EvalError> {
+    run(&bytecode::compile(program)?)
+}
+
+/// Runs `main` in a compiled program.
+pub fn run<'hir>(program: &bytecode::Program<'hir>) -> Result<Value<'hir>, EvalError> {
+    let globals: Vec<_> = program
+        .globals
+        .iter()
+        .map(|proto| {
+            Value::Closure(Rc::new(Closure {
+                proto: *proto,
+                upvalues: Box::new([]),
+            }))
+        })
+        .chain(program.methods.iter().map(|trait_| Value::Method(*trait_)))
+        .collect();
+
+    let null = Rc::default();
+    let mut vm = Vm {
+        program,
+        globals,
+        stack: vec![
+            Value::Record(Rc::clone(&null)),
+            Value::Record(Rc::clone(&null)),
+        ],
+        frames: vec![],
+        null,
+    };
+    let Value::Closure(main) = vm.globals[program.main as usize].clone() else {
+        unreachable!("`main` is a top level function");
+    };
+    vm.enter(main, 0);
+    vm.execute(0)
+}
+
+struct Frame<'hir> {
+    closure: Rc<Closure<'hir>>,
+    pc: usize,
+    /// Where the slots of the frame start on the stack.
+    base: usize,
+}
+
+struct Vm<'a, 'hir> {
+    program: &'a bytecode::Program<'hir>,
+    globals: Vec<Value<'hir>>,
+    stack: Vec<Value<'hir>>,
+    frames: Vec<Frame<'hir>>,
+    null: Rc<OwnedMap<Value<'hir>>>,
+}
+
+impl<'hir> Vm<'_, 'hir> {
+    /// Pushes a frame for calling `closure`, whose arguments are the two values at `base`.
+    fn enter(&mut self, closure: Rc<Closure<'hir>>, base: usize) {
+        let slots = self.program.protos[closure.proto as usize].slots as usize;
+        self.stack.resize(base + slots, Value::Bool(false));
+        self.frames.push(Frame {
+            closure,
+            pc: 0,
+            base,
+        });
+    }
+
+    #[inline]
+    fn pop(&mut self) -> Value<'hir> {
+        self.stack.pop().expect("stack underflow")
+    }
+
+    /// Runs until the frame above the first `depth` of them returns, and gives back what it
+    /// returned.
+    fn execute(&mut self, depth: usize) -> Result<Value<'hir>, EvalError> {
+        loop {
+            let frame = self.frames.last().expect("a frame to run");
+            let base = frame.base;
+            let proto = &self.program.protos[frame.closure.proto as usize];
+
+            // Run the current frame until it calls or returns.
+            let mut pc = frame.pc;
+            let returned = loop {
+                let op = proto.code[pc];
+                pc += 1;
+                match op {
+                    Op::Integer(int) => self.stack.push(Value::Integer(int)),
+                    Op::Bool(bool) => self.stack.push(Value::Bool(bool)),
+                    Op::Builtin(symbol) => self.stack.push(Value::Builtin(symbol)),
+                    Op::Local(slot) => self.stack.push(self.stack[base + slot as usize].load()),
+                    Op::Upvalue(index) => {
+                        let closure = &self.frames.last().unwrap().closure;
+                        self.stack.push(closure.upvalues[index as usize].load());
+                    }
+                    Op::Global(index) => self.stack.push(self.globals[index as usize].clone()),
+                    Op::Unbound(ident) => {
+                        return Err(EvalError::UnboundVariable {
+                            literal: ident.to_string(),
+                            span: ident.span,
+                        })
+                    }
+                    // `{}` is everywhere, so don't allocate a new one every time
+                    Op::Record([]) => self.stack.push(Value::Record(Rc::clone(&self.null))),
+                    Op::Record(entries) => {
+                        let values = self.stack.split_off(self.stack.len() - entries.len());
+                        let entries = entries
+                            .iter()
+                            .map(|(ident, _)| *ident)
+                            .zip(values)
+                            .collect();
+                        self.stack
+                            .push(Value::Record(Rc::new(OwnedMap::new(entries))));
+                    }
+                    Op::Field(name) => match self.pop() {
+                        Value::Record(map) => match map.get(name) {
+                            Some(value) => self.stack.push(value.clone()),
+                            None => return Err(EvalError::MissingField),
+                        },
+                        _ => return Err(EvalError::TypeMismatch),
+                    },
+                    Op::Choice(tag) => {
+                        let value = self.pop();
+                        self.stack
+                            .push(Value::Choice(Rc::new(Choice { tag, value })));
+                    }
+                    Op::Struct(name) => {
+                        let value = self.pop();
+                        self.stack
+                            .push(Value::Struct(Rc::new(Struct { name, value })));
+                    }
+                    Op::Closure(index) => {
+                        let upvalues = self.program.protos[index as usize]
+                            .captures
+                            .iter()
+                            .map(|capture| self.get(base, *capture))
+                            .collect();
+                        self.stack.push(Value::Closure(Rc::new(Closure {
+                            proto: index,
+                            upvalues,
+                        })));
+                    }
+                    Op::Binary(symbol) => {
+                        let right = self.pop();
+                        let left = self.pop();
+                        let result = self.binary(symbol, left, right)?;
+                        self.stack.push(result);
+                    }
+                    Op::Assert { assertion, span } => {
+                        let right = self.pop();
+                        let left = self.pop();
+                        self.check(assertion, &left, &right, span)?;
+                        self.stack.push(Value::Record(Rc::clone(&self.null)));
+                    }
+                    Op::Call | Op::TailCall | Op::CallGlobal(_) | Op::TailCallGlobal(_) => {
+                        let right = self.pop();
+                        let function = match op {
+                            Op::CallGlobal(index) | Op::TailCallGlobal(index) => {
+                                self.globals[index as usize].clone()
+                            }
+                            _ => self.pop(),
+                        };
+                        let left = self.pop();
+                        let tail = matches!(op, Op::TailCall | Op::TailCallGlobal(_));
+
+                        let function = match function {
+                            Value::Method(trait_) => self
+                                .impl_for(trait_, &left)
+                                .map(Value::Closure)
+                                .ok_or(EvalError::TypeMismatch)?,
+                            function => function,
+                        };
+                        match function {
+                            Value::Closure(callee) => {
+                                if tail {
+                                    self.stack.truncate(base);
+                                    self.frames.pop();
+                                } else {
+                                    self.frames.last_mut().unwrap().pc = pc;
+                                }
+                                let base = self.stack.len();
+                                self.stack.push(left);
+                                self.stack.push(right);
+                                self.enter(callee, base);
+                                break None;
+                            }
+                            Value::Builtin(symbol) => {
+                                let result = self.binary(symbol, left, right)?;
+                                if tail {
+                                    break Some(result);
+                                }
+                                self.stack.push(result);
+                            }
+                            _ => return Err(EvalError::TypeMismatch),
+                        }
+                    }
+                    Op::Return => break Some(self.pop()),
+                    Op::Store(slot) => self.stack[base + slot as usize] = self.pop(),
+                    Op::OpenRegion { from, slot, kind } => {
+                        let cell = match (kind, self.get(base, from)) {
+                            (RegionKind::Ref | RegionKind::RefMut, Value::Cell { value, .. }) => {
+                                value
+                            }
+                            (RegionKind::Mut, Value::Cell { value, .. }) => {
+                                Rc::new(RefCell::new(value.borrow().clone()))
+                            }
+                            (_, value) => Rc::new(RefCell::new(value)),
+                        };
+                        self.stack[base + slot as usize] = Value::Cell {
+                            value: cell,
+                            mutable: kind != RegionKind::Ref,
+                        };
+                    }
+                    Op::CloseRegion { from, slot } => {
+                        let value = self.stack[base + slot as usize].load();
+                        self.stack[base + slot as usize] = match self.get(base, from) {
+                            Value::Cell {
+                                value: outer,
+                                mutable,
+                            } => {
+                                *outer.borrow_mut() = value;
+                                Value::Cell {
+                                    value: outer,
+                                    mutable,
+                                }
+                            }
+                            _ => value,
+                        };
+                    }
+                    Op::Cell { from, span } => match self.get(base, from) {
+                        cell @ Value::Cell { mutable: true, .. } => self.stack.push(cell),
+                        _ => {
+                            return Err(EvalError::NotAssignable {
+                                span: span.start_len().into(),
+                            })
+                        }
+                    },
+                    Op::Assign => {
+                        let value = self.pop();
+                        let Value::Cell { value: cell, .. } = self.pop() else {
+                            unreachable!("only cells are assigned to");
+                        };
+                        *cell.borrow_mut() = value;
+                        self.stack.push(Value::Record(Rc::clone(&self.null)));
+                    }
+                    Op::NotAssignable(span) => {
+                        return Err(EvalError::NotAssignable {
+                            span: span.start_len().into(),
+                        })
+                    }
+                    Op::Switch { slot, table } => {
+                        let table = &proto.tables[table as usize];
+                        pc = match &self.stack[base + slot as usize] {
+                            Value::Choice(choice) => choice
+                                .tag
+                                .last()
+                                .and_then(|ident| table.targets.get(&ident.symbol))
+                                .copied()
+                                .unwrap_or(table.default),
+                            _ => table.default,
+                        } as usize;
+                    }
+                    Op::TestInteger { slot, value, fail } => {
+                        if !matches!(self.stack[base + slot as usize], Value::Integer(int) if int == value)
+                        {
+                            pc = self.fail(base, proto, fail);
+                        }
+                    }
+                    Op::TestBool { slot, value, fail } => {
+                        if !matches!(self.stack[base + slot as usize], Value::Bool(bool) if bool == value)
+                        {
+                            pc = self.fail(base, proto, fail);
+                        }
+                    }
+                    Op::TestNull { slot, fail } => {
+                        if !matches!(&self.stack[base + slot as usize], Value::Record(map) if map.entries.is_empty())
+                        {
+                            pc = self.fail(base, proto, fail);
+                        }
+                    }
+                    Op::MatchInteger { value, fail } => {
+                        if !matches!(self.pop(), Value::Integer(int) if int == value) {
+                            pc = self.fail(base, proto, fail);
+                        }
+                    }
+                    Op::MatchBool { value, fail } => {
+                        if !matches!(self.pop(), Value::Bool(bool) if bool == value) {
+                            pc = self.fail(base, proto, fail);
+                        }
+                    }
+                    Op::MatchRecord { fields, open, fail } => match self.pop() {
+                        Value::Record(map) if open || map.entries.len() == fields.len() => {
+                            let values: Option<Vec<_>> = fields
+                                .iter()
+                                .rev()
+                                .map(|(name, _)| map.get(*name).cloned())
+                                .collect();
+                            match values {
+                                Some(values) => self.stack.extend(values),
+                                None => pc = self.fail(base, proto, fail),
+                            }
+                        }
+                        _ => pc = self.fail(base, proto, fail),
+                    },
+                    Op::MatchChoice { tag, fail } => match self.pop() {
+                        Value::Choice(choice) if choice.tag == tag => {
+                            self.stack.push(choice.value.clone());
+                        }
+                        _ => pc = self.fail(base, proto, fail),
+                    },
+                    Op::MatchStruct { name, fail } => match self.pop() {
+                        Value::Struct(s) if s.name.symbol == name.symbol => {
+                            self.stack.push(s.value.clone());
+                        }
+                        _ => pc = self.fail(base, proto, fail),
+                    },
+                    Op::Fail(fail) => pc = self.fail(base, proto, fail),
+                    Op::Refuted => return Err(EvalError::PatternMatchRefuted),
+                    Op::SyntaxError(span) => {
+                        return Err(EvalError::SyntaxError {
+                            span: span.start_len().into(),
+                        })
+                    }
+                    Op::Unsupported(what, span) => {
+                        return Err(EvalError::Unsupported {
+                            what,
+                            span: span.start_len().into(),
+                        })
+                    }
+                }
+            };
+
+            if let Some(value) = returned {
+                self.stack.truncate(base);
+                self.frames.pop();
+                if self.frames.len() == depth {
+                    return Ok(value);
+                }
+                self.stack.push(value);
+            }
+        }
+    }
+
+    /// What's at a place in the current frame, without looking inside of it if it's a cell.
+    #[inline]
+    fn get(&self, base: usize, place: Capture) -> Value<'hir> {
+        match place {
+            Capture::Local(slot) => self.stack[base + slot as usize].clone(),
+            Capture::Upvalue(index) => {
+                self.frames.last().unwrap().closure.upvalues[index as usize].clone()
+            }
+        }
+    }
+
+    /// Throws away everything an arm pushed, returning where to go next.
+    #[inline]
+    fn fail(&mut self, base: usize, proto: &Proto, target: u32) -> usize {
+        self.stack.truncate(base + proto.slots as usize);
+        target as usize
+    }
+
+    /// Calls a function from inside of an instruction, running it on top of the current frame
+    /// until it returns.
+    fn call(
+        &mut self,
+        closure: Rc<Closure<'hir>>,
+        left: Value<'hir>,
+        right: Value<'hir>,
+    ) -> Result<Value<'hir>, EvalError> {
+        let depth = self.frames.len();
+        let base = self.stack.len();
+        self.stack.push(left);
+        self.stack.push(right);
+        self.enter(closure, base);
+        self.execute(depth)
+    }
+
+    /// Applies an operator, calling the `impl` that overloads it if there is one. This is the
+    /// same as the tree-walking interpreter.
+    fn binary(
+        &mut self,
+        symbol: Symbol,
+        left: Value<'hir>,
+        right: Value<'hir>,
+    ) -> Result<Value<'hir>, EvalError> {
+        if let Some(result) = self.call_impl(symbol, &left, &right) {
+            return result;
+        }
+
+        let ordering: fn(Ordering) -> bool = match symbol {
+            Symbol::Semi => return Ok(right),
+            Symbol::Eq => return self.equal(&left, &right, true).map(Value::Bool),
+            Symbol::Lt => Ordering::is_lt,
+            Symbol::Gt => Ordering::is_gt,
+            Symbol::Le => Ordering::is_le,
+            Symbol::Ge => Ordering::is_ge,
+            _ => return arithmetic(symbol, left, right),
+        };
+        self.compare(&left, &right)
+            .map(|order| Value::Bool(ordering(order)))
+    }
+
+    /// The `impl` of `trait_` for the type of `left`, or `None` if `left` isn't a value of a
+    /// choice or struct with such an impl.
+    fn impl_for(&self, trait_: Trait, left: &Value<'hir>) -> Option<Rc<Closure<'hir>>> {
+        let constructor = match left {
+            Value::Choice(choice) => choice.tag.last()?,
+            Value::Struct(s) => &s.name,
+            _ => return None,
+        };
+        let ty = self.program.constructors.get(&constructor.symbol)?;
+        let proto = *self.program.impls.get(&(trait_, *ty))?;
+        Some(Rc::new(Closure {
+            proto,
+            upvalues: Box::new([]),
+        }))
+    }
+
+    /// Calls the `impl` that overloads `symbol` for the type of `left`, or returns `None` if
+    /// `left` isn't a value of a choice or struct with such an impl.
+    ///
+    /// `Ord` impls only define `<`, so the other comparisons swap the arguments or negate the
+    /// result.
+    fn call_impl(
+        &mut self,
+        symbol: Symbol,
+        left: &Value<'hir>,
+        right: &Value<'hir>,
+    ) -> Option<Result<Value<'hir>, EvalError>> {
+        let closure = self.impl_for(symbol.trait_()?, left)?;
+
+        let (left, right, negate) = match symbol {
+            Symbol::Gt => (right, left, false),
+            Symbol::Le => (right, left, true),
+            Symbol::Ge => (left, right, true),
+            _ => (left, right, false),
+        };
+        let result = self.call(closure, left.clone(), right.clone());
+        if !negate {
+            return Some(result);
+        }
+        Some(result.and_then(|value| match value {
+            Value::Bool(b) => Ok(Value::Bool(!b)),
+            _ => Err(EvalError::TypeMismatch),
+        }))
+    }
+
+    /// Calls the `impl` that overloads `symbol` like [`Vm::call_impl`], for a symbol that gives
+    /// back a `Bool`.
+    fn call_bool_impl(
+        &mut self,
+        symbol: Symbol,
+        left: &Value<'hir>,
+        right: &Value<'hir>,
+    ) -> Option<Result<bool, EvalError>> {
+        let result = self.call_impl(symbol, left, right)?;
+        Some(result.and_then(|value| match value {
+            Value::Bool(b) => Ok(b),
+            _ => Err(EvalError::TypeMismatch),
+        }))
+    }
+
+    /// Checks an assertion. This is the same as [`builtins::check`](crate::builtins::check).
+    fn check(
+        &mut self,
+        assertion: Assertion,
+        left: &Value<'hir>,
+        right: &Value<'hir>,
+        span: Span,
+    ) -> Result<(), EvalError> {
+        let right_is_null = matches!(right, Value::Record(map) if map.entries.is_empty());
+        match assertion {
+            Assertion::Assert => match (left, right_is_null) {
+                (Value::Bool(true), true) => Ok(()),
+                (Value::Bool(false), true) => Err(EvalError::AssertionFailed {
+                    span: span.start_len().into(),
+                }),
+                _ => Err(EvalError::TypeMismatch),
+            },
+            Assertion::AssertEq => {
+                if self.equal(left, right, false)? {
+                    Ok(())
+                } else {
+                    Err(EvalError::AssertEqFailed {
+                        left: format!("{left:?}"),
+                        right: format!("{right:?}"),
+                        span: span.start_len().into(),
+                    })
+                }
+            }
+            Assertion::Panic if right_is_null => Err(EvalError::Panicked {
+                value: format!("{left:?}"),
+                span: span.start_len().into(),
+            }),
+            Assertion::Panic => Err(EvalError::TypeMismatch),
+        }
+    }
+
+    /// Compares two values field by field, using the `impl Eq` of any choice or struct that has
+    /// one if `impls` is set. Assertions don't, the same as in the tree-walking interpreter.
+    /// Functions can't be compared.
+    fn equal(
+        &mut self,
+        left: &Value<'hir>,
+        right: &Value<'hir>,
+        impls: bool,
+    ) -> Result<bool, EvalError> {
+        if impls {
+            if let Some(result) = self.call_bool_impl(Symbol::Eq, left, right) {
+                return result;
+            }
+        }
+
+        match (left, right) {
+            (Value::Integer(n), Value::Integer(m)) => Ok(n == m),
+            (Value::Bool(a), Value::Bool(b)) => Ok(a == b),
+            (Value::Record(a), Value::Record(b)) => {
+                if a.entries.len() != b.entries.len() {
+                    return Ok(false);
+                }
+                for ((a_name, a), (b_name, b)) in a.entries.iter().zip(&b.entries) {
+                    if a_name != b_name || !self.equal(a, b, impls)? {
+                        return Ok(false);
+                    }
+                }
+                Ok(true)
+            }
+            (Value::Choice(a), Value::Choice(b)) => {
+                Ok(a.tag == b.tag && self.equal(&a.value, &b.value, impls)?)
+            }
+            (Value::Struct(a), Value::Struct(b)) => self.equal(&a.value, &b.value, impls),
+            _ => Err(EvalError::TypeMismatch),
+        }
+    }
+
+    /// Orders two values the same way as the tree-walking interpreter: `false` before `true`,
+    /// records by their fields, choices by the position of their variants and then by their
+    /// payloads, and structs by their payloads, unless the type has an `impl Ord`.
+    fn compare(&mut self, left: &Value<'hir>, right: &Value<'hir>) -> Result<Ordering, EvalError> {
+        if let Some(less) = self.call_bool_impl(Symbol::Lt, left, right) {
+            if less? {
+                return Ok(Ordering::Less);
+            }
+            let greater = self
+                .call_bool_impl(Symbol::Lt, right, left)
+                .expect("both sides have the same type")?;
+            return Ok(if greater {
+                Ordering::Greater
+            } else {
+                Ordering::Equal
+            });
+        }
+
+        let program = self.program;
+        let variant_index = |tag: hir::Path<'_>| match tag {
+            [choice, variant] => program
+                .variants
+                .get(&(choice.symbol, variant.symbol))
+                .copied()
+                .ok_or(EvalError::TypeMismatch),
+            _ => Err(EvalError::TypeMismatch),
+        };
+
+        match (left, right) {
+            (Value::Integer(n), Value::Integer(m)) => Ok(n.cmp(m)),
+            (Value::Bool(a), Value::Bool(b)) => Ok(a.cmp(b)),
+            (Value::Record(a), Value::Record(b)) if a.entries.len() == b.entries.len() => {
+                for ((a_name, a), (b_name, b)) in a.entries.iter().zip(&b.entries) {
+                    if a_name != b_name {
+                        return Err(EvalError::TypeMismatch);
+                    }
+                    match self.compare(a, b)? {
+                        Ordering::Equal => {}
+                        ordering => return Ok(ordering),
+                    }
+                }
+                Ok(Ordering::Equal)
+            }
+            (Value::Choice(a), Value::Choice(b)) => {
+                match variant_index(a.tag)?.cmp(&variant_index(b.tag)?) {
+                    Ordering::Equal => self.compare(&a.value, &b.value),
+                    ordering => Ok(ordering),
+                }
+            }
+            (Value::Struct(a), Value::Struct(b)) => self.compare(&a.value, &b.value),
+            _ => Err(EvalError::TypeMismatch),
+        }
+    }
+}
+
+/// Applies an arithmetic operator to two integers.
+#[inline]
+fn arithmetic<'hir>(
+    symbol: Symbol,
+    left: Value<'hir>,
+    right: Value<'hir>,
+) -> Result<Value<'hir>, EvalError> {
+    let (Value::Integer(n), Value::Integer(m)) = (left, right) else {
+        return Err(EvalError::TypeMismatch);
+    };
+    Ok(match symbol {
+        Symbol::Plus => Value::Integer(n.wrapping_add(m)),
+        Symbol::Star => Value::Integer(n.wrapping_mul(m)),
+        Symbol::Minus => Value::Integer(n.wrapping_sub(m)),
+        Symbol::Slash => Value::Integer(n.checked_div(m).ok_or(EvalError::DivideByZero)?),
+        Symbol::Percent => Value::Integer(n.checked_rem(m).ok_or(EvalError::DivideByZero)?),
+        _ => unreachable!("not arithmetic"),
+    })
+}
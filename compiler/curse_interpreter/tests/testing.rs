@@ -0,0 +1,114 @@
+//! Runs the `#[test]` functions in `tests/testing`, which should all pass except for the ones in
+//! `failing.curse`, which should each fail the way their names say.
+
+use std::{fs, path::Path};
+
+use bumpalo::Bump;
+use curse_interpreter::{
+    error::EvalError,
+    flatten_asts,
+    testing::{self, Failure},
+};
+use curse_test_support::lock;
+
+/// Runs every test in `program` whose name contains `filter`, returning the name of each test
+/// along with how it went.
+fn run(program: &Path, filter: &str) -> Vec<(String, Result<(), Failure>)> {
+    // `dynamic_import`s are relative to the working directory.
+    std::env::set_current_dir(env!("CARGO_MANIFEST_DIR")).unwrap();
+
+    let ast_program = curse_test_support::parse_with(|interner| {
+        flatten_asts(interner, program.to_str().unwrap()).expect("parses")
+    });
+
+    let arena = Bump::new();
+    let hir_program = curse_test_support::lower_program(&arena, &ast_program);
+
+    testing::tests(&hir_program, filter)
+        .into_iter()
+        .map(|test| (test.ident.to_string(), testing::run(&hir_program, test)))
+        .collect()
+}
+
+#[test]
+fn passing_suites() {
+    let _interner = lock();
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let mut suites: Vec<_> = fs::read_dir(manifest_dir.join("tests/testing"))
+        .expect("directory exists")
+        .map(|entry| entry.expect("readable entry").path())
+        .filter(|path| path.file_stem().is_some_and(|stem| stem != "failing"))
+        .collect();
+    suites.sort();
+
+    for suite in suites {
+        let results = run(&suite, "");
+        assert!(!results.is_empty(), "{} has no tests", suite.display());
+        for (name, result) in results {
+            assert!(result.is_ok(), "{name} in {}: {result:?}", suite.display());
+        }
+    }
+}
+
+#[test]
+fn failing_tests_fail_the_way_their_names_say() {
+    let _interner = lock();
+    let failing = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/testing/failing.curse");
+    let results = run(&failing, "");
+    let names: Vec<_> = results.iter().map(|(name, _)| name.as_str()).collect();
+    assert_eq!(
+        names,
+        [
+            "assert_eq_fails",
+            "assert_fails",
+            "divides_by_zero",
+            "not_assignable",
+            "panics",
+            "passes",
+            "shadowed_assert",
+            "unbound"
+        ]
+    );
+    for (name, result) in &results {
+        match (name.as_str(), result) {
+            (
+                "assert_eq_fails",
+                Err(Failure::Error(EvalError::AssertEqFailed { left, right, span })),
+            ) => {
+                assert_eq!(left.replace(char::is_whitespace, ""), "{a:1,b:4,}");
+                assert_eq!(right.replace(char::is_whitespace, ""), "{a:1,b:5,}");
+                assert_eq!(
+                    &fs::read_to_string(&failing).unwrap()[span.offset()..][..span.len()],
+                    "{ a: 1, b: 2 in double } assert_eq { a: 1, b: 5 }"
+                );
+            }
+            ("assert_fails", Err(Failure::Error(EvalError::AssertionFailed { .. })))
+            | ("panics", Err(Failure::Error(EvalError::Panicked { .. })))
+            | ("unbound", Err(Failure::Error(EvalError::UnboundVariable { .. })))
+            | ("not_assignable", Err(Failure::Error(EvalError::NotAssignable { .. })))
+            | ("divides_by_zero", Err(Failure::Error(EvalError::DivideByZero)))
+            | ("passes" | "shadowed_assert", Ok(())) => {}
+            _ => panic!("{name} didn't go as expected: {result:?}"),
+        }
+    }
+}
+
+#[test]
+fn tests_are_filtered_by_name() {
+    let _interner = lock();
+    let failing = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/testing/failing.curse");
+    let filtered: Vec<_> = run(&failing, "ass")
+        .into_iter()
+        .map(|(name, _)| name)
+        .collect();
+    assert_eq!(
+        filtered,
+        [
+            "assert_eq_fails",
+            "assert_fails",
+            "not_assignable",
+            "passes",
+            "shadowed_assert"
+        ]
+    );
+}
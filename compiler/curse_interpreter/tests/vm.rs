@@ -0,0 +1,70 @@
+//! Runs every program in the interpreter's directory, the Project Euler solutions, the CPS
+//! expected-output programs, and the programs in `tests/vm` with both the tree-walking interpreter
+//! and the bytecode VM, and checks that they end the same way, errors included.
+
+use std::{fs, path::Path};
+
+use bumpalo::Bump;
+use curse_interpreter::{evaluation, flatten_asts, vm};
+use curse_test_support::lock;
+
+fn compare(program: &Path) {
+    let ast_program = curse_test_support::parse_with(|interner| {
+        flatten_asts(interner, program.to_str().unwrap()).expect("parses")
+    });
+
+    let arena = Bump::new();
+    let hir_program = curse_test_support::lower_program(&arena, &ast_program);
+
+    let expected = format!("{:#?}", evaluation::execute_program(&hir_program));
+    let actual = format!("{:#?}", vm::execute_program(&hir_program));
+    assert_eq!(actual, expected, "{}", program.display());
+}
+
+/// Checks every program in `dir`.
+fn agree(dir: &str) {
+    let _interner = lock();
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+
+    let mut programs: Vec<_> = fs::read_dir(manifest_dir.join(dir))
+        .expect("directory exists")
+        .map(|entry| entry.expect("readable entry").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "curse"))
+        .collect();
+    programs.sort();
+
+    // `dynamic_import`s are relative to the working directory.
+    std::env::set_current_dir(manifest_dir).unwrap();
+
+    // the tree-walking interpreter recurses a lot
+    std::thread::Builder::new()
+        .stack_size(1 << 30)
+        .spawn(move || {
+            for program in programs {
+                compare(&program);
+            }
+        })
+        .unwrap()
+        .join()
+        .unwrap();
+}
+
+#[test]
+fn interpreter_programs() {
+    agree(".");
+}
+
+#[test]
+fn project_euler() {
+    agree("project_euler");
+}
+
+#[test]
+fn expected_programs() {
+    agree("../curse_cps/tests/expected");
+}
+
+#[test]
+fn vm_programs() {
+    agree("tests/vm");
+}
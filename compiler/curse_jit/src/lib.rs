@@ -0,0 +1,704 @@
+//! An in-process JIT for the CPS IR using Cranelift.
+//!
+//! This works the same way as the C backend: every function in a `Fix` is closure converted into
+//! a Cranelift function that takes its own closure followed by up to three arguments, and every
+//! application is a tail call through the code pointer stored in the closure. Since everything is
+//! a tail call, the stack never grows, and `Halt` simply returns its value all the way back out to
+//! the caller of `run`.
+//!
+//! Objects are allocated on a `curse_runtime` heap by bumping its pointer inline. Every function
+//! starts by making sure there's room for everything it could allocate, collecting the heap with
+//! its closure and arguments as the roots if there isn't, so nothing is live in a register when a
+//! collection happens.
+
+use std::collections::HashMap;
+
+use cranelift_codegen::{
+    ir::{
+        condcodes::IntCC, types::I64, AbiParam, InstBuilder, MemFlags, Signature, StackSlotData,
+        StackSlotKind, Value as IrValue,
+    },
+    isa::CallConv,
+    settings::{self, Configurable},
+    Context,
+};
+use cranelift_frontend::{FunctionBuilder, FunctionBuilderContext};
+use cranelift_jit::{JITBuilder, JITModule};
+use cranelift_module::{default_libcall_names, FuncId, Linkage, Module, ModuleError};
+use curse_cps::cpsexpr::{CPSExpr, Function, Primop, Value};
+use curse_interner::InternedString;
+use curse_runtime::{Heap, KIND_CLOSURE, KIND_RECORD, KIND_STRING, LIMIT_OFFSET, PTR_OFFSET};
+use miette::Diagnostic;
+use thiserror::Error;
+
+mod runtime;
+
+pub use runtime::Value as JitValue;
+
+#[derive(Debug, Diagnostic, Error)]
+pub enum Error {
+    #[error("Unbound variable `{0}`")]
+    #[diagnostic(help("Every variable has to be defined somewhere in the program, including the standard library."))]
+    UnboundVariable(InternedString),
+
+    #[error("Cranelift error: {0}")]
+    Cranelift(String),
+}
+
+impl From<ModuleError> for Error {
+    fn from(error: ModuleError) -> Self {
+        Error::Cranelift(error.to_string())
+    }
+}
+
+/// Compiles `expr` and runs it, returning the value it halts with.
+pub fn run(expr: &CPSExpr) -> Result<JitValue, Error> {
+    run_with_heap_size(expr, curse_runtime::DEFAULT_WORDS)
+}
+
+/// Like [`run`], but starts out with a heap of `words` words instead of the default.
+pub fn run_with_heap_size(expr: &CPSExpr, words: usize) -> Result<JitValue, Error> {
+    let mut jit = Jit::new(words)?;
+    let result = jit.compile(expr).map(|entry| {
+        // SAFETY: `entry` was just compiled with this signature, and the value it returns is
+        // read before the heap is freed.
+        unsafe {
+            let entry: extern "C" fn() -> u64 = std::mem::transmute(entry);
+            JitValue::from_word(entry(), &jit.strings)
+        }
+    });
+
+    // SAFETY: nothing compiled by this module is still running.
+    unsafe { jit.module.free_memory() };
+    result
+}
+
+struct Jit {
+    module: JITModule,
+    ctx: Context,
+    builder_ctx: FunctionBuilderContext,
+    /// The signature every CPS function has: its own closure, then up to three arguments.
+    signature: Signature,
+    collect: FuncId,
+    compare: FuncId,
+    divide_by_zero: FuncId,
+    /// Boxed so that compiled code can refer to it by address.
+    heap: Box<Heap>,
+    strings: Vec<InternedString>,
+    /// The object for each string, which live outside of the heap since they're never freed.
+    string_objects: HashMap<InternedString, Box<[u64; 2]>>,
+}
+
+/// The compiled code for a CPS function, along with the free variables its closure stores.
+struct Compiled {
+    id: FuncId,
+    free_vars: Vec<InternedString>,
+}
+
+/// Every CPS function being compiled, keyed by address since they don't have unique names.
+type Functions = HashMap<*const Function, Compiled>;
+
+impl Jit {
+    fn new(words: usize) -> Result<Self, Error> {
+        let mut flags = settings::builder();
+        flags.set("opt_level", "speed").unwrap();
+        // Cranelift needs these for tail calls
+        flags.set("preserve_frame_pointers", "true").unwrap();
+        let isa = cranelift_native::builder()
+            .map_err(|e| Error::Cranelift(e.to_string()))?
+            .finish(settings::Flags::new(flags))
+            .map_err(|e| Error::Cranelift(e.to_string()))?;
+
+        let mut builder = JITBuilder::with_isa(isa, default_libcall_names());
+        builder.symbol("curse_collect", curse_runtime::curse_collect as *const u8);
+        builder.symbol("curse_compare", curse_runtime::curse_compare as *const u8);
+        builder.symbol(
+            "curse_divide_by_zero",
+            runtime::curse_divide_by_zero as *const u8,
+        );
+        let mut module = JITModule::new(builder);
+
+        let mut signature = Signature::new(CallConv::Tail);
+        signature.params = vec![AbiParam::new(I64); 4];
+        signature.returns = vec![AbiParam::new(I64)];
+
+        let mut collect_signature = module.make_signature();
+        collect_signature.params = vec![AbiParam::new(I64); 4];
+        let collect =
+            module.declare_function("curse_collect", Linkage::Import, &collect_signature)?;
+
+        let mut compare_signature = module.make_signature();
+        compare_signature.params = vec![AbiParam::new(I64); 2];
+        compare_signature.returns = vec![AbiParam::new(I64)];
+        let compare =
+            module.declare_function("curse_compare", Linkage::Import, &compare_signature)?;
+
+        let divide_by_zero = module.declare_function(
+            "curse_divide_by_zero",
+            Linkage::Import,
+            &module.make_signature(),
+        )?;
+
+        Ok(Jit {
+            ctx: module.make_context(),
+            module,
+            builder_ctx: FunctionBuilderContext::new(),
+            signature,
+            collect,
+            compare,
+            divide_by_zero,
+            heap: Box::new(Heap::new(words)),
+            strings: vec![],
+            string_objects: HashMap::new(),
+        })
+    }
+
+    /// Compiles every function in `expr` along with a function that runs `expr` itself, returning
+    /// a pointer to the latter.
+    fn compile(&mut self, expr: &CPSExpr) -> Result<*const u8, Error> {
+        let mut jobs = vec![];
+        let mut functions = Functions::new();
+        self.collect(expr, &mut jobs, &mut functions)?;
+
+        for function in jobs {
+            self.define(&function.continuation, Some(function), &functions)?;
+        }
+
+        let body = self.define(expr, None, &functions)?;
+
+        // The body uses the tail calling convention, so it needs a wrapper that Rust can call.
+        let entry = self
+            .module
+            .declare_anonymous_function(&self.module.make_signature())?;
+        self.ctx.func.signature = self.module.make_signature();
+        self.ctx.func.signature.returns = vec![AbiParam::new(I64)];
+        let mut builder = FunctionBuilder::new(&mut self.ctx.func, &mut self.builder_ctx);
+        let block = builder.create_block();
+        builder.switch_to_block(block);
+        builder.seal_block(block);
+        let body_ref = self.module.declare_func_in_func(body, builder.func);
+        let zero = builder.ins().iconst(I64, 0);
+        let call = builder.ins().call(body_ref, &[zero; 4]);
+        let result = builder.inst_results(call)[0];
+        builder.ins().return_(&[result]);
+        builder.finalize();
+        self.module.define_function(entry, &mut self.ctx)?;
+        self.module.clear_context(&mut self.ctx);
+
+        self.module.finalize_definitions()?;
+        Ok(self.module.get_finalized_function(entry))
+    }
+
+    /// Declares every function in `expr`, and allocates every string it uses.
+    fn collect<'cps>(
+        &mut self,
+        expr: &'cps CPSExpr,
+        jobs: &mut Vec<&'cps Function>,
+        functions: &mut Functions,
+    ) -> Result<(), Error> {
+        for value in expr.values() {
+            if let Value::String(string) = value {
+                if !self.string_objects.contains_key(&string) {
+                    let object = [
+                        curse_runtime::header(KIND_STRING, 1),
+                        self.strings.len() as u64,
+                    ];
+                    self.strings.push(string);
+                    self.string_objects.insert(string, Box::new(object));
+                }
+            }
+        }
+
+        match expr {
+            CPSExpr::Primop(primop) => {
+                for continuation in primop.continuations.iter() {
+                    self.collect(continuation, jobs, functions)?;
+                }
+            }
+            CPSExpr::Record(record) => self.collect(&record.continuation, jobs, functions)?,
+            CPSExpr::Select(select) => self.collect(&select.continuation, jobs, functions)?,
+            CPSExpr::Fix(fix) => {
+                for function in fix.functions.iter() {
+                    let name = function
+                        .name
+                        .var()
+                        .expect("functions are named by variables");
+                    let mut free_vars: Vec<_> = function
+                        .free_vars()
+                        .into_iter()
+                        .filter(|free_var| *free_var != name)
+                        .collect();
+                    free_vars.sort_by_cached_key(|free_var| free_var.string().to_string());
+
+                    let id = self.module.declare_anonymous_function(&self.signature)?;
+                    functions.insert(function, Compiled { id, free_vars });
+                    jobs.push(function);
+                    self.collect(&function.continuation, jobs, functions)?;
+                }
+                self.collect(&fix.continuation, jobs, functions)?;
+            }
+            CPSExpr::Appl(_) | CPSExpr::Halt(_) => {}
+        }
+
+        Ok(())
+    }
+
+    /// Defines a function that runs `body`. If it's the code for a CPS function, `function` says
+    /// which one so that its parameters and free variables can be bound.
+    fn define(
+        &mut self,
+        body: &CPSExpr,
+        function: Option<&Function>,
+        functions: &Functions,
+    ) -> Result<FuncId, Error> {
+        let id = match function {
+            Some(function) => functions[&(function as *const Function)].id,
+            None => self.module.declare_anonymous_function(&self.signature)?,
+        };
+
+        self.ctx.func.signature = self.signature.clone();
+        let mut builder = FunctionBuilder::new(&mut self.ctx.func, &mut self.builder_ctx);
+        let block = builder.create_block();
+        builder.append_block_params_for_function_params(block);
+        builder.switch_to_block(block);
+        builder.seal_block(block);
+        let params = builder.block_params(block).to_vec();
+
+        let signature = builder.import_signature(self.signature.clone());
+        let collect = self.module.declare_func_in_func(self.collect, builder.func);
+        let compare = self.module.declare_func_in_func(self.compare, builder.func);
+        let divide_by_zero = self
+            .module
+            .declare_func_in_func(self.divide_by_zero, builder.func);
+
+        let mut lowering = Lowering {
+            builder,
+            module: &mut self.module,
+            signature,
+            collect,
+            compare,
+            divide_by_zero,
+            heap: &mut *self.heap as *mut Heap as i64,
+            string_objects: &self.string_objects,
+            functions,
+        };
+
+        let params = match allocation(body, functions) {
+            0 => params,
+            words => lowering.reserve(words, &params),
+        };
+
+        let mut env = HashMap::new();
+        if let Some(function) = function {
+            let free_vars = &functions[&(function as *const Function)].free_vars;
+            for (index, free_var) in free_vars.iter().enumerate() {
+                let value = lowering.builder.ins().load(
+                    I64,
+                    MemFlags::trusted(),
+                    params[0],
+                    field_offset(index + 1),
+                );
+                env.insert(*free_var, value);
+            }
+            env.insert(function.name.var().unwrap(), params[0]);
+            for (param, value) in [Some(function.left), Some(function.right), function.ret]
+                .into_iter()
+                .zip(&params[1..])
+            {
+                if let Some(Value::Var(param)) = param {
+                    env.insert(param, *value);
+                }
+            }
+        }
+
+        if let Err(error) = lowering.expr(body, env) {
+            self.module.clear_context(&mut self.ctx);
+            return Err(error);
+        }
+        lowering.builder.finalize();
+
+        self.module.define_function(id, &mut self.ctx)?;
+        self.module.clear_context(&mut self.ctx);
+        Ok(id)
+    }
+}
+
+/// The offset of the `index`th field of an object, past its header.
+fn field_offset(index: usize) -> i32 {
+    (8 * (index + 1)) as i32
+}
+
+/// How many words running `expr` allocates at most, not counting what the functions it defines
+/// allocate when they're called.
+fn allocation(expr: &CPSExpr, functions: &Functions) -> usize {
+    match expr {
+        CPSExpr::Primop(primop) => primop
+            .continuations
+            .iter()
+            .map(|continuation| allocation(continuation, functions))
+            .max()
+            .unwrap_or(0),
+        CPSExpr::Record(record) => {
+            record.values.len() + 1 + allocation(&record.continuation, functions)
+        }
+        CPSExpr::Select(select) => allocation(&select.continuation, functions),
+        CPSExpr::Fix(fix) => {
+            let closures: usize = fix
+                .functions
+                .iter()
+                .map(|function| functions[&(function as *const Function)].free_vars.len() + 2)
+                .sum();
+            closures + allocation(&fix.continuation, functions)
+        }
+        CPSExpr::Appl(_) | CPSExpr::Halt(_) => 0,
+    }
+}
+
+struct Lowering<'a> {
+    builder: FunctionBuilder<'a>,
+    module: &'a mut JITModule,
+    signature: cranelift_codegen::ir::SigRef,
+    collect: cranelift_codegen::ir::FuncRef,
+    compare: cranelift_codegen::ir::FuncRef,
+    divide_by_zero: cranelift_codegen::ir::FuncRef,
+    /// The address of the heap.
+    heap: i64,
+    string_objects: &'a HashMap<InternedString, Box<[u64; 2]>>,
+    functions: &'a Functions,
+}
+
+impl Lowering<'_> {
+    fn value(
+        &mut self,
+        value: Value,
+        env: &HashMap<InternedString, IrValue>,
+    ) -> Result<IrValue, Error> {
+        match value {
+            Value::Var(name) => env.get(&name).copied().ok_or(Error::UnboundVariable(name)),
+            Value::Int(n) => Ok(self.builder.ins().iconst(I64, ((n as i64) << 1) | 1)),
+            Value::String(string) => {
+                let object = self.string_objects[&string].as_ptr();
+                Ok(self.builder.ins().iconst(I64, object as i64))
+            }
+        }
+    }
+
+    /// Makes sure that the heap has room for `words` more words, collecting it with `params` as
+    /// the roots if it doesn't. Returns the values of `params` after any collection.
+    fn reserve(&mut self, words: usize, params: &[IrValue]) -> Vec<IrValue> {
+        let heap = self.builder.ins().iconst(I64, self.heap);
+        let ptr = self
+            .builder
+            .ins()
+            .load(I64, MemFlags::trusted(), heap, PTR_OFFSET);
+        let limit = self
+            .builder
+            .ins()
+            .load(I64, MemFlags::trusted(), heap, LIMIT_OFFSET);
+        let available = self.builder.ins().isub(limit, ptr);
+        let full =
+            self.builder
+                .ins()
+                .icmp_imm(IntCC::UnsignedLessThan, available, 8 * words as i64);
+
+        let collect_block = self.builder.create_block();
+        let body_block = self.builder.create_block();
+        for _ in params {
+            self.builder.append_block_param(body_block, I64);
+        }
+        self.builder
+            .ins()
+            .brif(full, collect_block, &[], body_block, params);
+        self.builder.seal_block(collect_block);
+
+        // The roots have to be in memory so that the collector can update them.
+        self.builder.switch_to_block(collect_block);
+        self.builder.set_cold_block(collect_block);
+        let roots = self.builder.create_sized_stack_slot(StackSlotData::new(
+            StackSlotKind::ExplicitSlot,
+            8 * params.len() as u32,
+            3,
+        ));
+        for (index, param) in params.iter().enumerate() {
+            self.builder
+                .ins()
+                .stack_store(*param, roots, 8 * index as i32);
+        }
+        let roots_addr = self.builder.ins().stack_addr(I64, roots, 0);
+        let count = self.builder.ins().iconst(I64, params.len() as i64);
+        let needed = self.builder.ins().iconst(I64, words as i64);
+        self.builder
+            .ins()
+            .call(self.collect, &[heap, roots_addr, count, needed]);
+        let reloaded: Vec<_> = (0..params.len())
+            .map(|index| self.builder.ins().stack_load(I64, roots, 8 * index as i32))
+            .collect();
+        self.builder.ins().jump(body_block, &reloaded);
+        self.builder.seal_block(body_block);
+
+        self.builder.switch_to_block(body_block);
+        self.builder.block_params(body_block).to_vec()
+    }
+
+    /// Allocates an object on the heap, which [`Lowering::reserve`] must have made room for.
+    fn alloc(&mut self, kind: u64, len: usize) -> IrValue {
+        let heap = self.builder.ins().iconst(I64, self.heap);
+        let object = self
+            .builder
+            .ins()
+            .load(I64, MemFlags::trusted(), heap, PTR_OFFSET);
+        let next = self.builder.ins().iadd_imm(object, 8 * (len as i64 + 1));
+        self.builder
+            .ins()
+            .store(MemFlags::trusted(), next, heap, PTR_OFFSET);
+
+        let header = curse_runtime::header(kind, len);
+        let header = self.builder.ins().iconst(I64, header as i64);
+        self.builder
+            .ins()
+            .store(MemFlags::trusted(), header, object, 0);
+        object
+    }
+
+    fn store_field(&mut self, object: IrValue, index: usize, value: IrValue) {
+        self.builder
+            .ins()
+            .store(MemFlags::trusted(), value, object, field_offset(index));
+    }
+
+    /// Turns an untagged integer into a tagged one, wrapping it to 32 bits.
+    fn tag(&mut self, n: IrValue) -> IrValue {
+        let n = self.builder.ins().band_imm(n, 0xffff_ffff);
+        let n = self.builder.ins().ishl_imm(n, 1);
+        self.builder.ins().bor_imm(n, 1)
+    }
+
+    fn untag(&mut self, value: IrValue) -> IrValue {
+        self.builder.ins().ushr_imm(value, 1)
+    }
+
+    fn expr(
+        &mut self,
+        expr: &CPSExpr,
+        mut env: HashMap<InternedString, IrValue>,
+    ) -> Result<(), Error> {
+        match expr {
+            CPSExpr::Primop(primop) => {
+                let left = self.value(primop.left, &env)?;
+                let right = self.value(primop.right, &env)?;
+
+                let condition = match primop.primop {
+                    Primop::Eq => Some(self.compare(IntCC::Equal, left, right)),
+                    Primop::Lt => Some(self.compare(IntCC::UnsignedLessThan, left, right)),
+                    Primop::Gt => Some(self.compare(IntCC::UnsignedGreaterThan, left, right)),
+                    Primop::Le => Some(self.compare(IntCC::UnsignedLessThanOrEqual, left, right)),
+                    Primop::Ge => {
+                        Some(self.compare(IntCC::UnsignedGreaterThanOrEqual, left, right))
+                    }
+                    Primop::Record => Some(self.is_record(left)),
+                    _ => None,
+                };
+
+                if let Some(condition) = condition {
+                    let then_block = self.builder.create_block();
+                    let else_block = self.builder.create_block();
+                    self.builder
+                        .ins()
+                        .brif(condition, then_block, &[], else_block, &[]);
+                    self.builder.seal_block(then_block);
+                    self.builder.seal_block(else_block);
+
+                    self.builder.switch_to_block(then_block);
+                    self.expr(&primop.continuations[0], env.clone())?;
+                    self.builder.switch_to_block(else_block);
+                    return self.expr(&primop.continuations[1], env);
+                }
+
+                let result = match primop.primop {
+                    Primop::Semi => right,
+                    op => {
+                        let left = self.untag(left);
+                        let right = self.untag(right);
+                        if matches!(op, Primop::Div | Primop::Mod) {
+                            self.check_divisor(right);
+                        }
+
+                        let result = match op {
+                            Primop::Plus => self.builder.ins().iadd(left, right),
+                            Primop::Minus => self.builder.ins().isub(left, right),
+                            Primop::Times => self.builder.ins().imul(left, right),
+                            Primop::Div => self.builder.ins().udiv(left, right),
+                            Primop::Mod => self.builder.ins().urem(left, right),
+                            _ => unreachable!("branching primops are handled above"),
+                        };
+                        self.tag(result)
+                    }
+                };
+
+                env.insert(primop.name, result);
+                self.expr(&primop.continuations[0], env)
+            }
+            CPSExpr::Record(record) => {
+                let object = self.alloc(KIND_RECORD, record.values.len());
+                for (index, value) in record.values.iter().enumerate() {
+                    let value = self.value(*value, &env)?;
+                    self.store_field(object, index, value);
+                }
+                env.insert(record.name, object);
+                self.expr(&record.continuation, env)
+            }
+            CPSExpr::Select(select) => {
+                let record = self.value(select.record, &env)?;
+                let value = self.builder.ins().load(
+                    I64,
+                    MemFlags::trusted(),
+                    record,
+                    field_offset(select.index),
+                );
+                env.insert(select.result, value);
+                self.expr(&select.continuation, env)
+            }
+            CPSExpr::Appl(appl) => {
+                let function = self.value(appl.function, &env)?;
+                let code =
+                    self.builder
+                        .ins()
+                        .load(I64, MemFlags::trusted(), function, field_offset(0));
+
+                let mut args = vec![function];
+                for arg in appl.args.iter() {
+                    args.push(self.value(*arg, &env)?);
+                }
+                while args.len() < 4 {
+                    args.push(self.builder.ins().iconst(I64, 0));
+                }
+
+                self.builder
+                    .ins()
+                    .return_call_indirect(self.signature, code, &args);
+                Ok(())
+            }
+            CPSExpr::Fix(fix) => {
+                let functions = self.functions;
+
+                // Allocate all of the closures first so that they can refer to each other.
+                let mut closures = vec![];
+                for function in fix.functions.iter() {
+                    let compiled = &functions[&(function as *const Function)];
+                    let closure = self.alloc(KIND_CLOSURE, compiled.free_vars.len() + 1);
+                    env.insert(function.name.var().unwrap(), closure);
+                    closures.push((compiled, closure));
+                }
+
+                for (compiled, closure) in closures {
+                    let func_ref = self
+                        .module
+                        .declare_func_in_func(compiled.id, self.builder.func);
+                    let code = self.builder.ins().func_addr(I64, func_ref);
+                    self.store_field(closure, 0, code);
+                    for (index, free_var) in compiled.free_vars.iter().enumerate() {
+                        let value = self.value(Value::Var(*free_var), &env)?;
+                        self.store_field(closure, index + 1, value);
+                    }
+                }
+
+                self.expr(&fix.continuation, env)
+            }
+            CPSExpr::Halt(value) => {
+                let value = self.value(*value, &env)?;
+                self.builder.ins().return_(&[value]);
+                Ok(())
+            }
+        }
+    }
+
+    /// Compares two values with `cc`, which has to be one of the unsigned conditions. Tagging
+    /// preserves the order of integers, so they're compared directly, and anything else goes
+    /// through the runtime so that records are compared by their fields.
+    fn compare(&mut self, cc: IntCC, left: IrValue, right: IrValue) -> IrValue {
+        let int_block = self.builder.create_block();
+        let object_block = self.builder.create_block();
+        let done_block = self.builder.create_block();
+        self.builder
+            .append_block_param(done_block, cranelift_codegen::ir::types::I8);
+
+        let both = self.builder.ins().band(left, right);
+        let both_ints = self.builder.ins().band_imm(both, 1);
+        self.builder
+            .ins()
+            .brif(both_ints, int_block, &[], object_block, &[]);
+        self.builder.seal_block(int_block);
+        self.builder.seal_block(object_block);
+
+        self.builder.switch_to_block(int_block);
+        let result = self.builder.ins().icmp(cc, left, right);
+        self.builder.ins().jump(done_block, &[result]);
+
+        self.builder.switch_to_block(object_block);
+        let call = self.builder.ins().call(self.compare, &[left, right]);
+        let ordering = self.builder.inst_results(call)[0];
+        // the runtime returns -1, 0 or 1, which has to be compared against 0 as a signed number
+        let signed = match cc {
+            IntCC::UnsignedLessThan => IntCC::SignedLessThan,
+            IntCC::UnsignedGreaterThan => IntCC::SignedGreaterThan,
+            IntCC::UnsignedLessThanOrEqual => IntCC::SignedLessThanOrEqual,
+            IntCC::UnsignedGreaterThanOrEqual => IntCC::SignedGreaterThanOrEqual,
+            cc => cc,
+        };
+        let result = self.builder.ins().icmp_imm(signed, ordering, 0);
+        self.builder.ins().jump(done_block, &[result]);
+        self.builder.seal_block(done_block);
+
+        self.builder.switch_to_block(done_block);
+        self.builder.block_params(done_block)[0]
+    }
+
+    fn is_record(&mut self, value: IrValue) -> IrValue {
+        // Integers have their lowest bit set, so only check the header of pointers.
+        let pointer_block = self.builder.create_block();
+        let done_block = self.builder.create_block();
+        self.builder
+            .append_block_param(done_block, cranelift_codegen::ir::types::I8);
+
+        let is_int = self.builder.ins().band_imm(value, 1);
+        let no = self
+            .builder
+            .ins()
+            .iconst(cranelift_codegen::ir::types::I8, 0);
+        self.builder
+            .ins()
+            .brif(is_int, done_block, &[no], pointer_block, &[]);
+        self.builder.seal_block(pointer_block);
+
+        self.builder.switch_to_block(pointer_block);
+        let header = self.builder.ins().load(I64, MemFlags::trusted(), value, 0);
+        let kind = self.builder.ins().band_imm(header, 3);
+        let is_record = self
+            .builder
+            .ins()
+            .icmp_imm(IntCC::Equal, kind, KIND_RECORD as i64);
+        self.builder.ins().jump(done_block, &[is_record]);
+        self.builder.seal_block(done_block);
+
+        self.builder.switch_to_block(done_block);
+        self.builder.block_params(done_block)[0]
+    }
+
+    fn check_divisor(&mut self, divisor: IrValue) {
+        let fail_block = self.builder.create_block();
+        let ok_block = self.builder.create_block();
+        self.builder
+            .ins()
+            .brif(divisor, ok_block, &[], fail_block, &[]);
+        self.builder.seal_block(fail_block);
+        self.builder.seal_block(ok_block);
+
+        self.builder.switch_to_block(fail_block);
+        self.builder.ins().call(self.divide_by_zero, &[]);
+        self.builder
+            .ins()
+            .trap(cranelift_codegen::ir::TrapCode::unwrap_user(1));
+
+        self.builder.switch_to_block(ok_block);
+    }
+}
@@ -0,0 +1,64 @@
+//! The parts of the runtime that are specific to the JIT. The heap itself, along with the layout
+//! of values that the generated code shares with it, lives in `curse_runtime`.
+
+use curse_interner::InternedString;
+use curse_runtime::{field, is_int, kind, len, KIND_CLOSURE, KIND_RECORD, KIND_STRING};
+
+pub extern "C" fn curse_divide_by_zero() {
+    eprintln!("error: division by zero");
+    std::process::exit(1);
+}
+
+/// A value that a JIT compiled program halted with, copied out of the heap.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Value {
+    Int(u32),
+    String(InternedString),
+    Record(Vec<Value>),
+    Function,
+}
+
+impl Value {
+    /// Reads the value that `word` represents.
+    ///
+    /// # Safety
+    ///
+    /// `word` must have come from JIT compiled code, and the heap must not have been collected or
+    /// freed since.
+    pub unsafe fn from_word(word: u64, strings: &[InternedString]) -> Value {
+        if is_int(word) {
+            return Value::Int((word >> 1) as u32);
+        }
+
+        match kind(word) {
+            KIND_RECORD => Value::Record(
+                (0..len(word))
+                    .map(|index| Value::from_word(*field(word, index), strings))
+                    .collect(),
+            ),
+            KIND_CLOSURE => Value::Function,
+            KIND_STRING => Value::String(strings[*field(word, 0) as usize]),
+            kind => unreachable!("invalid object kind {kind}"),
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Value::Int(n) => write!(f, "{n}"),
+            Value::String(s) => write!(f, "\"{s}\""),
+            Value::Record(values) => {
+                f.write_str("{")?;
+                for (i, value) in values.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{value}")?;
+                }
+                f.write_str("}")
+            }
+            Value::Function => f.write_str("<function>"),
+        }
+    }
+}
@@ -0,0 +1,75 @@
+//! Runs the Project Euler programs and the CPS expected-output programs through the JIT, and
+//! checks that they halt with the same value as in the CPS reference evaluator.
+
+use std::{fs, path::Path};
+
+use bumpalo::Bump;
+use curse_cps::{eval, optimize};
+use curse_jit::JitValue;
+use curse_test_support::lock;
+
+fn observe(value: &eval::Value) -> JitValue {
+    match value {
+        eval::Value::Int(n) => JitValue::Int(*n),
+        eval::Value::String(s) => JitValue::String(*s),
+        eval::Value::Record(values) => JitValue::Record(values.iter().map(observe).collect()),
+        eval::Value::Function(_) => JitValue::Function,
+    }
+}
+
+/// Checks every program in `dir`.
+fn agree(dir: &str) {
+    let _interner = lock();
+    let manifest_dir = Path::new(env!("CARGO_MANIFEST_DIR"));
+    let interpreter_dir = manifest_dir.join("../curse_interpreter");
+
+    let mut programs: Vec<_> = fs::read_dir(manifest_dir.join(dir))
+        .expect("directory exists")
+        .map(|entry| entry.expect("readable entry").path())
+        .filter(|path| path.extension().is_some_and(|ext| ext == "curse"))
+        .collect();
+    programs.sort();
+
+    // `dynamic_import`s are relative to the working directory, and the standard library lives
+    // next to the interpreter.
+    std::env::set_current_dir(&interpreter_dir).unwrap();
+
+    for program in programs {
+        let ast_program = curse_test_support::parse_with(|interner| {
+            curse_interpreter::flatten_asts(interner, program.to_str().unwrap())
+                .expect("program parses")
+        });
+
+        let arena = Bump::new();
+        let hir_program = curse_test_support::lower_program(&arena, &ast_program);
+
+        let global = curse_mir::ctx::Global::default();
+        let mut typeck = curse_mir::ctx::Typeck::with_global(&global);
+        let typed = curse_mir::check_program(&mut typeck, &hir_program).expect("type checks");
+        let unoptimized =
+            curse_cps::convert_program(&hir_program, &typed.dispatches).expect("converts to CPS");
+        let expected = observe(&eval::eval(&unoptimized).expect("evaluates"));
+
+        // the JIT should work both with and without the optimizations
+        let actual = curse_jit::run(&unoptimized).expect("compiles");
+        assert_eq!(actual, expected, "{} (unoptimized)", program.display());
+
+        let optimized = optimize::optimize(unoptimized, Default::default());
+        let actual = curse_jit::run(&optimized).expect("compiles");
+        assert_eq!(actual, expected, "{}", program.display());
+
+        // a tiny heap makes sure that the garbage collector runs, and often
+        let actual = curse_jit::run_with_heap_size(&optimized, 16).expect("compiles");
+        assert_eq!(actual, expected, "{} (small heap)", program.display());
+    }
+}
+
+#[test]
+fn project_euler() {
+    agree("../curse_interpreter/project_euler");
+}
+
+#[test]
+fn expected_programs() {
+    agree("../curse_cps/tests/expected");
+}
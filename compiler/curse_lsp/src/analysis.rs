@@ -0,0 +1,615 @@
+//! Everything the language server knows about a file, worked out whenever it or something it
+//! imports changes.
+//!
+//! The program is checked with the query database, so only what a change could have affected is
+//! lowered and type checked again. The diagnostics, types, definitions and names in scope are then
+//! collected into plain data, and the requests that come in afterwards just look them up by offset.
+
+use curse_ast::ast::{self, Closure, Constructor, Expr, Lit, Pat, Path as AstPath, Record};
+use curse_db::Database;
+use curse_interner::{Ident, InternedString};
+use curse_mir::{ctx, ExprKind, PatKind, Ty, TypeKind, Var};
+use curse_span::{HasSpan, Span};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// A file that's part of the program, either the one being analyzed or one that it imports.
+#[derive(Clone, Debug)]
+pub struct File {
+    /// Where the file is. Files that have never been saved are named after their URI instead.
+    pub path: PathBuf,
+    pub input: String,
+}
+
+/// A place in one of the [`Analysis::files`].
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Location {
+    pub file: usize,
+    pub span: Span,
+}
+
+/// An error in the file being analyzed.
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub span: Span,
+    pub message: String,
+    /// The other places the error points at, with what it says about each of them.
+    pub related: Vec<(Span, String)>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum CompletionKind {
+    /// A parameter, or a name bound by a region.
+    Local,
+    Function,
+    Struct,
+    Choice,
+    Variant,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Completion {
+    pub label: String,
+    pub kind: CompletionKind,
+    /// The type of a function, or how a variant is written.
+    pub detail: Option<String>,
+}
+
+/// What's known about a file, along with everything it imports.
+#[derive(Debug)]
+pub struct Analysis {
+    /// The file being analyzed, followed by everything it imports.
+    pub files: Vec<File>,
+    /// The syntax, lowering and type errors in the file being analyzed. Only the syntax and type
+    /// errors in imported files are left out, since lowering errors don't say which file they're
+    /// in, the same as in `curse check`.
+    pub diagnostics: Vec<Diagnostic>,
+    /// The type of each expression and binding that type checked.
+    hovers: Vec<(Span, String)>,
+    /// What each name refers to.
+    references: Vec<(Span, Location)>,
+    /// The locals, along with where they're in scope.
+    locals: Vec<(Span, Completion)>,
+    /// The functions, structs and choices that can be used anywhere.
+    globals: Vec<Completion>,
+    /// The variants of each choice.
+    variants: HashMap<String, Vec<Completion>>,
+}
+
+/// Where the functions, structs, choices and variants of a program are defined.
+#[derive(Default)]
+struct Definitions {
+    functions: HashMap<InternedString, Location>,
+    structs: HashMap<InternedString, Location>,
+    choices: HashMap<InternedString, Location>,
+    /// Keyed by the choice and then the variant.
+    variants: HashMap<(InternedString, InternedString), Location>,
+}
+
+impl Analysis {
+    /// Analyzes the program that starts at the file at `path`, which has to have been given to
+    /// `db` or be on disk.
+    pub fn new(db: &mut Database<'_>, path: &Path) -> Analysis {
+        let (checked, ctx) = db.check(path);
+        let mut analysis = Analysis {
+            files: checked
+                .files
+                .iter()
+                .map(|file| File {
+                    path: file.path.clone(),
+                    input: file.parsed.input.to_string(),
+                })
+                .collect(),
+            diagnostics: vec![],
+            hovers: vec![],
+            references: vec![],
+            locals: vec![],
+            globals: vec![],
+            variants: HashMap::new(),
+        };
+
+        for error in &checked.files[0].parsed.errors {
+            analysis.diagnostics.push(diagnostic(error));
+        }
+        for import in checked.imports.iter().filter(|import| import.file == 0) {
+            match &import.imported {
+                Ok(file) => {
+                    let start = Span { start: 0, end: 0 };
+                    let location = Location {
+                        file: *file,
+                        span: start,
+                    };
+                    analysis.references.push((import.span, location));
+                }
+                Err(message) => analysis.diagnostics.push(Diagnostic {
+                    span: import.span,
+                    message: message.clone(),
+                    related: vec![],
+                }),
+            }
+        }
+
+        let programs: Vec<&ast::Program> = checked
+            .files
+            .iter()
+            .map(|file| &file.parsed.program)
+            .collect();
+        let mut definitions = Definitions::default();
+        for (file, program) in programs.iter().enumerate() {
+            definitions.add(file, program);
+        }
+        for (file, program) in programs.iter().enumerate() {
+            analysis.add_globals(file, program);
+        }
+
+        let mut resolver = Resolver {
+            definitions: &definitions,
+            locals: vec![],
+            references: &mut analysis.references,
+            scopes: &mut analysis.locals,
+        };
+        for def in &programs[0].function_defs {
+            resolver.closure(&def.function);
+        }
+        for def in &programs[0].impl_defs {
+            resolver.closure(&def.function);
+        }
+
+        for error in &checked.lowering_errors {
+            analysis.diagnostics.push(diagnostic(error));
+        }
+
+        let mut types = Types {
+            ctx,
+            generics: &[],
+            moved: 0,
+            hovers: &mut analysis.hovers,
+        };
+        for function in &checked.functions {
+            if function.file == 0 {
+                analysis
+                    .diagnostics
+                    .extend(function.errors.iter().map(diagnostic));
+            }
+            let Some(expr) = function.expr else {
+                continue;
+            };
+            let template = function.template.display(ctx).to_string();
+            let completion = analysis
+                .globals
+                .iter_mut()
+                .find(|completion| completion.label == function.name.to_string());
+            if let Some(completion) = completion {
+                completion.detail = Some(template.clone());
+            }
+            if function.file == 0 {
+                let location = definitions.functions.get(&function.name);
+                if let Some(location) = location.filter(|location| location.file == 0) {
+                    let hover = format!("fn {}: {template}", function.name);
+                    types.hovers.push((location.span, hover));
+                }
+                types.generics = &function.template.typevars;
+                types.moved = function.moved;
+                types.expr(expr);
+            }
+        }
+        types.generics = &[];
+        for checked in checked.impls.iter().filter(|checked| checked.file == 0) {
+            analysis
+                .diagnostics
+                .extend(checked.errors.iter().map(diagnostic));
+            if let Some(expr) = checked.expr {
+                types.moved = checked.moved;
+                types.expr(expr);
+            }
+        }
+
+        analysis
+    }
+
+    /// The type of whatever is at `offset`, along with its span.
+    pub fn hover(&self, offset: u32) -> Option<(Span, &str)> {
+        self.hovers
+            .iter()
+            .filter(|(span, _)| contains(*span, offset))
+            .min_by_key(|(span, _)| span.end - span.start)
+            .map(|(span, hover)| (*span, hover.as_str()))
+    }
+
+    /// Where the name at `offset` is defined.
+    pub fn definition(&self, offset: u32) -> Option<Location> {
+        self.references
+            .iter()
+            .find(|(span, _)| contains(*span, offset))
+            .map(|&(_, location)| location)
+    }
+
+    /// What could be written at `offset`. Right after `Choice::`, that's the choice's variants,
+    /// and otherwise it's every name in scope.
+    pub fn completions(&self, offset: u32) -> Vec<Completion> {
+        let input = &self.files[0].input[..offset as usize];
+        let before = input.trim_end_matches(is_ident_char);
+        if let Some(path) = before.strip_suffix("::") {
+            let choice = &path[path.trim_end_matches(is_ident_char).len()..];
+            return self.variants.get(choice).cloned().unwrap_or_default();
+        }
+
+        // innermost first, so that shadowed names are left out
+        let mut locals: Vec<_> = self
+            .locals
+            .iter()
+            .filter(|(span, _)| span.start <= offset && offset <= span.end)
+            .collect();
+        locals.sort_by_key(|(span, _)| std::cmp::Reverse(span.start));
+
+        let mut seen = HashSet::new();
+        locals
+            .into_iter()
+            .map(|(_, completion)| completion)
+            .chain(&self.globals)
+            .filter(|completion| seen.insert(completion.label.as_str()))
+            .cloned()
+            .collect()
+    }
+
+    fn add_globals(&mut self, file: usize, program: &ast::Program) {
+        let builtins = ["assert", "assert_eq", "panic"];
+        if file == 0 {
+            self.globals.extend(builtins.map(|builtin| Completion {
+                label: builtin.to_string(),
+                kind: CompletionKind::Function,
+                detail: None,
+            }));
+        }
+        for def in &program.function_defs {
+            self.globals.push(Completion {
+                label: def.ident.to_string(),
+                kind: CompletionKind::Function,
+                detail: None,
+            });
+        }
+        for def in &program.trait_defs {
+            let span = def.span();
+            self.globals.push(Completion {
+                label: def.method.to_string(),
+                kind: CompletionKind::Function,
+                detail: Some(
+                    self.files[file].input[span.start as usize..span.end as usize].to_string(),
+                ),
+            });
+        }
+        for def in &program.struct_defs {
+            self.globals.push(Completion {
+                label: def.ident.to_string(),
+                kind: CompletionKind::Struct,
+                detail: None,
+            });
+        }
+        for def in &program.choice_defs {
+            self.globals.push(Completion {
+                label: def.ident.to_string(),
+                kind: CompletionKind::Choice,
+                detail: None,
+            });
+            let input = &self.files[file].input;
+            let variants = def.variants.variants.iter().map(|(variant, _)| variant);
+            let variants = variants
+                .chain(&def.variants.last)
+                .map(|variant| {
+                    let span = variant.span();
+                    Completion {
+                        label: variant.ident.to_string(),
+                        kind: CompletionKind::Variant,
+                        detail: Some(input[span.start as usize..span.end as usize].to_string()),
+                    }
+                })
+                .collect();
+            self.variants.insert(def.ident.to_string(), variants);
+        }
+    }
+}
+
+impl Definitions {
+    fn add(&mut self, file: usize, program: &ast::Program) {
+        let location = |ident: Ident| Location {
+            file,
+            span: ident.span,
+        };
+        for def in &program.function_defs {
+            self.functions.insert(def.ident.symbol, location(def.ident));
+        }
+        // Methods are called like functions.
+        for def in &program.trait_defs {
+            self.functions
+                .insert(def.method.symbol, location(def.method));
+        }
+        for def in &program.struct_defs {
+            self.structs.insert(def.ident.symbol, location(def.ident));
+        }
+        for def in &program.choice_defs {
+            self.choices.insert(def.ident.symbol, location(def.ident));
+            let variants = def.variants.variants.iter().map(|(variant, _)| variant);
+            for variant in variants.chain(&def.variants.last) {
+                let key = (def.ident.symbol, variant.ident.symbol);
+                self.variants.insert(key, location(variant.ident));
+            }
+        }
+    }
+}
+
+/// Works out what each name in the file being analyzed refers to, and where each local is in
+/// scope.
+struct Resolver<'a> {
+    definitions: &'a Definitions,
+    /// The locals in scope, innermost last.
+    locals: Vec<Ident>,
+    references: &'a mut Vec<(Span, Location)>,
+    scopes: &'a mut Vec<(Span, Completion)>,
+}
+
+impl Resolver<'_> {
+    fn closure(&mut self, closure: &Closure) {
+        for arm in closure.iter_arms() {
+            let count = self.locals.len();
+            for param in arm.iter_params() {
+                self.bind(&param.pat);
+            }
+            let scope = Span {
+                start: arm.close.end(),
+                end: arm.body.end(),
+            };
+            self.scope(count, scope);
+            self.expr(&arm.body);
+            self.locals.truncate(count);
+        }
+    }
+
+    fn expr(&mut self, expr: &Expr) {
+        match expr {
+            Expr::Paren(paren) => self.expr(&paren.expr),
+            Expr::Lit(Lit::Ident(ident)) => self.name(*ident),
+            Expr::Record(record) => self.record(record, Self::expr, Self::name),
+            Expr::Constructor(constructor) => {
+                self.path(&constructor.path);
+                self.expr(&constructor.inner);
+            }
+            Expr::Closure(closure) => self.closure(closure),
+            Expr::Appl(appl) => {
+                self.expr(&appl.lhs);
+                self.expr(&appl.fun);
+                self.expr(&appl.rhs);
+            }
+            Expr::Region(region) => {
+                // the region shadows the names in its pattern, so they're used before being bound
+                let count = self.locals.len();
+                self.pat(&region.pat, Self::name);
+                self.pat(&region.pat, Self::local);
+                let scope = Span {
+                    start: region.lbrace.end(),
+                    end: region.rbrace.start(),
+                };
+                self.scope(count, scope);
+                self.expr(&region.body);
+                self.locals.truncate(count);
+            }
+            Expr::Lit(_) | Expr::Symbol(_) | Expr::Error(_) => {}
+        }
+    }
+
+    fn bind(&mut self, pat: &Pat) {
+        self.pat(pat, Self::local);
+    }
+
+    /// Goes through the names in `pat`, passing each of them to `ident`.
+    fn pat(&mut self, pat: &Pat, ident: fn(&mut Self, Ident)) {
+        match pat {
+            Pat::Lit(Lit::Ident(name)) => ident(self, *name),
+            Pat::Record(record) => self.record(record, |this, pat| this.pat(pat, ident), ident),
+            Pat::Constructor(constructor) => {
+                let Constructor { path, inner } = &**constructor;
+                self.path(path);
+                self.pat(inner, ident);
+            }
+            Pat::Lit(_) => {}
+        }
+    }
+
+    /// Goes through the fields of a record, where a field without a value, like `{ x }`, is a name.
+    fn record<T>(
+        &mut self,
+        record: &Record<T>,
+        mut value: impl FnMut(&mut Self, &T),
+        name: fn(&mut Self, Ident),
+    ) {
+        for field in record.iter_fields() {
+            match &field.value {
+                Some((_, field_value)) => value(self, field_value),
+                None => name(self, field.ident),
+            }
+        }
+    }
+
+    fn local(&mut self, ident: Ident) {
+        if ident.symbol.string().as_ref() as &str != "_" {
+            self.locals.push(ident);
+        }
+    }
+
+    /// Makes the locals bound since there were `count` of them available in `scope`.
+    fn scope(&mut self, count: usize, scope: Span) {
+        for local in &self.locals[count..] {
+            let completion = Completion {
+                label: local.to_string(),
+                kind: CompletionKind::Local,
+                detail: None,
+            };
+            self.scopes.push((scope, completion));
+        }
+    }
+
+    fn name(&mut self, ident: Ident) {
+        let local = self
+            .locals
+            .iter()
+            .rev()
+            .find(|local| local.symbol == ident.symbol);
+        let location = match local {
+            Some(local) => Location {
+                file: 0,
+                span: local.span,
+            },
+            None => match self.definitions.functions.get(&ident.symbol) {
+                Some(&location) => location,
+                None => return,
+            },
+        };
+        self.references.push((ident.span, location));
+    }
+
+    /// A struct like `Point`, or a variant like `Option::Some`.
+    fn path(&mut self, path: &AstPath) {
+        let parts: Vec<Ident> = path.iter_parts().copied().collect();
+        match parts[..] {
+            [.., choice, variant] => {
+                if let Some(&location) = self.definitions.choices.get(&choice.symbol) {
+                    self.references.push((choice.span, location));
+                }
+                let key = (choice.symbol, variant.symbol);
+                if let Some(&location) = self.definitions.variants.get(&key) {
+                    self.references.push((variant.span, location));
+                }
+            }
+            [name] => {
+                if let Some(&location) = self.definitions.structs.get(&name.symbol) {
+                    self.references.push((name.span, location));
+                }
+            }
+            [] => {}
+        }
+    }
+}
+
+/// Collects the types of a function's expressions and bindings.
+struct Types<'a, 'cx> {
+    ctx: &'a ctx::Typeck<'cx>,
+    /// The generics of the function, so that its types print the same way as its type does.
+    generics: &'a [Var],
+    /// How far the function has moved since it was checked.
+    moved: i64,
+    hovers: &'a mut Vec<(Span, String)>,
+}
+
+impl<'cx> Types<'_, 'cx> {
+    fn expr(&mut self, expr: curse_mir::Expr<'cx>) {
+        let ty = self.ty(expr.ty().kind);
+        let hover = match expr.kind {
+            ExprKind::Ident { literal, .. } => format!("{literal}: {ty}"),
+            _ => ty,
+        };
+        self.hovers.push((self.span(expr.span), hover));
+
+        match expr.kind {
+            ExprKind::Record { fields, .. } => {
+                for (_, field) in fields {
+                    self.expr(*field);
+                }
+            }
+            ExprKind::Constructor { inner, .. }
+            | ExprKind::Field { record: inner, .. }
+            | ExprKind::Region { body: inner, .. } => self.expr(*inner),
+            ExprKind::Closure { arms, .. } => {
+                for arm in arms {
+                    self.pat(arm.lhs);
+                    self.pat(arm.rhs);
+                    self.expr(arm.body);
+                }
+            }
+            ExprKind::Appl { appl, .. } => {
+                self.expr(appl.lhs);
+                self.expr(appl.function);
+                self.expr(appl.rhs);
+            }
+            ExprKind::Builtin { .. }
+            | ExprKind::I32(_)
+            | ExprKind::Bool(_)
+            | ExprKind::Ident { .. }
+            | ExprKind::Error { .. } => {}
+        }
+    }
+
+    fn pat(&mut self, pat: curse_mir::Pat<'cx>) {
+        match pat.kind {
+            PatKind::Ident { ty, literal } => {
+                let hover = format!("{literal}: {}", self.ty(ty));
+                self.hovers.push((self.span(pat.span), hover));
+            }
+            PatKind::Record { fields, .. } => {
+                for (_, field) in fields {
+                    self.pat(*field);
+                }
+            }
+            PatKind::Constructor { inner, .. } => self.pat(*inner),
+            PatKind::Bool(_) | PatKind::I32(_) | PatKind::Error { .. } => {}
+        }
+    }
+
+    /// Where `span` is now, given where it was when the function was checked.
+    fn span(&self, span: Span) -> Span {
+        Span {
+            start: (i64::from(span.start) + self.moved) as u32,
+            end: (i64::from(span.end) + self.moved) as u32,
+        }
+    }
+
+    fn ty(&self, ty: TypeKind<'cx>) -> String {
+        let mut printer = ty.display(self.ctx);
+        printer.generics = self.generics;
+        printer.to_string()
+    }
+}
+
+/// Turns an error from any stage of the compiler into a diagnostic, pointing at its first label.
+fn diagnostic(error: &impl miette::Diagnostic) -> Diagnostic {
+    let mut message = error.to_string();
+    if let Some(help) = error.help() {
+        message = format!("{message}\nhelp: {help}");
+    }
+
+    let mut related = vec![];
+    let mut labels = |error: &dyn miette::Diagnostic, prefix: Option<String>| {
+        for label in error.labels().into_iter().flatten() {
+            let span = Span {
+                start: label.offset() as u32,
+                end: (label.offset() + label.len()) as u32,
+            };
+            let text = label.label().unwrap_or_default();
+            let text = match &prefix {
+                Some(prefix) if text.is_empty() => prefix.clone(),
+                Some(prefix) => format!("{prefix}: {text}"),
+                None => text.to_string(),
+            };
+            related.push((span, text));
+        }
+    };
+    labels(error, None);
+    for reason in error.related().into_iter().flatten() {
+        labels(reason, Some(reason.to_string()));
+    }
+
+    let span = related
+        .first()
+        .map_or(Span { start: 0, end: 0 }, |&(span, _)| span);
+    Diagnostic {
+        span,
+        message,
+        related,
+    }
+}
+
+fn contains(span: Span, offset: u32) -> bool {
+    span.start <= offset && offset < span.end.max(span.start + 1)
+}
+
+fn is_ident_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
@@ -0,0 +1,331 @@
+//! The language server, which gives editors the errors in a file as it's being written, along
+//! with the types of things, where they're defined and what can be written where.
+//!
+//! Every open file is analyzed again whenever one of them changes, since any of them might import
+//! the one that changed. They're all checked with the same query database, so only what the change
+//! could have affected is lowered and type checked again. See [`analysis`] for what's worked out.
+//!
+//! What the database works out is never freed while it's in use, so once enough of it is out of
+//! date, the server starts over with a new database that has the same files.
+
+#![forbid(unsafe_code)]
+
+use analysis::{Analysis, CompletionKind, Location};
+use curse_db::{Arenas, Database, Inputs};
+use lines::Lines;
+use lsp_server::{Connection, ExtractError, Message, Notification, Request, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidCloseTextDocument, DidOpenTextDocument,
+    Notification as NotificationTrait, PublishDiagnostics,
+};
+use lsp_types::request::{Completion, GotoDefinition, HoverRequest, Request as RequestTrait};
+use lsp_types::{
+    CompletionItem, CompletionItemKind, CompletionOptions, CompletionParams, CompletionResponse,
+    DiagnosticRelatedInformation, DiagnosticSeverity, GotoDefinitionParams, GotoDefinitionResponse,
+    Hover, HoverContents, HoverParams, HoverProviderCapability, InitializeParams, LanguageString,
+    MarkedString, OneOf, PublishDiagnosticsParams, ServerCapabilities, TextDocumentSyncCapability,
+    TextDocumentSyncKind, Url,
+};
+use std::collections::HashMap;
+use std::path::PathBuf;
+use thiserror::Error;
+
+pub mod analysis;
+mod lines;
+
+/// How many out of date items the database can hold on to before the server starts over with a
+/// new one, which type checks the open files from scratch again.
+const MAX_GARBAGE: usize = 10_000;
+
+#[derive(Debug, Error)]
+pub enum ServerError {
+    #[error(transparent)]
+    Protocol(#[from] lsp_server::ProtocolError),
+    #[error("couldn't understand a message: {0}")]
+    Json(#[from] serde_json::Error),
+    #[error("couldn't talk to the editor: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("the editor hung up")]
+    Disconnected,
+}
+
+/// Runs the server over stdin and stdout until the editor shuts it down.
+pub fn run() -> Result<(), ServerError> {
+    let (connection, io_threads) = Connection::stdio();
+    serve(connection)?;
+    io_threads.join()?;
+    Ok(())
+}
+
+/// Runs the server over `connection` until the editor shuts it down.
+pub fn serve(connection: Connection) -> Result<(), ServerError> {
+    let capabilities = ServerCapabilities {
+        text_document_sync: Some(TextDocumentSyncCapability::Kind(TextDocumentSyncKind::FULL)),
+        hover_provider: Some(HoverProviderCapability::Simple(true)),
+        definition_provider: Some(OneOf::Left(true)),
+        completion_provider: Some(CompletionOptions {
+            trigger_characters: Some(vec![":".to_string()]),
+            ..CompletionOptions::default()
+        }),
+        ..ServerCapabilities::default()
+    };
+    let params = connection.initialize(serde_json::to_value(capabilities)?)?;
+    let params: InitializeParams = serde_json::from_value(params)?;
+
+    // imports are relative to the root of the workspace, like they're relative to wherever the
+    // interpreter is run from
+    #[allow(deprecated)]
+    let root = params
+        .workspace_folders
+        .and_then(|folders| folders.into_iter().next())
+        .map(|folder| folder.uri)
+        .or(params.root_uri)
+        .and_then(|uri| uri.to_file_path().ok())
+        .or_else(|| std::env::current_dir().ok())
+        .unwrap_or_default();
+
+    let mut inputs = Inputs::new(root);
+    let mut documents = HashMap::new();
+    loop {
+        let arenas = Arenas::default();
+        let mut server = Server {
+            connection: &connection,
+            db: Database::with_inputs(&arenas, inputs),
+            documents,
+        };
+        if !server.serve()? {
+            return Ok(());
+        }
+        inputs = server.db.into_inputs();
+        documents = server.documents;
+    }
+}
+
+struct Server<'a, 'db> {
+    connection: &'a Connection,
+    /// Has the contents of every open file, as they are in the editor.
+    db: Database<'db>,
+    /// Every open file.
+    documents: HashMap<Url, Analysis>,
+}
+
+impl Server<'_, '_> {
+    /// Handles messages until the editor shuts the server down, giving back `false`, or until
+    /// the database has too much that's out of date, giving back `true`.
+    fn serve(&mut self) -> Result<bool, ServerError> {
+        for message in &self.connection.receiver {
+            match message {
+                Message::Request(request) => {
+                    if self.connection.handle_shutdown(&request)? {
+                        return Ok(false);
+                    }
+                    self.request(request)?;
+                }
+                Message::Notification(notification) => self.notification(notification)?,
+                Message::Response(_) => {}
+            }
+            if self.db.garbage() > MAX_GARBAGE {
+                return Ok(true);
+            }
+        }
+        Ok(false)
+    }
+
+    fn request(&mut self, request: Request) -> Result<(), ServerError> {
+        let request = match extract::<HoverRequest>(request)? {
+            Ok((id, params)) => return self.respond(Response::new_ok(id, self.hover(params))),
+            Err(request) => request,
+        };
+        let request = match extract::<GotoDefinition>(request)? {
+            Ok((id, params)) => return self.respond(Response::new_ok(id, self.definition(params))),
+            Err(request) => request,
+        };
+        let request = match extract::<Completion>(request)? {
+            Ok((id, params)) => return self.respond(Response::new_ok(id, self.completion(params))),
+            Err(request) => request,
+        };
+
+        let message = format!("`{}` isn't supported", request.method);
+        let code = lsp_server::ErrorCode::MethodNotFound as i32;
+        self.respond(Response::new_err(request.id, code, message))
+    }
+
+    fn notification(&mut self, notification: Notification) -> Result<(), ServerError> {
+        match notification.method.as_str() {
+            DidOpenTextDocument::METHOD => {
+                let params: <DidOpenTextDocument as NotificationTrait>::Params =
+                    serde_json::from_value(notification.params)?;
+                let document = params.text_document;
+                self.db.set_input(path(&document.uri), document.text);
+                self.analyze(document.uri)
+            }
+            DidChangeTextDocument::METHOD => {
+                let params: <DidChangeTextDocument as NotificationTrait>::Params =
+                    serde_json::from_value(notification.params)?;
+                // only whole files are synced, so the last change has all of it
+                match params.content_changes.into_iter().last() {
+                    Some(change) => {
+                        let uri = params.text_document.uri;
+                        self.db.set_input(path(&uri), change.text);
+                        self.analyze(uri)
+                    }
+                    None => Ok(()),
+                }
+            }
+            DidCloseTextDocument::METHOD => {
+                let params: <DidCloseTextDocument as NotificationTrait>::Params =
+                    serde_json::from_value(notification.params)?;
+                let uri = params.text_document.uri;
+                // whatever imports it gets what's on disk from now on
+                self.documents.remove(&uri);
+                self.db.remove_input(&path(&uri));
+                self.publish(PublishDiagnosticsParams::new(uri, vec![], None))?;
+                self.analyze_open()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Analyzes the file at `uri` along with every other open file, since any of them might import
+    /// it, telling the editor about their errors.
+    fn analyze(&mut self, uri: Url) -> Result<(), ServerError> {
+        self.analyze_one(uri.clone())?;
+        let others: Vec<Url> = self
+            .documents
+            .keys()
+            .filter(|&other| *other != uri)
+            .cloned()
+            .collect();
+        for other in others {
+            self.analyze_one(other)?;
+        }
+        Ok(())
+    }
+
+    fn analyze_open(&mut self) -> Result<(), ServerError> {
+        let open: Vec<Url> = self.documents.keys().cloned().collect();
+        for uri in open {
+            self.analyze_one(uri)?;
+        }
+        Ok(())
+    }
+
+    fn analyze_one(&mut self, uri: Url) -> Result<(), ServerError> {
+        let analysis = Analysis::new(&mut self.db, &path(&uri));
+
+        let lines = Lines::new(&analysis.files[0].input);
+        let diagnostics = analysis
+            .diagnostics
+            .iter()
+            .map(|diagnostic| {
+                let related = diagnostic
+                    .related
+                    .iter()
+                    .map(|(span, message)| DiagnosticRelatedInformation {
+                        location: lsp_types::Location::new(uri.clone(), lines.range(*span)),
+                        message: message.clone(),
+                    })
+                    .collect();
+                lsp_types::Diagnostic {
+                    range: lines.range(diagnostic.span),
+                    severity: Some(DiagnosticSeverity::ERROR),
+                    source: Some("curse".to_string()),
+                    message: diagnostic.message.clone(),
+                    related_information: Some(related),
+                    ..lsp_types::Diagnostic::default()
+                }
+            })
+            .collect();
+
+        self.documents.insert(uri.clone(), analysis);
+        self.publish(PublishDiagnosticsParams::new(uri, diagnostics, None))
+    }
+
+    fn hover(&self, params: HoverParams) -> Option<Hover> {
+        let position = params.text_document_position_params;
+        let analysis = self.documents.get(&position.text_document.uri)?;
+        let lines = Lines::new(&analysis.files[0].input);
+        let (span, hover) = analysis.hover(lines.offset(position.position))?;
+        Some(Hover {
+            contents: HoverContents::Scalar(MarkedString::LanguageString(LanguageString {
+                language: "curse".to_string(),
+                value: hover.to_string(),
+            })),
+            range: Some(lines.range(span)),
+        })
+    }
+
+    fn definition(&self, params: GotoDefinitionParams) -> Option<GotoDefinitionResponse> {
+        let position = params.text_document_position_params;
+        let uri = position.text_document.uri;
+        let analysis = self.documents.get(&uri)?;
+        let offset = Lines::new(&analysis.files[0].input).offset(position.position);
+        let Location { file, span } = analysis.definition(offset)?;
+
+        let file_uri = match file {
+            0 => uri,
+            _ => Url::from_file_path(&analysis.files[file].path).ok()?,
+        };
+        let file = &analysis.files[file];
+        let range = Lines::new(&file.input).range(span);
+        Some(GotoDefinitionResponse::Scalar(lsp_types::Location::new(
+            file_uri, range,
+        )))
+    }
+
+    fn completion(&self, params: CompletionParams) -> Option<CompletionResponse> {
+        let position = params.text_document_position;
+        let analysis = self.documents.get(&position.text_document.uri)?;
+        let offset = Lines::new(&analysis.files[0].input).offset(position.position);
+        let items = analysis
+            .completions(offset)
+            .into_iter()
+            .map(|completion| CompletionItem {
+                label: completion.label,
+                kind: Some(match completion.kind {
+                    CompletionKind::Local => CompletionItemKind::VARIABLE,
+                    CompletionKind::Function => CompletionItemKind::FUNCTION,
+                    CompletionKind::Struct => CompletionItemKind::STRUCT,
+                    CompletionKind::Choice => CompletionItemKind::ENUM,
+                    CompletionKind::Variant => CompletionItemKind::ENUM_MEMBER,
+                }),
+                detail: completion.detail,
+                ..CompletionItem::default()
+            })
+            .collect();
+        Some(CompletionResponse::Array(items))
+    }
+
+    fn publish(&self, params: PublishDiagnosticsParams) -> Result<(), ServerError> {
+        let notification = Notification::new(PublishDiagnostics::METHOD.to_string(), params);
+        self.send(notification.into())
+    }
+
+    fn respond(&self, response: Response) -> Result<(), ServerError> {
+        self.send(response.into())
+    }
+
+    fn send(&self, message: Message) -> Result<(), ServerError> {
+        self.connection
+            .sender
+            .send(message)
+            .map_err(|_| ServerError::Disconnected)
+    }
+}
+
+/// Where the file at `uri` is, or its URI as a path if it's never been saved.
+fn path(uri: &Url) -> PathBuf {
+    uri.to_file_path()
+        .unwrap_or_else(|()| PathBuf::from(uri.as_str()))
+}
+
+/// Takes the parameters out of `request` if it's an `R`, and otherwise gives it back.
+fn extract<R: RequestTrait>(
+    request: Request,
+) -> Result<Result<(lsp_server::RequestId, R::Params), Request>, ServerError> {
+    match request.extract(R::METHOD) {
+        Ok(extracted) => Ok(Ok(extracted)),
+        Err(ExtractError::MethodMismatch(request)) => Ok(Err(request)),
+        Err(ExtractError::JsonError { error, .. }) => Err(error.into()),
+    }
+}
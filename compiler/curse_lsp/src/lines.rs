@@ -0,0 +1,47 @@
+use curse_span::Span;
+use lsp_types::{Position, Range};
+
+/// Converts between byte offsets and the line and column positions that editors use, where the
+/// columns count UTF-16 code units.
+pub struct Lines<'input> {
+    input: &'input str,
+    /// The offset that each line starts at.
+    starts: Vec<usize>,
+}
+
+impl<'input> Lines<'input> {
+    pub fn new(input: &'input str) -> Self {
+        let starts = std::iter::once(0)
+            .chain(input.match_indices('\n').map(|(index, _)| index + 1))
+            .collect();
+        Lines { input, starts }
+    }
+
+    /// The offset of `position`, which is clamped to the end of its line, or of the input.
+    pub fn offset(&self, position: Position) -> u32 {
+        let Some(&start) = self.starts.get(position.line as usize) else {
+            return self.input.len() as u32;
+        };
+        let line = self.input[start..].split('\n').next().unwrap_or("");
+        let mut column = 0;
+        for (index, ch) in line.char_indices() {
+            if column >= position.character as usize {
+                return (start + index) as u32;
+            }
+            column += ch.len_utf16();
+        }
+        (start + line.len()) as u32
+    }
+
+    pub fn position(&self, offset: u32) -> Position {
+        let offset = (offset as usize).min(self.input.len());
+        let line = self.starts.partition_point(|&start| start <= offset) - 1;
+        let start = self.starts[line];
+        let character = self.input[start..offset].encode_utf16().count();
+        Position::new(line as u32, character as u32)
+    }
+
+    pub fn range(&self, span: Span) -> Range {
+        Range::new(self.position(span.start), self.position(span.end))
+    }
+}
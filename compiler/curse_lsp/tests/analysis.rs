@@ -0,0 +1,194 @@
+//! Checks what the language server works out about a file: its errors, the types of things, where
+//! names are defined, and what can be written where.
+
+use curse_db::{Arenas, Database};
+use curse_lsp::analysis::{Analysis, CompletionKind, Location};
+use curse_span::Span;
+use curse_test_support::{lock, text};
+use std::path::{Path, PathBuf};
+
+const INPUT: &str = r#"dynamic_import "shapes.curse"
+
+fn area (
+    |Shape::Circle r| 3 * r * r,
+    |Shape::Square { side }| side double {},
+)
+
+fn main || ref s { 1 + 2 in |y| y + (Shape::Circle 2 area {}) }
+
+fn broken || 1 + true
+"#;
+
+/// The offset of the first `needle` in `input`, plus `plus`.
+fn at(input: &str, needle: &str, plus: u32) -> u32 {
+    input.find(needle).expect(needle) as u32 + plus
+}
+
+/// The workspace that `main.curse` is in, next to `shapes.curse`.
+fn root() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/workspace")
+}
+
+/// Analyzes `input` as `main.curse` in a new database.
+fn analyze(input: &str) -> Analysis {
+    let arenas = Arenas::default();
+    let mut db = Database::new(&arenas, root());
+    let main = root().join("main.curse");
+    db.set_input(main.clone(), input.to_string());
+    Analysis::new(&mut db, &main)
+}
+
+#[test]
+fn imported_files_are_analyzed_too() {
+    let _interner = lock();
+    let analysis = analyze(INPUT);
+    assert_eq!(analysis.files.len(), 2);
+    assert!(analysis.files[1].path.ends_with("shapes.curse"));
+}
+
+#[test]
+fn only_the_function_with_a_type_error_has_a_diagnostic() {
+    let _interner = lock();
+    let analysis = analyze(INPUT);
+    let [diagnostic] = &analysis.diagnostics[..] else {
+        panic!("{:?}", analysis.diagnostics);
+    };
+    assert!(diagnostic.message.starts_with("Cannot unify types"));
+    assert_eq!(text(INPUT, diagnostic.span), "true");
+}
+
+#[test]
+fn hovers_show_types() {
+    let _interner = lock();
+    let analysis = analyze(INPUT);
+    // printed the same way as the function's type, even with another function's error
+    let hover = |needle, plus| {
+        analysis
+            .hover(at(INPUT, needle, plus))
+            .map(|(_, hover)| hover)
+    };
+    assert_eq!(hover("main", 1), Some("fn main: ({} {} -> I32)"));
+    assert_eq!(hover("* r", 2), Some("r: I32"));
+    assert_eq!(hover("side }", 0), Some("side: I32"));
+    assert_eq!(hover("double", 0), Some("double: (I32 {} -> I32)"));
+    assert_eq!(hover("in |y|", 0), Some("in: (I32 (I32 {} -> I32) -> I32)"));
+    assert_eq!(hover("+ 2", 0), Some("(I32 I32 -> I32)"));
+    assert_eq!(hover("true", 0), None);
+}
+
+#[test]
+fn names_go_to_their_definitions() {
+    let _interner = lock();
+    let analysis = analyze(INPUT);
+    let shapes = std::fs::read_to_string(root().join("shapes.curse")).unwrap();
+    // even in other files
+    let definition = |needle, plus| analysis.definition(at(INPUT, needle, plus));
+    let in_shapes = |needle| Location {
+        file: 1,
+        span: Span {
+            start: at(&shapes, needle, 0),
+            end: at(&shapes, needle, needle.len() as u32),
+        },
+    };
+    assert_eq!(definition("double", 2), Some(in_shapes("double")));
+    assert_eq!(definition("Shape::Circle 2", 1), Some(in_shapes("Shape")));
+    assert_eq!(definition("Circle 2", 0), Some(in_shapes("Circle")));
+    assert_eq!(definition("Square", 0), Some(in_shapes("Square")));
+    let area = Location {
+        file: 0,
+        span: Span {
+            start: at(INPUT, "area", 0),
+            end: at(INPUT, "area", 4),
+        },
+    };
+    assert_eq!(definition("area {}", 0), Some(area));
+    let r = definition("* r", 2).unwrap();
+    assert_eq!((r.file, r.span.start), (0, at(INPUT, "r|", 0)));
+    let side = definition("side double", 0).unwrap();
+    assert_eq!((side.file, side.span.start), (0, at(INPUT, "side }", 0)));
+    let import = definition("shapes.curse", 0).unwrap();
+    assert_eq!(import.file, 1);
+    assert_eq!(definition("3 *", 0), None);
+}
+
+#[test]
+fn everything_in_scope_can_be_completed() {
+    let _interner = lock();
+    let analysis = analyze(INPUT);
+    // innermost first
+    let completions = analysis.completions(at(INPUT, "y + (", 2));
+    let labels: Vec<_> = completions.iter().map(|c| c.label.as_str()).collect();
+    assert_eq!(labels[..2], ["y", "s"]);
+    for label in ["area", "main", "double", "in", "assert_eq", "Shape"] {
+        assert!(labels.contains(&label), "{label} in {labels:?}");
+    }
+    assert!(!labels.contains(&"r"));
+    let double = completions.iter().find(|c| c.label == "double").unwrap();
+    assert_eq!(double.kind, CompletionKind::Function);
+    assert_eq!(double.detail.as_deref(), Some("(A {} -> A) where Add A"));
+}
+
+#[test]
+fn variants_of_a_choice_can_be_completed() {
+    let _interner = lock();
+    let analysis = analyze(INPUT);
+    let completions = analysis.completions(at(INPUT, "Circle 2", 2));
+    let variants: Vec<_> = completions
+        .iter()
+        .map(|c| (c.label.as_str(), c.kind, c.detail.as_deref()))
+        .collect();
+    assert_eq!(
+        variants,
+        [
+            ("Circle", CompletionKind::Variant, Some("Circle I32")),
+            (
+                "Square",
+                CompletionKind::Variant,
+                Some("Square { side: I32 }")
+            ),
+        ]
+    );
+}
+
+#[test]
+fn types_and_errors_move_with_their_functions() {
+    let _interner = lock();
+    let arenas = Arenas::default();
+    let mut db = Database::new(&arenas, root());
+    let main = root().join("main.curse");
+    db.set_input(main.clone(), INPUT.to_string());
+    Analysis::new(&mut db, &main);
+
+    // the functions that only moved aren't checked again
+    let moved = INPUT.replace("3 * r * r", "3 * r * r * 1");
+    db.set_input(main.clone(), moved.clone());
+    let analysis = Analysis::new(&mut db, &main);
+    let hover = analysis
+        .hover(at(&moved, "in |y|", 0))
+        .map(|(_, hover)| hover);
+    assert_eq!(hover, Some("in: (I32 (I32 {} -> I32) -> I32)"));
+    let [diagnostic] = &analysis.diagnostics[..] else {
+        panic!("{:?}", analysis.diagnostics);
+    };
+    assert_eq!(text(&moved, diagnostic.span), "true");
+}
+
+#[test]
+fn syntax_errors_are_reported_alongside_the_errors_after_them() {
+    let _interner = lock();
+    // even ones the lexer finds
+    let input = r#"dynamic_import "missing.curse"
+
+fn f |x: Nope| x + $
+
+fn g || 1 + true
+"#;
+    let analysis = analyze(input);
+    let mut errors: Vec<_> = analysis
+        .diagnostics
+        .iter()
+        .map(|diagnostic| text(input, diagnostic.span))
+        .collect();
+    errors.sort();
+    assert_eq!(errors, ["\"missing.curse\"", "$", "Nope", "fn", "true"]);
+}
@@ -0,0 +1,197 @@
+//! Talks to the language server the way an editor would, over an in-memory connection.
+
+use curse_test_support::lock;
+use lsp_server::{Connection, Message, Notification, Request, RequestId, Response};
+use lsp_types::notification::{
+    DidChangeTextDocument, DidOpenTextDocument, Exit, Initialized, Notification as _,
+    PublishDiagnostics,
+};
+use lsp_types::request::{GotoDefinition, HoverRequest, Initialize, Request as _, Shutdown};
+use lsp_types::{
+    DidChangeTextDocumentParams, DidOpenTextDocumentParams, GotoDefinitionResponse, Hover,
+    HoverContents, MarkedString, Position, PublishDiagnosticsParams,
+    TextDocumentContentChangeEvent, TextDocumentIdentifier, TextDocumentItem,
+    TextDocumentPositionParams, Url, VersionedTextDocumentIdentifier,
+};
+use serde_json::{json, Value};
+use std::{path::Path, thread::JoinHandle};
+
+const MAIN: &str = "dynamic_import \"shapes.curse\"\n\nfn main || 5 double {}\n";
+
+struct Editor {
+    connection: Connection,
+    id: i32,
+    server: JoinHandle<Result<(), curse_lsp::ServerError>>,
+    root_uri: Url,
+}
+
+impl Editor {
+    /// Starts a server for `tests/workspace` and initializes it, returning the capabilities it
+    /// has.
+    fn start() -> (Editor, Value) {
+        let (client, server) = Connection::memory();
+        let server = std::thread::spawn(move || curse_lsp::serve(server));
+        let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/workspace");
+        let mut editor = Editor {
+            connection: client,
+            id: 0,
+            server,
+            root_uri: Url::from_directory_path(&root).unwrap(),
+        };
+
+        let result = editor.request(
+            Initialize::METHOD,
+            json!({ "capabilities": {}, "rootUri": editor.root_uri }),
+        );
+        editor.notify(Initialized::METHOD, json!({}));
+        (editor, result["capabilities"].clone())
+    }
+
+    /// Opens `main.curse` with [`MAIN`] in it, which has no errors, returning its uri.
+    fn open(&self) -> Url {
+        let uri = self.root_uri.join("main.curse").unwrap();
+        let open = DidOpenTextDocumentParams {
+            text_document: TextDocumentItem::new(
+                uri.clone(),
+                "curse".to_string(),
+                1,
+                MAIN.to_string(),
+            ),
+        };
+        self.notify(
+            DidOpenTextDocument::METHOD,
+            serde_json::to_value(open).unwrap(),
+        );
+        let published = self.diagnostics();
+        assert_eq!(published.uri, uri);
+        assert!(
+            published.diagnostics.is_empty(),
+            "{:?}",
+            published.diagnostics
+        );
+        uri
+    }
+
+    /// Shuts the server down, checking that it stops cleanly.
+    fn stop(mut self) {
+        self.request(Shutdown::METHOD, Value::Null);
+        self.notify(Exit::METHOD, Value::Null);
+        self.server.join().unwrap().unwrap();
+    }
+
+    fn request(&mut self, method: &str, params: Value) -> Value {
+        self.id += 1;
+        let id = RequestId::from(self.id);
+        let request = Request::new(id.clone(), method.to_string(), params);
+        self.connection.sender.send(request.into()).unwrap();
+        match self.connection.receiver.recv().unwrap() {
+            Message::Response(Response {
+                id: response_id,
+                result: Some(result),
+                error: None,
+            }) if response_id == id => result,
+            message => panic!("{message:?}"),
+        }
+    }
+
+    fn notify(&self, method: &str, params: Value) {
+        let notification = Notification::new(method.to_string(), params);
+        self.connection.sender.send(notification.into()).unwrap();
+    }
+
+    /// Replaces the contents of the file at `uri` with `text`, returning the errors in it.
+    fn change(&self, uri: &Url, version: i32, text: &str) -> PublishDiagnosticsParams {
+        let change = DidChangeTextDocumentParams {
+            text_document: VersionedTextDocumentIdentifier::new(uri.clone(), version),
+            content_changes: vec![TextDocumentContentChangeEvent {
+                range: None,
+                range_length: None,
+                text: text.to_string(),
+            }],
+        };
+        self.notify(
+            DidChangeTextDocument::METHOD,
+            serde_json::to_value(change).unwrap(),
+        );
+        self.diagnostics()
+    }
+
+    fn diagnostics(&self) -> PublishDiagnosticsParams {
+        match self.connection.receiver.recv().unwrap() {
+            Message::Notification(notification)
+                if notification.method == PublishDiagnostics::METHOD =>
+            {
+                serde_json::from_value(notification.params).unwrap()
+            }
+            message => panic!("{message:?}"),
+        }
+    }
+
+    fn position(&mut self, method: &str, uri: &Url, line: u32, character: u32) -> Value {
+        let params = TextDocumentPositionParams {
+            text_document: TextDocumentIdentifier::new(uri.clone()),
+            position: Position::new(line, character),
+        };
+        self.request(method, serde_json::to_value(params).unwrap())
+    }
+}
+
+#[test]
+fn capabilities() {
+    let _interner = lock();
+    let (editor, capabilities) = Editor::start();
+    assert_eq!(capabilities["hoverProvider"], true);
+    assert_eq!(capabilities["definitionProvider"], true);
+    editor.stop();
+}
+
+#[test]
+fn errors_are_published_when_a_file_is_opened_and_changed() {
+    let _interner = lock();
+    let (editor, _) = Editor::start();
+    let uri = editor.open();
+
+    let text = MAIN.replace("{}", "true");
+    let published = editor.change(&uri, 2, &text);
+    let [diagnostic] = &published.diagnostics[..] else {
+        panic!("{:?}", published.diagnostics);
+    };
+    assert_eq!(diagnostic.range.start, Position::new(2, 13));
+    editor.stop();
+}
+
+#[test]
+fn hovering_shows_types_once_it_type_checks() {
+    let _interner = lock();
+    let (mut editor, _) = Editor::start();
+    let uri = editor.open();
+
+    editor.change(&uri, 2, &MAIN.replace("{}", "true"));
+    let hover = editor.position(HoverRequest::METHOD, &uri, 2, 14);
+    assert_eq!(hover, Value::Null);
+
+    assert!(editor.change(&uri, 3, MAIN).diagnostics.is_empty());
+    let hover = editor.position(HoverRequest::METHOD, &uri, 2, 14);
+    let hover: Hover = serde_json::from_value(hover).unwrap();
+    let HoverContents::Scalar(MarkedString::LanguageString(hover)) = hover.contents else {
+        panic!("{:?}", hover.contents);
+    };
+    assert_eq!(hover.value, "double: (I32 {} -> I32)");
+    editor.stop();
+}
+
+#[test]
+fn definitions_can_be_in_other_files() {
+    let _interner = lock();
+    let (mut editor, _) = Editor::start();
+    let uri = editor.open();
+
+    let definition = editor.position(GotoDefinition::METHOD, &uri, 2, 14);
+    let definition: GotoDefinitionResponse = serde_json::from_value(definition).unwrap();
+    let GotoDefinitionResponse::Scalar(location) = definition else {
+        panic!("{definition:?}");
+    };
+    assert_eq!(location.uri, editor.root_uri.join("shapes.curse").unwrap());
+    assert_eq!(location.range.start, Position::new(7, 3));
+    editor.stop();
+}
@@ -1,23 +1,19 @@
-use ast::tok;
-use curse_ast::{self as ast, Span};
-use smallvec::smallvec;
+use curse_interner::{Ident, InternedString};
+use curse_span::Span;
+use smallvec::{smallvec, SmallVec};
 use std::{
-    cell::RefCell,
     collections::HashMap,
-    iter,
     ops::{Index, IndexMut},
 };
-use string_interner::{DefaultSymbol, StringInterner};
 use typed_arena::Arena;
 
 use crate::{
-    defs, expr, pat, types, Equations, Type, TypeFunction, TypeIdent, TypeKind, TypeSymbol,
-    TypeTemplate, Typevar, ValueIdent, ValueSymbol, Var,
+    expr, pat, types, Constraint, Equations, Type, TypeFunction, TypeKind, TypeTemplate, Typevar,
+    Var,
 };
 
 #[derive(Default)]
 pub struct Global<'cx> {
-    pub string_interner: RefCell<StringInterner>,
     pub type_fns: Arena<types::TypeFunction<'cx>>,
     pub types: Arena<types::Type<'cx>>,
     pub pats: Arena<pat::Pat<'cx>>,
@@ -26,49 +22,20 @@ pub struct Global<'cx> {
     pub exprs: Arena<expr::Expr<'cx>>,
 
     // For slices only
-    pub type_idents: Arena<TypeIdent>,
-    pub value_idents: Arena<ValueIdent>,
-
-    pub defs: DefArena<'cx>,
-}
-
-#[derive(Default)]
-pub struct DefArena<'cx> {
-    pub named_fields: Arena<defs::NamedField<'cx>>,
-    pub field_types: Arena<defs::FieldType<'cx>>,
-    pub variant_defs: Arena<defs::VariantDef<'cx>>,
+    pub type_fields: Arena<(InternedString, types::Type<'cx>)>,
+    pub pat_fields: Arena<(Ident, pat::Pat<'cx>)>,
+    pub expr_fields: Arena<(Ident, expr::Expr<'cx>)>,
+    pub paths: Arena<Ident>,
 }
 
 pub struct Typeck<'cx> {
     pub global: &'cx Global<'cx>,
     pub typevars: Vec<Typevar<'cx>>,
     pub equations: Equations<'cx>,
-}
-
-pub struct Defs<'cx> {
-    pub global: &'cx Global<'cx>,
-    pub struct_defs: HashMap<TypeSymbol, defs::StructDef<'cx>>,
-    pub choice_defs: HashMap<TypeSymbol, defs::ChoiceDef<'cx>>,
-}
-
-impl<'cx> Global<'cx> {
-    fn get_or_intern(&self, string: &str) -> DefaultSymbol {
-        self.string_interner.borrow_mut().get_or_intern(string)
-    }
-
-    pub fn get_or_intern_type_ident(&self, ident: &tok::TypeIdent<'_>) -> TypeIdent {
-        TypeIdent {
-            symbol: TypeSymbol(self.get_or_intern(ident.literal)),
-            location: ident.location,
-        }
-    }
-
-    pub fn get_or_intern_value_ident(&self, ident: &tok::Ident<'_>) -> ValueIdent {
-        ValueIdent {
-            symbol: ValueSymbol(self.get_or_intern(ident.literal)),
-            location: ident.location,
-        }
-    }
+    /// Types that have to implement traits, which haven't been checked yet.
+    pub constraints: Vec<Constraint<'cx>>,
+    /// Uses of top level functions, which haven't been looked at yet.
+    pub references: Vec<Ident>,
 }
 
 impl<'cx> Typeck<'cx> {
@@ -77,6 +44,8 @@ impl<'cx> Typeck<'cx> {
             global,
             typevars: Vec::new(),
             equations: Equations::new(),
+            constraints: Vec::new(),
+            references: Vec::new(),
         }
     }
 
@@ -85,188 +54,236 @@ impl<'cx> Typeck<'cx> {
         self.typevars.push(Typevar::Unbound);
         var
     }
-    /// Get a global environment with the type signatures of `in` and `print`
-    /// already loaded in.
+
+    /// Get a global environment with the type signatures of the assertion
+    /// builtins already loaded in.
     ///
-    /// `in`: `x (x () -> y) -> y`
+    /// `assert`: `Bool {} -> {}`
+    ///
+    /// `assert_eq`: `x x -> {}`
     ///
     /// and
     ///
-    /// `print`: `x () -> ()`
-    pub fn default_globals(&mut self) -> impl Iterator<Item = (ValueSymbol, TypeTemplate<'cx>)> {
-        let dummy = (0, 0);
-        [
-            (ValueSymbol(self.global.get_or_intern("in")), {
-                let x = self.new_typevar();
-                let x_type = Type {
-                    kind: TypeKind::Var(x),
-                    span: dummy,
-                };
-                let y = self.new_typevar();
-                let y_type = Type {
-                    kind: TypeKind::Var(y),
-                    span: dummy,
-                };
+    /// `panic`: `x {} -> y`
+    pub fn default_globals(&mut self) -> impl Iterator<Item = (InternedString, TypeTemplate<'cx>)> {
+        let dummy = Span { start: 0, end: 0 };
+        let ty = |kind| Type { kind, span: dummy };
+        let function = |ctx: &mut Self, lhs, rhs, output| {
+            ty(TypeKind::Function(ctx.global.type_fns.alloc(
+                TypeFunction {
+                    lhs: ty(lhs),
+                    rhs: ty(rhs),
+                    output: ty(output),
+                },
+            )))
+        };
 
-                TypeTemplate {
-                    typevars: smallvec![x, y],
-                    ty: Type {
-                        kind: TypeKind::Function(self.global.type_fns.alloc(TypeFunction {
-                            lhs: x_type,
-                            rhs: Type {
-                                kind: TypeKind::Function(self.global.type_fns.alloc(
-                                    TypeFunction {
-                                        lhs: x_type,
-                                        rhs: Type {
-                                            kind: TypeKind::unit(),
-                                            span: dummy,
-                                        },
-                                        output: y_type,
-                                    },
-                                )),
-                                span: dummy,
-                            },
-                            output: y_type,
-                        })),
-                        span: dummy,
-                    },
-                }
-            }),
-            (ValueSymbol(self.global.get_or_intern("print")), {
-                let x = self.new_typevar();
-                let x_type = Type {
-                    kind: TypeKind::Var(x),
-                    span: dummy,
-                };
+        let assert = TypeTemplate::new(function(
+            self,
+            TypeKind::Bool,
+            TypeKind::unit(),
+            TypeKind::unit(),
+        ));
 
-                TypeTemplate {
-                    typevars: smallvec![x],
-                    ty: Type {
-                        kind: TypeKind::Function(self.global.type_fns.alloc(TypeFunction {
-                            lhs: x_type,
-                            rhs: Type {
-                                kind: TypeKind::unit(),
-                                span: dummy,
-                            },
-                            output: Type {
-                                kind: TypeKind::unit(),
-                                span: dummy,
-                            },
-                        })),
-                        span: dummy,
-                    },
-                }
-            }),
+        let x = self.new_typevar();
+        let assert_eq = TypeTemplate {
+            typevars: smallvec![x],
+            constraints: vec![],
+            ty: function(self, TypeKind::Var(x), TypeKind::Var(x), TypeKind::unit()),
+        };
+
+        let x = self.new_typevar();
+        let y = self.new_typevar();
+        let panic = TypeTemplate {
+            typevars: smallvec![x, y],
+            constraints: vec![],
+            ty: function(self, TypeKind::Var(x), TypeKind::unit(), TypeKind::Var(y)),
+        };
+
+        [
+            (InternedString::get_or_intern("assert"), assert),
+            (InternedString::get_or_intern("assert_eq"), assert_eq),
+            (InternedString::get_or_intern("panic"), panic),
         ]
         .into_iter()
     }
 
-    pub fn monomorphize(&mut self, template: &TypeTemplate<'cx>) -> Type<'cx> {
-        // Takes a polymorphic type and replaces all instances of generics
-        // with a fixed, unbound type.
-        // For example, id: T -> T is a polymorphic type, so it goes through
-        // and replaces both `T`s with an unbound type variable like `a0`,
-        // which is then bound later on.
+    /// Takes a polymorphic type and replaces all instances of generics with a
+    /// fixed, unbound type, pointing every part of it at `span`. The new type
+    /// variables have to implement the same traits as the generics they
+    /// replace.
+    /// For example, id: T -> T is a polymorphic type, so it goes through
+    /// and replaces both `T`s with an unbound type variable like `a0`,
+    /// which is then bound later on.
+    pub fn monomorphize(&mut self, template: &TypeTemplate<'cx>, span: Span) -> Type<'cx> {
         fn replace_unbound_typevars<'cx>(
-            tbl: &HashMap<Var, TypeKind<'cx>>,
+            tbl: &HashMap<Var, Var>,
             hir: &'cx Global<'cx>,
             ty: Type<'cx>,
+            span: Span,
         ) -> Type<'cx> {
-            match ty.kind {
-                TypeKind::Var(var) => Type {
-                    kind: tbl[&var],
-                    ..ty
-                },
-                TypeKind::Function(fun) => Type {
-                    kind: TypeKind::Function(hir.type_fns.alloc(TypeFunction {
-                        lhs: replace_unbound_typevars(tbl, hir, fun.lhs),
-                        rhs: replace_unbound_typevars(tbl, hir, fun.rhs),
-                        output: replace_unbound_typevars(tbl, hir, fun.output),
-                    })),
-                    ..ty
-                },
-                TypeKind::Record(types) => {
-                    let replaced_types = hir
-                        .types
-                        .alloc_extend(iter::repeat_with(Type::default).take(types.len()));
-
-                    for (slot, ty) in replaced_types.iter_mut().zip(types) {
-                        *slot = replace_unbound_typevars(tbl, hir, *ty);
+            let kind = match ty.kind {
+                TypeKind::I32 | TypeKind::Bool => ty.kind,
+                TypeKind::Var(var) => TypeKind::Var(tbl.get(&var).copied().unwrap_or(var)),
+                // Collected first since the arena can't be allocated into
+                // while it's already allocating.
+                TypeKind::Record { fields, rest } => {
+                    let fields: Vec<_> = fields
+                        .iter()
+                        .map(|&(name, ty)| (name, replace_unbound_typevars(tbl, hir, ty, span)))
+                        .collect();
+                    TypeKind::Record {
+                        fields: hir.type_fields.alloc_extend(fields),
+                        rest: rest.map(|var| tbl.get(&var).copied().unwrap_or(var)),
                     }
-
-                    Type {
-                        kind: TypeKind::Record(replaced_types),
-                        ..ty
+                }
+                TypeKind::Named { name, generic_args } => {
+                    let generic_args: Vec<_> = generic_args
+                        .iter()
+                        .map(|&ty| replace_unbound_typevars(tbl, hir, ty, span))
+                        .collect();
+                    TypeKind::Named {
+                        name,
+                        generic_args: hir.types.alloc_extend(generic_args),
                     }
                 }
-                _ => ty,
-            }
+                TypeKind::Function(fun) => TypeKind::Function(hir.type_fns.alloc(TypeFunction {
+                    lhs: replace_unbound_typevars(tbl, hir, fun.lhs, span),
+                    rhs: replace_unbound_typevars(tbl, hir, fun.rhs, span),
+                    output: replace_unbound_typevars(tbl, hir, fun.output, span),
+                })),
+            };
+
+            Type { kind, span }
         }
 
-        let tvs_to_replace = template
+        let tbl: HashMap<Var, Var> = template
             .typevars
             .iter()
-            .map(|tv| (*tv, TypeKind::Var(self.new_typevar())))
+            .map(|&var| (var, self.new_typevar()))
             .collect();
 
-        replace_unbound_typevars(&tvs_to_replace, self.global, template.ty)
+        for &(trait_, var) in &template.constraints {
+            self.constraints.push(Constraint {
+                trait_,
+                ty: Type {
+                    kind: TypeKind::Var(tbl[&var]),
+                    span,
+                },
+            });
+        }
+
+        replace_unbound_typevars(&tbl, self.global, template.ty, span)
     }
 
-    /// Convert an [`ast::Type<'_, 'input>`] annotation into an HIR [`Type<'cx>`].
-    pub fn type_from_ast(
-        &mut self,
-        ty: &ast::Type<'_, '_>,
-        map: &HashMap<TypeSymbol, Type<'cx>>,
-    ) -> Type<'cx> {
-        match ty {
-            // TODO(quinn): Should do a trie implementation for types?
-            // Because types are basically trees...
-            ast::Type::Named(named) => match named.name.literal {
-                "I32" => Type {
-                    kind: TypeKind::I32,
-                    span: named.span(),
-                },
-                "Bool" => Type {
-                    kind: TypeKind::Bool,
-                    span: named.span(),
-                },
-                // TODO(quinn): for now this doesn't support generic types at all
-                _ => map
-                    .get(&self.global.get_or_intern_type_ident(&named.name).symbol)
-                    .copied()
-                    .expect("type not found"),
-            },
-            ast::Type::Tuple(tuple) => {
-                let types = self
-                    .global
-                    .types
-                    .alloc_extend(iter::repeat_with(Type::default).take(tuple.len()));
+    /// Turns the type of a top level function into a template that's generic
+    /// over every type variable that's still unbound in it.
+    ///
+    /// This is only sound because nothing else in scope can mention those
+    /// variables: every function that a top level function uses is either
+    /// already a template, or being generalized along with it.
+    pub fn generalize(&mut self, ty: Type<'cx>) -> TypeTemplate<'cx> {
+        fn free_typevars(ty: Type<'_>, typevars: &mut SmallVec<[Var; 4]>) {
+            match ty.kind {
+                TypeKind::I32 | TypeKind::Bool => {}
+                TypeKind::Var(var) => {
+                    if !typevars.contains(&var) {
+                        typevars.push(var);
+                    }
+                }
+                TypeKind::Record { fields, rest } => {
+                    for &(_, ty) in fields {
+                        free_typevars(ty, typevars);
+                    }
+                    if let Some(var) = rest {
+                        if !typevars.contains(&var) {
+                            typevars.push(var);
+                        }
+                    }
+                }
+                TypeKind::Named { generic_args, .. } => {
+                    for &ty in generic_args {
+                        free_typevars(ty, typevars);
+                    }
+                }
+                TypeKind::Function(fun) => {
+                    free_typevars(fun.lhs, typevars);
+                    free_typevars(fun.rhs, typevars);
+                    free_typevars(fun.output, typevars);
+                }
+            }
+        }
 
-                for (i, ty) in tuple.iter_elements().enumerate() {
-                    types[i] = self.type_from_ast(ty, map);
+        let ty = self.expand(ty);
+        let mut typevars = SmallVec::new();
+        free_typevars(ty, &mut typevars);
+        TypeTemplate {
+            typevars,
+            constraints: vec![],
+            ty,
+        }
+    }
+
+    /// Replaces every bound type variable in `ty` with what it's bound to, so
+    /// that the only variables left in it are unbound.
+    pub fn expand(&self, ty: Type<'cx>) -> Type<'cx> {
+        let kind = match ty.kind {
+            TypeKind::I32 | TypeKind::Bool => ty.kind,
+            TypeKind::Var(var) => match self[var].binding() {
+                Some(binding) => self.expand(*binding).kind,
+                None => ty.kind,
+            },
+            TypeKind::Record { fields, rest } => {
+                let (fields, rest) = self.row(fields, rest);
+                let fields: Vec<_> = fields
+                    .into_iter()
+                    .map(|(name, ty)| (name, self.expand(ty)))
+                    .collect();
+                TypeKind::Record {
+                    fields: self.global.type_fields.alloc_extend(fields),
+                    rest,
+                }
+            }
+            TypeKind::Named { name, generic_args } => {
+                let generic_args: Vec<_> = generic_args.iter().map(|&ty| self.expand(ty)).collect();
+                TypeKind::Named {
+                    name,
+                    generic_args: self.global.types.alloc_extend(generic_args),
                 }
+            }
+            TypeKind::Function(fun) => {
+                TypeKind::Function(self.global.type_fns.alloc(TypeFunction {
+                    lhs: self.expand(fun.lhs),
+                    rhs: self.expand(fun.rhs),
+                    output: self.expand(fun.output),
+                }))
+            }
+        };
+
+        Type { kind, ..ty }
+    }
 
-                Type {
-                    kind: TypeKind::Record(types),
-                    span: tuple.span(),
+    /// All the fields of a record with `fields` and `rest`, sorted by name,
+    /// following what the row variable is bound to. Also returns the row
+    /// variable at the end, if it's still unbound.
+    pub fn row(
+        &self,
+        fields: &[(InternedString, Type<'cx>)],
+        mut rest: Option<Var>,
+    ) -> (Vec<(InternedString, Type<'cx>)>, Option<Var>) {
+        let mut all = fields.to_vec();
+        while let Some(var) = rest {
+            match self[var].binding().map(|ty| ty.kind) {
+                Some(TypeKind::Record { fields, rest: next }) => {
+                    all.extend_from_slice(fields);
+                    rest = next;
                 }
+                Some(TypeKind::Var(next)) => rest = Some(next),
+                _ => break,
             }
-            ast::Type::Error => todo!("Handle errors :)"),
-            // ast::Type::Function(fun) => {
-            //     let lhs = self.type_from_ast(fun.lhs, map);
-            //     let rhs = self.type_from_ast(fun.rhs, map);
-            //     let output = self.type_from_ast(fun.output, map);
-            //
-            //     Type {
-            //         kind: TypeKind::Function(self.global.type_fns.alloc(TypeFunction {
-            //             lhs,
-            //             rhs,
-            //             output,
-            //         })),
-            //         span: fun.span(),
-            //     }
-            // }
         }
+        all.sort_by_key(|&(name, _)| name);
+        (all, rest)
     }
 
     pub fn occurs(&self, var: Var, ty: &Type<'_>) -> bool {
@@ -278,12 +295,27 @@ impl<'cx> Typeck<'cx> {
                     var == typevar
                 }
             }
+            TypeKind::Record { fields, rest } => {
+                fields.iter().any(|(_, ty)| self.occurs(var, ty))
+                    || rest.is_some_and(|rest| {
+                        self.occurs(
+                            var,
+                            &Type {
+                                kind: TypeKind::Var(rest),
+                                span: ty.span,
+                            },
+                        )
+                    })
+            }
+            TypeKind::Named { generic_args, .. } => {
+                generic_args.iter().any(|ty| self.occurs(var, ty))
+            }
             TypeKind::Function(fun) => {
                 self.occurs(var, &fun.lhs)
                     || self.occurs(var, &fun.rhs)
                     || self.occurs(var, &fun.output)
             }
-            _ => false,
+            TypeKind::I32 | TypeKind::Bool => false,
         }
     }
 
@@ -0,0 +1,90 @@
+//! Which `impl` each use of a trait goes to.
+//!
+//! The interpreter picks an `impl` by looking at the values it's given, but
+//! the compiled backends need to know ahead of time. Type checking works out
+//! the type at every place that a trait is used, so it records what that
+//! means there: the builtin operation, a particular `impl`, or, inside of a
+//! generic function, whatever the function is given for one of its
+//! constraints. A backend can then make a copy of each generic function for
+//! every set of [`Dispatch`]es that it's used with.
+
+use curse_hir::hir::Trait;
+use curse_interner::InternedString;
+use curse_span::Span;
+use std::collections::HashMap;
+
+/// What using a trait at some type means.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Dispatch {
+    /// The builtin operation on `I32`s and `Bool`s, or comparing a record,
+    /// struct or choice structurally when nothing in it has an `impl`.
+    Builtin,
+    /// The `impl` of the trait for the named type.
+    Impl(Trait, InternedString),
+    /// Whatever the enclosing function is given for the constraint of its
+    /// template at this index.
+    Generic(usize),
+    /// Comparing a record, struct or choice structurally, where some of what
+    /// it contains has an `impl` or is generic. Only those parts are listed.
+    Structural(Vec<Dispatch>),
+    /// The type isn't decided by anything, so only running the program can
+    /// tell which `impl` it is.
+    Unknown,
+}
+
+impl Dispatch {
+    /// Replaces each [`Dispatch::Generic`] with what the enclosing function
+    /// was given for it in `dictionary`.
+    pub fn substitute(&self, dictionary: &[Dispatch]) -> Dispatch {
+        match self {
+            Dispatch::Generic(index) => {
+                dictionary.get(*index).cloned().unwrap_or(Dispatch::Unknown)
+            }
+            Dispatch::Structural(nested) => Dispatch::structural(
+                nested
+                    .iter()
+                    .map(|dispatch| dispatch.substitute(dictionary))
+                    .collect(),
+            ),
+            Dispatch::Builtin | Dispatch::Impl(..) | Dispatch::Unknown => self.clone(),
+        }
+    }
+
+    /// Compares structurally with `nested` inside, leaving out anything that
+    /// is builtin, so that it's only [`Dispatch::Structural`] if it has to be.
+    pub fn structural(nested: Vec<Dispatch>) -> Dispatch {
+        let nested: Vec<Dispatch> = nested
+            .into_iter()
+            .flat_map(|dispatch| match dispatch {
+                Dispatch::Builtin => vec![],
+                Dispatch::Structural(nested) => nested,
+                dispatch => vec![dispatch],
+            })
+            .collect();
+        if nested.is_empty() {
+            Dispatch::Builtin
+        } else {
+            Dispatch::Structural(nested)
+        }
+    }
+}
+
+/// A top level function or an `impl`, which the places that traits are used
+/// in belong to.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Owner {
+    Function(InternedString),
+    Impl(Trait, InternedString),
+}
+
+/// The [`Dispatch`]es of a program.
+#[derive(Clone, Debug, Default)]
+pub struct Dispatches {
+    /// The traits that each generic function is generic over, in the order of
+    /// the constraints of its template.
+    pub generics: HashMap<InternedString, Vec<Trait>>,
+    /// What each use of a trait means, keyed by where it is. A symbol like
+    /// `+` uses one trait, and a generic function uses each one that it's
+    /// generic over, in order.
+    pub sites: HashMap<(Owner, Span), Vec<Dispatch>>,
+}
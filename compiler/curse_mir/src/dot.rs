@@ -1,98 +1,172 @@
-//! Utilities for writing an AST in dot format for Graphviz.
-use crate::{ctx, Expr, ExprKind};
-use std::fmt::Write as _;
+//! Utilities for writing typed expressions in dot format for Graphviz.
+use crate::{ctx, Expr, ExprKind, Pat, PatKind, Ty, TypeKind, TypedFunction, TypedProgram, Var};
+use curse_hir::hir::RegionKind;
+use curse_interner::Ident;
+use curse_span::Span;
+use std::fmt::{self, Write as _};
 
-pub struct Builder<'cx> {
-    ctx: &'cx ctx::Typeck<'cx>,
+pub struct Builder<'a, 'cx> {
+    ctx: &'a ctx::Typeck<'cx>,
+    /// The generics of the function being drawn, so that its types print
+    /// the same way as its type does.
+    generics: &'a [Var],
     count: u32,
     out: String,
 }
 
-impl<'cx> Builder<'cx> {
-    pub fn new(ctx: &'cx ctx::Typeck<'cx>) -> Self {
-        let out = String::with_capacity(2048) + "digraph example {";
+impl<'a, 'cx> Builder<'a, 'cx> {
+    pub fn new(ctx: &'a ctx::Typeck<'cx>) -> Self {
+        let out = String::with_capacity(2048) + "digraph mir {\n    node [shape = box]";
 
-        Builder { ctx, count: 0, out }
+        Builder {
+            ctx,
+            generics: &[],
+            count: 0,
+            out,
+        }
     }
 
-    pub fn visit_expr(&mut self, expr: Expr<'cx>, parent: Option<u32>, name: Option<&str>) {
-        let id = self.fresh();
-        self.out += "\n    ";
+    /// Draws every function in `program`, in the order they were checked.
+    pub fn visit_program(&mut self, program: &'a TypedProgram<'cx>) {
+        for name in program.components.iter().flatten() {
+            self.visit_function(&name.to_string(), &program.functions[name]);
+        }
+    }
 
-        match expr.kind {
-            ExprKind::Builtin(builtin) => {
-                write!(
-                    self.out,
-                    "p{id}[label = \"{builtin}: {ty}\"]",
-                    ty = builtin.type_kind().display(self.ctx),
-                )
-                .unwrap();
-            }
-            ExprKind::I32(i) => {
-                write!(self.out, "p{id}[label = \"{i}: i32\"]").unwrap();
-            }
-            ExprKind::Bool(b) => {
-                write!(self.out, "p{id}[label = \"{b}: bool\"]").unwrap();
-            }
-            ExprKind::Ident { ty, literal } => {
-                write!(
-                    self.out,
-                    "p{id}[label = \"{lit}: {ty}\"]",
-                    lit = literal.display(self.ctx.global),
-                    ty = ty.display(self.ctx)
-                )
-                .unwrap();
+    pub fn visit_function(&mut self, name: &str, function: &'a TypedFunction<'cx>) {
+        self.generics = &function.template.typevars;
+        let label = format!("fn {name}: {}", function.template.display(self.ctx));
+        let id = self.node(&label, function.expr.span);
+        self.visit_expr(function.expr, Some(id), "");
+        self.generics = &[];
+    }
+
+    pub fn visit_expr(&mut self, expr: Expr<'cx>, parent: Option<u32>, name: &str) -> u32 {
+        let label = match expr.kind {
+            ExprKind::Builtin { builtin, .. } => builtin.to_string(),
+            ExprKind::I32(i) => i.to_string(),
+            ExprKind::Bool(b) => b.to_string(),
+            ExprKind::Ident { literal, .. } => literal.to_string(),
+            ExprKind::Record { .. } => "record".to_string(),
+            ExprKind::Constructor { path, .. } => path_label(path),
+            ExprKind::Closure { .. } => "closure".to_string(),
+            ExprKind::Appl { .. } => "appl".to_string(),
+            ExprKind::Field { field, .. } => format!(".{field}"),
+            ExprKind::Region { kind, .. } => match kind {
+                RegionKind::Ref => "ref",
+                RegionKind::Mut => "mut",
+                RegionKind::RefMut => "ref mut",
             }
-            ExprKind::Record { ty, exprs } => {
-                write!(
-                    self.out,
-                    "p{id}[label = \"tuple: {ty}\"]",
-                    ty = ty.display(self.ctx)
-                )
-                .unwrap();
-                for expr in exprs.iter() {
-                    self.visit_expr(*expr, Some(id), None);
+            .to_string(),
+            ExprKind::Error { .. } => "<error>".to_string(),
+        };
+        let id = self.node(&format!("{label}: {}", self.ty(expr.ty().kind)), expr.span);
+
+        match expr.kind {
+            ExprKind::Record { fields, .. } => {
+                for (ident, field) in fields {
+                    self.visit_expr(*field, Some(id), &ident.to_string());
                 }
             }
-            ExprKind::Constructor { .. } => {
-                todo!("dot visualization for ctors")
+            ExprKind::Constructor { inner, .. }
+            | ExprKind::Field { record: inner, .. }
+            | ExprKind::Region { body: inner, .. } => {
+                self.visit_expr(*inner, Some(id), "");
             }
-            ExprKind::Closure { ty, arms } => {
-                let name = name.unwrap_or("<closure>");
-                write!(
-                    self.out,
-                    "p{id}[label = \"{name}: {ty}\"]",
-                    ty = ty.display(self.ctx)
-                )
-                .unwrap();
-                for arm in arms.iter() {
-                    self.visit_expr(arm.body, Some(id), None);
+            ExprKind::Closure { arms, .. } => {
+                for arm in arms {
+                    let label =
+                        format!("|{}, {}|", PatLabel(arm.lhs, self), PatLabel(arm.rhs, self));
+                    let span = Span {
+                        start: arm.lhs.span.start.min(arm.body.span.start),
+                        end: arm.body.span.end,
+                    };
+                    let arm_id = self.node(&label, span);
+                    self.edge(id, arm_id, "");
+                    self.visit_expr(arm.body, Some(arm_id), "");
                 }
             }
-            ExprKind::Appl { ty, appl } => {
-                write!(
-                    self.out,
-                    "p{id}[label = \"<appl>: {ty}\"]",
-                    ty = ty.display(self.ctx)
-                )
-                .unwrap();
-                self.visit_expr(appl.lhs, Some(id), None);
-                self.visit_expr(appl.function, Some(id), None);
-                self.visit_expr(appl.rhs, Some(id), None);
+            ExprKind::Appl { appl, .. } => {
+                self.visit_expr(appl.lhs, Some(id), "lhs");
+                self.visit_expr(appl.function, Some(id), "fun");
+                self.visit_expr(appl.rhs, Some(id), "rhs");
             }
+            ExprKind::Builtin { .. }
+            | ExprKind::I32(_)
+            | ExprKind::Bool(_)
+            | ExprKind::Ident { .. }
+            | ExprKind::Error { .. } => {}
         }
 
-        if let Some(parent_id) = parent {
-            write!(self.out, "\n    p{parent_id} -> p{id}[label = \"\"]").unwrap();
+        if let Some(parent) = parent {
+            self.edge(parent, id, name);
         }
+        id
     }
 
-    fn fresh(&mut self) -> u32 {
+    fn ty(&self, ty: TypeKind<'cx>) -> String {
+        let mut printer = ty.display(self.ctx);
+        printer.generics = self.generics;
+        printer.to_string()
+    }
+
+    fn node(&mut self, label: &str, span: Span) -> u32 {
         self.count += 1;
-        self.count
+        let id = self.count;
+        let label = format!("{label}\n{span:?}");
+        write!(self.out, "\n    p{id}[label = {label:?}]").unwrap();
+        id
+    }
+
+    fn edge(&mut self, from: u32, to: u32, label: &str) {
+        write!(self.out, "\n    p{from} -> p{to}[label = {label:?}]").unwrap();
     }
 
     pub fn finish(self) -> String {
-        self.out + "\n}"
+        self.out + "\n}\n"
+    }
+}
+
+fn path_label(path: &[Ident]) -> String {
+    path.iter()
+        .map(|ident| ident.to_string())
+        .collect::<Vec<_>>()
+        .join("::")
+}
+
+/// Writes a pattern the way it would be written in the source, with the
+/// types of its bindings.
+struct PatLabel<'b, 'a, 'cx>(Pat<'cx>, &'b Builder<'a, 'cx>);
+
+impl fmt::Display for PatLabel<'_, '_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let PatLabel(pat, builder) = self;
+        match pat.kind {
+            PatKind::Bool(b) => write!(f, "{b}"),
+            PatKind::I32(i) => write!(f, "{i}"),
+            PatKind::Ident { ty, literal } => write!(f, "{literal}: {}", builder.ty(ty)),
+            PatKind::Record { ty, fields } => {
+                let open = matches!(ty, TypeKind::Record { rest: Some(_), .. });
+                if fields.is_empty() && !open {
+                    return f.write_str("{}");
+                }
+                f.write_str("{ ")?;
+                for (i, (ident, field)) in fields.iter().enumerate() {
+                    if i > 0 {
+                        f.write_str(", ")?;
+                    }
+                    write!(f, "{ident}: {}", PatLabel(*field, builder))?;
+                }
+                match (open, fields.is_empty()) {
+                    (false, _) => f.write_str(" }"),
+                    (true, true) => f.write_str(".. }"),
+                    (true, false) => f.write_str(", .. }"),
+                }
+            }
+            PatKind::Constructor { path, inner, .. } => {
+                write!(f, "{} {}", path_label(path), PatLabel(*inner, builder))
+            }
+            PatKind::Error { .. } => f.write_str("<error>"),
+        }
     }
 }
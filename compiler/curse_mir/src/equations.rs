@@ -1,6 +1,12 @@
-use crate::{Type, Var};
+use crate::{ctx, Reason, Type, TypeKind, Var};
+use curse_interner::InternedString;
 use displaydoc::Display;
-use petgraph::graph::{DiGraph, NodeIndex};
+use petgraph::{
+    dot::Dot,
+    graph::{DiGraph, NodeIndex},
+    visit::EdgeRef,
+    Direction,
+};
 
 /// A node on the inference graph.
 #[derive(Copy, Clone, Debug)]
@@ -10,10 +16,7 @@ pub enum Node<'cx> {
     // #[displaydoc("{0} ≢ {1}")]
     NotEquiv(Type<'cx>, Type<'cx>),
     // #[displaydoc("{var} := {definition}")]
-    Binding {
-        var: Var,
-        definition: Type<'cx>,
-    },
+    Binding { var: Var, definition: Type<'cx> },
 }
 
 /// An edge on the inference graph i.e. the reason why a proof (node) leads to
@@ -28,8 +31,12 @@ pub enum Edge {
     FunctionOutput,
     #[displaydoc("transitivity")]
     Transitivity,
-    #[displaydoc("tuple_{0}")]
-    Tuple(usize),
+    #[displaydoc("field_{0}")]
+    Field(InternedString),
+    #[displaydoc("rest")]
+    Rest,
+    #[displaydoc("generic_arg_{0}")]
+    GenericArg(usize),
 }
 
 #[derive(Default)]
@@ -51,32 +58,123 @@ impl<'cx> Equations<'cx> {
     pub fn add_proof(&mut self, proof: NodeIndex, conclusion: NodeIndex, edge: Edge) {
         self.graph.add_edge(proof, conclusion, edge);
     }
+
+    /// Explains why the two types at `node` had to be the same, by following
+    /// the conclusions that it's a proof of out to the unification that
+    /// started it.
+    pub fn explain(&self, node: NodeIndex, ctx: &ctx::Typeck<'cx>) -> Vec<Reason> {
+        let mut reasons: Vec<Reason> = vec![];
+        let mut proof = node;
+
+        while let Some(edge) = self.graph.edges_directed(proof, Direction::Outgoing).next() {
+            let (Node::Equiv(t1, t2) | Node::NotEquiv(t1, t2)) = self.graph[edge.target()] else {
+                break;
+            };
+
+            let reason = match edge.weight() {
+                // One of the types is a type variable that was already bound,
+                // so say where it was bound.
+                Edge::Transitivity => {
+                    let var_ty = if let TypeKind::Var(_) = t1.kind {
+                        t1
+                    } else {
+                        t2
+                    };
+                    let TypeKind::Var(var) = var_ty.kind else {
+                        break;
+                    };
+                    let Some(binding) = ctx[var].binding() else {
+                        break;
+                    };
+
+                    Reason {
+                        message: format!("this was inferred to be `{}`", binding.kind.display(ctx)),
+                        span: var_ty.span.start_len().into(),
+                        label: "this".to_string(),
+                        because: (binding.span != var_ty.span)
+                            .then(|| binding.span.start_len().into()),
+                    }
+                }
+                part => Reason {
+                    message: format!(
+                        "the {} of `{}` and `{}` have to match",
+                        part.describe(),
+                        t1.kind.display(ctx),
+                        t2.kind.display(ctx)
+                    ),
+                    span: t1.span.start_len().into(),
+                    label: "while unifying this".to_string(),
+                    because: (t1.span != t2.span).then(|| t2.span.start_len().into()),
+                },
+            };
+
+            if !reasons
+                .iter()
+                .any(|other| other.message == reason.message && other.span == reason.span)
+            {
+                reasons.push(reason);
+            }
+            proof = edge.target();
+        }
+
+        reasons
+    }
+
+    /// Writes the graph in Graphviz's dot format, with everything that
+    /// couldn't be unified in red.
+    pub fn dot(&self, ctx: &ctx::Typeck<'cx>) -> String {
+        let labels = self
+            .graph
+            .map(|_, node| node.display(ctx), |_, edge| edge.to_string());
+
+        let red = |failed: bool| {
+            if failed {
+                "color = red".to_string()
+            } else {
+                String::new()
+            }
+        };
+
+        Dot::with_attr_getters(
+            &labels,
+            &[],
+            &|_, edge| red(matches!(self.graph[edge.source()], Node::NotEquiv(..))),
+            &|_, (index, _)| red(matches!(self.graph[index], Node::NotEquiv(..))),
+        )
+        .to_string()
+    }
+}
+
+impl<'cx> Node<'cx> {
+    /// The node as a string, with where each type came from.
+    fn display(&self, ctx: &ctx::Typeck<'cx>) -> String {
+        // Unlike in errors, show which type variables things are bound to.
+        let ty = |ty: &Type<'cx>| match ty.kind {
+            TypeKind::Var(var) if ctx[var].binding().is_some() => {
+                format!("{var} = {} {:?}", ty.kind.display(ctx), ty.span)
+            }
+            _ => format!("{} {:?}", ty.kind.display(ctx), ty.span),
+        };
+
+        match self {
+            Node::Equiv(t1, t2) => format!("{} ≡ {}", ty(t1), ty(t2)),
+            Node::NotEquiv(t1, t2) => format!("{} ≢ {}", ty(t1), ty(t2)),
+            Node::Binding { var, definition } => format!("{var} := {}", ty(definition)),
+        }
+    }
 }
 
-// impl fmt::Display for Equations<'_> {
-//     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-//         fn get_edge_attributes(
-//             graph: &DiGraph<Node<'_>, Edge>,
-//             edge: EdgeReference<Edge>,
-//         ) -> String {
-//             if let Node::NotEquiv(_, _) = &graph[edge.source()] {
-//                 "color = red".to_string()
-//             } else {
-//                 String::new()
-//             }
-//         }
-
-//         fn get_node_attributes(
-//             _graph: &DiGraph<Node<'_>, Edge>,
-//             (_ix, rule): (NodeIndex, &Node<'_>),
-//         ) -> String {
-//             if let Node::NotEquiv(_, _) = rule {
-//                 "color = red".to_string()
-//             } else {
-//                 String::new()
-//             }
-//         }
-
-//         Dot::with_attr_getters(&self.graph, &[], &get_edge_attributes, &get_node_attributes).fmt(f)
-//     }
-// }
+impl Edge {
+    /// The part of a conclusion that a proof is about, for error messages.
+    fn describe(&self) -> String {
+        match self {
+            Edge::FunctionLhs => "left arguments".to_string(),
+            Edge::FunctionRhs => "right arguments".to_string(),
+            Edge::FunctionOutput => "outputs".to_string(),
+            Edge::Transitivity => "bindings".to_string(),
+            Edge::Field(name) => format!("`{name}` fields"),
+            Edge::Rest => "other fields".to_string(),
+            Edge::GenericArg(index) => format!("generic arguments at {index}"),
+        }
+    }
+}
@@ -1,42 +1,39 @@
-use crate::{ctx, Type, TypeKind, Var};
-use curse_ast::{self as ast, Span};
-use miette::{Diagnostic, NamedSource, SourceSpan};
+use crate::{ctx, Type, Var};
+use curse_interner::Ident;
+use curse_span::HasSpan;
+use miette::{Diagnostic, SourceSpan};
+use petgraph::graph::NodeIndex;
 use thiserror::Error;
 
-#[derive(Debug, Diagnostic, Error)]
-#[error("Errors in lowering")]
-pub struct LowerErrors<'cx> {
-    #[source_code]
-    pub code: NamedSource,
-
-    #[related]
-    pub errors: Vec<LowerError<'cx>>,
-}
-
 #[derive(Clone, Debug, Diagnostic, Error)]
-pub enum LowerError<'cx> {
+pub enum LowerError {
     #[error("Cannot unify types: {ty1_kind} and {ty2_kind}")]
     #[diagnostic(help("Use types that can be unified"))]
     Unify {
         #[label("First type")]
-        ty1_span: (usize, usize),
+        ty1_span: SourceSpan,
         ty1_kind: String,
 
         #[label("Second type")]
-        ty2_span: (usize, usize),
+        ty2_span: SourceSpan,
         ty2_kind: String,
+
+        /// Where the types didn't match on the inference graph.
+        node: NodeIndex,
+        #[related]
+        reasons: Vec<Reason>,
     },
 
     #[error("Infinite recursive type")]
     #[diagnostic(help("Don't make the type depend on itself."))]
     CyclicType {
         #[label("Type that could not be assigned")]
-        var_span: (usize, usize),
+        var_span: SourceSpan,
         var: Var,
 
         #[label("Type that was attempted to be assigned to")]
-        ty_span: (usize, usize),
-        ty_kind: TypeKind<'cx>,
+        ty_span: SourceSpan,
+        ty_kind: String,
     },
 
     #[error("Identifier not found: `{literal}`")]
@@ -47,47 +44,129 @@ pub enum LowerError<'cx> {
         literal: String,
     },
 
-    #[error("Cannot parse integer {literal}")]
-    #[diagnostic(help("Use a smaller integer"))]
-    ParseInt {
-        #[label("This number here")]
+    #[error("Type not found: `{path}`")]
+    #[diagnostic(help("Use a struct or choice that is defined"))]
+    TypeNotFound {
+        #[label("This type here")]
         span: SourceSpan,
-        literal: String,
+        path: String,
     },
 
-    #[error("Function arm has too many parameters.")]
-    #[diagnostic(help("If you need many parameters, try putting them into a tuple."))]
-    TooManyParams {
-        #[label("This arm here")]
+    #[error("Constructor not found: `{path}`")]
+    #[diagnostic(help("Constructors are written `Choice::Variant` or `Struct`"))]
+    ConstructorNotFound {
+        #[label("This constructor here")]
         span: SourceSpan,
+        path: String,
     },
-}
 
-impl From<&ast::tok::Integer<'_>> for LowerError<'_> {
-    fn from(value: &ast::tok::Integer<'_>) -> Self {
-        LowerError::ParseInt {
-            span: value.span().into(),
-            literal: value.literal.to_string(),
-        }
-    }
+    #[error("`{ty_kind}` doesn't implement `{trait_name}`")]
+    #[diagnostic(help(
+        "Only `I32` works with every symbol. `=` and the comparisons also work on `Bool`, and on records, structs and choices whose contents they work on. Other types need an `impl {trait_name}`"
+    ))]
+    MissingImpl {
+        #[label("`{ty_kind}` is used with this")]
+        span: SourceSpan,
+        trait_name: String,
+        ty_kind: String,
+    },
+
+    #[error("`{name}` is generic, so it can't implement traits yet")]
+    #[diagnostic(help("Implement traits for structs and choices without generic parameters"))]
+    GenericImpl {
+        #[label("This type")]
+        span: SourceSpan,
+        name: String,
+    },
+
+    #[error("`{symbol}` isn't supported by the type checker yet")]
+    UnsupportedSymbol {
+        #[label("This symbol here")]
+        span: SourceSpan,
+        symbol: &'static str,
+    },
 }
 
-impl From<&ast::tok::Ident<'_>> for LowerError<'_> {
-    fn from(value: &ast::tok::Ident<'_>) -> Self {
-        LowerError::IdentNotFound {
-            span: value.span().into(),
-            literal: value.literal.to_string(),
-        }
-    }
+/// Part of why two types had to be the same.
+#[derive(Clone, Debug, Diagnostic, Error)]
+#[error("{message}")]
+pub struct Reason {
+    pub message: String,
+    #[label("{label}")]
+    pub span: SourceSpan,
+    pub label: String,
+    #[label("because of this")]
+    pub because: Option<SourceSpan>,
 }
 
-impl<'cx> LowerError<'cx> {
-    pub fn unify(t1: Type<'cx>, t2: Type<'cx>, ctx: &ctx::Typeck<'cx>) -> Self {
+impl LowerError {
+    pub fn unify<'cx>(
+        t1: Type<'cx>,
+        t2: Type<'cx>,
+        node: NodeIndex,
+        ctx: &ctx::Typeck<'cx>,
+    ) -> Self {
         LowerError::Unify {
-            ty1_span: t1.span,
+            ty1_span: t1.span.start_len().into(),
             ty1_kind: t1.kind.display(ctx).to_string(),
-            ty2_span: t2.span,
+            ty2_span: t2.span.start_len().into(),
             ty2_kind: t2.kind.display(ctx).to_string(),
+            node,
+            reasons: vec![],
+        }
+    }
+
+    /// Fills in why the types in the error had to be the same, once
+    /// everything that led to it is on the inference graph.
+    pub fn explain(&mut self, ctx: &ctx::Typeck<'_>) {
+        if let LowerError::Unify { node, reasons, .. } = self {
+            *reasons = ctx.equations.explain(*node, ctx);
+        }
+    }
+
+    /// Moves every span in the error along by `by` bytes, for when the
+    /// function it's in has moved since it was checked.
+    pub fn shift(&mut self, by: i64) {
+        match self {
+            LowerError::Unify {
+                ty1_span,
+                ty2_span,
+                reasons,
+                ..
+            } => {
+                shift(ty1_span, by);
+                shift(ty2_span, by);
+                for reason in reasons {
+                    shift(&mut reason.span, by);
+                    if let Some(because) = &mut reason.because {
+                        shift(because, by);
+                    }
+                }
+            }
+            LowerError::CyclicType {
+                var_span, ty_span, ..
+            } => {
+                shift(var_span, by);
+                shift(ty_span, by);
+            }
+            LowerError::IdentNotFound { span, .. }
+            | LowerError::TypeNotFound { span, .. }
+            | LowerError::ConstructorNotFound { span, .. }
+            | LowerError::MissingImpl { span, .. }
+            | LowerError::GenericImpl { span, .. }
+            | LowerError::UnsupportedSymbol { span, .. } => shift(span, by),
+        }
+    }
+
+    pub fn ident_not_found(ident: Ident) -> Self {
+        LowerError::IdentNotFound {
+            span: ident.span().start_len().into(),
+            literal: ident.to_string(),
         }
     }
 }
+
+fn shift(span: &mut SourceSpan, by: i64) {
+    let offset = (span.offset() as i64 + by) as usize;
+    *span = SourceSpan::new(offset.into(), span.len().into());
+}
@@ -1,5 +1,6 @@
-
-use crate::{Type, TypeFunction, TypeKind};
+use crate::{ctx, Constraint, Type, TypeFunction, TypeKind};
+use curse_hir::hir::Trait;
+use curse_span::Span;
 use std::fmt;
 
 #[derive(Copy, Clone, Debug)]
@@ -14,6 +15,7 @@ pub enum Builtin {
     Gt,
     Le,
     Ge,
+    Semi,
 }
 
 impl Builtin {
@@ -30,43 +32,58 @@ impl Builtin {
             Gt => ">",
             Le => "<=",
             Ge => ">=",
+            Semi => ";",
         }
     }
 
-    pub fn type_kind(&self) -> TypeKind<'static> {
+    /// The trait that the type of both sides has to implement.
+    pub fn trait_(&self) -> Option<Trait> {
         use Builtin::*;
         match self {
-            Add | Sub | Mul | Rem => TypeKind::Function(&TypeFunction {
-                lhs: Type {
-                    kind: TypeKind::I32,
-                    span: (0, 0),
-                },
-                rhs: Type {
-                    kind: TypeKind::I32,
-                    span: (0, 0),
-                },
-                output: Type {
-                    kind: TypeKind::I32,
-                    span: (0, 0),
-                },
-            }),
-            Div => todo!("Type of div"),
-            Eq | Lt | Gt | Le | Ge => TypeKind::Function(&TypeFunction {
-                lhs: Type {
-                    kind: TypeKind::I32,
-                    span: (0, 0),
-                },
-                rhs: Type {
-                    kind: TypeKind::I32,
-                    span: (0, 0),
-                },
-                output: Type {
-                    kind: TypeKind::I32,
-                    span: (0, 0),
-                },
-            }),
+            Add => Some(Trait::Add),
+            Sub => Some(Trait::Sub),
+            Mul => Some(Trait::Mul),
+            Rem => Some(Trait::Rem),
+            Div => Some(Trait::Div),
+            Eq => Some(Trait::Eq),
+            Lt | Gt | Le | Ge => Some(Trait::Ord),
+            Semi => None,
         }
     }
+
+    /// The type of the builtin where it's used at `span`. Every builtin is
+    /// generic, so this makes fresh type variables for it, and records which
+    /// trait they have to implement.
+    pub fn type_kind<'cx>(&self, ctx: &mut ctx::Typeck<'cx>, span: Span) -> TypeKind<'cx> {
+        use Builtin::*;
+        let ty = |kind| Type { kind, span };
+        let (lhs, rhs, output) = match self {
+            Add | Sub | Mul | Rem | Div | Eq | Lt | Gt | Le | Ge => {
+                let operand = TypeKind::Var(ctx.new_typevar());
+                let trait_ = self.trait_().expect("only `;` has no trait");
+                ctx.constraints.push(Constraint {
+                    trait_,
+                    ty: ty(operand),
+                });
+                let output = if trait_.is_comparison() {
+                    TypeKind::Bool
+                } else {
+                    operand
+                };
+                (operand, operand, output)
+            }
+            Semi => {
+                let rhs = TypeKind::Var(ctx.new_typevar());
+                (TypeKind::Var(ctx.new_typevar()), rhs, rhs)
+            }
+        };
+
+        TypeKind::Function(ctx.global.type_fns.alloc(TypeFunction {
+            lhs: ty(lhs),
+            rhs: ty(rhs),
+            output: ty(output),
+        }))
+    }
 }
 
 impl fmt::Display for Builtin {
@@ -74,4 +91,3 @@ impl fmt::Display for Builtin {
         f.write_str(self.as_str())
     }
 }
-
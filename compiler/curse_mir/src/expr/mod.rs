@@ -1,4 +1,6 @@
-use crate::{Spanned, Type, TypeKind, ValueIdent};
+use crate::{Spanned, Type, TypeKind};
+use curse_hir::hir::RegionKind;
+use curse_interner::Ident;
 
 mod arm;
 pub use arm::ExprArm;
@@ -6,9 +8,6 @@ pub use arm::ExprArm;
 mod builtin;
 pub use builtin::Builtin;
 
-mod constructor;
-pub use constructor::ExprFields;
-
 pub trait Ty<'cx> {
     fn ty(&self) -> Type<'cx>;
 }
@@ -17,20 +16,25 @@ pub type Expr<'cx> = Spanned<ExprKind<'cx>>;
 
 #[derive(Copy, Clone, Debug)]
 pub enum ExprKind<'cx> {
-    Builtin(Builtin),
-    I32(i32),
+    Builtin {
+        ty: TypeKind<'cx>,
+        builtin: Builtin,
+    },
+    I32(u32),
     Bool(bool),
     Ident {
         ty: TypeKind<'cx>,
-        literal: ValueIdent,
+        literal: Ident,
     },
     Record {
         ty: TypeKind<'cx>,
-        exprs: &'cx [Expr<'cx>],
+        fields: &'cx [(Ident, Expr<'cx>)],
     },
+    /// A choice variant or a struct, e.g. `Option::Some 5` or `Id 5`.
     Constructor {
         ty: TypeKind<'cx>,
-        fields: &'cx Expr<'cx>,
+        path: &'cx [Ident],
+        inner: &'cx Expr<'cx>,
     },
     Closure {
         ty: TypeKind<'cx>,
@@ -40,13 +44,27 @@ pub enum ExprKind<'cx> {
         ty: TypeKind<'cx>,
         appl: &'cx ExprAppl<'cx>,
     },
+    /// Looking up a field of a record, e.g. `r . x`.
+    Field {
+        ty: TypeKind<'cx>,
+        record: &'cx Expr<'cx>,
+        field: Ident,
+    },
+    Region {
+        kind: RegionKind,
+        body: &'cx Expr<'cx>,
+    },
+    /// Something that failed to lower to HIR.
+    Error {
+        ty: TypeKind<'cx>,
+    },
 }
 
 impl Default for ExprKind<'_> {
     fn default() -> Self {
         ExprKind::Record {
-            ty: TypeKind::Record(&[]),
-            exprs: &[],
+            ty: TypeKind::unit(),
+            fields: &[],
         }
     }
 }
@@ -61,10 +79,6 @@ pub struct ExprAppl<'cx> {
 impl<'cx> Ty<'cx> for Expr<'cx> {
     fn ty(&self) -> Type<'cx> {
         match self.kind {
-            ExprKind::Builtin(builtin) => Type {
-                kind: builtin.type_kind(),
-                span: self.span,
-            },
             ExprKind::I32(_) => Type {
                 kind: TypeKind::I32,
                 span: self.span,
@@ -73,11 +87,15 @@ impl<'cx> Ty<'cx> for Expr<'cx> {
                 kind: TypeKind::Bool,
                 span: self.span,
             },
-            ExprKind::Ident { ty, .. }
+            ExprKind::Region { body, .. } => body.ty(),
+            ExprKind::Builtin { ty, .. }
+            | ExprKind::Ident { ty, .. }
             | ExprKind::Record { ty, .. }
             | ExprKind::Constructor { ty, .. }
             | ExprKind::Closure { ty, .. }
-            | ExprKind::Appl { ty, .. } => Type {
+            | ExprKind::Appl { ty, .. }
+            | ExprKind::Field { ty, .. }
+            | ExprKind::Error { ty } => Type {
                 kind: ty,
                 span: self.span,
             },
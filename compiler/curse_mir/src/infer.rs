@@ -0,0 +1,780 @@
+//! Type checking whole programs.
+//!
+//! Top level functions don't have type signatures, so their types are
+//! inferred from how they're defined. A function can only be used at more
+//! than one type once its type is known, so functions are checked in the
+//! order they depend on each other: everything that a function calls is
+//! generalized into a [`TypeTemplate`] before the function itself is checked.
+//!
+//! Functions that call each other can't go one after the other, so each
+//! strongly connected component of the call graph is checked together, with
+//! its functions only being generalized once the whole component is done.
+//! Inside of the component, the functions are monomorphic.
+//!
+//! Symbols like `+` work on any type that implements the right trait. Once a
+//! component is done, every type that a symbol was used on is checked. If it
+//! isn't known yet, the functions that it's part of the type of are generic
+//! over types that implement the trait, like `(A A -> A) where Add A`.
+//! Otherwise it's an `I32`.
+//!
+//! `=` and the comparisons also work structurally: `Bool`s, records, and
+//! structs and choices without an `impl` can be compared as long as
+//! everything in them can.
+//!
+//! The bodies of `impl`s are checked last, since they can use any function.
+//!
+//! Once a component is generalized, every use of a trait in it is resolved to
+//! a [`Dispatch`], for the backends that can't pick an `impl` at runtime.
+
+use crate::{
+    ctx, Constraint, Dispatch, Dispatches, Expr, LowerError, Owner, Scope, Ty, Type, TypeFunction,
+    TypeKind, TypeTemplate, Typevar, Var,
+};
+use curse_hir::hir::{
+    self, Arm, ExprKind, ExprRef, FunctionDef, ImplDef, Lit, PatKind, PatRef, Program, Symbol,
+    Trait,
+};
+use curse_interner::{Ident, InternedString};
+use petgraph::{algo::tarjan_scc, graph::DiGraph};
+use smallvec::smallvec;
+use std::collections::HashMap;
+
+/// A type checked top level function.
+#[derive(Debug)]
+pub struct TypedFunction<'cx> {
+    pub template: TypeTemplate<'cx>,
+    pub expr: crate::Expr<'cx>,
+}
+
+/// The type checked functions of a program.
+pub struct TypedProgram<'cx> {
+    pub functions: HashMap<InternedString, TypedFunction<'cx>>,
+    /// The body of each `impl`, keyed by the trait and the name of the type.
+    pub impls: HashMap<(Trait, InternedString), Expr<'cx>>,
+    /// The strongly connected components of the call graph, in the order
+    /// they were checked.
+    pub components: Vec<Vec<InternedString>>,
+    /// What each use of a trait in the functions and `impl`s means.
+    pub dispatches: Dispatches,
+}
+
+/// Infers the type of every function in `program`.
+///
+/// If any function has type errors, returns the errors of each function that
+/// has them, along with its name. Errors in an `impl` are returned along with
+/// the name of the type.
+pub fn check_program<'cx>(
+    ctx: &mut ctx::Typeck<'cx>,
+    program: &Program<'_>,
+) -> Result<TypedProgram<'cx>, Vec<(InternedString, Vec<LowerError>)>> {
+    let (typed, failures) = check_program_partially(ctx, program);
+    if failures.is_empty() {
+        Ok(typed)
+    } else {
+        Err(failures)
+    }
+}
+
+/// Like [`check_program`], but also gives back every function and `impl`
+/// that did type check when others didn't, for tools that want to know as
+/// much as they can about a program that's still being written.
+pub fn check_program_partially<'cx>(
+    ctx: &mut ctx::Typeck<'cx>,
+    program: &Program<'_>,
+) -> (TypedProgram<'cx>, Vec<(InternedString, Vec<LowerError>)>) {
+    let mut globals: HashMap<InternedString, TypeTemplate<'cx>> = ctx.default_globals().collect();
+    globals.extend(method_globals(ctx, program));
+    let mut functions = HashMap::with_capacity(program.function_defs.len());
+    let mut components = vec![];
+    let mut dispatches = Dispatches::default();
+    let mut failures = vec![];
+
+    for component in call_graph_components(program) {
+        let checked = check_component(ctx, program, &mut globals, &component, &mut dispatches);
+        for (name, checked) in checked {
+            match checked {
+                Ok(expr) => {
+                    let template = globals[&name].clone();
+                    functions.insert(name, TypedFunction { template, expr });
+                }
+                Err(errors) => failures.push((name, errors)),
+            }
+        }
+        components.push(component.iter().map(|def| def.ident.symbol).collect());
+    }
+
+    let mut impl_defs: Vec<&ImplDef<'_>> = program.impl_defs.values().collect();
+    impl_defs.sort_by_key(|def| (def.ty.symbol.string().to_string(), def.trait_.name()));
+
+    let mut impls = HashMap::with_capacity(impl_defs.len());
+    for def in impl_defs {
+        match check_impl(ctx, program, &globals, def, &mut dispatches) {
+            Ok(expr) => {
+                impls.insert((def.trait_, def.ty.symbol), expr);
+            }
+            Err(errors) => failures.push((def.ty.symbol, errors)),
+        }
+    }
+
+    let typed = TypedProgram {
+        functions,
+        impls,
+        components,
+        dispatches,
+    };
+    (typed, failures)
+}
+
+/// The types of the methods of the traits that `program` declares, which are
+/// generic over the `Self` type that has to implement the trait, e.g.
+/// `|S| (S S -> S) where Combine S`.
+pub fn method_globals<'cx>(
+    ctx: &mut ctx::Typeck<'cx>,
+    program: &Program<'_>,
+) -> Vec<(InternedString, TypeTemplate<'cx>)> {
+    let mut defs: Vec<_> = program.trait_defs.values().collect();
+    defs.sort_by_key(|def| def.span.start);
+
+    let mut methods = Vec::with_capacity(defs.len());
+    for def in defs {
+        let self_ = ctx.new_typevar();
+        let self_ty = [Type {
+            kind: TypeKind::Var(self_),
+            span: def.lhs.span,
+        }];
+        // Types that don't exist are reported by lowering.
+        let (Some(lhs), Some(rhs), Some(output)) = (
+            instantiate(ctx, program, def.lhs, &self_ty),
+            instantiate(ctx, program, def.rhs, &self_ty),
+            instantiate(ctx, program, def.output, &self_ty),
+        ) else {
+            continue;
+        };
+        let ty = Type {
+            kind: TypeKind::Function(ctx.global.type_fns.alloc(TypeFunction { lhs, rhs, output })),
+            span: def.method.span,
+        };
+        let template = TypeTemplate {
+            typevars: smallvec![self_],
+            constraints: vec![(Trait::Declared(def.ident.symbol), self_)],
+            ty,
+        };
+        methods.push((def.method.symbol, template));
+    }
+    methods
+}
+
+/// Checks the functions of one strongly connected component of the call
+/// graph, where everything they use from outside of the component is
+/// already in `globals`. Each function's type is generalized into `globals`,
+/// even when it has errors, and each function's body or errors are given
+/// back in the same order as `component`. What the traits used in them mean
+/// goes into `dispatches`.
+pub fn check_component<'cx>(
+    ctx: &mut ctx::Typeck<'cx>,
+    program: &Program<'_>,
+    globals: &mut HashMap<InternedString, TypeTemplate<'cx>>,
+    component: &[&FunctionDef<'_>],
+    dispatches: &mut Dispatches,
+) -> Vec<(InternedString, Result<Expr<'cx>, Vec<LowerError>>)> {
+    // Give each function a placeholder type that its uses in the component
+    // get unified with.
+    for def in component {
+        let ty = Type {
+            kind: TypeKind::Var(ctx.new_typevar()),
+            span: def.ident.span,
+        };
+        globals.insert(def.ident.symbol, TypeTemplate::new(ty));
+    }
+
+    let mut checked = Vec::with_capacity(component.len());
+    for def in component {
+        let mut errors = vec![];
+        let mut locals = vec![];
+        let mut scope = Scope::new(ctx, program, &mut errors, globals, &mut locals);
+
+        let expr = scope.lower_closure(def.arms, def.span).ok();
+        if let Some(expr) = expr {
+            scope.unify(globals[&def.ident.symbol].ty, expr.ty());
+        }
+        drop(scope);
+
+        let constraints = std::mem::take(&mut ctx.constraints);
+        let references = std::mem::take(&mut ctx.references);
+        checked.push((def.ident.symbol, expr, errors, constraints, references));
+    }
+
+    // The types that have to implement traits can only be checked once the
+    // whole component is unified, since a later function can still decide
+    // what they are.
+    let mut unknown = vec![];
+    for (_, _, errors, constraints, _) in &mut checked {
+        // Don't pile onto errors that probably caused these ones.
+        let had_errors = !errors.is_empty();
+        for &constraint in constraints.iter() {
+            match check_constraint(ctx, program, constraint) {
+                Ok(vars) => unknown.extend(vars.into_iter().map(|var| (constraint, var))),
+                Err(_) if had_errors => {}
+                Err(error) => errors.push(error),
+            }
+        }
+    }
+    for &(constraint, var) in &unknown {
+        let in_signature = component
+            .iter()
+            .any(|def| ctx.occurs(var, &globals[&def.ident.symbol].ty));
+        if !in_signature && !matches!(constraint.trait_, Trait::Declared(_)) {
+            default_to_i32(ctx, var, constraint.ty.span);
+        }
+    }
+
+    let mut results = Vec::with_capacity(component.len());
+    let mut uses = Vec::with_capacity(component.len());
+    for (name, expr, mut errors, constraints, references) in checked {
+        for error in &mut errors {
+            error.explain(ctx);
+        }
+
+        match expr {
+            Some(expr) if errors.is_empty() => results.push((name, Ok(expr))),
+            _ => results.push((name, Err(errors))),
+        }
+        uses.push((name, constraints, references));
+    }
+
+    for def in component {
+        let mut template = ctx.generalize(globals[&def.ident.symbol].ty);
+        for &(constraint, var) in &unknown {
+            let generic = (constraint.trait_, var);
+            if template.typevars.contains(&var) && !template.constraints.contains(&generic) {
+                template.constraints.push(generic);
+            }
+        }
+        globals.insert(def.ident.symbol, template);
+    }
+
+    for (name, mut constraints, references) in uses {
+        // Functions in the component were used before they were generic, so
+        // what they have to be given only comes from their finished templates.
+        for reference in references {
+            if component
+                .iter()
+                .any(|def| def.ident.symbol == reference.symbol)
+            {
+                for &(trait_, var) in &globals[&reference.symbol].constraints {
+                    constraints.push(Constraint {
+                        trait_,
+                        ty: Type {
+                            kind: TypeKind::Var(var),
+                            span: reference.span,
+                        },
+                    });
+                }
+            }
+        }
+
+        let generics = &globals[&name].constraints;
+        if !generics.is_empty() {
+            let traits = generics.iter().map(|&(trait_, _)| trait_).collect();
+            dispatches.generics.insert(name, traits);
+        }
+        record_dispatches(
+            ctx,
+            program,
+            Owner::Function(name),
+            generics,
+            &constraints,
+            dispatches,
+        );
+    }
+
+    results
+}
+
+/// Records what each of `constraints` means in `owner`, which is generic over
+/// the constraints in `generics`.
+fn record_dispatches<'cx>(
+    ctx: &ctx::Typeck<'cx>,
+    program: &Program<'_>,
+    owner: Owner,
+    generics: &[(Trait, Var)],
+    constraints: &[Constraint<'cx>],
+    dispatches: &mut Dispatches,
+) {
+    for constraint in constraints {
+        let dispatch = dispatch(
+            ctx,
+            program,
+            constraint.trait_,
+            constraint.ty,
+            generics,
+            &mut vec![],
+        );
+        dispatches
+            .sites
+            .entry((owner, constraint.ty.span))
+            .or_default()
+            .push(dispatch);
+    }
+}
+
+/// Checks that the type in `constraint` implements its trait, returning the
+/// type variables in it that have to implement it too, since they aren't
+/// known yet.
+fn check_constraint<'cx>(
+    ctx: &ctx::Typeck<'cx>,
+    program: &Program<'_>,
+    constraint: Constraint<'cx>,
+) -> Result<Vec<Var>, LowerError> {
+    let mut unknown = vec![];
+    if implements(
+        ctx,
+        program,
+        constraint.trait_,
+        constraint.ty,
+        &mut vec![],
+        &mut unknown,
+    ) {
+        Ok(unknown)
+    } else {
+        Err(LowerError::MissingImpl {
+            span: constraint.ty.span.start_len().into(),
+            trait_name: constraint.trait_.name(),
+            ty_kind: ctx.expand(constraint.ty).kind.display(ctx).to_string(),
+        })
+    }
+}
+
+/// Whether `ty` implements `trait_`, either with an `impl` or, for `Eq` and
+/// `Ord`, because everything in it does. Type variables are assumed to, and
+/// are pushed onto `unknown`.
+///
+/// `visiting` holds the structs and choices being checked, so that recursive
+/// ones like `List A` only have to be looked at once.
+fn implements<'cx>(
+    ctx: &ctx::Typeck<'cx>,
+    program: &Program<'_>,
+    trait_: Trait,
+    ty: Type<'cx>,
+    visiting: &mut Vec<InternedString>,
+    unknown: &mut Vec<Var>,
+) -> bool {
+    let (contents, name) = match ctx.expand(ty).kind {
+        TypeKind::Var(var) => {
+            unknown.push(var);
+            return true;
+        }
+        TypeKind::I32 => return !matches!(trait_, Trait::Declared(_)),
+        TypeKind::Bool => return trait_.is_comparison(),
+        TypeKind::Function(_) => return false,
+        TypeKind::Record { fields, .. } => (fields.iter().map(|&(_, ty)| ty).collect(), None),
+        TypeKind::Named { name, generic_args } => {
+            if program.impl_defs.contains_key(&(trait_, name)) || visiting.contains(&name) {
+                return true;
+            }
+            let Some(contents) = contents(ctx, program, name, generic_args) else {
+                return true;
+            };
+            (contents, Some(name))
+        }
+    };
+    if !trait_.is_comparison() {
+        return false;
+    }
+
+    visiting.extend(name);
+    let implemented = contents
+        .into_iter()
+        .all(|ty| implements(ctx, program, trait_, ty, visiting, unknown));
+    if name.is_some() {
+        visiting.pop();
+    }
+    implemented
+}
+
+/// What `ty` means for `trait_`, where the enclosing function is generic
+/// over the constraints in `generics`. This follows [`implements`], which has
+/// already checked that it does.
+fn dispatch<'cx>(
+    ctx: &ctx::Typeck<'cx>,
+    program: &Program<'_>,
+    trait_: Trait,
+    ty: Type<'cx>,
+    generics: &[(Trait, Var)],
+    visiting: &mut Vec<InternedString>,
+) -> Dispatch {
+    let (contents, name) = match ctx.expand(ty).kind {
+        TypeKind::Var(var) => {
+            return match generics
+                .iter()
+                .position(|&generic| generic == (trait_, var))
+            {
+                Some(index) => Dispatch::Generic(index),
+                None => Dispatch::Unknown,
+            };
+        }
+        TypeKind::Named { name, .. } if program.impl_defs.contains_key(&(trait_, name)) => {
+            return Dispatch::Impl(trait_, name);
+        }
+        _ if !trait_.is_comparison() => return Dispatch::Builtin,
+        TypeKind::I32 | TypeKind::Bool | TypeKind::Function(_) => return Dispatch::Builtin,
+        TypeKind::Record { fields, .. } => (fields.iter().map(|&(_, ty)| ty).collect(), None),
+        TypeKind::Named { name, generic_args } => {
+            if visiting.contains(&name) {
+                return Dispatch::Builtin;
+            }
+            let Some(contents) = contents(ctx, program, name, generic_args) else {
+                return Dispatch::Builtin;
+            };
+            (contents, Some(name))
+        }
+    };
+
+    visiting.extend(name);
+    let nested = contents
+        .into_iter()
+        .map(|ty| dispatch(ctx, program, trait_, ty, generics, visiting))
+        .collect();
+    if name.is_some() {
+        visiting.pop();
+    }
+    Dispatch::structural(nested)
+}
+
+/// The types of what's in the struct or choice named `name`, or `None` if
+/// there isn't one.
+fn contents<'cx>(
+    ctx: &ctx::Typeck<'cx>,
+    program: &Program<'_>,
+    name: InternedString,
+    generic_args: &[Type<'cx>],
+) -> Option<Vec<Type<'cx>>> {
+    let defined: Vec<_> = match (
+        program.choice_defs.get(&name),
+        program.struct_defs.get(&name),
+    ) {
+        (Some(choice), _) => choice.variants.entries.iter().map(|&(_, ty)| ty).collect(),
+        (_, Some(struct_)) => vec![struct_.ty],
+        (None, None) => return None,
+    };
+    Some(
+        defined
+            .into_iter()
+            .filter_map(|ty| instantiate(ctx, program, ty, generic_args))
+            .collect(),
+    )
+}
+
+/// Converts a type in the definition of a struct or choice to a [`Type`],
+/// replacing its generic parameters with `generic_args`. Returns `None` if it
+/// names a type that doesn't exist, which is reported elsewhere.
+fn instantiate<'cx>(
+    ctx: &ctx::Typeck<'cx>,
+    program: &Program<'_>,
+    ty: &hir::Type<'_>,
+    generic_args: &[Type<'cx>],
+) -> Option<Type<'cx>> {
+    let kind = match ty.kind {
+        hir::TypeKind::Named {
+            path: [name],
+            generic_args: args,
+        } if program.struct_defs.contains_key(&name.symbol)
+            || program.choice_defs.contains_key(&name.symbol) =>
+        {
+            let args = args
+                .iter()
+                .map(|arg| instantiate(ctx, program, arg, generic_args))
+                .collect::<Option<Vec<_>>>()?;
+            TypeKind::Named {
+                name: name.symbol,
+                generic_args: ctx.global.types.alloc_extend(args),
+            }
+        }
+        hir::TypeKind::Named { .. } | hir::TypeKind::Error => return None,
+        hir::TypeKind::Generic { index, .. } => generic_args.get(index as usize)?.kind,
+        hir::TypeKind::Record(map) => {
+            let fields = map
+                .entries
+                .iter()
+                .map(|&(ident, ty)| {
+                    Some((ident.symbol, instantiate(ctx, program, ty, generic_args)?))
+                })
+                .collect::<Option<Vec<_>>>()?;
+            TypeKind::Record {
+                fields: ctx.global.type_fields.alloc_extend(fields),
+                rest: None,
+            }
+        }
+        hir::TypeKind::Primitive(hir::PrimitiveType::I32) => TypeKind::I32,
+        hir::TypeKind::Primitive(hir::PrimitiveType::Bool) => TypeKind::Bool,
+    };
+
+    Some(Type {
+        kind,
+        span: ty.span,
+    })
+}
+
+/// Makes a type that has to implement a trait, but that nothing else decides,
+/// be an `I32`, which implements all of them.
+fn default_to_i32(ctx: &mut ctx::Typeck<'_>, var: Var, span: curse_span::Span) {
+    if ctx[var].binding().is_none() {
+        ctx[var] = Typevar::Bound {
+            ty: Type {
+                kind: TypeKind::I32,
+                span,
+            },
+        };
+    }
+}
+
+/// Checks the body of an `impl` against the type that its trait needs, which
+/// is `T T -> T` for the arithmetic traits, `T T -> Bool` for `Eq` and `Ord`,
+/// and the type of the method with `Self` as `T` for declared traits. What the
+/// traits used in it mean goes into `dispatches`.
+pub fn check_impl<'cx>(
+    ctx: &mut ctx::Typeck<'cx>,
+    program: &Program<'_>,
+    globals: &HashMap<InternedString, TypeTemplate<'cx>>,
+    def: &ImplDef<'_>,
+    dispatches: &mut Dispatches,
+) -> Result<Expr<'cx>, Vec<LowerError>> {
+    let generic_params = match (
+        program.choice_defs.get(&def.ty.symbol),
+        program.struct_defs.get(&def.ty.symbol),
+    ) {
+        (Some(choice), _) => choice.generic_params,
+        (_, Some(struct_)) => struct_.generic_params,
+        (None, None) => {
+            return Err(vec![LowerError::TypeNotFound {
+                span: def.ty.span.start_len().into(),
+                path: def.ty.to_string(),
+            }]);
+        }
+    };
+    if !generic_params.is_empty() {
+        return Err(vec![LowerError::GenericImpl {
+            span: def.ty.span.start_len().into(),
+            name: def.ty.to_string(),
+        }]);
+    }
+
+    let self_ty = Type {
+        kind: TypeKind::Named {
+            name: def.ty.symbol,
+            generic_args: &[],
+        },
+        span: def.ty.span,
+    };
+    let function = match def.trait_ {
+        Trait::Declared(name) => {
+            let Some(trait_def) = program.trait_defs.get(&name) else {
+                return Err(vec![]);
+            };
+            let method = |ty| instantiate(ctx, program, ty, &[self_ty]);
+            let (Some(lhs), Some(rhs), Some(output)) = (
+                method(trait_def.lhs),
+                method(trait_def.rhs),
+                method(trait_def.output),
+            ) else {
+                // The types that don't exist are reported by lowering.
+                return Err(vec![]);
+            };
+            TypeFunction { lhs, rhs, output }
+        }
+        trait_ if trait_.is_comparison() => TypeFunction {
+            lhs: self_ty,
+            rhs: self_ty,
+            output: Type {
+                kind: TypeKind::Bool,
+                span: def.trait_span,
+            },
+        },
+        _ => TypeFunction {
+            lhs: self_ty,
+            rhs: self_ty,
+            output: self_ty,
+        },
+    };
+    let expected = Type {
+        kind: TypeKind::Function(ctx.global.type_fns.alloc(function)),
+        span: def.trait_span,
+    };
+
+    let mut errors = vec![];
+    let mut locals = vec![];
+    let mut scope = Scope::new(ctx, program, &mut errors, globals, &mut locals);
+    let expr = scope.lower_closure(def.arms, def.span).ok();
+    if let Some(expr) = expr {
+        scope.unify(expected, expr.ty());
+    }
+    drop(scope);
+
+    // An `impl` isn't generic, so everything in it has to be known by now.
+    let had_errors = !errors.is_empty();
+    let constraints = std::mem::take(&mut ctx.constraints);
+    ctx.references.clear();
+    for &constraint in &constraints {
+        match check_constraint(ctx, program, constraint) {
+            // Nothing but `I32` is known to implement a declared trait, so
+            // those are left to whatever the rest of the program decides.
+            Ok(_) if matches!(constraint.trait_, Trait::Declared(_)) => {}
+            Ok(vars) => {
+                for var in vars {
+                    default_to_i32(ctx, var, constraint.ty.span);
+                }
+            }
+            Err(_) if had_errors => {}
+            Err(error) => errors.push(error),
+        }
+    }
+    let owner = Owner::Impl(def.trait_, def.ty.symbol);
+    record_dispatches(ctx, program, owner, &[], &constraints, dispatches);
+
+    for error in &mut errors {
+        error.explain(ctx);
+    }
+    match expr {
+        Some(expr) if errors.is_empty() => Ok(expr),
+        _ => Err(errors),
+    }
+}
+
+/// Splits the functions of `program` into the strongly connected components
+/// of its call graph, where every function comes after the functions it uses.
+pub fn call_graph_components<'a, 'hir>(
+    program: &'a Program<'hir>,
+) -> Vec<Vec<&'a FunctionDef<'hir>>> {
+    let uses: HashMap<InternedString, Vec<InternedString>> = program
+        .function_defs
+        .values()
+        .map(|def| (def.ident.symbol, free_names(def.arms)))
+        .collect();
+    call_graph_components_with(program, |def| &uses[&def.ident.symbol])
+}
+
+/// The same as [`call_graph_components`], but with the [`free_names`] of
+/// each function already worked out.
+pub fn call_graph_components_with<'a, 'hir, 'uses>(
+    program: &'a Program<'hir>,
+    uses: impl Fn(&FunctionDef<'hir>) -> &'uses [InternedString],
+) -> Vec<Vec<&'a FunctionDef<'hir>>> {
+    // Sorted so that functions are always checked in the same order, which
+    // keeps the names of type variables in errors the same between runs.
+    let mut defs: Vec<&FunctionDef<'_>> = program.function_defs.values().collect();
+    defs.sort_by_key(|def| def.ident.symbol.string().to_string());
+
+    let mut graph = DiGraph::<&FunctionDef<'_>, ()>::with_capacity(defs.len(), 0);
+    let nodes: HashMap<InternedString, _> = defs
+        .iter()
+        .map(|def| (def.ident.symbol, graph.add_node(def)))
+        .collect();
+
+    for def in &defs {
+        for global in uses(def) {
+            if let Some(&callee) = nodes.get(global) {
+                graph.update_edge(nodes[&def.ident.symbol], callee, ());
+            }
+        }
+    }
+
+    // Tarjan's algorithm finds the components in reverse topological order,
+    // which is callees first since the edges go from callers to callees.
+    tarjan_scc(&graph)
+        .into_iter()
+        .map(|component| {
+            let mut component: Vec<_> = component.into_iter().map(|node| graph[node]).collect();
+            component.sort_by_key(|def| def.ident.symbol.string().to_string());
+            component
+        })
+        .collect()
+}
+
+/// The names that a function uses without binding them itself, which are
+/// the top level functions and builtins that it calls, in the order they're
+/// first used.
+pub fn free_names(arms: &[Arm<'_>]) -> Vec<InternedString> {
+    let mut uses = Uses {
+        locals: vec![],
+        globals: vec![],
+    };
+    for arm in arms {
+        uses.arm(arm);
+    }
+    uses.globals
+}
+
+/// Finds the names that a function uses without binding them itself.
+struct Uses {
+    locals: Vec<InternedString>,
+    globals: Vec<InternedString>,
+}
+
+impl Uses {
+    fn arm(&mut self, arm: &Arm<'_>) {
+        let len = self.locals.len();
+        for param in arm.params {
+            self.pat(param.pat);
+        }
+        self.expr(arm.body);
+        self.locals.truncate(len);
+    }
+
+    fn expr(&mut self, expr: ExprRef<'_>) {
+        match expr.kind {
+            ExprKind::Lit(Lit::Ident(ident)) => self.ident(ident),
+            ExprKind::Symbol(_) | ExprKind::Lit(_) | ExprKind::Error => {}
+            ExprKind::Record(map) => {
+                for &(ident, value) in map.entries {
+                    match value {
+                        Some(value) => self.expr(value),
+                        None => self.ident(ident),
+                    }
+                }
+            }
+            ExprKind::Constructor(constructor) => self.expr(constructor.inner),
+            ExprKind::Closure(arms) => {
+                for arm in arms {
+                    self.arm(arm);
+                }
+            }
+            // the name after `.` is a field, not a variable
+            ExprKind::Appl(appl)
+                if matches!(
+                    (appl.fun().kind, appl.rhs().kind),
+                    (ExprKind::Symbol(Symbol::Dot), ExprKind::Lit(Lit::Ident(_)))
+                ) =>
+            {
+                self.expr(appl.lhs());
+            }
+            ExprKind::Appl(appl) => {
+                for part in appl.parts {
+                    self.expr(part);
+                }
+            }
+            ExprKind::Region(region) => self.expr(region.body),
+        }
+    }
+
+    fn ident(&mut self, ident: Ident) {
+        if !self.locals.contains(&ident.symbol) && !self.globals.contains(&ident.symbol) {
+            self.globals.push(ident.symbol);
+        }
+    }
+
+    fn pat(&mut self, pat: PatRef<'_>) {
+        match &pat.kind {
+            PatKind::Lit(Lit::Ident(ident)) => self.locals.push(ident.symbol),
+            PatKind::Lit(_) | PatKind::Error => {}
+            PatKind::Record(map, _) => {
+                for &(ident, value) in map.entries {
+                    match value {
+                        Some(value) => self.pat(value),
+                        None => self.locals.push(ident.symbol),
+                    }
+                }
+            }
+            PatKind::Constructor(_, inner) => self.pat(inner),
+        }
+    }
+}
@@ -1,26 +1,27 @@
 #![forbid(unsafe_code)]
 
-// pub mod ctx;
-// mod defs;
-// pub mod dot;
-// mod equations;
-// mod error;
-// mod expr;
-// mod ident;
-// mod lowering;
-// mod lowering_from_ast;
-// mod pat;
-// mod spanned;
-// mod type_context;
-// mod types;
+pub mod ctx;
+mod dispatch;
+pub mod dot;
+mod equations;
+mod error;
+mod expr;
+mod infer;
+mod lowering;
+mod pat;
+mod spanned;
+mod types;
 // pub mod usefulness;
-//
-// pub use equations::{Edge, Equations, Node};
-// pub use error::*;
-// pub use expr::*;
-// pub use ident::{TypeIdent, TypeSymbol, ValueIdent, ValueSymbol};
-// pub use lowering::*;
-// pub use pat::*;
-// pub use spanned::Spanned;
-// pub use type_context::TypeContext;
-// pub use types::*;
+
+pub use dispatch::{Dispatch, Dispatches, Owner};
+pub use equations::{Edge, Equations, Node};
+pub use error::*;
+pub use expr::*;
+pub use infer::{
+    call_graph_components, call_graph_components_with, check_component, check_impl, check_program,
+    check_program_partially, free_names, method_globals, TypedFunction, TypedProgram,
+};
+pub use lowering::*;
+pub use pat::*;
+pub use spanned::Spanned;
+pub use types::*;
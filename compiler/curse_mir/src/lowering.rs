@@ -1,36 +1,36 @@
 use crate::{
-    ctx, Builtin, Edge, Expr, ExprAppl, ExprArm, ExprFields, ExprKind, LowerError, Node, Pat,
-    PatKind, Ty, Type, TypeFunction, TypeKind, TypeSymbol, TypeTemplate, Typevar, ValueIdent,
-    ValueSymbol,
+    ctx, Builtin, Edge, Expr, ExprAppl, ExprArm, ExprKind, LowerError, Node, Pat, PatKind, Ty,
+    Type, TypeFunction, TypeKind, TypeTemplate, Typevar, Var,
 };
-use ast::Span;
-use curse_ast as ast;
+use curse_hir::hir;
+use curse_interner::{Ident, InternedString};
+use curse_span::Span;
 use petgraph::graph::NodeIndex;
-use std::{collections::HashMap, iter};
+use std::collections::HashMap;
 
 pub struct Scope<'outer, 'cx> {
     pub ctx: &'outer mut ctx::Typeck<'cx>,
-    type_map: &'outer HashMap<TypeSymbol, Type<'cx>>,
-    errors: &'outer mut Vec<LowerError<'cx>>,
+    program: &'outer hir::Program<'outer>,
+    errors: &'outer mut Vec<LowerError>,
     original_errors_len: usize,
-    globals: &'cx HashMap<ValueSymbol, TypeTemplate<'cx>>,
-    locals: &'outer mut Vec<(ValueIdent, Type<'cx>)>,
+    globals: &'outer HashMap<InternedString, TypeTemplate<'cx>>,
+    locals: &'outer mut Vec<(Ident, Type<'cx>)>,
     original_locals_len: usize,
 }
 
 impl<'outer, 'cx: 'outer> Scope<'outer, 'cx> {
     pub fn new(
         ctx: &'outer mut ctx::Typeck<'cx>,
-        type_map: &'outer HashMap<TypeSymbol, Type<'cx>>,
-        errors: &'outer mut Vec<LowerError<'cx>>,
-        globals: &'cx HashMap<ValueSymbol, TypeTemplate<'cx>>,
-        locals: &'outer mut Vec<(ValueIdent, Type<'cx>)>,
+        program: &'outer hir::Program<'outer>,
+        errors: &'outer mut Vec<LowerError>,
+        globals: &'outer HashMap<InternedString, TypeTemplate<'cx>>,
+        locals: &'outer mut Vec<(Ident, Type<'cx>)>,
     ) -> Self {
         let original_errors_len = errors.len();
         let original_locals_len = locals.len();
         Scope {
             ctx,
-            type_map,
+            program,
             errors,
             original_errors_len,
             globals,
@@ -40,19 +40,24 @@ impl<'outer, 'cx: 'outer> Scope<'outer, 'cx> {
     }
 
     /// Search through local variables first, then search through global variables.
-    pub fn type_of(&mut self, var: ValueIdent) -> Option<Type<'cx>> {
+    pub fn type_of(&mut self, var: Ident) -> Option<Type<'cx>> {
         self.locals
             .iter()
             .rev()
-            .find_map(|(ident, ty)| (ident.symbol == var.symbol).then(|| *ty))
+            .find_map(|(ident, ty)| {
+                (ident.symbol == var.symbol).then_some(Type {
+                    kind: ty.kind,
+                    span: var.span,
+                })
+            })
             .or_else(move || {
-                self.globals
-                    .get(&var.symbol)
-                    .map(|polytype| self.ctx.monomorphize(polytype))
+                let polytype = self.globals.get(&var.symbol)?;
+                self.ctx.references.push(var);
+                Some(self.ctx.monomorphize(polytype, var.span))
             })
     }
 
-    pub fn add_local(&mut self, var: ValueIdent, ty: Type<'cx>) {
+    pub fn add_local(&mut self, var: Ident, ty: Type<'cx>) {
         self.locals.push((var, ty));
     }
 
@@ -66,7 +71,7 @@ impl<'outer, 'cx: 'outer> Scope<'outer, 'cx> {
     pub fn enter_scope(&mut self) -> Scope<'_, 'cx> {
         Scope::new(
             self.ctx,
-            self.type_map,
+            self.program,
             self.errors,
             self.globals,
             self.locals,
@@ -77,196 +82,252 @@ impl<'outer, 'cx: 'outer> Scope<'outer, 'cx> {
         self.errors.len() > self.original_errors_len
     }
 
-    // TODO(quinn): Move each branch into its own method to clean up this function.
-    pub fn lower(&mut self, expr: &ast::Expr<'_, '_>) -> Result<Expr<'cx>, PushedErrors> {
-        match expr {
-            ast::Expr::Paren(paren) => self.lower(paren.expr),
-            ast::Expr::Symbol(symbol) => match symbol {
-                ast::Symbol::Plus(plus) => Ok(Expr {
-                    kind: ExprKind::Builtin(Builtin::Add),
-                    span: plus.span(),
-                }),
-                ast::Symbol::Minus(minus) => Ok(Expr {
-                    kind: ExprKind::Builtin(Builtin::Sub),
-                    span: minus.span(),
-                }),
-                ast::Symbol::Star(star) => Ok(Expr {
-                    kind: ExprKind::Builtin(Builtin::Mul),
-                    span: star.span(),
-                }),
-                ast::Symbol::Percent(percent) => Ok(Expr {
-                    kind: ExprKind::Builtin(Builtin::Rem),
-                    span: percent.span(),
-                }),
-                ast::Symbol::Slash(slash) => Ok(Expr {
-                    kind: ExprKind::Builtin(Builtin::Div),
-                    span: slash.span(),
-                }),
-                ast::Symbol::Dot(_dot) => todo!("lower `.`"),
-                ast::Symbol::DotDot(_dotdot) => todo!("lower `..`"),
-                ast::Symbol::Semi(_semi) => todo!("lower `;`"),
-                ast::Symbol::Eq(equal) => Ok(Expr {
-                    kind: ExprKind::Builtin(Builtin::Eq),
-                    span: equal.span(),
-                }),
-                ast::Symbol::Lt(less) => Ok(Expr {
-                    kind: ExprKind::Builtin(Builtin::Lt),
-                    span: less.span(),
-                }),
-                ast::Symbol::Gt(greater) => Ok(Expr {
-                    kind: ExprKind::Builtin(Builtin::Gt),
-                    span: greater.span(),
-                }),
-                ast::Symbol::Le(less_equal) => Ok(Expr {
-                    kind: ExprKind::Builtin(Builtin::Le),
-                    span: less_equal.span(),
-                }),
-                ast::Symbol::Ge(greater_equal) => Ok(Expr {
-                    kind: ExprKind::Builtin(Builtin::Ge),
-                    span: greater_equal.span(),
-                }),
-            },
-            ast::Expr::Lit(lit) => match lit {
-                ast::Lit::Integer(integer) => match integer.literal.parse() {
-                    Ok(int) => Ok(Expr {
-                        kind: ExprKind::I32(int),
-                        span: integer.span(),
-                    }),
-                    Err(_) => {
-                        self.errors.push(LowerError::from(integer));
-                        Err(PushedErrors)
-                    }
-                },
-                ast::Lit::Ident(ident) => {
-                    let interned = self.ctx.global.get_or_intern_value_ident(ident);
-                    if let Some(ty) = self.type_of(interned) {
-                        Ok(Expr {
-                            kind: ExprKind::Ident {
-                                literal: interned,
-                                ty: ty.kind,
+    fn fresh(&mut self, span: Span) -> Type<'cx> {
+        Type {
+            kind: TypeKind::Var(self.ctx.new_typevar()),
+            span,
+        }
+    }
+
+    pub fn lower(&mut self, expr: &hir::Expr<'_>) -> Result<Expr<'cx>, PushedErrors> {
+        let kind = match expr.kind {
+            hir::ExprKind::Symbol(symbol) => {
+                let builtin = match symbol {
+                    hir::Symbol::Plus => Builtin::Add,
+                    hir::Symbol::Minus => Builtin::Sub,
+                    hir::Symbol::Star => Builtin::Mul,
+                    hir::Symbol::Percent => Builtin::Rem,
+                    hir::Symbol::Slash => Builtin::Div,
+                    hir::Symbol::Eq => Builtin::Eq,
+                    hir::Symbol::Lt => Builtin::Lt,
+                    hir::Symbol::Gt => Builtin::Gt,
+                    hir::Symbol::Le => Builtin::Le,
+                    hir::Symbol::Ge => Builtin::Ge,
+                    hir::Symbol::Semi => Builtin::Semi,
+                    hir::Symbol::Dot | hir::Symbol::DotDot => {
+                        self.errors.push(LowerError::UnsupportedSymbol {
+                            span: expr.span.start_len().into(),
+                            symbol: if symbol == hir::Symbol::Dot {
+                                "."
+                            } else {
+                                ".."
                             },
-                            span: ident.span(),
-                        })
-                    } else {
-                        self.errors.push(LowerError::from(ident));
-                        Err(PushedErrors)
+                        });
+                        return Err(PushedErrors);
                     }
+                };
+
+                ExprKind::Builtin {
+                    ty: builtin.type_kind(self.ctx, expr.span),
+                    builtin,
                 }
-                ast::Lit::True(tru) => Ok(Expr {
-                    kind: ExprKind::Bool(true),
-                    span: tru.span(),
-                }),
-                ast::Lit::False(fals) => Ok(Expr {
-                    kind: ExprKind::Bool(false),
-                    span: fals.span(),
-                }),
-            },
-            ast::Expr::Record(record) => {
-                let (types, exprs) = tuple
-                    .elements
-                    .as_ref()
-                    .map(|nonempty| {
-                        let len = 1
-                            + nonempty.remaining.len()
-                            + if nonempty.trailing.is_some() { 1 } else { 0 };
-
-                        let types = self
-                            .ctx
-                            .global
-                            .types
-                            .alloc_extend(iter::repeat_with(Type::default).take(len));
-
-                        let exprs = self
-                            .ctx
-                            .global
-                            .exprs
-                            .alloc_extend(iter::repeat_with(Expr::default).take(len));
-
-                        iter::once(nonempty.first)
-                            .chain(nonempty.remaining.iter().map(|(expr, _)| expr))
-                            .chain(nonempty.trailing)
-                            .enumerate()
-                            .try_for_each(|(index, expr)| {
-                                self.lower(expr).map(|expr| {
-                                    types[index] = expr.ty();
-                                    exprs[index] = expr;
-                                })
-                            })
-                            .map(|()| (types, exprs))
-                    })
-                    .transpose()?
-                    .unwrap_or_default();
-
-                Ok(Expr {
-                    kind: ExprKind::Record {
-                        ty: TypeKind::Record(types),
-                        exprs,
+            }
+            hir::ExprKind::Lit(hir::Lit::Integer(integer)) => ExprKind::I32(integer),
+            hir::ExprKind::Lit(hir::Lit::Bool(boolean)) => ExprKind::Bool(boolean),
+            hir::ExprKind::Lit(hir::Lit::Ident(ident)) => self.lower_ident(ident)?.kind,
+            hir::ExprKind::Record(map) => {
+                let mut fields = Vec::with_capacity(map.entries.len());
+                for &(ident, value) in map.entries {
+                    let value = match value {
+                        Some(value) => self.lower(value)?,
+                        // `{ x }` is short for `{ x: x }`
+                        None => self.lower_ident(ident)?,
+                    };
+                    fields.push((ident, value));
+                }
+
+                let types = self.ctx.global.type_fields.alloc_extend(
+                    fields
+                        .iter()
+                        .map(|(ident, value)| (ident.symbol, value.ty())),
+                );
+
+                ExprKind::Record {
+                    ty: TypeKind::Record {
+                        fields: types,
+                        rest: None,
                     },
-                    span: tuple.span(),
-                })
+                    fields: self.ctx.global.expr_fields.alloc_extend(fields),
+                }
             }
-            ast::Expr::Constructor(constructor) => {
-                todo!()
+            hir::ExprKind::Constructor(constructor) => {
+                let (ty, payload) = self.constructor(constructor.path, expr.span)?;
+                let inner = self.lower(constructor.inner)?;
+                self.unify(inner.ty(), payload);
+                if self.had_errors() {
+                    return Err(PushedErrors);
+                }
+
+                ExprKind::Constructor {
+                    ty: ty.kind,
+                    path: self
+                        .ctx
+                        .global
+                        .paths
+                        .alloc_extend(constructor.path.iter().copied()),
+                    inner: self.ctx.global.exprs.alloc(inner),
+                }
+            }
+            hir::ExprKind::Closure(arms) => return self.lower_closure(arms, expr.span),
+            hir::ExprKind::Appl(appl) => return self.lower_appl(&appl, expr.span),
+            hir::ExprKind::Region(region) => ExprKind::Region {
+                kind: region.kind,
+                body: self.ctx.global.exprs.alloc(self.lower(region.body)?),
             },
-            ast::Expr::Closure(closure) => self.lower_closure(closure),
-            ast::Expr::Appl(appl) => self.lower_appl(appl),
-            ast::Expr::Error => todo!("error handling :)"),
-        }
+            hir::ExprKind::Error => ExprKind::Error {
+                ty: self.fresh(expr.span).kind,
+            },
+        };
+
+        Ok(Expr {
+            kind,
+            span: expr.span,
+        })
+    }
+
+    fn lower_ident(&mut self, ident: Ident) -> Result<Expr<'cx>, PushedErrors> {
+        let Some(ty) = self.type_of(ident) else {
+            self.errors.push(LowerError::ident_not_found(ident));
+            return Err(PushedErrors);
+        };
+
+        Ok(Expr {
+            kind: ExprKind::Ident {
+                ty: ty.kind,
+                literal: ident,
+            },
+            span: ident.span,
+        })
     }
 
-    pub fn lower_constructor(
+    /// Looks up the constructor at `path`, which is either a choice variant
+    /// like `Option::Some` or a struct like `Id`, returning the type that it
+    /// makes and the type of its payload.
+    fn constructor(
         &mut self,
-        constructor: &ast::Constructor<'_, '_>,
-    ) -> Result<Expr<'cx>, PushedErrors> {
-        // TODO(quinn): figure out what the type is based on the
-        // name by searching for it in some ctx
+        path: &[Ident],
+        span: Span,
+    ) -> Result<(Type<'cx>, Type<'cx>), PushedErrors> {
+        let program = self.program;
+        let constructor = match path {
+            [name] => program
+                .struct_defs
+                .get(&name.symbol)
+                .map(|def| (def.ident, def.generic_params, def.ty)),
+            [choice, variant] => program.choice_defs.get(&choice.symbol).and_then(|def| {
+                def.variants
+                    .entries
+                    .iter()
+                    .find(|(ident, _)| ident.symbol == variant.symbol)
+                    .map(|&(_, payload)| (def.ident, def.generic_params, payload))
+            }),
+            _ => None,
+        };
 
-        let fields = match constructor.fields {
-            ast::fields::FieldsKind::Newtype(expr) => {
-                let lowered = self.ctx.global.exprs.alloc(self.lower(expr)?);
-                ExprFields::Newtype(lowered)
-            }
-            ast::fields::FieldsKind::Record { ref fields, .. } => {
-                let lowered = self
-                    .ctx
-                    .global
-                    .exprs
-                    .alloc_extend(iter::repeat_with(Expr::default).take(fields.len()));
+        let Some((ident, generic_params, payload)) = constructor else {
+            self.errors.push(LowerError::ConstructorNotFound {
+                span: span.start_len().into(),
+                path: path
+                    .iter()
+                    .map(|ident| ident.to_string())
+                    .collect::<Vec<_>>()
+                    .join("::"),
+            });
+            return Err(PushedErrors);
+        };
+
+        let generic_args: Vec<_> = generic_params.iter().map(|_| self.fresh(span)).collect();
+        let generic_args = self.ctx.global.types.alloc_extend(generic_args);
+        let payload = self.type_from_hir(payload, generic_args, span)?;
+
+        Ok((
+            Type {
+                kind: TypeKind::Named {
+                    name: ident.symbol,
+                    generic_args,
+                },
+                span,
+            },
+            payload,
+        ))
+    }
+
+    /// Converts a type written in the program to a [`Type`], pointing every
+    /// part of it at `span`. Generic parameters are replaced with
+    /// `generic_args`.
+    pub fn type_from_hir(
+        &mut self,
+        ty: &hir::Type<'_>,
+        generic_args: &[Type<'cx>],
+        span: Span,
+    ) -> Result<Type<'cx>, PushedErrors> {
+        let kind = match ty.kind {
+            hir::TypeKind::Named {
+                path,
+                generic_args: args,
+            } => {
+                let name = match path {
+                    [name]
+                        if self.program.struct_defs.contains_key(&name.symbol)
+                            || self.program.choice_defs.contains_key(&name.symbol) =>
+                    {
+                        name.symbol
+                    }
+                    _ => {
+                        self.errors.push(LowerError::TypeNotFound {
+                            span: ty.span.start_len().into(),
+                            path: path
+                                .iter()
+                                .map(|ident| ident.to_string())
+                                .collect::<Vec<_>>()
+                                .join("::"),
+                        });
+                        return Err(PushedErrors);
+                    }
+                };
+
+                let mut lowered = Vec::with_capacity(args.len());
+                for arg in args {
+                    lowered.push(self.type_from_hir(arg, generic_args, span)?);
+                }
 
-                for (slot, (field, _comma)) in lowered.iter_mut().zip(fields) {
-                    *slot = self.lower(&field.value)?;
+                TypeKind::Named {
+                    name,
+                    generic_args: self.ctx.global.types.alloc_extend(lowered),
+                }
+            }
+            hir::TypeKind::Generic { index, .. } => generic_args[index as usize].kind,
+            hir::TypeKind::Record(map) => {
+                let mut fields = Vec::with_capacity(map.entries.len());
+                for &(ident, ty) in map.entries {
+                    fields.push((ident.symbol, self.type_from_hir(ty, generic_args, span)?));
                 }
 
-                ExprFields::Record(lowered)
+                TypeKind::Record {
+                    fields: self.ctx.global.type_fields.alloc_extend(fields),
+                    rest: None,
+                }
             }
+            hir::TypeKind::Primitive(hir::PrimitiveType::I32) => TypeKind::I32,
+            hir::TypeKind::Primitive(hir::PrimitiveType::Bool) => TypeKind::Bool,
+            hir::TypeKind::Error => TypeKind::Var(self.ctx.new_typevar()),
         };
 
-        Ok(Expr {
-            kind: ExprKind::Constructor {
-                fields,
-                ty: todo!("figure out the type of the ctor"),
-            },
-            span: constructor.span(),
-        })
+        Ok(Type { kind, span })
     }
 
-    /// Lowers an [`astClosure`].
+    /// Lowers the arms of a closure, or of a top level function.
     pub fn lower_closure(
         &mut self,
-        closure: &ast::Closure<'_, '_>,
+        arms: &[hir::Arm<'_>],
+        span: Span,
     ) -> Result<Expr<'cx>, PushedErrors> {
-        // Preallocate so we can arena allocate recursively
-        let arms = self
-            .ctx
-            .global
-            .arms
-            .alloc_extend(iter::repeat_with(ExprArm::default).take(closure.arm_count()));
-
+        let mut lowered = Vec::with_capacity(arms.len());
         let mut unifying_types: Option<[Type; 3]> = None;
 
-        for (slot, arm) in arms.iter_mut().zip(closure.iter_arms()) {
+        for arm in arms {
             let mut inner = self.enter_scope();
-            let [lhs, rhs] = inner.pats_of_many_params(arm)?;
+            let [lhs, rhs] = inner.pats_of_many_params(arm, span)?;
             let body = inner.lower(arm.body)?;
             drop(inner);
 
@@ -282,16 +343,12 @@ impl<'outer, 'cx: 'outer> Scope<'outer, 'cx> {
                 unifying_types = Some([lhs.ty(), rhs.ty(), body.ty()]);
             }
 
-            *slot = ExprArm {
-                open: arm.open,
-                lhs,
-                rhs,
-                close: arm.close,
-                body,
-            };
+            lowered.push(ExprArm { lhs, rhs, body });
         }
 
-        let [lhs, rhs, body] = unifying_types.expect("at least one closure arm");
+        // `()` can't be called, so it can be any function at all
+        let [lhs, rhs, body] = unifying_types
+            .unwrap_or_else(|| [self.fresh(span), self.fresh(span), self.fresh(span)]);
 
         Ok(Expr {
             kind: ExprKind::Closure {
@@ -300,26 +357,31 @@ impl<'outer, 'cx: 'outer> Scope<'outer, 'cx> {
                     rhs,
                     output: body,
                 })),
-                arms,
+                arms: self.ctx.global.arms.alloc_extend(lowered),
             },
-            span: closure.span(),
+            span,
         })
     }
 
-    /// Lowers an [`ast::Appl`].
-    fn lower_appl(&mut self, appl: &ast::Appl<'_, '_>) -> Result<Expr<'cx>, PushedErrors> {
-        let lhs = self.lower(appl.lhs);
-        let rhs = self.lower(appl.rhs);
-        let function = self.lower(appl.fun);
+    /// Lowers a [`hir::Appl`].
+    fn lower_appl(&mut self, appl: &hir::Appl<'_>, span: Span) -> Result<Expr<'cx>, PushedErrors> {
+        if let (
+            hir::ExprKind::Symbol(hir::Symbol::Dot),
+            hir::ExprKind::Lit(hir::Lit::Ident(field)),
+        ) = (appl.fun().kind, appl.rhs().kind)
+        {
+            return self.lower_field(appl.lhs(), field, span);
+        }
+
+        let lhs = self.lower(appl.lhs());
+        let function = self.lower(appl.fun());
+        let rhs = self.lower(appl.rhs());
 
         let (Ok(lhs), Ok(rhs), Ok(function)) = (lhs, rhs, function) else {
             return Err(PushedErrors);
         };
 
-        let ty = Type {
-            kind: TypeKind::Var(self.ctx.new_typevar()),
-            span: appl.span(),
-        };
+        let ty = self.fresh(span);
 
         let expected_function = self.ctx.global.type_fns.alloc(TypeFunction {
             lhs: lhs.ty(),
@@ -331,7 +393,7 @@ impl<'outer, 'cx: 'outer> Scope<'outer, 'cx> {
             function.ty(),
             Type {
                 kind: TypeKind::Function(expected_function),
-                span: appl.fun.span(),
+                span: appl.fun().span,
             },
         );
 
@@ -344,86 +406,125 @@ impl<'outer, 'cx: 'outer> Scope<'outer, 'cx> {
                 ty: ty.kind,
                 appl: self.ctx.global.appls.alloc(ExprAppl { lhs, function, rhs }),
             },
-            span: appl.span(),
+            span,
         })
     }
 
-    /// Returns the [`Type<'cx>`] of an [`astPat`].
-    fn lower_pat(&mut self, pat: &ast::Pat<'_, '_>) -> Result<Pat<'cx>, PushedErrors> {
-        match pat {
-            ast::Pat::Lit(lit) => match lit {
-                ast::Lit::Integer(integer) => match integer.literal.parse() {
-                    Ok(int) => Ok(Pat {
-                        kind: PatKind::I32(int),
-                        span: integer.span(),
-                    }),
-                    Err(_) => {
-                        self.errors.push(LowerError::from(integer));
-                        Err(PushedErrors)
-                    }
-                },
-                ast::Lit::Ident(ident) => {
-                    let ty = TypeKind::Var(self.ctx.new_typevar());
-                    self.add_local(
-                        self.ctx.global.get_or_intern_value_ident(ident),
-                        Type {
-                            kind: ty,
-                            span: ident.span(),
-                        },
-                    );
-                    Ok(Pat {
-                        kind: PatKind::Ident {
-                            literal: self.ctx.global.get_or_intern_value_ident(ident),
-                            ty,
-                        },
-                        span: ident.span(),
-                    })
-                }
-                ast::Lit::True(tru) => Ok(Pat {
-                    kind: PatKind::Bool(true),
-                    span: tru.span(),
-                }),
-                ast::Lit::False(fals) => Ok(Pat {
-                    kind: PatKind::Bool(false),
-                    span: fals.span(),
-                }),
-            },
-            ast::Pat::Tuple(tuple) => {
-                let pats = self
+    /// Lowers `record . field`, where `record` can be any record that has a
+    /// `field`.
+    fn lower_field(
+        &mut self,
+        record: &hir::Expr<'_>,
+        field: Ident,
+        span: Span,
+    ) -> Result<Expr<'cx>, PushedErrors> {
+        let record = self.lower(record)?;
+        let ty = self.fresh(span);
+        let expected = Type {
+            kind: TypeKind::Record {
+                fields: self
                     .ctx
                     .global
-                    .pats
-                    .alloc_extend(iter::repeat_with(Pat::default).take(tuple.len()));
+                    .type_fields
+                    .alloc_extend([(field.symbol, ty)]),
+                rest: Some(self.ctx.new_typevar()),
+            },
+            span: field.span,
+        };
+        self.unify(record.ty(), expected);
+        if self.had_errors() {
+            return Err(PushedErrors);
+        }
 
-                let types = self
-                    .ctx
-                    .global
-                    .types
-                    .alloc_extend(iter::repeat_with(Type::default).take(tuple.len()));
+        Ok(Expr {
+            kind: ExprKind::Field {
+                ty: ty.kind,
+                record: self.ctx.global.exprs.alloc(record),
+                field,
+            },
+            span,
+        })
+    }
 
-                for (i, ast_pat) in tuple.iter_elements().enumerate() {
-                    let hir_pat = self.lower_pat(ast_pat)?;
-                    types[i] = hir_pat.ty();
-                    pats[i] = hir_pat;
+    /// Returns the [`Type<'cx>`] of a [`hir::Pat`].
+    fn lower_pat(&mut self, pat: &hir::Pat<'_>) -> Result<Pat<'cx>, PushedErrors> {
+        let kind = match pat.kind {
+            hir::PatKind::Lit(hir::Lit::Integer(integer)) => PatKind::I32(integer),
+            hir::PatKind::Lit(hir::Lit::Bool(boolean)) => PatKind::Bool(boolean),
+            hir::PatKind::Lit(hir::Lit::Ident(ident)) => {
+                let ty = self.fresh(ident.span);
+                self.add_local(ident, ty);
+                PatKind::Ident {
+                    ty: ty.kind,
+                    literal: ident,
+                }
+            }
+            hir::PatKind::Record(map, open) => {
+                let mut fields = Vec::with_capacity(map.entries.len());
+                for &(ident, value) in map.entries {
+                    let value = match value {
+                        Some(value) => self.lower_pat(value)?,
+                        // `{ x }` is short for `{ x: x }`
+                        None => {
+                            let ty = self.fresh(ident.span);
+                            self.add_local(ident, ty);
+                            Pat {
+                                kind: PatKind::Ident {
+                                    ty: ty.kind,
+                                    literal: ident,
+                                },
+                                span: ident.span,
+                            }
+                        }
+                    };
+                    fields.push((ident, value));
                 }
 
-                Ok(Pat {
-                    kind: PatKind::Tuple { ty: types, pats },
-                    span: tuple.span(),
-                })
-            } // When we add struct destructuring, we can unify the type of the field
-              // with the returned type of the pattern in that field.
-              // e.g. If we have a `struct Number(i32)` and have the pattern
-              // `Number(x)`, then `x` might be type `Var(Typevar::Unbound(0))` which
-              // we can unify with `i32` to see that it should be `Var(Typevar::Bound(&Int))`
-        }
+                let types = self.ctx.global.type_fields.alloc_extend(
+                    fields
+                        .iter()
+                        .map(|(ident, value)| (ident.symbol, value.ty())),
+                );
+
+                // `{ x, .. }` matches records with any other fields too
+                PatKind::Record {
+                    ty: TypeKind::Record {
+                        fields: types,
+                        rest: open.then(|| self.ctx.new_typevar()),
+                    },
+                    fields: self.ctx.global.pat_fields.alloc_extend(fields),
+                }
+            }
+            hir::PatKind::Constructor(path, inner) => {
+                let (ty, payload) = self.constructor(path, pat.span)?;
+                let inner = self.lower_pat(inner)?;
+                self.unify(inner.ty(), payload);
+                if self.had_errors() {
+                    return Err(PushedErrors);
+                }
+
+                PatKind::Constructor {
+                    ty: ty.kind,
+                    path: self.ctx.global.paths.alloc_extend(path.iter().copied()),
+                    inner: self.ctx.global.pats.alloc(inner),
+                }
+            }
+            hir::PatKind::Error => PatKind::Error {
+                ty: self.fresh(pat.span).kind,
+            },
+        };
+
+        Ok(Pat {
+            kind,
+            span: pat.span,
+        })
     }
 
-    /// Returns the [`Type<'cx>`] of a single [`astParam`].
-    fn lower_param(&mut self, param: &ast::Param<'_, '_>) -> Result<Pat<'cx>, PushedErrors> {
+    /// Returns the [`Type<'cx>`] of a single [`hir::Param`].
+    fn lower_param(&mut self, param: &hir::Param<'_>) -> Result<Pat<'cx>, PushedErrors> {
         let pat = self.lower_pat(param.pat)?;
-        if let Some((_, annotation)) = param.ascription {
-            let t2 = self.ctx.type_from_ast(annotation, self.type_map);
+        if let Some(annotation) = param.ascription {
+            let t2 = self.type_from_hir(annotation, &[], annotation.span)?;
             self.unify(pat.ty(), t2);
             if self.had_errors() {
                 return Err(PushedErrors);
@@ -433,115 +534,90 @@ impl<'outer, 'cx: 'outer> Scope<'outer, 'cx> {
         Ok(pat)
     }
 
-    /// Returns the [`Type<'cx>`]s of various [`astParams`].
+    /// Returns the [`Type<'cx>`]s of the parameters of an arm, where missing
+    /// ones are `{}`.
     fn pats_of_many_params(
         &mut self,
-        arm: &ast::Arm<'_, '_>,
+        arm: &hir::Arm<'_>,
+        span: Span,
     ) -> Result<[Pat<'cx>; 2], PushedErrors> {
-        let mut params = arm.params.iter().map(|(param, _comma)| param);
-
         let mut pats = [Pat {
             kind: PatKind::unit(),
-            span: arm.close.span(),
+            span,
         }; 2];
 
-        for (pat, param) in pats.iter_mut().zip(params.by_ref()) {
+        // lowering to HIR already made sure there are at most two
+        for (pat, param) in pats.iter_mut().zip(arm.params) {
             *pat = self.lower_param(param)?;
         }
 
-        if params.count() == 0 {
-            Ok(pats)
-        } else {
-            self.errors.push(LowerError::TooManyParams {
-                span: (arm.open.start(), arm.close.end()).into(),
-            });
-            Err(PushedErrors)
-        }
+        Ok(pats)
     }
 
     /// Unify two types.
     pub fn unify(&mut self, t1: Type<'cx>, t2: Type<'cx>) -> NodeIndex {
-        match (t1, t2) {
-            (
-                Type {
-                    kind: TypeKind::I32,
-                    ..
-                },
-                Type {
-                    kind: TypeKind::I32,
-                    ..
-                },
-            ) => self.ctx.equations.add_rule(Node::Equiv(t1, t2)),
+        match (t1.kind, t2.kind) {
+            (TypeKind::I32, TypeKind::I32) | (TypeKind::Bool, TypeKind::Bool) => {
+                self.ctx.equations.add_rule(Node::Equiv(t1, t2))
+            }
             (
-                Type {
-                    kind: TypeKind::Bool,
-                    ..
+                TypeKind::Record {
+                    fields: a,
+                    rest: rest1,
                 },
-                Type {
-                    kind: TypeKind::Bool,
-                    ..
+                TypeKind::Record {
+                    fields: b,
+                    rest: rest2,
                 },
-            ) => self.ctx.equations.add_rule(Node::Equiv(t1, t2)),
+            ) => self.unify_records(t1, (a, rest1), t2, (b, rest2)),
             (
-                Type {
-                    kind: TypeKind::Record(a),
-                    ..
+                TypeKind::Named {
+                    name: name1,
+                    generic_args: args1,
                 },
-                Type {
-                    kind: TypeKind::Record(b),
-                    ..
+                TypeKind::Named {
+                    name: name2,
+                    generic_args: args2,
                 },
-            ) => {
-                if a.len() == b.len() {
-                    let conclusion = self.ctx.equations.add_rule(Node::Equiv(t1, t2));
-
-                    for (i, (&t1, &t2)) in a.iter().zip(b.iter()).enumerate() {
-                        let mut inner = self.enter_scope();
-                        let proof = inner.unify(t1, t2);
-                        if inner.had_errors() {
-                            inner.ctx.equations.graph[conclusion] = Node::NotEquiv(t1, t2);
-                        }
-                        inner
-                            .ctx
-                            .equations
-                            .add_proof(proof, conclusion, Edge::Tuple(i));
+            ) if name1 == name2 && args1.len() == args2.len() => {
+                let conclusion = self.ctx.equations.add_rule(Node::Equiv(t1, t2));
+
+                for (i, (&t1, &t2)) in args1.iter().zip(args2).enumerate() {
+                    let mut inner = self.enter_scope();
+                    let proof = inner.unify(t1, t2);
+                    if inner.had_errors() {
+                        inner.ctx.equations.graph[conclusion] = Node::NotEquiv(t1, t2);
                     }
-                    conclusion
-                } else {
-                    // different length tuples
-                    self.errors.push(LowerError::unify(t1, t2, self.ctx));
-                    self.ctx.equations.add_rule(Node::NotEquiv(t1, t2))
+                    inner
+                        .ctx
+                        .equations
+                        .add_proof(proof, conclusion, Edge::GenericArg(i));
                 }
+                conclusion
             }
-            (
-                Type {
-                    kind: TypeKind::Var(var),
-                    span: var_span,
-                },
-                a,
-            )
-            | (
-                a,
-                Type {
-                    kind: TypeKind::Var(var),
-                    span: var_span,
-                },
-            ) => {
+            (TypeKind::Var(var), _) | (_, TypeKind::Var(var)) => {
+                let (var_span, a) = match t1.kind {
+                    TypeKind::Var(v) if v == var => (t1.span, t2),
+                    _ => (t2.span, t1),
+                };
+
                 // Typevar::Bound also tracks where the typevar was bounded,
                 // which we can use if we want to. For now, using
-                // `.binding()` is easier because it allows us to also
-                // get the default if it's unbounded but has a default.
+                // `.binding()` is easier.
                 if let Some(b) = self.ctx[var].binding() {
-                    let proof = self.unify(a, *b);
+                    let b = *b;
+                    let mut inner = self.enter_scope();
+                    let proof = inner.unify(a, b);
 
-                    let conclusion = if self.had_errors() {
-                        self.ctx.equations.add_rule(Node::NotEquiv(t1, t2))
+                    let conclusion = if inner.had_errors() {
+                        inner.ctx.equations.add_rule(Node::NotEquiv(t1, t2))
                     } else {
-                        self.ctx.equations.add_rule(Node::Equiv(t1, t2))
+                        inner.ctx.equations.add_rule(Node::Equiv(t1, t2))
                     };
                     // If we wanted, we could also add an edge with `_binding_source`,
                     // which tells us exactly where the typevar was bound.
-                    self.ctx
+                    inner
+                        .ctx
                         .equations
                         .add_proof(proof, conclusion, Edge::Transitivity);
                     conclusion
@@ -549,10 +625,10 @@ impl<'outer, 'cx: 'outer> Scope<'outer, 'cx> {
                     self.ctx.equations.add_rule(Node::Equiv(t1, t2))
                 } else if self.ctx.occurs(var, &a) {
                     self.errors.push(LowerError::CyclicType {
-                        var_span,
+                        var_span: var_span.start_len().into(),
                         var,
-                        ty_span: a.span,
-                        ty_kind: a.kind,
+                        ty_span: a.span.start_len().into(),
+                        ty_kind: a.kind.display(self.ctx).to_string(),
                     });
                     self.ctx.equations.add_rule(Node::NotEquiv(t1, t2))
                 } else {
@@ -566,61 +642,137 @@ impl<'outer, 'cx: 'outer> Scope<'outer, 'cx> {
                     conclusion
                 }
             }
-            (
-                Type {
-                    kind: TypeKind::Function(f1),
-                    ..
-                },
-                Type {
-                    kind: TypeKind::Function(f2),
-                    ..
-                },
-            ) => {
+            (TypeKind::Function(f1), TypeKind::Function(f2)) => {
                 let conclusion = self.ctx.equations.add_rule(Node::Equiv(t1, t2));
-                // TODO(quinn): The problem at hand is we need to be able to unify
-                // the types of builtin functions with the types of custom functions.
-                // I made an enum `TypeFunctionKind` that allows for either, but its
-                // lhs/rhs/output functions return a `&TypeKind`, not a `Type`,
-                // meaning I can't unify on it (which was the whole point...).
-                // I know that unification only requires the type in the case
-                // that we do var binding, and only uses the span if it's a var
-                // (which it never will be for builtins), or if it's a cyclic type
-                // (which it never is because it's literally addition).
-                // So I think I need some other alternative.
-                //
-                // One idea I had was to create a customized error message when
-                // unification fails on a builtin (e.g. "tried to pass bool to `+` operator"),
-                // but this wouldn't work well with higher-order functions.
-                //
-                // A more permanent solution would be to create some pseudo definition
-                // site for the builtins and have error messages reference that instead.
-                // That would require having miette work on errors with text from different
-                // sources though...
-                let lhs_proof = self.unify(f1.lhs, f2.lhs);
-                let rhs_proof = self.unify(f1.rhs, f2.rhs);
-                let output_proof = self.unify(f1.output, f2.output); // here
 
-                if self.had_errors() {
-                    self.ctx.equations.graph[conclusion] = Node::NotEquiv(t1, t2);
+                let mut inner = self.enter_scope();
+                let lhs_proof = inner.unify(f1.lhs, f2.lhs);
+                let rhs_proof = inner.unify(f1.rhs, f2.rhs);
+                let output_proof = inner.unify(f1.output, f2.output);
+
+                if inner.had_errors() {
+                    inner.ctx.equations.graph[conclusion] = Node::NotEquiv(t1, t2);
                 }
 
-                self.ctx
+                inner
+                    .ctx
                     .equations
                     .add_proof(lhs_proof, conclusion, Edge::FunctionLhs);
-                self.ctx
+                inner
+                    .ctx
                     .equations
                     .add_proof(rhs_proof, conclusion, Edge::FunctionRhs);
-                self.ctx
+                inner
+                    .ctx
                     .equations
                     .add_proof(output_proof, conclusion, Edge::FunctionOutput);
 
                 conclusion
             }
             _ => {
-                self.errors.push(LowerError::unify(t1, t2, self.ctx));
-                self.ctx.equations.add_rule(Node::NotEquiv(t1, t2))
+                let node = self.ctx.equations.add_rule(Node::NotEquiv(t1, t2));
+                self.errors.push(LowerError::unify(t1, t2, node, self.ctx));
+                node
+            }
+        }
+    }
+}
+
+impl<'cx> Scope<'_, 'cx> {
+    /// Unifies two record types, where either of them might be a row. The
+    /// fields they share have to match, and the fields that only one of them
+    /// has go in the other one's row variable.
+    fn unify_records(
+        &mut self,
+        t1: Type<'cx>,
+        (a, rest1): (&[(InternedString, Type<'cx>)], Option<Var>),
+        t2: Type<'cx>,
+        (b, rest2): (&[(InternedString, Type<'cx>)], Option<Var>),
+    ) -> NodeIndex {
+        let (a, rest1) = self.ctx.row(a, rest1);
+        let (b, rest2) = self.ctx.row(b, rest2);
+        let missing = |from: &[(InternedString, Type<'cx>)], to: &[(InternedString, Type<'cx>)]| {
+            from.iter()
+                .filter(|(name, _)| !to.iter().any(|(other, _)| other == name))
+                .copied()
+                .collect::<Vec<_>>()
+        };
+        let only_a = missing(&a, &b);
+        let only_b = missing(&b, &a);
+
+        // a closed record can't take on any fields, and a row can't take on
+        // fields from itself
+        let fits = match (rest1, rest2) {
+            (None, None) => only_a.is_empty() && only_b.is_empty(),
+            (Some(_), None) => only_a.is_empty(),
+            (None, Some(_)) => only_b.is_empty(),
+            (Some(rest1), Some(rest2)) => {
+                rest1 != rest2 || (only_a.is_empty() && only_b.is_empty())
+            }
+        };
+        if !fits {
+            let node = self.ctx.equations.add_rule(Node::NotEquiv(t1, t2));
+            self.errors.push(LowerError::unify(t1, t2, node, self.ctx));
+            return node;
+        }
+
+        let conclusion = self.ctx.equations.add_rule(Node::Equiv(t1, t2));
+        let mut inner = self.enter_scope();
+        for &(name, ty1) in &a {
+            let Some(&(_, ty2)) = b.iter().find(|(other, _)| *other == name) else {
+                continue;
+            };
+            let proof = inner.unify(ty1, ty2);
+            inner
+                .ctx
+                .equations
+                .add_proof(proof, conclusion, Edge::Field(name));
+        }
+
+        let record = |inner: &mut Scope<'_, 'cx>, fields: Vec<_>, rest, span| Type {
+            kind: TypeKind::Record {
+                fields: inner.ctx.global.type_fields.alloc_extend(fields),
+                rest,
+            },
+            span,
+        };
+        let var = |var, span| Type {
+            kind: TypeKind::Var(var),
+            span,
+        };
+        let mut proofs = vec![];
+        match (rest1, rest2) {
+            (Some(rest1), None) => {
+                let ty = record(&mut inner, only_b, None, t2.span);
+                proofs.push(inner.unify(var(rest1, t1.span), ty));
+            }
+            (None, Some(rest2)) => {
+                let ty = record(&mut inner, only_a, None, t1.span);
+                proofs.push(inner.unify(var(rest2, t2.span), ty));
             }
+            (Some(rest1), Some(rest2)) if rest1 == rest2 => {}
+            (Some(rest1), Some(rest2)) if only_a.is_empty() && only_b.is_empty() => {
+                proofs.push(inner.unify(var(rest1, t1.span), var(rest2, t2.span)));
+            }
+            (Some(rest1), Some(rest2)) => {
+                // both rows end in the same fields that neither of them
+                // mentions
+                let rest = Some(inner.ctx.new_typevar());
+                let ty = record(&mut inner, only_b, rest, t2.span);
+                proofs.push(inner.unify(var(rest1, t1.span), ty));
+                let ty = record(&mut inner, only_a, rest, t1.span);
+                proofs.push(inner.unify(var(rest2, t2.span), ty));
+            }
+            (None, None) => {}
+        }
+        for proof in proofs {
+            inner.ctx.equations.add_proof(proof, conclusion, Edge::Rest);
+        }
+
+        if inner.had_errors() {
+            inner.ctx.equations.graph[conclusion] = Node::NotEquiv(t1, t2);
         }
+        conclusion
     }
 }
 
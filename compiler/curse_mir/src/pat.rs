@@ -1,35 +1,38 @@
-use crate::{Spanned, Ty, Type, TypeChoice, TypeKind, ValueIdent};
+use crate::{Spanned, Ty, Type, TypeKind};
+use curse_interner::Ident;
 
 pub type Pat<'cx> = Spanned<PatKind<'cx>>;
 
 #[derive(Copy, Clone, Debug)]
 pub enum PatKind<'cx> {
     Bool(bool),
-    I32(i32),
+    I32(u32),
     Ident {
         ty: TypeKind<'cx>,
-        literal: ValueIdent,
+        literal: Ident,
     },
-    Tuple {
-        ty: &'cx [Type<'cx>],
-        pats: &'cx [Pat<'cx>],
+    Record {
+        ty: TypeKind<'cx>,
+        fields: &'cx [(Ident, Pat<'cx>)],
+    },
+    /// A choice variant or a struct, e.g. `Option::Some x` or `Id n`.
+    Constructor {
+        ty: TypeKind<'cx>,
+        path: &'cx [Ident],
+        inner: &'cx Pat<'cx>,
     },
-    Choice {
-        ty: &'cx TypeChoice<'cx>,
-        variant: u32,
-        payload: Option<&'cx Pat<'cx>>,
+    /// Something that failed to lower to HIR.
+    Error {
+        ty: TypeKind<'cx>,
     },
 }
 
 impl<'cx> PatKind<'cx> {
     pub fn unit() -> Self {
-        PatKind::Tuple { ty: &[], pats: &[] }
-    }
-}
-
-impl Default for PatKind<'_> {
-    fn default() -> Self {
-        PatKind::unit()
+        PatKind::Record {
+            ty: TypeKind::unit(),
+            fields: &[],
+        }
     }
 }
 
@@ -38,9 +41,10 @@ impl<'cx> Ty<'cx> for Pat<'cx> {
         let kind = match self.kind {
             PatKind::Bool(_) => TypeKind::Bool,
             PatKind::I32(_) => TypeKind::I32,
-            PatKind::Ident { ty, .. } => ty,
-            PatKind::Tuple { ty, .. } => TypeKind::Record(ty),
-            PatKind::Choice { ty, .. } => TypeKind::Choice(ty),
+            PatKind::Ident { ty, .. }
+            | PatKind::Record { ty, .. }
+            | PatKind::Constructor { ty, .. }
+            | PatKind::Error { ty } => ty,
         };
 
         Type {
@@ -1,10 +1,10 @@
-use curse_ast::Span;
+use curse_span::{HasSpan, Span};
 use std::fmt;
 
-#[derive(Copy, Clone, Default)]
+#[derive(Copy, Clone)]
 pub struct Spanned<Kind> {
     pub kind: Kind,
-    pub span: (usize, usize),
+    pub span: Span,
 }
 
 impl<Kind: fmt::Debug> fmt::Debug for Spanned<Kind> {
@@ -19,12 +19,16 @@ impl<Kind: fmt::Display> fmt::Display for Spanned<Kind> {
     }
 }
 
-impl<Kind> Span for Spanned<Kind> {
-    fn start(&self) -> usize {
-        self.span.0
+impl<Kind> HasSpan for Spanned<Kind> {
+    fn start(&self) -> u32 {
+        self.span.start
     }
 
-    fn end(&self) -> usize {
-        self.span.1
+    fn end(&self) -> u32 {
+        self.span.end
+    }
+
+    fn span(&self) -> Span {
+        self.span
     }
 }
@@ -1,7 +0,0 @@
-
-/// Look up a type and get information on it.
-/// Basically keeps track of what's in scope.
-pub struct TypeContext {
-
-}
-
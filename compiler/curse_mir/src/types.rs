@@ -1,14 +1,20 @@
-use crate::{ctx, Spanned, TypeIdent};
+use crate::{ctx, Spanned};
+use curse_hir::hir::Trait;
+use curse_interner::InternedString;
 use displaydoc::Display;
 use smallvec::SmallVec;
 use thiserror::Error;
 
 mod printer;
-use printer::TypePrinter;
+use printer::{TemplatePrinter, TypePrinter};
 
+/// A type that might be generic over some type variables, e.g. `|A| (A {} -> A)`.
 #[derive(Clone, Debug)]
 pub struct TypeTemplate<'cx> {
     pub typevars: SmallVec<[Var; 4]>,
+    /// The traits that some of the type variables have to implement, e.g.
+    /// because they were added together.
+    pub constraints: Vec<(Trait, Var)>,
     pub ty: Type<'cx>,
 }
 
@@ -16,9 +22,27 @@ impl<'cx> TypeTemplate<'cx> {
     pub fn new(ty: Type<'cx>) -> Self {
         TypeTemplate {
             typevars: SmallVec::new(),
+            constraints: Vec::new(),
             ty,
         }
     }
+
+    /// Returns a [`Display`](std::fmt::Display)able template, where the type
+    /// variables it's generic over are named `A`, `B`, `C`, and so on, e.g.
+    /// `(A A -> A) where Add A`.
+    pub fn display<'a>(&'a self, ctx: &'a ctx::Typeck<'cx>) -> TemplatePrinter<'a, 'cx> {
+        TemplatePrinter {
+            template: self,
+            ctx,
+        }
+    }
+}
+
+/// A type that has to implement a trait, e.g. because `+` was used on it.
+#[derive(Copy, Clone, Debug)]
+pub struct Constraint<'cx> {
+    pub trait_: Trait,
+    pub ty: Type<'cx>,
 }
 
 #[derive(Copy, Clone)]
@@ -54,22 +78,38 @@ pub type Type<'cx> = Spanned<TypeKind<'cx>>;
 pub enum TypeKind<'cx> {
     I32,
     Bool,
-    EmptyRecord,
     Var(Var),
-    Record(&'cx [Type<'cx>]),
-    Choice(&'cx TypeChoice<'cx>),
+    /// A record, with its fields sorted by name. If it has a `rest`, it's a
+    /// row: a record with at least these fields, and whatever other fields
+    /// the row variable ends up bound to, e.g. `{ x: I32 | A }`.
+    Record {
+        fields: &'cx [(InternedString, Type<'cx>)],
+        rest: Option<Var>,
+    },
+    /// A struct or choice along with its generic arguments, e.g. `Option I32`.
+    Named {
+        name: InternedString,
+        generic_args: &'cx [Type<'cx>],
+    },
     Function(&'cx TypeFunction<'cx>),
 }
 
 impl<'cx> TypeKind<'cx> {
     pub fn unit() -> Self {
-        TypeKind::Record(&[])
+        TypeKind::Record {
+            fields: &[],
+            rest: None,
+        }
     }
 
-    /// Returns a [`Display`](fmt::Display)able type that prints a [`Type`],
+    /// Returns a [`Display`](std::fmt::Display)able type that prints a [`Type`],
     /// except with all type variables fully expanded as much as possible.
     pub fn display<'a>(self, ctx: &'a ctx::Typeck<'cx>) -> TypePrinter<'a, 'cx> {
-        TypePrinter { ty: self, ctx }
+        TypePrinter {
+            ty: self,
+            ctx,
+            generics: &[],
+        }
     }
 
     pub fn resolve(&self, ctx: &ctx::Typeck<'cx>) -> Result<Self, UnboundTypevar> {
@@ -93,15 +133,3 @@ pub struct TypeFunction<'cx> {
     pub rhs: Type<'cx>,
     pub output: Type<'cx>,
 }
-
-#[derive(Debug)]
-pub struct TypeChoice<'cx> {
-    pub name: TypeIdent,
-    pub variants: Vec<ChoiceVariant<'cx>>,
-}
-
-#[derive(Debug)]
-pub struct ChoiceVariant<'cx> {
-    pub name: TypeIdent,
-    pub payload: Type<'cx>,
-}
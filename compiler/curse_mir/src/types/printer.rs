@@ -1,9 +1,21 @@
-use crate::{ctx, TypeKind};
+use crate::{ctx, TypeKind, TypeTemplate, Var};
 use std::fmt;
 
 pub struct TypePrinter<'a, 'cx> {
     pub ty: TypeKind<'cx>,
     pub ctx: &'a ctx::Typeck<'cx>,
+    /// Type variables to print as `A`, `B`, `C`, ... instead of as `T0`, `T1`, ...
+    pub generics: &'a [Var],
+}
+
+impl<'a, 'cx> TypePrinter<'a, 'cx> {
+    fn with(&self, ty: TypeKind<'cx>) -> TypePrinter<'a, 'cx> {
+        TypePrinter {
+            ty,
+            ctx: self.ctx,
+            generics: self.generics,
+        }
+    }
 }
 
 impl fmt::Display for TypePrinter<'_, '_> {
@@ -13,34 +25,87 @@ impl fmt::Display for TypePrinter<'_, '_> {
             TypeKind::Bool => write!(f, "Bool"),
             TypeKind::Var(var) => {
                 if let Some(ty) = self.ctx[var].binding() {
-                    write!(f, "{}", ty.kind.display(self.ctx))
+                    write!(f, "{}", self.with(ty.kind))
+                } else if let Some(index @ 0..=25) =
+                    self.generics.iter().position(|generic| *generic == var)
+                {
+                    write!(f, "{}", char::from(b'A' + index as u8))
                 } else {
                     write!(f, "{var}")
                 }
             }
-            TypeKind::Record(tuple) => {
-                write!(f, "(")?;
-                let mut iter = tuple.iter();
-                if let Some(ty) = iter.next() {
-                    write!(f, "{}", ty.kind.display(self.ctx))?;
+            TypeKind::Record { fields, rest } => {
+                let (fields, rest) = self.ctx.row(fields, rest);
+                if fields.is_empty() && rest.is_none() {
+                    return write!(f, "{{}}");
                 }
-                for ty in iter {
-                    write!(f, ", {}", ty.kind.display(self.ctx))?;
+                write!(f, "{{ ")?;
+                for (i, (name, ty)) in fields.iter().enumerate() {
+                    let separator = if i == 0 { "" } else { ", " };
+                    write!(f, "{separator}{name}: {}", self.with(ty.kind))?;
                 }
-                write!(f, ")")
+                if let Some(rest) = rest {
+                    let separator = if fields.is_empty() { "" } else { " " };
+                    write!(f, "{separator}| {}", self.with(TypeKind::Var(rest)))?;
+                }
+                write!(f, " }}")
             }
-            TypeKind::Choice(choice) => {
-                write!(f, "{}", choice.name.display(self.ctx.global))
+            TypeKind::Named { name, generic_args } => {
+                write!(f, "{name}")?;
+                match generic_args {
+                    [] => Ok(()),
+                    [arg] => match arg.kind.resolve(self.ctx) {
+                        Ok(TypeKind::Named { generic_args, .. }) if !generic_args.is_empty() => {
+                            write!(f, " ({})", self.with(arg.kind))
+                        }
+                        _ => write!(f, " {}", self.with(arg.kind)),
+                    },
+                    [first, rest @ ..] => {
+                        write!(f, " ({}", self.with(first.kind))?;
+                        for arg in rest {
+                            write!(f, " * {}", self.with(arg.kind))?;
+                        }
+                        write!(f, ")")
+                    }
+                }
             }
             TypeKind::Function(fun) => {
                 write!(
                     f,
                     "({} {} -> {})",
-                    fun.lhs.kind.display(self.ctx),
-                    fun.rhs.kind.display(self.ctx),
-                    fun.output.kind.display(self.ctx)
+                    self.with(fun.lhs.kind),
+                    self.with(fun.rhs.kind),
+                    self.with(fun.output.kind)
                 )
             }
         }
     }
 }
+
+pub struct TemplatePrinter<'a, 'cx> {
+    pub template: &'a TypeTemplate<'cx>,
+    pub ctx: &'a ctx::Typeck<'cx>,
+}
+
+impl fmt::Display for TemplatePrinter<'_, '_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let generics = &self.template.typevars;
+        let printer = TypePrinter {
+            ty: self.template.ty.kind,
+            ctx: self.ctx,
+            generics,
+        };
+        write!(f, "{printer}")?;
+
+        for (i, &(trait_, var)) in self.template.constraints.iter().enumerate() {
+            let separator = if i == 0 { " where" } else { "," };
+            write!(
+                f,
+                "{separator} {} {}",
+                trait_.name(),
+                printer.with(TypeKind::Var(var))
+            )?;
+        }
+        Ok(())
+    }
+}
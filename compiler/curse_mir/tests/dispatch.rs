@@ -0,0 +1,94 @@
+//! Checks that type checking records which `impl` each use of a trait goes to, and what generic
+//! functions are given for the traits they need.
+
+use bumpalo::Bump;
+use curse_mir::{ctx, Dispatches, Owner};
+use curse_test_support::{lock, text};
+
+const INPUT: &str = "
+choice Money { Cents I32 }
+
+impl Add Money |Money::Cents a, Money::Cents b| Money::Cents (a + b)
+impl Eq Money |Money::Cents a, Money::Cents b| a = b
+
+fn double |x| x + x
+
+fn count (
+    |x, 0| x,
+    |x, n| (x double {}) count (n - 1),
+)
+
+fn main || {
+    money: (Money::Cents 1) count 2,
+    ints: 3 count 2,
+    nested: { m: Money::Cents 1 } = { m: Money::Cents 1 },
+}
+";
+
+/// Type checks [`INPUT`], returning its dispatches.
+fn dispatches() -> Dispatches {
+    let arena = Bump::new();
+    let program = curse_test_support::lower(&arena, INPUT);
+
+    let global = ctx::Global::default();
+    let mut typeck = ctx::Typeck::with_global(&global);
+    let typed = curse_mir::check_program(&mut typeck, &program).expect("type checks");
+    typed.dispatches
+}
+
+#[test]
+fn generic_functions() {
+    let _interner = lock();
+    let dispatches = dispatches();
+    let mut generics: Vec<_> = dispatches
+        .generics
+        .iter()
+        .map(|(name, traits)| {
+            let traits: Vec<_> = traits.iter().map(|trait_| trait_.name()).collect();
+            (name.to_string(), traits.join(", "))
+        })
+        .collect();
+    generics.sort();
+    assert_eq!(
+        generics,
+        [
+            ("count".to_string(), "Add".to_string()),
+            ("double".to_string(), "Add".to_string()),
+        ]
+    );
+}
+
+#[test]
+fn sites() {
+    let _interner = lock();
+    let dispatches = dispatches();
+    let mut sites: Vec<_> = dispatches.sites.iter().collect();
+    sites.sort_by_key(|((_, span), _)| *span);
+    let sites: Vec<_> = sites
+        .into_iter()
+        .map(|((owner, span), dispatches)| {
+            let owner = match owner {
+                Owner::Function(name) => name.to_string(),
+                Owner::Impl(trait_, ty) => format!("impl {} {ty}", trait_.name()),
+            };
+            (owner, text(INPUT, *span), format!("{dispatches:?}"))
+        })
+        .collect();
+    let expected = [
+        ("impl Add Money", "+", "[Builtin]"),
+        ("impl Eq Money", "=", "[Builtin]"),
+        ("double", "+", "[Generic(0)]"),
+        // the function is used before it's generic, but still passes on what it's given
+        ("count", "double", "[Generic(0)]"),
+        ("count", "count", "[Generic(0)]"),
+        ("count", "-", "[Builtin]"),
+        ("main", "count", "[Impl(Add, \"Money\")]"),
+        ("main", "count", "[Builtin]"),
+        ("main", "=", "[Structural([Impl(Eq, \"Money\")])]"),
+    ];
+    let expected: Vec<_> = expected
+        .into_iter()
+        .map(|(owner, text, dispatches)| (owner.to_string(), text, dispatches.to_string()))
+        .collect();
+    assert_eq!(sites, expected);
+}
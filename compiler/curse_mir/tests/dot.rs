@@ -0,0 +1,49 @@
+//! Checks that the HIR and typed MIR of a function can be drawn for Graphviz, with spans, types
+//! and pattern arms in the labels.
+
+use bumpalo::Bump;
+use curse_mir::ctx;
+use curse_test_support::lock;
+
+const INPUT: &str = "fn pick (|true, x| x, |false, x| 0)";
+
+#[test]
+fn hir() {
+    let _interner = lock();
+    let arena = Bump::new();
+    let program = curse_test_support::lower(&arena, INPUT);
+
+    let mut builder = curse_hir::dot::Builder::new();
+    builder.visit_program(&program);
+    let hir = builder.finish();
+    assert!(hir.starts_with("digraph hir {"), "{hir}");
+    assert!(hir.contains(r#"[label = "fn pick\n[0, 35)"]"#), "{hir}");
+    assert!(hir.contains(r#"[label = "|true, x|\n[10, 20)"]"#), "{hir}");
+    assert!(hir.contains(r#"[label = "x\n[19, 20)"]"#), "{hir}");
+}
+
+#[test]
+fn mir() {
+    let _interner = lock();
+    let arena = Bump::new();
+    let program = curse_test_support::lower(&arena, INPUT);
+
+    let global = ctx::Global::default();
+    let mut typeck = ctx::Typeck::with_global(&global);
+    let typed = curse_mir::check_program(&mut typeck, &program).unwrap();
+
+    let mut builder = curse_mir::dot::Builder::new(&typeck);
+    builder.visit_program(&typed);
+    let mir = builder.finish();
+    assert!(mir.starts_with("digraph mir {"), "{mir}");
+    assert!(
+        mir.contains(r#"[label = "fn pick: (Bool I32 -> I32)\n"#),
+        "{mir}"
+    );
+    assert!(
+        mir.contains(r#"[label = "|true, x: I32|\n[10, 20)"]"#),
+        "{mir}"
+    );
+    assert!(mir.contains(r#"[label = "x: I32\n[19, 20)"]"#), "{mir}");
+    assert!(mir.contains(r#"[label = "0: I32\n[33, 34)"]"#), "{mir}");
+}
@@ -0,0 +1,82 @@
+//! Checks that type errors explain why the two types had to be the same, and that the inference
+//! graph can be written out for Graphviz.
+
+use bumpalo::Bump;
+use curse_mir::{ctx, LowerError};
+use curse_test_support::lock;
+use miette::SourceSpan;
+
+const INPUT: &str = "fn f |x| (x + 1) ; (x assert {})";
+
+#[test]
+fn errors_say_why_types_had_to_match() {
+    let _interner = lock();
+    let arena = Bump::new();
+    let program = curse_test_support::lower(&arena, INPUT);
+
+    let global = ctx::Global::default();
+    let mut typeck = ctx::Typeck::with_global(&global);
+    let failures = curse_mir::check_program(&mut typeck, &program)
+        .err()
+        .unwrap();
+
+    let text = |span: SourceSpan| &INPUT[span.offset()..span.offset() + span.len()];
+    let [(_, errors)] = &failures[..] else {
+        panic!("{failures:?}");
+    };
+    let [LowerError::Unify {
+        ty1_span,
+        ty1_kind,
+        ty2_span,
+        ty2_kind,
+        reasons,
+        ..
+    }] = &errors[..]
+    else {
+        panic!("{errors:?}");
+    };
+
+    // `assert` needs a `Bool`, but `x` is an `I32`...
+    assert_eq!((&**ty1_kind, text(*ty1_span)), ("Bool", "assert"));
+    assert_eq!((&**ty2_kind, text(*ty2_span)), ("I32", "1"));
+
+    // ...because it was added to `1`
+    let reasons: Vec<_> = reasons
+        .iter()
+        .map(|reason| {
+            (
+                reason.message.as_str(),
+                text(reason.span),
+                reason.because.map(text),
+            )
+        })
+        .collect();
+    assert_eq!(
+        reasons,
+        [
+            ("this was inferred to be `I32`", "x", Some("1")),
+            (
+                "the left arguments of `(Bool {} -> {})` and `(I32 {} -> {})` have to match",
+                "assert",
+                None
+            ),
+        ]
+    );
+}
+
+#[test]
+fn graph_shows_where_it_went_wrong() {
+    let _interner = lock();
+    let arena = Bump::new();
+    let program = curse_test_support::lower(&arena, INPUT);
+
+    let global = ctx::Global::default();
+    let mut typeck = ctx::Typeck::with_global(&global);
+    assert!(curse_mir::check_program(&mut typeck, &program).is_err());
+
+    let dot = typeck.equations.dot(&typeck);
+    assert!(dot.starts_with("digraph {"), "{dot}");
+    assert!(dot.contains(" := I32 [14, 15)"), "{dot}");
+    assert!(dot.contains("Bool [22, 28) ≢ I32 [14, 15)"), "{dot}");
+    assert!(dot.contains("color = red"), "{dot}");
+}
@@ -0,0 +1,132 @@
+//! Checks that top level functions without type signatures get generic types, and can be used at
+//! more than one type.
+
+use bumpalo::Bump;
+use curse_interner::InternedString;
+use curse_mir::{ctx, LowerError};
+use curse_test_support::lock;
+
+const STD: &str = include_str!("../../curse_interpreter/std.curse");
+
+/// The name and type of each function in the order they were checked, and which functions were
+/// checked together.
+type Checked = (Vec<(String, String)>, Vec<Vec<String>>);
+
+/// Type checks `input` along with std.curse.
+fn check(input: &str) -> Result<Checked, Vec<LowerError>> {
+    let input = format!("{STD}\n{input}");
+    let arena = Bump::new();
+    let program = curse_test_support::lower(&arena, &input);
+
+    let global = ctx::Global::default();
+    let mut typeck = ctx::Typeck::with_global(&global);
+    let typed = curse_mir::check_program(&mut typeck, &program).map_err(|failures| {
+        failures
+            .into_iter()
+            .flat_map(|(_, errors)| errors)
+            .collect::<Vec<_>>()
+    })?;
+
+    let names = |component: &Vec<InternedString>| -> Vec<String> {
+        component.iter().map(|name| name.to_string()).collect()
+    };
+    let types = typed
+        .components
+        .iter()
+        .flatten()
+        .map(|name| {
+            let template = &typed.functions[name].template;
+            (name.to_string(), template.display(&typeck).to_string())
+        })
+        .collect();
+    Ok((types, typed.components.iter().map(names).collect()))
+}
+
+const INPUT: &str = "
+fn both || {
+    number: true then 1 else 0,
+    boolean: false then true else false,
+}
+
+fn is_even (
+    |0| true,
+    |n| n - 1 in is_odd,
+)
+
+fn is_odd (
+    |0| false,
+    |n| n - 1 in is_even,
+)
+";
+
+/// The type of the function `name` in `types`.
+fn type_of<'a>(types: &'a [(String, String)], name: &str) -> &'a str {
+    types
+        .iter()
+        .find_map(|(function, ty)| (function == name).then_some(ty.as_str()))
+        .unwrap()
+}
+
+#[test]
+fn std_helpers_are_generic() {
+    let _interner = lock();
+    let (types, _) = check(INPUT).unwrap();
+    assert_eq!(type_of(&types, "then"), "(Bool A -> Option A)");
+    assert_eq!(type_of(&types, "else_do"), "(Option A ({} {} -> A) -> A)");
+    assert_eq!(
+        type_of(&types, "option_map"),
+        "(Option A (A {} -> B) -> Option B)"
+    );
+    assert_eq!(
+        type_of(&types, "rec"),
+        "(A ((A {} -> B) {} -> (A {} -> B)) -> B)"
+    );
+}
+
+#[test]
+fn generic_functions_can_be_used_at_different_types() {
+    let _interner = lock();
+    let (types, _) = check(INPUT).unwrap();
+    // in the same function
+    assert_eq!(
+        type_of(&types, "both"),
+        "({} {} -> { number: I32, boolean: Bool })"
+    );
+}
+
+#[test]
+fn functions_that_call_each_other_are_checked_together() {
+    let _interner = lock();
+    let (types, components) = check(INPUT).unwrap();
+    assert_eq!(type_of(&types, "is_even"), "(I32 {} -> Bool)");
+    assert_eq!(type_of(&types, "is_odd"), "(I32 {} -> Bool)");
+
+    // and after the functions they use
+    let position = |name: &str| {
+        components
+            .iter()
+            .position(|component| component.iter().any(|function| function == name))
+            .unwrap()
+    };
+    assert_eq!(components[position("is_even")], ["is_even", "is_odd"]);
+    assert!(position("in") < position("is_even"));
+    assert!(position("then") < position("both") && position("else") < position("both"));
+}
+
+#[test]
+fn functions_arent_generic_in_their_own_component() {
+    let _interner = lock();
+    let errors = check(
+        "
+fn id_twice (
+    |true| 1 in id_twice,
+    |n| n,
+)
+",
+    )
+    .unwrap_err();
+    assert!(
+        matches!(errors[..], [LowerError::Unify { .. }]),
+        "{errors:?}"
+    );
+}
@@ -0,0 +1,105 @@
+//! Checks that records can be used by the fields they have, with `.` and
+//! `{ x, .. }` patterns, without saying what other fields they have.
+
+use bumpalo::Bump;
+use curse_mir::{ctx, LowerError};
+use curse_test_support::lock;
+
+/// The name and type of each function, in the order they were checked.
+fn check(input: &str) -> Result<Vec<(String, String)>, Vec<LowerError>> {
+    let input = &format!("fn in |x, f| x f {{}}\n{input}");
+    let arena = Bump::new();
+    let program = curse_test_support::lower(&arena, input);
+
+    let global = ctx::Global::default();
+    let mut typeck = ctx::Typeck::with_global(&global);
+    let typed = curse_mir::check_program(&mut typeck, &program).map_err(|failures| {
+        failures
+            .into_iter()
+            .flat_map(|(_, errors)| errors)
+            .collect::<Vec<_>>()
+    })?;
+
+    Ok(typed
+        .components
+        .iter()
+        .flatten()
+        .map(|name| {
+            let template = &typed.functions[name].template;
+            (name.to_string(), template.display(&typeck).to_string())
+        })
+        .collect())
+}
+
+/// Checks that `input` fails to type check because two types don't match.
+fn fail(input: &str) {
+    let errors = check(input).unwrap_err();
+    assert!(
+        matches!(errors[..], [LowerError::Unify { .. }]),
+        "{input}: {errors:?}"
+    );
+}
+
+#[test]
+fn functions_only_need_the_fields_they_use() {
+    let _interner = lock();
+    let types = check(
+        "
+fn get_x |r| r . x + 1
+
+fn get_y |{ y, .. }| y
+
+fn sum |r| (r in get_x) + (r in get_y)
+
+fn anything |{ .. }| {}
+
+fn point || {
+    x: { x: 1 } in get_x,
+    y: { x: 2, y: 3, z: true } in sum,
+    z: { x: 4, y: 5 } in sum,
+    w: { x: 6 } . x,
+    v: { y: false, a: {} } in get_y,
+    u: {} in anything,
+}
+",
+    )
+    .unwrap_or_else(|errors| panic!("{errors:?}"));
+
+    let type_of = |name: &str| {
+        types
+            .iter()
+            .find_map(|(function, ty)| (function == name).then_some(ty.as_str()))
+            .unwrap()
+    };
+
+    assert_eq!(type_of("get_x"), "({ x: I32 | A } {} -> I32)");
+    assert_eq!(type_of("get_y"), "({ y: A | B } {} -> A)");
+    assert_eq!(type_of("anything"), "({ | A } {} -> {})");
+    // using a row in two ways needs both fields
+    assert_eq!(type_of("sum"), "({ x: I32, y: I32 | A } {} -> I32)");
+}
+
+#[test]
+fn closed_records_dont_have_other_fields() {
+    let _interner = lock();
+    fail("fn f || { y: 1 } . x");
+    fail("fn f || { y: 1 } in (|{ x, .. }| x)");
+}
+
+#[test]
+fn closed_patterns_dont_match_records_with_more_fields() {
+    let _interner = lock();
+    fail("fn f || { x: 1, y: 2 } in (|{ x }| x)");
+}
+
+#[test]
+fn open_patterns_only_match_records() {
+    let _interner = lock();
+    fail("fn f || 1 in (|{ .. }| {})");
+}
+
+#[test]
+fn fields_still_have_to_have_the_right_types() {
+    let _interner = lock();
+    fail("fn f || { x: true } . x + 1");
+}
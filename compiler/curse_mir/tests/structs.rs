@@ -0,0 +1,103 @@
+//! Checks that structs can be built with `Id 5` and taken apart with `|Id n|`, and that their
+//! payloads have to match the type in the definition.
+
+use bumpalo::Bump;
+use curse_mir::{ctx, LowerError};
+use curse_test_support::lock;
+
+const DEFS: &str = "
+struct Id I32
+
+struct Wrap |T| { inner: T }
+";
+
+/// The name and type of each function, in the order they were checked.
+fn check(input: &str) -> Result<Vec<(String, String)>, Vec<LowerError>> {
+    let input = &format!("{DEFS}\n{input}");
+    let arena = Bump::new();
+    let program = curse_test_support::lower(&arena, input);
+
+    let global = ctx::Global::default();
+    let mut typeck = ctx::Typeck::with_global(&global);
+    let typed = curse_mir::check_program(&mut typeck, &program).map_err(|failures| {
+        failures
+            .into_iter()
+            .flat_map(|(_, errors)| errors)
+            .collect::<Vec<_>>()
+    })?;
+
+    Ok(typed
+        .components
+        .iter()
+        .flatten()
+        .map(|name| {
+            let template = &typed.functions[name].template;
+            (name.to_string(), template.display(&typeck).to_string())
+        })
+        .collect())
+}
+
+/// Checks that `input` fails to type check with a single error that's `expected`.
+fn fail(input: &str, expected: fn(&LowerError) -> bool) {
+    let errors = check(input).unwrap_err();
+    assert!(
+        matches!(&errors[..], [error] if expected(error)),
+        "{input}: {errors:?}"
+    );
+}
+
+#[test]
+fn structs_are_built_and_taken_apart() {
+    let _interner = lock();
+    let types = check(
+        "
+fn make || Id 5
+
+fn unwrap |Id n| n
+
+fn wrap |x| Wrap { inner: x }
+
+fn inner |Wrap { inner }| inner
+",
+    )
+    .unwrap_or_else(|errors| panic!("{errors:?}"));
+
+    let type_of = |name: &str| {
+        types
+            .iter()
+            .find_map(|(function, ty)| (function == name).then_some(ty.as_str()))
+            .unwrap()
+    };
+
+    assert_eq!(type_of("make"), "({} {} -> Id)");
+    assert_eq!(type_of("unwrap"), "(Id {} -> I32)");
+    assert_eq!(type_of("wrap"), "(A {} -> Wrap A)");
+    assert_eq!(type_of("inner"), "(Wrap A {} -> A)");
+}
+
+#[test]
+fn payloads_have_the_type_in_the_definition() {
+    let _interner = lock();
+    fail("fn f || Id true", |error| {
+        matches!(error, LowerError::Unify { .. })
+    });
+    fail("fn f |Id { n }| n", |error| {
+        matches!(error, LowerError::Unify { .. })
+    });
+}
+
+#[test]
+fn structs_arent_what_they_wrap() {
+    let _interner = lock();
+    fail("fn f || (Id 1) + 1", |error| {
+        matches!(error, LowerError::Unify { .. })
+    });
+}
+
+#[test]
+fn only_structs_and_variants_are_constructors() {
+    let _interner = lock();
+    fail("fn f || Missing 1", |error| {
+        matches!(error, LowerError::ConstructorNotFound { .. })
+    });
+}
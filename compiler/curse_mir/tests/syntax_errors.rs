@@ -0,0 +1,72 @@
+//! Checks that code with syntax errors gets type checked without more errors coming from the
+//! broken parts, so that only the real type errors are reported alongside the syntax errors.
+
+use bumpalo::Bump;
+use curse_mir::{ctx, LowerError};
+use curse_test_support::lock;
+
+/// Checks `input`, which has to have syntax errors, returning the type errors.
+fn check(input: &str) -> Vec<LowerError> {
+    let program = curse_test_support::parse_with(|interner| {
+        let mut parser = curse_parse::Parser::new(interner);
+        let program = parser.parse_program(input);
+        assert!(!parser.errors.is_empty(), "{input}");
+        program
+    });
+
+    let arena = Bump::new();
+    let program = curse_test_support::lower_program(&arena, &program);
+
+    let global = ctx::Global::default();
+    let mut typeck = ctx::Typeck::with_global(&global);
+    match curse_mir::check_program(&mut typeck, &program) {
+        Ok(_) => vec![],
+        Err(failures) => failures
+            .into_iter()
+            .flat_map(|(_, errors)| errors)
+            .collect(),
+    }
+}
+
+#[test]
+fn broken_parts_fit_wherever_theyre_used() {
+    let _interner = lock();
+    // expressions, types and functions
+    let errors = check(
+        "
+fn broken |x y| x
+
+fn uses_broken |x| x broken 1
+
+fn bad_body |x| (x + ) * 2
+
+fn uses_body || (1 bad_body {}) + 1
+
+struct Point { x: I32, y: }
+
+fn point || Point { x: 1, y: 2 }
+
+fn main || (1 uses_broken {}) + ({} broken 2)
+",
+    );
+    assert!(errors.is_empty(), "{errors:?}");
+}
+
+#[test]
+fn real_type_errors_are_still_found() {
+    let _interner = lock();
+    let errors = check(
+        "
+fn bad_body |x| (x + ) * 2
+
+fn type_error |x| x + true
+",
+    );
+    assert!(
+        matches!(
+            &errors[..],
+            [LowerError::MissingImpl { trait_name, .. }] if trait_name == "Add"
+        ),
+        "{errors:?}"
+    );
+}
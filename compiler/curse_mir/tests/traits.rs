@@ -0,0 +1,190 @@
+//! Checks that the builtin symbols work on user types that implement the matching trait, and that
+//! generic functions using them remember which traits their type variables need.
+
+use bumpalo::Bump;
+use curse_mir::{ctx, LowerError};
+use curse_test_support::lock;
+use miette::SourceSpan;
+
+const INPUT: &str = "
+choice Money { Cents I32 }
+
+impl Add Money |Money::Cents a, Money::Cents b| Money::Cents (a + b)
+impl Ord Money |Money::Cents a, Money::Cents b| a < b
+
+fn double |x| x + x
+
+fn bigger |a, b| a > b
+
+fn main || ((Money::Cents 1) double {}) bigger (Money::Cents 1)
+";
+
+/// The name and type of each function and impl, or the errors if it doesn't type check.
+fn check(input: &str) -> Result<Vec<(String, String)>, Vec<LowerError>> {
+    let arena = Bump::new();
+    let program = curse_test_support::lower(&arena, input);
+
+    let global = ctx::Global::default();
+    let mut typeck = ctx::Typeck::with_global(&global);
+    let typed = curse_mir::check_program(&mut typeck, &program).map_err(|failures| {
+        failures
+            .into_iter()
+            .flat_map(|(_, errors)| errors)
+            .collect::<Vec<_>>()
+    })?;
+
+    let mut types: Vec<_> = typed
+        .functions
+        .iter()
+        .map(|(name, function)| {
+            let template = function.template.display(&typeck);
+            (name.to_string(), template.to_string())
+        })
+        .chain(typed.impls.iter().map(|((trait_, ty), expr)| {
+            let ty_printer = curse_mir::Ty::ty(expr).kind.display(&typeck);
+            (
+                format!("impl {} {ty}", trait_.name()),
+                ty_printer.to_string(),
+            )
+        }))
+        .collect();
+    types.sort();
+    Ok(types)
+}
+
+#[test]
+fn symbols_work_on_types_with_an_impl() {
+    let _interner = lock();
+    let types = check(INPUT).unwrap();
+    let types: Vec<_> = types
+        .iter()
+        .map(|(name, ty)| (name.as_str(), ty.as_str()))
+        .collect();
+    assert_eq!(
+        types,
+        [
+            ("bigger", "(A A -> Bool) where Ord A"),
+            ("double", "(A {} -> A) where Add A"),
+            ("impl Add Money", "(Money Money -> Money)"),
+            ("impl Ord Money", "(Money Money -> Bool)"),
+            ("main", "({} {} -> Bool)"),
+        ]
+    );
+}
+
+#[test]
+fn comparisons_work_on_anything_made_of_comparable_things() {
+    let _interner = lock();
+    let types = check(
+        "
+choice Boxed |T| { Box T, Empty {} }
+
+fn same |a, b| (a = b) = ({ x: a } < { x: b })
+
+fn main || (Boxed::Box { b: true }) same (Boxed::Empty {})
+",
+    )
+    .unwrap();
+    assert_eq!(
+        types,
+        [
+            ("main".to_string(), "({} {} -> Bool)".to_string()),
+            (
+                "same".to_string(),
+                "(A A -> Bool) where Eq A, Ord A".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn functions_cant_be_compared_and_records_only_can_be() {
+    let _interner = lock();
+    let input = format!(
+        "{INPUT}\nchoice Boxed |T| {{ Box T }}\nfn equal || ((Boxed::Box |x| x + 1) = (Boxed::Box |x| x)); {{ x: 1 }} - {{ x: 1 }}"
+    );
+    let errors = check(&input).unwrap_err();
+    let missing: Vec<_> = errors
+        .iter()
+        .map(|error| match error {
+            LowerError::MissingImpl {
+                span,
+                trait_name,
+                ty_kind,
+            } => (trait_name.as_str(), ty_kind.as_str(), text(&input, *span)),
+            _ => panic!("{error:?}"),
+        })
+        .collect();
+    assert_eq!(
+        missing,
+        [
+            ("Eq", "Boxed (I32 {} -> I32)", "="),
+            ("Sub", "{ x: I32 }", "-")
+        ]
+    );
+}
+
+#[test]
+fn declared_traits_are_generic_over_self() {
+    let _interner = lock();
+    let types = check(
+        "
+struct Score I32
+
+trait Combine combine |Self, I32| Self
+
+impl Combine Score |Score a, n| Score (a + n)
+
+fn twice |x, n| (x combine n) combine n
+
+fn main || (Score 1) twice 2
+",
+    )
+    .unwrap();
+    assert_eq!(
+        types,
+        [
+            (
+                "impl Combine Score".to_string(),
+                "(Score I32 -> Score)".to_string()
+            ),
+            ("main".to_string(), "({} {} -> Score)".to_string()),
+            (
+                "twice".to_string(),
+                "(A I32 -> A) where Combine A".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn declared_traits_are_only_implemented_by_impls() {
+    let _interner = lock();
+    // not even by `I32`
+    let input = "trait Combine combine |Self, I32| Self\nfn main || 1 combine 2";
+    let errors = check(input).unwrap_err();
+    assert!(
+        matches!(
+            &errors[..],
+            [LowerError::MissingImpl { trait_name, ty_kind, .. }]
+                if trait_name == "Combine" && ty_kind == "I32"
+        ),
+        "{errors:?}"
+    );
+}
+
+#[test]
+fn impls_have_to_give_back_the_right_type() {
+    let _interner = lock();
+    let errors =
+        check("choice Money { Cents I32 }\nimpl Add Money |Money::Cents a, Money::Cents b| a + b")
+            .unwrap_err();
+    assert!(
+        matches!(errors[..], [LowerError::Unify { .. }]),
+        "{errors:?}"
+    );
+}
+
+fn text(input: &str, span: SourceSpan) -> &str {
+    &input[span.offset()..span.offset() + span.len()]
+}
@@ -86,11 +86,17 @@ impl From<LalrParseError<'_>> for Error {
             } => Error::ExtraToken {
                 span: token.span().start_len().into(),
             },
-            User { error } => match error {
-                LexError::UnknownSeq(span) => Error::UnknownSeq(span.start_len().into()),
-                LexError::InvalidIdent(span) => Error::InvalidIdent(span.start_len().into()),
-                LexError::InvalidInteger(span) => Error::InvalidInteger(span.start_len().into()),
-            },
+            User { error } => error.into(),
+        }
+    }
+}
+
+impl From<LexError> for Error {
+    fn from(value: LexError) -> Self {
+        match value {
+            LexError::UnknownSeq(span) => Error::UnknownSeq(span.start_len().into()),
+            LexError::InvalidIdent(span) => Error::InvalidIdent(span.start_len().into()),
+            LexError::InvalidInteger(span) => Error::InvalidInteger(span.start_len().into()),
         }
     }
 }
@@ -1,4 +1,5 @@
 use curse_ast::ast::tok;
+use curse_ast::cst::{Tokens, TriviaKind};
 use curse_span::{HasSpan, Span};
 use logos::Logos;
 use std::fmt;
@@ -42,9 +43,12 @@ macro_rules! declare_tokens {
     ($($(#[$attr:meta])* $tok:literal => $name:ident,)*) => {
 
         #[derive(Clone, Debug, Logos)]
-        #[logos(skip r"\s+")] // Whitespace
-        #[logos(skip r"//[^\r\n]*")] // Comments
         enum LogosToken {
+            // kept rather than skipped, so that they end up in the `Tokens`
+            #[regex(r"\s+")]
+            Whitespace,
+            #[regex(r"//[^\r\n]*")]
+            Comment,
             #[regex("\\w+", lex_word)]
             Word(Word),
             // TODO(quinn): support escape sequences
@@ -100,12 +104,19 @@ macro_rules! declare_tokens {
         #[derive(Clone, Debug)]
         pub struct Lexer<'input> {
             lex: logos::Lexer<'input, LogosToken>,
+            /// Every token that's been lexed so far, with the trivia between them.
+            pub tokens: Tokens<'input>,
+            /// Everything that couldn't be lexed. The parser can't recover from errors coming out
+            /// of the lexer, so they're kept here and skipped over instead.
+            pub errors: Vec<LexError>,
         }
 
         impl<'input> Lexer<'input> {
             pub fn new(input: &'input str) -> Self {
                 Lexer {
                     lex: Logos::lexer(input),
+                    tokens: Tokens::new(input),
+                    errors: vec![],
                 }
             }
         }
@@ -114,9 +125,30 @@ macro_rules! declare_tokens {
             type Item = Result<(usize, Token<'input>, usize), LexError>;
 
             fn next(&mut self) -> Option<Self::Item> {
-                let token = self.lex.next()?;
-                let std::ops::Range { start, end } = self.lex.span();
-                let span = Span { start: start as u32, end: end as u32 };
+                let (token, start, end, span) = loop {
+                    let token = self.lex.next()?;
+                    let std::ops::Range { start, end } = self.lex.span();
+                    let span = Span { start: start as u32, end: end as u32 };
+                    let error = match token {
+                        Ok(LogosToken::Whitespace) => {
+                            self.tokens.push_trivia(TriviaKind::Whitespace, span);
+                            continue;
+                        }
+                        Ok(LogosToken::Comment) => {
+                            self.tokens.push_trivia(TriviaKind::Comment, span);
+                            continue;
+                        }
+                        Ok(LogosToken::Word(Word::InvalidIdent)) => LexError::InvalidIdent(span),
+                        Ok(LogosToken::Word(Word::InvalidInteger)) => {
+                            LexError::InvalidInteger(span)
+                        }
+                        Err(()) => LexError::UnknownSeq(span),
+                        token => break (token, start, end, span),
+                    };
+                    self.tokens.push_trivia(TriviaKind::Unknown, span);
+                    self.errors.push(error);
+                };
+                self.tokens.push_token(span);
                 let token = match token {
                     Ok(LogosToken::Word(word)) => match word {
                         Word::Ident => Token::Ident(tok::Literal {
@@ -131,8 +163,9 @@ macro_rules! declare_tokens {
                             location: span.start,
                             literal: self.lex.slice(),
                         }),
-                        Word::InvalidIdent => return Some(Err(LexError::InvalidIdent(span))),
-                        Word::InvalidInteger => return Some(Err(LexError::InvalidInteger(span))),
+                        Word::InvalidIdent | Word::InvalidInteger => {
+                            unreachable!("invalid words are skipped above")
+                        }
                     },
                     Ok(LogosToken::StringLiteral) => Token::StringLiteral(tok::Literal {
                         location: span.start,
@@ -143,7 +176,9 @@ macro_rules! declare_tokens {
                             location: span.start
                         }),
                     )*
-                    Err(()) => return Some(Err(LexError::UnknownSeq(span))),
+                    Ok(LogosToken::Whitespace | LogosToken::Comment) | Err(()) => {
+                        unreachable!("trivia and errors are skipped above")
+                    }
                 };
 
                 Some(Ok((start, token, end)))
@@ -173,9 +208,14 @@ declare_tokens! {
     "fn" => Fn,
     "choice" => Choice,
     "struct" => Struct,
+    "impl" => Impl,
+    "trait" => Trait,
     "{" => LBrace,
     "}" => RBrace,
     "->" => Arrow,
+    "#" => Pound,
+    "[" => LBracket,
+    "]" => RBracket,
 
     "=" => Eq,
     "<" => Lt,
@@ -1,6 +1,6 @@
 #![forbid(unsafe_code)]
 
-use curse_ast::ast;
+use curse_ast::{ast, cst};
 use curse_interner::StringInterner;
 use lalrpop_util::lalrpop_mod;
 
@@ -29,14 +29,30 @@ impl<'intern> Parser<'intern> {
     }
 
     pub fn parse_program(&mut self, input: &str) -> ast::Program {
-        grammar::ProgramParser::new()
-            .parse(self, Lexer::new(input))
-            .expect("`Program` rule recovers from all errors")
+        self.parse_cst(input).program
+    }
+
+    /// Parses a program, keeping its comments and whitespace so that it can be written back out.
+    pub fn parse_cst<'input>(&mut self, input: &'input str) -> cst::Cst<'input> {
+        let mut lexer = Lexer::new(input);
+        let program = grammar::ProgramParser::new()
+            .parse(self, &mut lexer)
+            .expect("`Program` rule recovers from all errors");
+        self.errors
+            .extend(lexer.errors.into_iter().map(Error::from));
+        cst::Cst {
+            program,
+            tokens: lexer.tokens,
+        }
     }
 
     pub fn parse_expr(&mut self, input: &str) -> ast::Expr {
-        grammar::EndExprParser::new()
-            .parse(self, Lexer::new(input))
-            .expect("`EndExpr` rule recovers from all errors")
+        let mut lexer = Lexer::new(input);
+        let expr = grammar::EndExprParser::new()
+            .parse(self, &mut lexer)
+            .expect("`EndExpr` rule recovers from all errors");
+        self.errors
+            .extend(lexer.errors.into_iter().map(Error::from));
+        expr
     }
 }
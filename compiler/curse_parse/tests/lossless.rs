@@ -0,0 +1,105 @@
+//! Checks that the concrete syntax tree gives back its input byte for byte, and that comments
+//! are attached to the nodes next to them.
+
+use curse_ast::cst::{Cst, Trivia};
+use curse_interner::StringInterner;
+use curse_span::HasSpan;
+use std::path::Path;
+
+fn parse(input: &str) -> Cst<'_> {
+    let mut interner = StringInterner::new();
+    let mut parser = curse_parse::Parser::new(&mut interner);
+    let cst = parser.parse_cst(input);
+    assert!(parser.errors.is_empty(), "{input}");
+    cst
+}
+
+/// Parses `input`, which might have syntax errors, and writes it back out.
+fn round_trip(input: &str) -> String {
+    let mut interner = StringInterner::new();
+    curse_parse::Parser::new(&mut interner)
+        .parse_cst(input)
+        .to_string()
+}
+
+fn curse_files(dir: &Path, files: &mut Vec<String>) {
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        if path.is_dir() {
+            curse_files(&path, files);
+        } else if path
+            .extension()
+            .is_some_and(|extension| extension == "curse")
+        {
+            files.push(std::fs::read_to_string(path).unwrap());
+        }
+    }
+}
+
+fn text<'a>(trivia: &[Trivia<'a>]) -> Vec<&'a str> {
+    trivia.iter().map(|trivia| trivia.literal.literal).collect()
+}
+
+#[test]
+fn lossless() {
+    // everything in the repo round trips, even the old files that don't parse anymore
+    let root = Path::new(env!("CARGO_MANIFEST_DIR")).join("../..");
+    let mut files = vec![];
+    curse_files(&root.join("examples"), &mut files);
+    curse_files(&root.join("compiler/curse_interpreter"), &mut files);
+    assert!(!files.is_empty());
+    for input in &files {
+        assert_eq!(round_trip(input), *input);
+    }
+
+    // as do odd line endings and comments at the very start and end
+    let input = "// start\r\nfn f||\t1//one\r\n\n  // end";
+    assert_eq!(parse(input).to_string(), input);
+    let input = "";
+    assert_eq!(round_trip(input), input);
+    let input = "fn f |x y| (x + ) // broken\n";
+    assert_eq!(round_trip(input), input);
+    // even when there's something the lexer doesn't know, like an unclosed string
+    let input = "fn f || 1 $ 2\n\nfn g || \"abc\n";
+    assert_eq!(round_trip(input), input);
+
+    let input = "
+// adds things
+// together
+fn add |x, y| x + y // or concatenates them
+
+fn main || 1 add 2
+
+// the end
+";
+    let cst = parse(input);
+    let [add, main] = &cst.program.function_defs[..] else {
+        panic!("{:?}", cst.program.function_defs);
+    };
+
+    // comments above a node lead it, along with the indentation before it
+    let leading = text(cst.tokens.leading(add.fn_));
+    assert_eq!(leading, ["\n", "// adds things", "\n", "// together", "\n"]);
+    assert_eq!(text(cst.tokens.leading(main.fn_)), ["\n\n"]);
+
+    // comments after a node on the same line trail it
+    assert_eq!(
+        text(cst.tokens.trailing(&add.function)),
+        [" ", "// or concatenates them"]
+    );
+    assert_eq!(text(cst.tokens.trailing(add.fn_)), [" "]);
+    assert!(cst.tokens.leading(add.ident).is_empty());
+    assert!(cst.tokens.trailing(&main.function).is_empty());
+
+    // and anything after the last line belongs to nothing
+    assert_eq!(text(cst.tokens.dangling()), ["\n\n", "// the end", "\n"]);
+
+    // nodes that don't start or end at a token have nothing attached
+    let body = add.function.span();
+    let middle = curse_span::Span {
+        start: body.start + 1,
+        end: body.end - 1,
+    };
+    assert!(cst.tokens.leading(middle).is_empty());
+    assert!(cst.tokens.trailing(middle).is_empty());
+}
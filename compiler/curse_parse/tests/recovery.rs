@@ -0,0 +1,98 @@
+//! Checks that syntax errors only cost the definition or closure arm that they're in, so that
+//! everything else is still parsed and every error is reported.
+
+use curse_ast::ast::{Closure, Program};
+use curse_interner::StringInterner;
+
+/// Parses `input`, returning the program along with the text at the start of each error.
+fn parse(input: &str) -> (Program, Vec<String>) {
+    let mut interner = StringInterner::new();
+    let mut parser = curse_parse::Parser::new(&mut interner);
+    let program = parser.parse_program(input);
+    let errors = parser
+        .errors
+        .iter()
+        .map(|error| {
+            let label = miette::Diagnostic::labels(error)
+                .and_then(|mut labels| labels.next())
+                .expect("errors are labeled");
+            input[label.offset()..label.offset() + label.len()].to_string()
+        })
+        .collect();
+    (program, errors)
+}
+
+fn function_names<'a>(input: &'a str, program: &Program) -> Vec<&'a str> {
+    program
+        .function_defs
+        .iter()
+        .map(|def| &input[def.ident.span.start as usize..def.ident.span.end as usize])
+        .collect()
+}
+
+#[test]
+fn recovery() {
+    // a broken definition is skipped up to the next one, though functions whose names parsed are
+    // kept so that they can still be called
+    let input = "
+fn before || 1
+
+fn broken |x y| x
+
+struct Point { x: I32, y: I32 }
+
+fn fn oops
+
+choice Maybe { Yes {}, No {} }
+
+fn after || 2
+";
+    let (program, errors) = parse(input);
+    assert_eq!(errors, ["y", "fn", "choice"]);
+    assert_eq!(
+        function_names(input, &program),
+        ["before", "broken", "oops", "after"]
+    );
+    assert!(matches!(
+        program.function_defs[1].function,
+        Closure::Error(_)
+    ));
+    assert_eq!(program.struct_defs.len(), 1);
+    assert_eq!(program.choice_defs.len(), 1);
+
+    // a broken arm is left out of its closure, and the arms after it are still parsed
+    let input = "
+fn arms (
+    |0 1| 0,
+    |1| 1,
+    |2, 3 4| 2,
+    |_| 3,
+)
+
+fn after || 4
+";
+    let (program, errors) = parse(input);
+    assert_eq!(errors, ["1", "4"]);
+    assert_eq!(function_names(input, &program), ["arms", "after"]);
+    assert_eq!(program.function_defs[0].function.iter_arms().count(), 2);
+
+    // errors in expressions are kept in the definition
+    let input = "
+fn incomplete || 1 +
+
+fn after || 5
+";
+    let (program, errors) = parse(input);
+    assert_eq!(errors, ["fn"]);
+    assert_eq!(function_names(input, &program), ["incomplete", "after"]);
+
+    // and so is anything the lexer doesn't know, which is skipped over
+    let input = "
+fn money || 1 + $ 2
+
+fn after || 6
+";
+    let (program, errors) = parse(input);
+    assert_eq!(errors, ["$"]);
+    assert_eq!(function_names(input, &program), ["money", "after"]);
+}
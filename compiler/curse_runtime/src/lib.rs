@@ -0,0 +1,258 @@
+//! The garbage collected heap that natively compiled Curse programs allocate on.
+//!
+//! Every value is a single word. Integers have their lowest bit set, and everything else is a
+//! pointer to an object whose first word is a header, which holds the kind of object in its low
+//! two bits and the number of fields after the header in the rest:
+//!
+//! - records have a field for each of their values. Choices are records of their tag, which is the
+//!   position of their variant, and their value.
+//! - closures have a code pointer, followed by the free variables of the function.
+//! - strings have a single field that only the compiler knows the meaning of.
+//!
+//! The collector is a Cheney-style copying collector. Compiled CPS code never returns, so there's
+//! no native stack to scan: when a function is entered, the only things that are live are its
+//! closure and its arguments. Compiled code checks at the start of every function that the heap
+//! has room for everything the function allocates, and if it doesn't, it collects with exactly
+//! those as the roots. Pointers to objects outside of the heap, like strings that the compiler
+//! allocated up front, are left alone.
+//!
+//! The JIT calls into this crate directly. Programs from `curse_codegen_c` are C, so this crate is
+//! also built as a static library that they're linked against, and the functions they call are
+//! exported unmangled. Their runtime spells out the same object layout in C, so any change to it
+//! has to be made in both places.
+
+use std::{cmp::Ordering, mem, ops::Range, ptr};
+
+pub const KIND_RECORD: u64 = 0;
+pub const KIND_CLOSURE: u64 = 1;
+pub const KIND_STRING: u64 = 2;
+/// The kind of an object that's already been copied, whose header points to the copy.
+const KIND_FORWARDED: u64 = 3;
+
+/// How many words a heap starts out with if nobody asks otherwise.
+pub const DEFAULT_WORDS: usize = 1 << 20;
+
+/// Where compiled code can find the allocation pointer of a [`Heap`].
+pub const PTR_OFFSET: i32 = mem::offset_of!(Heap, ptr) as i32;
+/// Where compiled code can find the end of the space that a [`Heap`] is allocating into.
+pub const LIMIT_OFFSET: i32 = mem::offset_of!(Heap, limit) as i32;
+
+/// The header of an object of `kind` with `len` fields.
+pub const fn header(kind: u64, len: usize) -> u64 {
+    ((len as u64) << 2) | kind
+}
+
+pub const fn is_int(word: u64) -> bool {
+    word & 1 == 1
+}
+
+/// The kind of the object that `object` points to.
+///
+/// # Safety
+///
+/// `object` has to point to an object.
+pub unsafe fn kind(object: u64) -> u64 {
+    *(object as *const u64) & 3
+}
+
+/// How many fields the object that `object` points to has.
+///
+/// # Safety
+///
+/// `object` has to point to an object.
+pub unsafe fn len(object: u64) -> usize {
+    (*(object as *const u64) >> 2) as usize
+}
+
+/// A pointer to the `index`th field of the object that `object` points to.
+///
+/// # Safety
+///
+/// `object` has to point to an object with more than `index` fields.
+pub unsafe fn field(object: u64, index: usize) -> *mut u64 {
+    (object as *mut u64).add(index + 1)
+}
+
+/// A heap that objects are bump allocated on.
+///
+/// Compiled code allocates by reading and bumping `ptr` directly, so the layout of the first two
+/// fields is part of the ABI.
+#[repr(C)]
+pub struct Heap {
+    ptr: *mut u64,
+    limit: *mut u64,
+    space: Box<[u64]>,
+    collections: usize,
+}
+
+impl Heap {
+    pub fn new(words: usize) -> Heap {
+        let mut space = vec![0; words].into_boxed_slice();
+        let Range { start, end } = space.as_mut_ptr_range();
+        Heap {
+            ptr: start,
+            limit: end,
+            space,
+            collections: 0,
+        }
+    }
+
+    /// How many more words can be allocated before the heap has to be collected.
+    pub fn available(&self) -> usize {
+        // SAFETY: both pointers are into `space`.
+        unsafe { self.limit.offset_from(self.ptr) as usize }
+    }
+
+    /// How many times the heap has been collected.
+    pub fn collections(&self) -> usize {
+        self.collections
+    }
+
+    /// Allocates an object of `kind` with `len` fields, which are all zero. Returns `None` if
+    /// there isn't enough room.
+    pub fn alloc(&mut self, kind: u64, len: usize) -> Option<u64> {
+        if self.available() < len + 1 {
+            return None;
+        }
+
+        let object = self.ptr;
+        // SAFETY: there's room for the header and the fields.
+        unsafe {
+            *object = header(kind, len);
+            self.ptr = object.add(len + 1);
+        }
+        Some(object as u64)
+    }
+
+    /// Copies everything reachable from `roots` into a new space that has room for at least
+    /// `needed` more words, updating `roots` to point to the copies. Any other pointers into the
+    /// heap are invalid afterwards.
+    ///
+    /// # Safety
+    ///
+    /// Every root, and every field of every object reachable from them, has to be a valid value:
+    /// either an integer, a pointer to an object in this heap, or a pointer to an object outside of
+    /// it that doesn't point back in. The one exception is the code pointer of a closure, which is
+    /// never looked at.
+    pub unsafe fn collect(&mut self, roots: &mut [u64], needed: usize) {
+        self.collections += 1;
+
+        // Nothing can be live that wasn't allocated, so a space the same size is always enough.
+        let live = self.copy(roots, self.space.len());
+
+        // Keep the heap at most half full so that collections don't happen too often.
+        let wanted = (2 * live).max(live + needed);
+        if wanted > self.space.len() {
+            self.copy(roots, wanted.max(2 * self.space.len()));
+        }
+    }
+
+    /// Copies everything reachable from `roots` into a new space of `words` words, returning how
+    /// many of them are used.
+    unsafe fn copy(&mut self, roots: &mut [u64], words: usize) -> usize {
+        let from = self.space.as_ptr_range();
+        let mut to_space = vec![0; words].into_boxed_slice();
+        let start = to_space.as_mut_ptr();
+        let mut free = start;
+
+        for root in roots.iter_mut() {
+            *root = forward(*root, &from, &mut free);
+        }
+
+        // Everything between `scan` and `free` has been copied, but its fields still point into
+        // the old space.
+        let mut scan = start;
+        while scan < free {
+            let object = scan as u64;
+            let first = match kind(object) {
+                KIND_RECORD => 0,
+                // skip the code pointer
+                KIND_CLOSURE => 1,
+                _ => len(object),
+            };
+            for index in first..len(object) {
+                let field = field(object, index);
+                *field = forward(*field, &from, &mut free);
+            }
+            scan = scan.add(len(object) + 1);
+        }
+
+        let live = free.offset_from(start) as usize;
+        self.space = to_space;
+        self.ptr = free;
+        self.limit = start.add(words);
+        live
+    }
+}
+
+/// Returns where the object `word` points to lives now, copying it to `free` if it hasn't been
+/// copied yet.
+unsafe fn forward(word: u64, from: &Range<*const u64>, free: &mut *mut u64) -> u64 {
+    let object = word as *mut u64;
+    if is_int(word) || !from.contains(&(object as *const u64)) {
+        return word;
+    }
+
+    let header = *object;
+    if header & 3 == KIND_FORWARDED {
+        return header & !3;
+    }
+
+    let words = len(word) + 1;
+    ptr::copy_nonoverlapping(object, *free, words);
+    let copy = *free as u64;
+    *object = copy | KIND_FORWARDED;
+    *free = free.add(words);
+    copy
+}
+
+/// Makes a heap of `words` words for compiled code that isn't run from Rust, which lives for as
+/// long as the program does.
+#[no_mangle]
+pub extern "C" fn curse_heap_new(words: usize) -> *mut Heap {
+    Box::into_raw(Box::new(Heap::new(words)))
+}
+
+/// Collects `heap` on behalf of compiled code. See [`Heap::collect`].
+///
+/// # Safety
+///
+/// `heap` has to point to a heap, `roots` has to point to `count` words, and everything that
+/// [`Heap::collect`] requires has to hold.
+#[no_mangle]
+pub unsafe extern "C" fn curse_collect(
+    heap: *mut Heap,
+    roots: *mut u64,
+    count: usize,
+    needed: usize,
+) {
+    (*heap).collect(std::slice::from_raw_parts_mut(roots, count), needed);
+}
+
+/// Compares two values for compiled code, returning -1, 0 or 1 when `left` is less than, equal to
+/// or greater than `right`. Integers compare by value and records compare field by field, so
+/// choices are ordered by their variant and then their value. Any other objects are only ever
+/// equal to themselves, and are ordered by where they live.
+///
+/// # Safety
+///
+/// Both words have to be values.
+#[no_mangle]
+pub unsafe extern "C" fn curse_compare(left: u64, right: u64) -> i64 {
+    compare(left, right) as i64
+}
+
+unsafe fn compare(left: u64, right: u64) -> Ordering {
+    // tagging keeps integers in order, so they compare the same way as anything else that isn't
+    // a record
+    if is_int(left) || is_int(right) || kind(left) != KIND_RECORD || kind(right) != KIND_RECORD {
+        return left.cmp(&right);
+    }
+    for index in 0..len(left).min(len(right)) {
+        match compare(*field(left, index), *field(right, index)) {
+            Ordering::Equal => {}
+            ordering => return ordering,
+        }
+    }
+    len(left).cmp(&len(right))
+}
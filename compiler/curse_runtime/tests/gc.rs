@@ -0,0 +1,87 @@
+//! Builds object graphs by hand and checks that collecting preserves them.
+
+use curse_runtime::{field, is_int, kind, len, Heap, KIND_CLOSURE, KIND_RECORD, KIND_STRING};
+
+fn int(n: u64) -> u64 {
+    (n << 1) | 1
+}
+
+fn record(heap: &mut Heap, values: &[u64]) -> u64 {
+    let object = heap.alloc(KIND_RECORD, values.len()).expect("room");
+    for (index, value) in values.iter().enumerate() {
+        // SAFETY: the record was just allocated with a field for each value.
+        unsafe { *field(object, index) = *value };
+    }
+    object
+}
+
+unsafe fn get(object: u64, index: usize) -> u64 {
+    *field(object, index)
+}
+
+#[test]
+fn collect() {
+    let mut heap = Heap::new(64);
+
+    // a string outside of the heap, like the ones the JIT allocates up front
+    let mut string = [curse_runtime::header(KIND_STRING, 1), 0];
+    let string = string.as_mut_ptr() as u64;
+
+    let garbage = record(&mut heap, &[int(1), int(2), int(3)]);
+    let shared = record(&mut heap, &[int(4), string]);
+    let pair = record(&mut heap, &[shared, shared]);
+    let _ = record(&mut heap, &[garbage, garbage]);
+
+    // two closures that refer to each other, with a fake code pointer
+    let even = heap.alloc(KIND_CLOSURE, 2).unwrap();
+    let odd = heap.alloc(KIND_CLOSURE, 2).unwrap();
+    unsafe {
+        *field(even, 0) = 0x1000;
+        *field(even, 1) = odd;
+        *field(odd, 0) = 0x2000;
+        *field(odd, 1) = even;
+    }
+
+    let mut roots = [pair, int(5), even];
+    unsafe { heap.collect(&mut roots, 0) };
+    assert_eq!(heap.collections(), 1);
+
+    let [pair, five, even] = roots;
+    assert_eq!(five, int(5));
+    unsafe {
+        assert_eq!(kind(pair), KIND_RECORD);
+        assert_eq!(len(pair), 2);
+        let shared = get(pair, 0);
+        assert_eq!(get(pair, 1), shared, "sharing is preserved");
+        assert_eq!(get(shared, 0), int(4));
+        assert_eq!(
+            get(shared, 1),
+            string,
+            "objects outside the heap aren't moved"
+        );
+
+        assert_eq!(kind(even), KIND_CLOSURE);
+        assert_eq!(get(even, 0), 0x1000);
+        let odd = get(even, 1);
+        assert!(!is_int(odd));
+        assert_eq!(get(odd, 0), 0x2000);
+        assert_eq!(get(odd, 1), even, "cycles are preserved");
+    }
+
+    // 3 + 3 for the records, and 3 + 3 for the closures
+    assert_eq!(heap.available(), 64 - 12);
+}
+
+#[test]
+fn collect_grows() {
+    let mut heap = Heap::new(8);
+    let small = record(&mut heap, &[int(1)]);
+
+    let mut roots = [small];
+    unsafe { heap.collect(&mut roots, 100) };
+    assert!(heap.available() >= 100);
+    unsafe { assert_eq!(get(roots[0], 0), int(1)) };
+
+    let big = heap.alloc(KIND_RECORD, 99).expect("room after growing");
+    assert_eq!(unsafe { len(big) }, 99);
+}
@@ -0,0 +1,76 @@
+//! What the compiler's tests share: getting a program from source to HIR, and taking turns with the
+//! global string interner.
+//!
+//! The interner is global, and so is the counter that CPS conversion names things with, so tests
+//! that use either can't run at the same time as each other. Every such test starts by calling
+//! [`lock`], which waits for the others to finish and gives it a fresh interner, so tests can be
+//! split up as finely as they like and still run in one binary.
+
+use bumpalo::Bump;
+use curse_ast::ast;
+use curse_ast_lowering::{Lower, Lowerer};
+use curse_hir::hir;
+use curse_interner::StringInterner;
+use curse_span::Span;
+use parking_lot::{Mutex, MutexGuard};
+
+static INTERNER: Mutex<()> = Mutex::new(());
+
+/// Keeps other tests away from the global string interner until it's dropped.
+#[must_use = "the interner is only locked until this is dropped"]
+pub struct InternerLock {
+    _guard: MutexGuard<'static, ()>,
+}
+
+/// Waits until no other test is using the global string interner, and then replaces it with an
+/// empty one.
+pub fn lock() -> InternerLock {
+    let guard = INTERNER.lock();
+    curse_interner::init();
+    InternerLock { _guard: guard }
+}
+
+/// Lends the global string interner to `parse`, which is what the parser interns into.
+pub fn parse_with<T>(parse: impl FnOnce(&mut StringInterner) -> T) -> T {
+    let mut interner = curse_interner::replace(None).expect("the interner is locked");
+    let parsed = parse(&mut interner);
+    curse_interner::replace(Some(interner));
+    parsed
+}
+
+/// Parses `input`, which has to be free of syntax errors.
+pub fn parse(input: &str) -> ast::Program {
+    parse_with(|interner| {
+        let mut parser = curse_parse::Parser::new(interner);
+        let program = parser.parse_program(input);
+        assert!(parser.errors.is_empty(), "{:?}", parser.errors);
+        program
+    })
+}
+
+/// Lowers `program` into `arena`, returning the errors along with it.
+pub fn lower_with_errors<'hir>(
+    arena: &'hir Bump,
+    program: &ast::Program,
+) -> (hir::Program<'hir>, Vec<curse_ast_lowering::LoweringError>) {
+    let mut lowerer = Lowerer::new(arena);
+    let program = program.lower(&mut lowerer);
+    (program, lowerer.errors)
+}
+
+/// Parses and lowers `input` into `arena`, which both have to go without errors.
+pub fn lower<'hir>(arena: &'hir Bump, input: &str) -> hir::Program<'hir> {
+    lower_program(arena, &parse(input))
+}
+
+/// Lowers `program` into `arena`, which has to go without errors.
+pub fn lower_program<'hir>(arena: &'hir Bump, program: &ast::Program) -> hir::Program<'hir> {
+    let (program, errors) = lower_with_errors(arena, program);
+    assert!(errors.is_empty(), "{errors:?}");
+    program
+}
+
+/// The part of `input` that `span` covers.
+pub fn text(input: &str, span: Span) -> &str {
+    &input[span.start as usize..span.end as usize]
+}